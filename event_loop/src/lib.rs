@@ -96,7 +96,13 @@
 //! Incrementing!
 //! ```
 
-use std::{future::Future, marker::PhantomData};
+use std::{
+    any::Any,
+    future::Future,
+    marker::PhantomData,
+    panic::AssertUnwindSafe,
+    time::{Duration, Instant},
+};
 
 use futures::future::BoxFuture;
 use tokio::{sync::mpsc::UnboundedReceiver, task::JoinHandle};
@@ -111,10 +117,64 @@ where
     handlers: Vec<H>,
     queue: Vec<M>,
     async_tasks: Vec<JoinHandle<Option<M>>>,
+    metrics: Metrics,
 
     state: PhantomData<S>,
 }
 
+/// How much work each handler has done, in the order they were added.
+///
+/// Only the time spent in [`MessageHandler::handle_message`] is counted. Any
+/// futures a handler returns run afterwards on their own and aren't timed,
+/// since they mostly spend their time waiting on the network or disk.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub handlers: Vec<HandlerMetrics>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HandlerMetrics {
+    pub name: &'static str,
+    /// Messages passed to the handler
+    pub messages: u64,
+    /// Messages and futures the handler returned
+    pub outputs: u64,
+    pub panics: u64,
+    /// Time spent in the handler itself, not counting the futures it returned
+    pub total_time: Duration,
+    /// The longest a single message took to handle
+    pub max_time: Duration,
+}
+
+impl HandlerMetrics {
+    #[must_use]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            messages: 0,
+            outputs: 0,
+            panics: 0,
+            total_time: Duration::ZERO,
+            max_time: Duration::ZERO,
+        }
+    }
+
+    /// Count another message that took `elapsed` to handle
+    pub fn record(&mut self, elapsed: Duration) {
+        self.messages += 1;
+        self.total_time += elapsed;
+        self.max_time = self.max_time.max(elapsed);
+    }
+
+    #[must_use]
+    pub fn average_time(&self) -> Duration {
+        u32::try_from(self.messages)
+            .ok()
+            .filter(|&n| n > 0)
+            .map_or(Duration::ZERO, |n| self.total_time / n)
+    }
+}
+
 impl<S, M, H> EventLoop<S, M, H>
 where
     S: Send,
@@ -128,6 +188,7 @@ where
             handlers: Vec::new(),
             queue: Vec::new(),
             async_tasks: Vec::new(),
+            metrics: Metrics::default(),
             state: PhantomData,
         }
     }
@@ -140,19 +201,55 @@ where
 
     #[must_use]
     pub fn add_handler(mut self, handler: impl Into<H>) -> Self {
-        self.handlers.push(handler.into());
+        let handler = handler.into();
+        self.metrics
+            .handlers
+            .push(HandlerMetrics::new(handler.name()));
+        self.handlers.push(handler);
         self
     }
 
+    #[must_use]
+    pub const fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Runs the message through every handler before updating the state with
+    /// it. A handler that panics is logged and skipped, and the rest still
+    /// see the message.
     pub fn handle_message(&mut self, mut message: M, state: &mut S) -> Vec<Action<M>> {
         let mut out = Vec::new();
 
         message.preprocess(state);
 
-        for h in &mut self.handlers {
-            match h.handle_message(state, &message) {
-                Some(Handled(Internal::Single(m))) => out.push(m),
-                Some(Handled(Internal::Batch(ms))) => out.extend(ms),
+        for (h, metrics) in self.handlers.iter_mut().zip(&mut self.metrics.handlers) {
+            let started = Instant::now();
+            let result =
+                std::panic::catch_unwind(AssertUnwindSafe(|| h.handle_message(state, &message)));
+            metrics.record(started.elapsed());
+
+            let actions = match result {
+                Ok(handled) => handled,
+                Err(panic) => {
+                    metrics.panics += 1;
+                    tracing::error!(
+                        "Handler {} panicked: {}",
+                        metrics.name,
+                        panic_message(panic.as_ref())
+                    );
+                    None
+                }
+            };
+
+            match actions {
+                Some(Handled(Internal::Single(m))) => {
+                    metrics.outputs += 1;
+                    out.push(m);
+                }
+                Some(Handled(Internal::Batch(ms))) => {
+                    metrics.outputs += ms.len() as u64;
+                    out.extend(ms);
+                }
                 None => {}
             }
         }
@@ -230,6 +327,19 @@ where
 
 pub trait MessageHandler<S, IM, OM> {
     fn handle_message(&mut self, state: &S, message: &IM) -> Option<Handled<OM>>;
+
+    /// What the handler is called in [`Metrics`] and logs
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
 }
 
 impl<S, IM, OM, T> MessageHandler<S, IM, OM> for &T {
@@ -432,6 +542,12 @@ macro_rules! define_events {
                     $($handler_enum::$handler(inner) => inner.handle_message(state, message)),+
                 }
             }
+
+            fn name(&self) -> &'static str {
+                match self {
+                    $($handler_enum::$handler(_) => stringify!($handler)),+
+                }
+            }
         }
 
         $(
@@ -443,3 +559,59 @@ macro_rules! define_events {
         )+
     };
 }
+
+#[cfg(test)]
+mod test {
+    use super::{EventLoop, Handled, Message, MessageHandler};
+
+    struct Ping;
+    impl Message<u32> for Ping {
+        fn update_state(self, state: &mut u32) {
+            *state += 1;
+        }
+    }
+
+    enum Handler {
+        Echo,
+        Broken,
+    }
+
+    impl MessageHandler<u32, Ping, Ping> for Handler {
+        fn handle_message(&mut self, _: &u32, _: &Ping) -> Option<Handled<Ping>> {
+            match self {
+                Self::Echo => Handled::single(Ping),
+                Self::Broken => panic!("Broken handler"),
+            }
+        }
+
+        fn name(&self) -> &'static str {
+            match self {
+                Self::Echo => "Echo",
+                Self::Broken => "Broken",
+            }
+        }
+    }
+
+    #[test]
+    fn panicking_handler_is_skipped() {
+        let mut event_loop: EventLoop<u32, Ping, Handler> = EventLoop::new()
+            .add_handler(Handler::Broken)
+            .add_handler(Handler::Echo);
+        let mut state = 0;
+
+        let out = event_loop.handle_message(Ping, &mut state);
+        assert_eq!(out.len(), 1);
+        assert_eq!(state, 1);
+
+        event_loop.handle_message(Ping, &mut state);
+        let metrics = &event_loop.metrics().handlers;
+        assert_eq!(metrics[0].name, "Broken");
+        assert_eq!(metrics[0].messages, 2);
+        assert_eq!(metrics[0].panics, 2);
+        assert_eq!(metrics[0].outputs, 0);
+        assert_eq!(metrics[1].name, "Echo");
+        assert_eq!(metrics[1].messages, 2);
+        assert_eq!(metrics[1].panics, 0);
+        assert_eq!(metrics[1].outputs, 2);
+    }
+}
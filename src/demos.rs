@@ -1,29 +1,38 @@
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
-    io::{ErrorKind, Read},
-    path::PathBuf,
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
     sync::mpsc::Sender,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
-use serde::{Deserialize, Serialize};
+use iced::widget::scrollable::Viewport;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tf2_monitor_core::{
+    atomic_write_file::AtomicWriteFile,
     demos::analyser::{
         self,
         progress::{self, Progress},
-        AnalysedDemo,
+        AnalysedDemo, DemoPlayer, TeamPeriod,
     },
-    settings::ConfigFilesError,
+    event_loop::HandlerMetrics,
+    masterbase,
+    players::records::Verdict,
+    settings::{instance, ConfigFilesError},
     steamid_ng::SteamID,
-    tf_demo_parser::demo::parser::analyser::Class,
+    tf_demo_parser::demo::parser::analyser::{Class, Team},
 };
 use thiserror::Error;
 use threadpool::ThreadPool;
 use tokio::{io::AsyncReadExt, sync::mpsc::UnboundedReceiver, task::JoinSet};
 
-use crate::{graph::KDAChart, gui::View, App, Message, APP};
+use crate::{app, graph::KDAChart, gui::View, search::search_match, App, Message};
+
+use self::bookmarks::{Bookmark, Bookmarks};
+
+pub mod bookmarks;
 
 pub const CLASSES: [Class; 9] = [
     Class::Scout,
@@ -41,26 +50,114 @@ pub const SORT_OPTIONS: &[SortBy] = &[SortBy::FileCreated, SortBy::FileSize, Sor
 pub const SORT_DIRECTIONS: &[SortDirection] =
     &[SortDirection::Ascending, SortDirection::Descending];
 
+/// Shows every demo on one page
+pub const ALL_DEMOS: usize = usize::MAX;
+pub const PER_PAGE_OPTIONS: &[DemosPerPage] = &[
+    DemosPerPage(50),
+    DemosPerPage(100),
+    DemosPerPage(500),
+    DemosPerPage(1000),
+    DemosPerPage(ALL_DEMOS),
+];
+
 pub type AnalysedDemoID = tf2_monitor_core::md5::Digest;
+/// Remembers which demos have been uploaded to the masterbase, by their hash
+const UPLOADED_DEMOS_FILE_NAME: &str = "uploaded_demos.json";
 type AnalysedDemoResult = (PathBuf, Option<(AnalysedDemoID, Box<AnalysedDemo>)>);
 
+/// How long to wait between checks that a finished demo has stopped growing
+const AUTO_ANALYSE_DELAY: Duration = Duration::from_secs(10);
+/// Demos that still haven't stopped growing after this many checks are left
+/// for the user to analyse
+const AUTO_ANALYSE_MAX_CHECKS: u32 = 6;
+/// Allowance for the time between a demo file being created and recording
+/// actually starting
+const AUTO_ANALYSE_SLACK: Duration = Duration::from_secs(60);
+
 pub struct State {
     pub demo_files: Vec<Demo>,
     pub demos_to_display: Vec<usize>,
     pub analysed_demos: HashMap<AnalysedDemoID, MaybeAnalysedDemo>,
+    /// Demos being or already manually uploaded to the masterbase
+    pub uploads: HashMap<AnalysedDemoID, DemoUpload>,
 
+    /// [`ALL_DEMOS`] to show them all on one page
     pub demos_per_page: usize,
     pub page: usize,
+    /// Which part of the demo list is on screen, so only those rows are built
+    pub list_viewport: ListViewport,
+    /// How long building the rows of the demo list takes, shown with the
+    /// event handler timings in the settings. The outputs are the rows built.
+    pub list_metrics: RefCell<HandlerMetrics>,
 
     /// Analysed demo view stuff
+    /// The player selected in analysed demos, kept apart from the one selected
+    /// in the other views so looking between them doesn't lose either
     pub viewing_player: Option<SteamID>,
     pub chart: KDAChart,
+    pub bookmarks: Bookmarks,
+    /// What is typed in to add a new bookmark
+    pub new_bookmark_time: String,
+    pub new_bookmark_label: String,
+    /// The demo tick of the bookmark being shown on the chart
+    pub highlighted_tick: Option<(AnalysedDemoID, u32)>,
+    /// Show verdicts and badges in the KDA table
+    pub show_player_details: bool,
+    /// Demos that finished recording and are waiting to be analysed
+    pub auto_analysis: Option<AutoAnalysis>,
+    /// Demos analysed while others were still being analysed, which are summarised
+    /// together once they're all done
+    pub finished_analyses: Vec<AnalysedDemoID>,
 
     pub request_analysis: Sender<(PathBuf, progress::Updater)>,
     #[allow(clippy::pub_underscore_fields, clippy::type_complexity)]
     pub _demo_analysis_output: RefCell<Option<UnboundedReceiver<AnalysedDemoResult>>>,
 }
 
+/// Demos are only analysed automatically once their files have stopped
+/// changing, so a demo still being written isn't analysed too early
+#[derive(Debug)]
+pub struct AutoAnalysis {
+    /// Demos created after this are the ones that finished recording
+    since: SystemTime,
+    /// The size of each demo file at the last check
+    sizes: HashMap<PathBuf, u64>,
+    checks: u32,
+    /// Whether the demo list is being refreshed for a check, rather than
+    /// waiting for the next one
+    refreshing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DemosPerPage(pub usize);
+
+impl Display for DemosPerPage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0 == ALL_DEMOS {
+            write!(f, "All")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// The visible part of the demo list, in pixels from the top of the list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ListViewport {
+    pub offset: f32,
+    pub height: f32,
+}
+
+impl Default for ListViewport {
+    fn default() -> Self {
+        // Until the list has been scrolled, enough for a tall window
+        Self {
+            offset: 0.0,
+            height: 1440.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Filters {
@@ -69,14 +166,133 @@ pub struct Filters {
 
     pub show_analysed: bool,
     pub show_non_analysed: bool,
+    /// Demos analysed by an older version of the analyser
+    pub show_outdated: bool,
 
     // Steamid (any format), name (case-insensitive, will include previous names if records exist)
-    pub contains_players: Vec<String>,
+    pub contains_players: Vec<PlayerFilter>,
 
     // Map, server name, IP, file name
     pub search: String,
 }
 
+/// One player in [`Filters::contains_players`], which demos either have to
+/// include or not include
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(from = "PlayerFilterRepr")]
+pub struct PlayerFilter {
+    pub player: String,
+    /// Only show demos the player is not in
+    pub exclude: bool,
+    pub team: TeamScope,
+}
+
+/// Older configs only have the player for each entry
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PlayerFilterRepr {
+    Player(String),
+    Full {
+        player: String,
+        #[serde(default)]
+        exclude: bool,
+        #[serde(default)]
+        team: TeamScope,
+    },
+}
+
+impl From<PlayerFilterRepr> for PlayerFilter {
+    fn from(repr: PlayerFilterRepr) -> Self {
+        match repr {
+            PlayerFilterRepr::Player(player) => Self::new(player),
+            PlayerFilterRepr::Full {
+                player,
+                exclude,
+                team,
+            } => Self {
+                player,
+                exclude,
+                team,
+            },
+        }
+    }
+}
+
+impl PlayerFilter {
+    #[must_use]
+    pub fn new(player: String) -> Self {
+        Self {
+            player,
+            exclude: false,
+            team: TeamScope::Any,
+        }
+    }
+
+    /// Whether a demo passes this filter. `is_player` says whether a player in
+    /// the demo is the one being filtered for.
+    fn passes(
+        &self,
+        analysed: &AnalysedDemo,
+        is_player: impl Fn(SteamID, &DemoPlayer) -> bool,
+    ) -> bool {
+        let user = analysed.players.get(&analysed.user);
+        let found = analysed
+            .players
+            .iter()
+            .any(|(s, p)| is_player(*s, p) && self.team.matches(user, p));
+
+        found != self.exclude
+    }
+}
+
+pub const TEAM_SCOPES: &[TeamScope] = &[TeamScope::Any, TeamScope::MyTeam, TeamScope::EnemyTeam];
+
+/// Which team a player has to have been on, compared to the user, to count
+/// as being in a demo
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TeamScope {
+    #[default]
+    Any,
+    MyTeam,
+    EnemyTeam,
+}
+
+impl Display for TeamScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Any => "Any team",
+            Self::MyTeam => "My team",
+            Self::EnemyTeam => "Enemy team",
+        };
+        write!(f, "{str}")
+    }
+}
+
+impl TeamScope {
+    /// Whether `player` was ever on the required team, going by the ticks
+    /// they and the user spent on each team. Players who swapped teams can
+    /// count as both.
+    #[must_use]
+    pub fn matches(self, user: Option<&DemoPlayer>, player: &DemoPlayer) -> bool {
+        let same_team = match self {
+            Self::Any => return true,
+            Self::MyTeam => true,
+            Self::EnemyTeam => false,
+        };
+        let Some(user) = user else {
+            return false;
+        };
+
+        let playing = |p: &&TeamPeriod| matches!(p.team, Team::Red | Team::Blue);
+        player.ticks_on_teams.iter().filter(playing).any(|p| {
+            user.ticks_on_teams.iter().filter(playing).any(|u| {
+                let overlaps = p.start <= u.start + u.duration && u.start <= p.start + p.duration;
+                overlaps && (p.team == u.team) == same_team
+            })
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum AnalysedDemoView {
     Players,
@@ -149,21 +365,49 @@ pub enum DemosMessage {
     Refresh,
     SetDemos(Vec<Demo>),
     SetPage(usize),
+    SetPerPage(DemosPerPage),
+    ScrolledList(Viewport),
     AnalyseDemo(usize),
     AnalyseAll,
+    /// Analyse a demo again that was analysed by an older version of the
+    /// analyser
+    ReanalyseDemo(usize),
+    ReanalyseOutdated,
     DemoAnalysed(AnalysedDemoResult),
+    /// Check whether demos that finished recording are ready to be analysed
+    AutoAnalyseCheck,
+    /// Upload a demo that was recorded while the monitor wasn't running
+    UploadDemo(usize),
+    DemoUploaded(AnalysedDemoID, Result<(), String>),
 
     SetAnalysedDemoView(AnalysedDemoView),
     InspectPlayer(SteamID),
+    /// Look up the Steam profiles of everyone in the demo
+    FetchProfiles(AnalysedDemoID),
+    ShowPlayerDetails(bool),
+
+    SetNewBookmarkTime(String),
+    SetNewBookmarkLabel(String),
+    /// Bookmark the typed time in the demo
+    AddBookmark(AnalysedDemoID),
+    /// Bookmark the kill with the given index in the demo
+    BookmarkKill(AnalysedDemoID, usize),
+    SetBookmarkLabel(AnalysedDemoID, usize, String),
+    RemoveBookmark(AnalysedDemoID, usize),
+    /// Highlight the bookmark on the chart
+    ShowBookmark(AnalysedDemoID, usize),
 
     FilterSortBy(SortBy),
     FilterSortDirection(SortDirection),
     FilterShowAnalysed(bool),
     FilterShowNonAnalysed(bool),
+    FilterShowOutdated(bool),
     FilterContainsPlayerUpdate(String),
     FilterContainsPlayerAdd,
     FilterSearchUpdate(String),
     FilterRemovePlayer(usize),
+    FilterTogglePlayerExclude(usize),
+    FilterSetPlayerTeam(usize, TeamScope),
     ApplyFilters,
     ClearFilters,
 }
@@ -182,6 +426,12 @@ pub enum MaybeAnalysedDemo {
     Uninit,
 }
 
+pub enum DemoUpload {
+    InProgress(progress::Checker),
+    Failed(String),
+    Uploaded,
+}
+
 impl MaybeAnalysedDemo {
     #[must_use]
     pub const fn get_demo(&self) -> Option<&AnalysedDemo> {
@@ -204,6 +454,14 @@ impl MaybeAnalysedDemo {
         }
         false
     }
+    /// Analysed, but by an older version of the analyser
+    #[must_use]
+    pub const fn is_outdated(&self) -> bool {
+        if let Self::Analysed(demo) = self {
+            return demo.is_outdated();
+        }
+        false
+    }
     #[must_use]
     pub fn analysing_progress(&self) -> Option<Progress> {
         if let Self::InProgress(checker) = self {
@@ -215,22 +473,219 @@ impl MaybeAnalysedDemo {
 
 impl State {
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(demos_per_page: usize) -> Self {
         let (request_tx, completed_rx) = spawn_demo_analyser_thread();
 
-        Self {
+        let mut state = Self {
             demo_files: Vec::new(),
             demos_to_display: Vec::new(),
             analysed_demos: HashMap::new(),
+            uploads: HashMap::new(),
 
-            demos_per_page: 50,
+            demos_per_page: demos_per_page.max(1),
             page: 0,
+            list_viewport: ListViewport::default(),
+            list_metrics: RefCell::new(HandlerMetrics::new("Demo list rows")),
 
             viewing_player: None,
             chart: KDAChart::default(),
+            bookmarks: Bookmarks::load(),
+            new_bookmark_time: String::new(),
+            new_bookmark_label: String::new(),
+            highlighted_tick: None,
+            show_player_details: false,
+            auto_analysis: None,
+            finished_analyses: Vec::new(),
 
             request_analysis: request_tx,
             _demo_analysis_output: RefCell::new(Some(completed_rx)),
+        };
+
+        state.reload_uploaded_demos();
+        state
+    }
+
+    /// How many demos are being analysed in the background
+    #[must_use]
+    pub fn num_analysing(&self) -> usize {
+        self.analysed_demos
+            .values()
+            .filter(|d| d.is_analyzing())
+            .count()
+    }
+
+    /// The demo file with the given hash, if it is in the list of demos
+    #[must_use]
+    pub fn demo_file(&self, id: AnalysedDemoID) -> Option<&Demo> {
+        self.demo_files.iter().find(|d| d.analysed == id)
+    }
+
+    /// Send a demo off to be analysed in the background, replacing any
+    /// previous analysis of it
+    fn request_analysis(&mut self, demo_index: usize) {
+        let Some(demo) = self.demo_files.get(demo_index) else {
+            return;
+        };
+
+        let (updater, checker) = progress::create_pair();
+        self.analysed_demos
+            .insert(demo.analysed, MaybeAnalysedDemo::InProgress(checker));
+
+        self.request_analysis
+            .send((demo.path.clone(), updater))
+            .expect("Couldn't request analysis of demo. Demo analyser thread ded?");
+    }
+
+    /// Analyse the demo that just finished recording once TF2 has finished
+    /// writing it. `recording_seconds` is how long it was recording for, to
+    /// find which demo file it was.
+    pub fn queue_auto_analysis(&mut self, recording_seconds: f32) -> iced::Command<Message> {
+        let recording = Duration::try_from_secs_f32(recording_seconds).unwrap_or_default();
+        let since = SystemTime::now()
+            .checked_sub(recording + AUTO_ANALYSE_SLACK)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        match &mut self.auto_analysis {
+            // Already waiting on another demo
+            Some(pending) => {
+                pending.since = pending.since.min(since);
+                pending.checks = 0;
+                iced::Command::none()
+            }
+            None => {
+                self.auto_analysis = Some(AutoAnalysis {
+                    since,
+                    sizes: HashMap::new(),
+                    checks: 0,
+                    refreshing: false,
+                });
+                Self::schedule_auto_analysis_check()
+            }
+        }
+    }
+
+    fn schedule_auto_analysis_check() -> iced::Command<Message> {
+        iced::Command::perform(tokio::time::sleep(AUTO_ANALYSE_DELAY), |()| {
+            Message::Demos(DemosMessage::AutoAnalyseCheck)
+        })
+    }
+
+    /// Analyses the recently finished demos whose files are the same size as
+    /// at the last check. Demos still being uploaded to the masterbase are
+    /// waited on as well.
+    fn run_auto_analysis(state: &mut App) -> iced::Command<Message> {
+        if !state
+            .demos
+            .auto_analysis
+            .as_ref()
+            .is_some_and(|pending| pending.refreshing)
+        {
+            return iced::Command::none();
+        }
+        let Some(mut pending) = state.demos.auto_analysis.take() else {
+            return iced::Command::none();
+        };
+        pending.refreshing = false;
+        pending.checks += 1;
+
+        let uploading_live = state
+            .mac
+            .upload_status
+            .session_open
+            .then_some(state.mac.upload_status.demo_name.as_deref())
+            .flatten();
+
+        let mut ready = Vec::new();
+        let mut waiting = false;
+        for (i, d) in state.demos.demo_files.iter().enumerate() {
+            if d.created < pending.since || state.demos.analysed_demos.contains_key(&d.analysed) {
+                continue;
+            }
+
+            let stopped_growing =
+                pending.sizes.insert(d.path.clone(), d.file_size) == Some(d.file_size);
+            let uploading = uploading_live == Some(d.name.as_str())
+                || matches!(
+                    state.demos.uploads.get(&d.analysed),
+                    Some(DemoUpload::InProgress(_))
+                );
+
+            if stopped_growing && !uploading {
+                ready.push(i);
+            } else {
+                waiting = true;
+            }
+        }
+
+        for i in ready {
+            tracing::info!(
+                "Automatically analysing {:?}",
+                state.demos.demo_files[i].name
+            );
+            state.demos.request_analysis(i);
+        }
+
+        if !waiting {
+            return iced::Command::none();
+        }
+        if pending.checks >= AUTO_ANALYSE_MAX_CHECKS {
+            tracing::warn!("Gave up waiting for finished demos to stop changing to analyse them");
+            return iced::Command::none();
+        }
+
+        state.demos.auto_analysis = Some(pending);
+        Self::schedule_auto_analysis_check()
+    }
+
+    /// Once nothing else is being analysed, shows a summary of the demo that
+    /// was, or how many were if there were several
+    fn summarise_finished_analyses(state: &mut App) {
+        if state.demos.num_analysing() > 0 {
+            return;
+        }
+
+        let finished = std::mem::take(&mut state.demos.finished_analyses);
+        if !state.settings.demo_summaries {
+            return;
+        }
+
+        match finished.as_slice() {
+            [] => {}
+            &[hash] => {
+                let Some(analysed) = state
+                    .demos
+                    .analysed_demos
+                    .get(&hash)
+                    .and_then(MaybeAnalysedDemo::get_demo)
+                else {
+                    return;
+                };
+                let summary = match_summary(state, analysed);
+                state.notify_with_action(
+                    summary,
+                    ("View", Message::SetView(View::AnalysedDemo(hash))),
+                );
+            }
+            finished => {
+                state.notify_with_action(
+                    format!("{} demos analysed", finished.len()),
+                    ("View", Message::SetView(View::Demos)),
+                );
+            }
+        }
+    }
+
+    /// Forget uploads that have finished and read which demos have been
+    /// uploaded from disk again, e.g. after switching profiles
+    pub fn reload_uploaded_demos(&mut self) {
+        self.uploads
+            .retain(|_, u| matches!(u, DemoUpload::InProgress(_)));
+
+        match load_uploaded_demos() {
+            Ok(hashes) => self
+                .uploads
+                .extend(hashes.into_iter().map(|h| (h, DemoUpload::Uploaded))),
+            Err(e) => tracing::error!("Failed to load list of uploaded demos: {e}"),
         }
     }
 
@@ -246,6 +701,21 @@ impl State {
                 return Self::refresh_demos(state);
             }
             DemosMessage::SetPage(page) => state.demos.page = page,
+            DemosMessage::SetPerPage(DemosPerPage(per_page)) => {
+                // Stay around the same demos
+                let first = state.demos.page.saturating_mul(state.demos.demos_per_page);
+                state.settings.demos_per_page = per_page;
+                state.demos.demos_per_page = per_page.max(1);
+                state.demos.page = first / state.demos.demos_per_page;
+            }
+            DemosMessage::ScrolledList(viewport) => {
+                state.demos.list_viewport = ListViewport {
+                    offset: viewport.absolute_offset().y,
+                    height: viewport.bounds().height,
+                };
+                state.view_ui.entry(View::Demos).or_default().scroll =
+                    Some(viewport.relative_offset());
+            }
             DemosMessage::SetDemos(demo_files) => {
                 state.demos.demo_files = demo_files;
                 state.update_demo_list();
@@ -275,6 +745,7 @@ impl State {
                         |r| Message::Demos(DemosMessage::DemoAnalysed((PathBuf::new(), r))),
                     ));
                 }
+                commands.push(Self::run_auto_analysis(state));
                 return iced::Command::batch(commands);
             }
             DemosMessage::AnalyseDemo(demo_index) => {
@@ -291,17 +762,41 @@ impl State {
                     return iced::Command::none();
                 }
 
-                let (updater, checker) = progress::create_pair();
-                state
+                state.demos.request_analysis(demo_index);
+            }
+            DemosMessage::ReanalyseDemo(demo_index) => {
+                let Some(demo) = state.demos.demo_files.get(demo_index) else {
+                    return iced::Command::none();
+                };
+
+                if state
                     .demos
                     .analysed_demos
-                    .insert(demo.analysed, MaybeAnalysedDemo::InProgress(checker));
-
-                state
+                    .get(&demo.analysed)
+                    .is_some_and(MaybeAnalysedDemo::is_outdated)
+                {
+                    state.demos.request_analysis(demo_index);
+                }
+            }
+            DemosMessage::ReanalyseOutdated => {
+                let outdated: Vec<usize> = state
                     .demos
-                    .request_analysis
-                    .send((demo.path.clone(), updater))
-                    .expect("Couldn't request analysis of demo. Demo analyser thread ded?");
+                    .demo_files
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, d)| {
+                        state
+                            .demos
+                            .analysed_demos
+                            .get(&d.analysed)
+                            .is_some_and(MaybeAnalysedDemo::is_outdated)
+                    })
+                    .map(|(i, _)| i)
+                    .collect();
+
+                for i in outdated {
+                    state.demos.request_analysis(i);
+                }
             }
             DemosMessage::DemoAnalysed((demo_path, analysed_demo)) => match analysed_demo {
                 Some((hash, analysed_demo)) => {
@@ -310,24 +805,63 @@ impl State {
                         .analysed_demos
                         .insert(hash, MaybeAnalysedDemo::Analysed(analysed_demo));
 
-                    if let View::AnalysedDemo(demo) = state.settings.view {
-                        if state
+                    if state.settings.view == View::AnalysedDemo(hash) {
+                        state.demos.chart = KDAChart::new(state, hash, state.demos.viewing_player);
+                    }
+
+                    // Demos loaded from the cache have already been counted,
+                    // unless they were analysed before shared playtime was
+                    let fresh = !demo_path.as_os_str().is_empty();
+                    if fresh || !state.mac.players.shared_playtime.contains(hash) {
+                        if let Some(analysed) = state
                             .demos
-                            .demo_files
-                            .get(demo)
-                            .is_some_and(|d| d.analysed == hash)
+                            .analysed_demos
+                            .get(&hash)
+                            .and_then(MaybeAnalysedDemo::get_demo)
                         {
-                            state.demos.chart = KDAChart::new(state, demo, state.selected_player);
+                            state.mac.players.shared_playtime.add_demo(hash, analysed);
                         }
                     }
 
                     tracing::debug!("Successfully got analysed demo {demo_path:?}");
+
+                    // Demos loaded from the cache have no path
+                    if !demo_path.as_os_str().is_empty() {
+                        state.demos.finished_analyses.push(hash);
+                        Self::summarise_finished_analyses(state);
+                    }
                 }
                 None if !demo_path.as_os_str().is_empty() => {
                     tracing::error!("Failed to analyse demo {demo_path:?}");
+
+                    // So it can be analysed again, and doesn't hold up the summary
+                    if let Some(hash) = state
+                        .demos
+                        .demo_files
+                        .iter()
+                        .find(|d| d.path == demo_path)
+                        .map(|d| d.analysed)
+                    {
+                        if state
+                            .demos
+                            .analysed_demos
+                            .get(&hash)
+                            .is_some_and(MaybeAnalysedDemo::is_analyzing)
+                        {
+                            state.demos.analysed_demos.remove(&hash);
+                        }
+                    }
+                    Self::summarise_finished_analyses(state);
                 }
                 None => {}
             },
+            DemosMessage::AutoAnalyseCheck => {
+                let Some(pending) = &mut state.demos.auto_analysis else {
+                    return iced::Command::none();
+                };
+                pending.refreshing = true;
+                return Self::refresh_demos(state);
+            }
             DemosMessage::AnalyseAll => {
                 for d in &state.demos.demo_files {
                     if state
@@ -352,6 +886,33 @@ impl State {
                         .expect("Couldn't request analysis of demo. Demo analyser thread ded?");
                 }
             }
+            DemosMessage::UploadDemo(demo_index) => {
+                return Self::upload_demo(state, demo_index);
+            }
+            DemosMessage::DemoUploaded(hash, result) => {
+                let name = state
+                    .demos
+                    .demo_files
+                    .iter()
+                    .find(|d| d.analysed == hash)
+                    .map_or_else(|| format!("{hash:x}"), |d| d.name.clone());
+
+                match result {
+                    Ok(()) => {
+                        state.demos.uploads.insert(hash, DemoUpload::Uploaded);
+                        state.notify(format!("Uploaded {name} to the Masterbase"));
+
+                        if let Err(e) = save_uploaded_demos(&state.demos.uploads) {
+                            tracing::error!("Failed to save list of uploaded demos: {e}");
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to upload {name}: {e}");
+                        state.notify(format!("Failed to upload {name}: {e}"));
+                        state.demos.uploads.insert(hash, DemoUpload::Failed(e));
+                    }
+                }
+            }
             DemosMessage::ApplyFilters => {
                 state.update_demo_list();
             }
@@ -377,6 +938,10 @@ impl State {
                 state.settings.demo_filters.show_non_analysed = show;
                 state.update_demo_list();
             }
+            DemosMessage::FilterShowOutdated(show) => {
+                state.settings.demo_filters.show_outdated = show;
+                state.update_demo_list();
+            }
             DemosMessage::FilterContainsPlayerUpdate(player) => {
                 if let Some(last) = state
                     .settings
@@ -385,21 +950,21 @@ impl State {
                     .iter_mut()
                     .last()
                 {
-                    *last = player;
+                    last.player = player;
                 } else {
-                    state.settings.demo_filters.contains_players = vec![player];
+                    state.settings.demo_filters.contains_players = vec![PlayerFilter::new(player)];
                 }
 
                 // state.update_demo_list();
             }
             DemosMessage::FilterContainsPlayerAdd => {
                 if let Some(last) = state.settings.demo_filters.contains_players.iter().last() {
-                    if !last.trim().is_empty() {
+                    if !last.player.trim().is_empty() {
                         state
                             .settings
                             .demo_filters
                             .contains_players
-                            .push(String::new());
+                            .push(PlayerFilter::default());
                     }
                 } else {
                     state.settings.demo_filters.contains_players = vec![];
@@ -415,13 +980,207 @@ impl State {
                 state.settings.demo_filters.contains_players.remove(i);
                 state.update_demo_list();
             }
-            DemosMessage::SetAnalysedDemoView(view) => state.settings.analysed_demo_view = view,
+            DemosMessage::FilterTogglePlayerExclude(i) => {
+                if let Some(f) = state.settings.demo_filters.contains_players.get_mut(i) {
+                    f.exclude = !f.exclude;
+                    state.update_demo_list();
+                }
+            }
+            DemosMessage::FilterSetPlayerTeam(i, team) => {
+                if let Some(f) = state.settings.demo_filters.contains_players.get_mut(i) {
+                    f.team = team;
+                    state.update_demo_list();
+                }
+            }
+            DemosMessage::SetAnalysedDemoView(view) => {
+                state.settings.analysed_demo_view = view;
+                state
+                    .view_ui
+                    .entry(state.settings.view)
+                    .or_default()
+                    .analysed_demo_tab = Some(view);
+            }
             DemosMessage::InspectPlayer(p) => state.demos.viewing_player = Some(p),
+            DemosMessage::ShowPlayerDetails(show) => state.demos.show_player_details = show,
+            DemosMessage::FetchProfiles(demo) => {
+                let Some(players) = state
+                    .demos
+                    .analysed_demos
+                    .get(&demo)
+                    .and_then(|d| d.get_demo())
+                    .map(|a| a.players.keys().copied().collect::<Vec<_>>())
+                else {
+                    return iced::Command::none();
+                };
+
+                // Pfps for players whose profiles are already known, the rest
+                // come once their profiles do
+                let mut commands: Vec<_> = players
+                    .iter()
+                    .map(|&s| state.request_pfp_lookup_for_existing_player(s))
+                    .collect();
+                commands.push(state.request_profile_lookup(players));
+                return iced::Command::batch(commands);
+            }
+            DemosMessage::SetNewBookmarkTime(time) => state.demos.new_bookmark_time = time,
+            DemosMessage::SetNewBookmarkLabel(label) => state.demos.new_bookmark_label = label,
+            DemosMessage::AddBookmark(demo) => {
+                let Some(analysed) = state
+                    .demos
+                    .analysed_demos
+                    .get(&demo)
+                    .and_then(|d| d.get_demo())
+                else {
+                    return iced::Command::none();
+                };
+
+                let Some(seconds) = bookmarks::parse_time(&state.demos.new_bookmark_time) else {
+                    state.notify(format!(
+                        "\"{}\" isn't a time in the demo. Use minutes:seconds, e.g. 12:30",
+                        state.demos.new_bookmark_time.trim()
+                    ));
+                    return iced::Command::none();
+                };
+
+                let bookmark = Bookmark {
+                    tick: bookmarks::seconds_to_tick(analysed, seconds),
+                    label: std::mem::take(&mut state.demos.new_bookmark_label),
+                };
+                state.demos.new_bookmark_time.clear();
+                state.demos.bookmarks.add(demo, bookmark);
+                state.demos.bookmarks.save();
+            }
+            DemosMessage::BookmarkKill(demo, kill) => {
+                let Some(analysed) = state
+                    .demos
+                    .analysed_demos
+                    .get(&demo)
+                    .and_then(|d| d.get_demo())
+                else {
+                    return iced::Command::none();
+                };
+                let Some(death) = analysed.kills.get(kill) else {
+                    return iced::Command::none();
+                };
+
+                let name = |s: SteamID| {
+                    analysed
+                        .players
+                        .get(&s)
+                        .map_or_else(|| s.steam3(), |p| p.name.clone())
+                };
+                let label = match death.attacker {
+                    Some(attacker) if attacker != death.victim => format!(
+                        "{} killed {} ({})",
+                        name(attacker),
+                        name(death.victim),
+                        death.weapon
+                    ),
+                    _ => format!("{} died", name(death.victim)),
+                };
+
+                let bookmark = Bookmark {
+                    tick: death.tick.0,
+                    label,
+                };
+                state.demos.bookmarks.add(demo, bookmark);
+                state.demos.bookmarks.save();
+            }
+            DemosMessage::SetBookmarkLabel(demo, i, label) => {
+                state.demos.bookmarks.set_label(demo, i, label);
+                state.demos.bookmarks.save();
+            }
+            DemosMessage::RemoveBookmark(demo, i) => {
+                state.demos.bookmarks.remove(demo, i);
+                state.demos.bookmarks.save();
+            }
+            DemosMessage::ShowBookmark(demo, i) => {
+                let Some(bookmark) = state.demos.bookmarks.get(demo).get(i) else {
+                    return iced::Command::none();
+                };
+                state.demos.highlighted_tick = Some((demo, bookmark.tick));
+
+                // The chart is only on the players tab, for the selected player
+                state.settings.analysed_demo_view = AnalysedDemoView::Players;
+                state
+                    .view_ui
+                    .entry(state.settings.view)
+                    .or_default()
+                    .analysed_demo_tab = Some(AnalysedDemoView::Players);
+                if state.demos.viewing_player.is_none() {
+                    state.demos.viewing_player = state
+                        .demos
+                        .analysed_demos
+                        .get(&demo)
+                        .and_then(|d| d.get_demo())
+                        .map(|a| a.user);
+                }
+                state.demos.chart = KDAChart::new(state, demo, state.demos.viewing_player);
+            }
         }
 
         iced::Command::none()
     }
 
+    fn upload_demo(state: &mut App, demo_index: usize) -> iced::Command<Message> {
+        let Some(demo) = state.demos.demo_files.get(demo_index) else {
+            return iced::Command::none();
+        };
+
+        if matches!(
+            state.demos.uploads.get(&demo.analysed),
+            Some(DemoUpload::Uploaded | DemoUpload::InProgress(_))
+        ) {
+            return iced::Command::none();
+        }
+
+        // The masterbase only allows one session at a time for each key
+        let reason = if state.mac.settings.masterbase_key.trim().is_empty() {
+            Some("Set your Masterbase key in the settings first.")
+        } else if state.mac.upload_status.session_open {
+            Some("Wait for the demo currently being recorded to finish uploading.")
+        } else if state
+            .demos
+            .uploads
+            .values()
+            .any(|u| matches!(u, DemoUpload::InProgress(_)))
+        {
+            Some("Wait for the other demo to finish uploading.")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            let name = demo.name.clone();
+            state.notify(format!("Can't upload {name}. {reason}"));
+            return iced::Command::none();
+        }
+
+        let hash = demo.analysed;
+        let path = demo.path.clone();
+        let name = demo.name.clone();
+        let host = state.mac.settings.masterbase_host.clone();
+        let key = state.mac.settings.masterbase_key.clone();
+        let http = state.mac.settings.masterbase_http;
+
+        let (updater, checker) = progress::create_pair();
+        state
+            .demos
+            .uploads
+            .insert(hash, DemoUpload::InProgress(checker));
+
+        iced::Command::perform(
+            async move {
+                let bytes = tokio::fs::read(&path)
+                    .await
+                    .map_err(|e| format!("Couldn't read demo: {e}"))?;
+                masterbase::upload_demo_file(host, key, http, &name, &bytes, updater)
+                    .await
+                    .map_err(|e| masterbase::error_message(&e))
+            },
+            move |r| Message::Demos(DemosMessage::DemoUploaded(hash, r)),
+        )
+    }
+
     /// Clear the current store of demo files and search the directories for new demo files
     pub fn refresh_demos(state: &App) -> iced::Command<Message> {
         let mut dirs_to_search = state.settings.demo_directories.clone();
@@ -503,6 +1262,63 @@ impl Default for State {
     }
 }
 
+/// The map and length of the match, how the user did, who killed them the most,
+/// and any marked cheaters that were in it
+fn match_summary(state: &App, analysed: &AnalysedDemo) -> String {
+    let mut summary = format!(
+        "{} ({})",
+        analysed.header.map,
+        crate::gui::format_time(analysed.header.duration as u32)
+    );
+
+    // The name in the demo header doesn't always match, e.g. if the user
+    // changed their name since
+    let user = Some(analysed.user)
+        .into_iter()
+        .chain(state.mac.settings.steam_user)
+        .find_map(|s| analysed.players.get(&s).map(|p| (s, p)));
+
+    if let Some((user, player)) = user {
+        summary.push_str(&format!(
+            ": {}/{}/{}",
+            player.kills.len(),
+            player.deaths.len(),
+            player.assists.len()
+        ));
+
+        let mut killers: HashMap<SteamID, usize> = HashMap::new();
+        for attacker in player
+            .deaths
+            .iter()
+            .filter_map(|&d| analysed.kills.get(d)?.attacker)
+            .filter(|&a| a != user)
+        {
+            *killers.entry(attacker).or_default() += 1;
+        }
+        if let Some((killer, kills)) = killers.into_iter().max_by_key(|&(_, k)| k) {
+            let name = analysed
+                .players
+                .get(&killer)
+                .map_or_else(|| killer.steam3(), |p| p.name.clone());
+            summary.push_str(&format!(", killed most by {name} ({kills})"));
+        }
+    }
+
+    let cheaters: Vec<&str> = analysed
+        .players
+        .iter()
+        .filter(|(&s, _)| state.mac.players.verdict(s) == Verdict::Cheater)
+        .map(|(_, p)| p.name.as_str())
+        .collect();
+    if cheaters.is_empty() {
+        summary.push_str(". No marked cheaters.");
+    } else {
+        summary.push_str(&format!(". Marked cheaters: {}", cheaters.join(", ")));
+    }
+
+    summary
+}
+
 // Spawn a thread with a thread pool to analyse demos. Requests for demos to be analysed
 // can be sent over the channel and their result will eventually come back over the other one.
 fn spawn_demo_analyser_thread() -> (
@@ -560,10 +1376,16 @@ enum CachedDemoError {
     RmpEnc(#[from] rmp_serde::encode::Error),
     #[error("Rmp: {0}")]
     RmpDec(#[from] rmp_serde::decode::Error),
+    #[error("Json: {0}")]
+    Json(#[from] serde_json::Error),
 }
 
 fn cache_analysed_demo(hash: &AnalysedDemoID, demo: &AnalysedDemo) -> Result<(), CachedDemoError> {
-    let dir = tf2_monitor_core::settings::Settings::locate_config_directory(APP)?;
+    if instance::is_read_only() {
+        return Ok(());
+    }
+
+    let dir = tf2_monitor_core::settings::Settings::locate_config_directory(app())?;
     let dir = dir.join("analysed_demos");
 
     if !dir.try_exists()? {
@@ -573,15 +1395,86 @@ fn cache_analysed_demo(hash: &AnalysedDemoID, demo: &AnalysedDemo) -> Result<(),
     let bytes = rmp_serde::to_vec(demo)?;
 
     let file_path = dir.join(format!("{hash:x}.bin"));
-    std::fs::write(file_path, bytes)?;
+    write_atomically(&file_path, &bytes)?;
+
+    Ok(())
+}
+
+fn load_uploaded_demos() -> Result<Vec<AnalysedDemoID>, CachedDemoError> {
+    let file_path = tf2_monitor_core::settings::Settings::locate_config_directory(app())?
+        .join(UPLOADED_DEMOS_FILE_NAME);
+
+    let contents = match std::fs::read_to_string(file_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let hashes: Vec<String> = serde_json::from_str(&contents)?;
+    Ok(hashes.iter().filter_map(|h| parse_hash(h)).collect())
+}
+
+fn save_uploaded_demos(
+    uploads: &HashMap<AnalysedDemoID, DemoUpload>,
+) -> Result<(), CachedDemoError> {
+    if instance::is_read_only() {
+        return Ok(());
+    }
+
+    let file_path = tf2_monitor_core::settings::Settings::locate_config_directory(app())?
+        .join(UPLOADED_DEMOS_FILE_NAME);
 
+    let mut hashes: Vec<String> = uploads
+        .iter()
+        .filter(|(_, u)| matches!(u, DemoUpload::Uploaded))
+        .map(|(h, _)| format!("{h:x}"))
+        .collect();
+    hashes.sort();
+
+    write_atomically(&file_path, serde_json::to_string(&hashes)?.as_bytes())?;
     Ok(())
 }
 
+/// Replaces the file, so it's never left half written if the app is closed
+/// while writing it
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = AtomicWriteFile::open(path)?;
+    file.write_all(contents)?;
+    file.commit()
+}
+
+fn parse_hash(hex: &str) -> Option<AnalysedDemoID> {
+    if hex.len() != 32 {
+        return None;
+    }
+
+    let mut bytes = [0u8; 16];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(tf2_monitor_core::md5::Digest(bytes))
+}
+
+/// Analysed demo IDs are stored as their hex string, the same as the cached
+/// demo file names
+///
+/// # Errors
+/// If the serializer fails
+pub fn serialize_id<S: Serializer>(id: &AnalysedDemoID, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&format!("{id:x}"))
+}
+
+/// # Errors
+/// If the value isn't the hex string of an MD5 hash
+pub fn deserialize_id<'de, D: Deserializer<'de>>(d: D) -> Result<AnalysedDemoID, D::Error> {
+    let s: String = Deserialize::deserialize(d)?;
+    parse_hash(&s).ok_or_else(|| serde::de::Error::custom(format!("Invalid demo hash \"{s}\"")))
+}
+
 async fn read_cached_demo(
     hash: AnalysedDemoID,
 ) -> Result<(AnalysedDemoID, Box<AnalysedDemo>), CachedDemoError> {
-    let dir = tf2_monitor_core::settings::Settings::locate_config_directory(APP)?;
+    let dir = tf2_monitor_core::settings::Settings::locate_config_directory(app())?;
     let dir = dir.join("analysed_demos");
     let file_path = dir.join(format!("{hash:x}.bin"));
 
@@ -599,6 +1492,7 @@ impl Filters {
             direction: SortDirection::Descending,
             show_analysed: true,
             show_non_analysed: true,
+            show_outdated: true,
             contains_players: Vec::new(),
             search: String::new(),
         }
@@ -610,7 +1504,7 @@ impl Filters {
             .demo_filters
             .contains_players
             .iter()
-            .map(|s| SteamID::try_from(s.as_str()).ok())
+            .map(|f| SteamID::try_from(f.player.trim()).ok())
             .collect();
 
         let mut demos: Vec<(usize, &Demo)> = state
@@ -618,12 +1512,11 @@ impl Filters {
             .demo_files
             .iter()
             .enumerate()
-            // Filter analysed / non-analysed
-            .filter(|(_, d)| {
-                self.show_analysed || !state.demos.analysed_demos.contains_key(&d.analysed)
-            })
-            .filter(|(_, d)| {
-                self.show_non_analysed || state.demos.analysed_demos.contains_key(&d.analysed)
+            // Filter analysed / outdated / non-analysed
+            .filter(|(_, d)| match state.demos.analysed_demos.get(&d.analysed) {
+                None => self.show_non_analysed,
+                Some(a) if a.is_outdated() => self.show_outdated,
+                Some(_) => self.show_analysed,
             })
             // Search bar
             .filter(|(_, d)| {
@@ -638,16 +1531,13 @@ impl Filters {
                     .and_then(|d| d.get_demo());
 
                 for term in self.search.split_whitespace() {
-                    let lower_term = term.to_lowercase();
-
                     // Map
-                    if analysed.is_some_and(|a| a.header.map.to_lowercase().contains(&lower_term)) {
+                    if analysed.is_some_and(|a| search_match(&a.header.map, term)) {
                         continue;
                     }
 
                     // Server name
-                    if analysed.is_some_and(|a| a.server_name.to_lowercase().contains(&lower_term))
-                    {
+                    if analysed.is_some_and(|a| search_match(&a.server_name, term)) {
                         continue;
                     }
 
@@ -657,7 +1547,7 @@ impl Filters {
                     }
 
                     // File name
-                    if d.name.to_lowercase().contains(&lower_term) {
+                    if search_match(&d.name, term) {
                         continue;
                     }
 
@@ -669,7 +1559,7 @@ impl Filters {
             // Filter players
             .filter(|(_, d)| {
                 let players = &state.settings.demo_filters.contains_players;
-                if players.is_empty() || (players.len() == 1 && players[0].trim().is_empty()) {
+                if players.iter().all(|f| f.player.trim().is_empty()) {
                     return true;
                 }
 
@@ -683,43 +1573,37 @@ impl Filters {
                     return false;
                 };
 
-                'outer: for (i, searched_player) in players.iter().enumerate() {
-                    let searched_lower = searched_player.to_lowercase();
-                    for (s, p) in &analysed.players {
+                players.iter().enumerate().all(|(i, filter)| {
+                    let searched_player = filter.player.trim();
+                    if searched_player.is_empty() {
+                        return true;
+                    }
+
+                    filter.passes(analysed, |s, p| {
                         // SteamID - Ensure player_steamids is the same length as players
-                        if player_steamids
+                        player_steamids
                             .get(i)
                             .and_then(Option::as_ref)
-                            .is_some_and(|s2| s == s2)
-                        {
-                            continue 'outer;
-                        }
+                            .is_some_and(|s2| s == *s2)
 
                         // Name in demo
-                        if p.name.to_lowercase().contains(&searched_lower) {
-                            continue 'outer;
-                        }
+                        || search_match(&p.name, searched_player)
 
                         // Steam name
-                        if state.mac.players.steam_info.get(s).is_some_and(|si| {
-                            si.account_name.to_lowercase().contains(&searched_lower)
-                        }) {
-                            continue 'outer;
-                        }
+                        || state
+                            .mac
+                            .players
+                            .steam_info
+                            .get(&s)
+                            .is_some_and(|si| search_match(&si.account_name, searched_player))
 
                         // Previous names
-                        if state.mac.players.records.get(s).is_some_and(|r| {
-                            r.previous_names()
-                                .iter()
-                                .any(|pn| pn.to_lowercase().contains(&searched_lower))
-                        }) {
-                            continue 'outer;
-                        }
-                    }
-                    return false;
-                }
-
-                true
+                        || state.mac.players.records.get(&s).is_some_and(|r| {
+                            r.previous_name_strs()
+                                .any(|pn| search_match(pn, searched_player))
+                        })
+                    })
+                })
             })
             .collect();
 
@@ -767,3 +1651,72 @@ impl SortDirection {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use tf2_monitor_core::{
+        demos::analyser::{DemoPlayer, TeamPeriod},
+        tf_demo_parser::demo::parser::analyser::Team,
+    };
+
+    use super::{Filters, PlayerFilter, TeamScope};
+
+    fn player(periods: &[(Team, u32, u32)]) -> DemoPlayer {
+        DemoPlayer {
+            ticks_on_teams: periods
+                .iter()
+                .map(|&(team, start, duration)| TeamPeriod {
+                    team,
+                    start,
+                    duration,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn team_scope() {
+        let user = player(&[(Team::Red, 0, 1000)]);
+        let teammate = player(&[(Team::Red, 100, 500)]);
+        let enemy = player(&[(Team::Blue, 0, 1000)]);
+        let spectator = player(&[(Team::Spectator, 0, 1000)]);
+        // Was on blue, then swapped to red after the user left
+        let late_swap = player(&[(Team::Blue, 0, 800), (Team::Red, 1200, 500)]);
+
+        assert!(TeamScope::MyTeam.matches(Some(&user), &teammate));
+        assert!(!TeamScope::EnemyTeam.matches(Some(&user), &teammate));
+        assert!(TeamScope::EnemyTeam.matches(Some(&user), &enemy));
+        assert!(!TeamScope::MyTeam.matches(Some(&user), &enemy));
+        assert!(!TeamScope::MyTeam.matches(Some(&user), &spectator));
+        assert!(!TeamScope::EnemyTeam.matches(Some(&user), &spectator));
+        assert!(TeamScope::EnemyTeam.matches(Some(&user), &late_swap));
+        assert!(!TeamScope::MyTeam.matches(Some(&user), &late_swap));
+
+        assert!(TeamScope::Any.matches(None, &enemy));
+        assert!(!TeamScope::EnemyTeam.matches(None, &enemy));
+    }
+
+    #[test]
+    fn old_player_filters_load() {
+        let filters: Filters =
+            serde_json::from_str(r#"{"contains_players": ["pootis", "[U:1:123]"]}"#)
+                .expect("Plain lists of players should still load");
+        assert_eq!(
+            filters.contains_players,
+            vec![
+                PlayerFilter::new(String::from("pootis")),
+                PlayerFilter::new(String::from("[U:1:123]")),
+            ]
+        );
+
+        let excluded = PlayerFilter {
+            player: String::from("pootis"),
+            exclude: true,
+            team: TeamScope::EnemyTeam,
+        };
+        let json = serde_json::to_string(&excluded).expect("Should serialize");
+        let loaded: PlayerFilter = serde_json::from_str(&json).expect("Should deserialize");
+        assert_eq!(loaded, excluded);
+    }
+}
@@ -0,0 +1,188 @@
+//! Interesting moments in demos the user wants to come back to. They're kept
+//! apart from the analysed demo cache so analysing a demo again doesn't lose
+//! them.
+
+use std::{collections::HashMap, io::ErrorKind};
+
+use serde::{Deserialize, Serialize};
+use tf2_monitor_core::{demos::analyser::AnalysedDemo, settings::instance};
+
+use super::{parse_hash, write_atomically, AnalysedDemoID, CachedDemoError};
+use crate::app;
+
+const BOOKMARKS_FILE_NAME: &str = "demo_bookmarks.json";
+/// How many seconds either side of a bookmark are highlighted on the chart
+pub const HIGHLIGHT_SECONDS: u32 = 5;
+/// Used for demos that don't say how long their ticks are
+const DEFAULT_INTERVAL_PER_TICK: f32 = 0.015;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// The demo tick, the same as the ticks of kills
+    pub tick: u32,
+    pub label: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    bookmarks: HashMap<AnalysedDemoID, Vec<Bookmark>>,
+}
+
+impl Bookmarks {
+    /// Reads the bookmarks from the config directory, or none if they couldn't
+    /// be read
+    #[must_use]
+    pub fn load() -> Self {
+        match load_bookmarks() {
+            Ok(bookmarks) => Self { bookmarks },
+            Err(e) => {
+                tracing::error!("Failed to load demo bookmarks: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = save_bookmarks(&self.bookmarks) {
+            tracing::error!("Failed to save demo bookmarks: {e}");
+        }
+    }
+
+    /// The demo's bookmarks, in the order they happen in the demo
+    #[must_use]
+    pub fn get(&self, demo: AnalysedDemoID) -> &[Bookmark] {
+        self.bookmarks
+            .get(&demo)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn add(&mut self, demo: AnalysedDemoID, bookmark: Bookmark) {
+        let bookmarks = self.bookmarks.entry(demo).or_default();
+        let i = bookmarks.partition_point(|b| b.tick <= bookmark.tick);
+        bookmarks.insert(i, bookmark);
+    }
+
+    pub fn set_label(&mut self, demo: AnalysedDemoID, index: usize, label: String) {
+        if let Some(b) = self
+            .bookmarks
+            .get_mut(&demo)
+            .and_then(|bs| bs.get_mut(index))
+        {
+            b.label = label;
+        }
+    }
+
+    pub fn remove(&mut self, demo: AnalysedDemoID, index: usize) {
+        let Some(bookmarks) = self.bookmarks.get_mut(&demo) else {
+            return;
+        };
+
+        if index < bookmarks.len() {
+            bookmarks.remove(index);
+        }
+        if bookmarks.is_empty() {
+            self.bookmarks.remove(&demo);
+        }
+    }
+}
+
+fn interval_per_tick(analysed: &AnalysedDemo) -> f32 {
+    if analysed.interval_per_tick > 0.0 {
+        analysed.interval_per_tick
+    } else {
+        DEFAULT_INTERVAL_PER_TICK
+    }
+}
+
+/// The demo tick `seconds` into the demo
+#[must_use]
+#[allow(
+    clippy::cast_sign_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss
+)]
+pub fn seconds_to_tick(analysed: &AnalysedDemo, seconds: u32) -> u32 {
+    (seconds as f32 / interval_per_tick(analysed)) as u32
+}
+
+/// How many seconds into the demo the tick is
+#[must_use]
+#[allow(
+    clippy::cast_sign_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss
+)]
+pub fn tick_to_seconds(analysed: &AnalysedDemo, tick: u32) -> u32 {
+    (tick as f32 * interval_per_tick(analysed)) as u32
+}
+
+/// Reads a time typed as "h:mm:ss", "m:ss" or just seconds
+#[must_use]
+pub fn parse_time(time: &str) -> Option<u32> {
+    let parts: Vec<&str> = time.trim().split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    parts.iter().try_fold(0u32, |total, part| {
+        let part: u32 = part.trim().parse().ok()?;
+        total.checked_mul(60)?.checked_add(part)
+    })
+}
+
+fn load_bookmarks() -> Result<HashMap<AnalysedDemoID, Vec<Bookmark>>, CachedDemoError> {
+    let file_path = tf2_monitor_core::settings::Settings::locate_config_directory(app())?
+        .join(BOOKMARKS_FILE_NAME);
+
+    let contents = match std::fs::read_to_string(file_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let bookmarks: HashMap<String, Vec<Bookmark>> = serde_json::from_str(&contents)?;
+    Ok(bookmarks
+        .into_iter()
+        .filter_map(|(h, b)| parse_hash(&h).map(|h| (h, b)))
+        .collect())
+}
+
+fn save_bookmarks(
+    bookmarks: &HashMap<AnalysedDemoID, Vec<Bookmark>>,
+) -> Result<(), CachedDemoError> {
+    if instance::is_read_only() {
+        return Ok(());
+    }
+
+    let file_path = tf2_monitor_core::settings::Settings::locate_config_directory(app())?
+        .join(BOOKMARKS_FILE_NAME);
+
+    let bookmarks: HashMap<String, &Vec<Bookmark>> = bookmarks
+        .iter()
+        .map(|(h, b)| (format!("{h:x}"), b))
+        .collect();
+
+    write_atomically(
+        &file_path,
+        serde_json::to_string_pretty(&bookmarks)?.as_bytes(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_time;
+
+    #[test]
+    fn typed_times() {
+        assert_eq!(parse_time("12:30"), Some(12 * 60 + 30));
+        assert_eq!(parse_time(" 1:02:03 "), Some(60 * 60 + 2 * 60 + 3));
+        assert_eq!(parse_time("45"), Some(45));
+        assert_eq!(parse_time("0:05"), Some(5));
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("12:"), None);
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time("aimlock"), None);
+    }
+}
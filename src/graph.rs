@@ -12,7 +12,11 @@ use tf2_monitor_core::{
 };
 
 use crate::{
-    gui::styles::colours::{team_blu, team_red},
+    demos::{
+        bookmarks::{self, HIGHLIGHT_SECONDS},
+        AnalysedDemoID,
+    },
+    gui::styles::colours::{team_blu, team_red, yellow},
     App, IcedElement, Message,
 };
 
@@ -27,16 +31,18 @@ pub struct KDAChart {
     pub ticks_on_teams: Vec<TeamPeriod>,
     pub first_tick: u32,
     pub last_tick: u32,
+    /// Ticks around the bookmark being shown
+    pub highlight: Option<(u32, u32)>,
 }
 
 impl KDAChart {
     /// Provided a player who is in the demo, the graph will reflect that player's k/d/a.
     /// If the provided player is not contained in the demo, or no player is provided,
     /// it defaults to tracking the user who recorded the demo.
-    pub fn new(state: &App, demo: usize, player: Option<SteamID>) -> Self {
+    pub fn new(state: &App, demo: AnalysedDemoID, player: Option<SteamID>) -> Self {
         let mut chart = Self::default();
 
-        let col = state.settings.theme.palette().text;
+        let col = state.settings.current_theme().palette().text;
         chart.col = RGBAColor(
             (col.r * 255.0) as u8,
             (col.g * 255.0) as u8,
@@ -46,10 +52,8 @@ impl KDAChart {
 
         if let Some(analysed_demo) = state
             .demos
-            .demo_files
-            .get(demo)
-            .map(|d| &d.analysed)
-            .and_then(|d| state.demos.analysed_demos.get(d))
+            .analysed_demos
+            .get(&demo)
             .and_then(|d| d.get_demo())
         {
             let mut player = player.unwrap_or(analysed_demo.user);
@@ -79,6 +83,11 @@ impl KDAChart {
                 .clone_from(&analysed_player.ticks_on_classes);
             chart.first_tick = analysed_player.first_tick;
             chart.last_tick = analysed_player.last_tick;
+
+            if let Some((_, tick)) = state.demos.highlighted_tick.filter(|(id, _)| *id == demo) {
+                let margin = bookmarks::seconds_to_tick(analysed_demo, HIGHLIGHT_SECONDS);
+                chart.highlight = Some((tick.saturating_sub(margin), tick.saturating_add(margin)));
+            }
         }
 
         chart
@@ -149,6 +158,23 @@ impl Chart<Message> for KDAChart {
                 .expect("Chart stuff");
         }
 
+        // Bookmark
+        if let Some((start, end)) = self.highlight {
+            let col = yellow();
+            chart
+                .draw_series(AreaSeries::new(
+                    [(start, max_kills), (end, max_kills)],
+                    0,
+                    RGBAColor(
+                        (col.r * 255.0) as u8,
+                        (col.g * 255.0) as u8,
+                        (col.b * 255.0) as u8,
+                        0.4,
+                    ),
+                ))
+                .expect("Chart stuff");
+        }
+
         // Kills
         chart
             .draw_series(
@@ -1,29 +1,43 @@
-use std::{fmt::Display, rc::Rc};
+use std::{
+    fmt::Display,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
+use chrono::{DateTime, Local, Utc};
 use iced::{
     theme,
-    widget::{self, column, row, Button, PickList, Rule, Tooltip},
+    widget::{self, column, row, scrollable::RelativeOffset, Button, PickList, Rule, Tooltip},
     Color, Length,
 };
 use serde::{Deserialize, Serialize};
-use tf2_monitor_core::{players::records::Verdict, steamid_ng::SteamID};
+use tf2_monitor_core::{masterbase::UploadStatus, players::records::Verdict, steamid_ng::SteamID};
 
-use crate::{settings::PanelSide, App, IcedElement, Message};
+use crate::{
+    demos::{AnalysedDemoID, AnalysedDemoView},
+    settings::{PanelSide, DEFAULT_SIDE_PANEL_RATIO},
+    App, IcedElement, Message,
+};
 
 use self::styles::picklist::VerdictPickList;
 
 pub mod chat;
+pub mod context_menu;
 pub mod demos;
 pub mod demos_analyzed;
 pub mod history;
 pub mod icons;
 pub mod killfeed;
+pub mod logs;
+pub mod parties;
 pub mod player;
 pub mod records;
 pub mod replay;
 pub mod server;
 pub mod settings;
+pub mod split;
 pub mod styles;
+pub mod votes;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub enum View {
@@ -32,8 +46,52 @@ pub enum View {
     Settings,
     Records,
     Demos,
-    AnalysedDemo(usize),
+    AnalysedDemo(
+        #[serde(serialize_with = "crate::demos::serialize_id")]
+        #[serde(deserialize_with = "crate::demos::deserialize_id")]
+        AnalysedDemoID,
+    ),
     Replay,
+    Logs,
+}
+
+/// A [`View`] without the demo of [`View::AnalysedDemo`], for remembering
+/// things about each view that shouldn't depend on which demo is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub enum ViewKind {
+    Server,
+    History,
+    Settings,
+    Records,
+    Demos,
+    AnalysedDemo,
+    Replay,
+    Logs,
+}
+
+impl ViewKind {
+    pub const ALL: &'static [Self] = &[
+        Self::Server,
+        Self::History,
+        Self::Settings,
+        Self::Records,
+        Self::Demos,
+        Self::AnalysedDemo,
+        Self::Replay,
+        Self::Logs,
+    ];
+
+    /// The side panels that can be opened in the view
+    #[must_use]
+    pub const fn side_panels(self) -> &'static [SidePanel] {
+        match self {
+            Self::Server | Self::History => {
+                &[SidePanel::ChatKills, SidePanel::Parties, SidePanel::Votes]
+            }
+            Self::Demos => &[SidePanel::DemoFilters],
+            Self::Settings | Self::Records | Self::AnalysedDemo | Self::Replay | Self::Logs => &[],
+        }
+    }
 }
 
 impl View {
@@ -46,22 +104,57 @@ impl View {
             Self::Demos => demos::demos_list_view(state),
             Self::AnalysedDemo(demo) => demos_analyzed::analysed_demo_view(state, *demo),
             Self::Replay => replay::view(state),
+            Self::Logs => logs::view(state),
+        }
+    }
+
+    #[must_use]
+    pub const fn kind(&self) -> ViewKind {
+        match self {
+            Self::Server => ViewKind::Server,
+            Self::History => ViewKind::History,
+            Self::Settings => ViewKind::Settings,
+            Self::Records => ViewKind::Records,
+            Self::Demos => ViewKind::Demos,
+            Self::AnalysedDemo(_) => ViewKind::AnalysedDemo,
+            Self::Replay => ViewKind::Replay,
+            Self::Logs => ViewKind::Logs,
         }
     }
 
     #[must_use]
     pub const fn side_panels(&self) -> &'static [SidePanel] {
+        self.kind().side_panels()
+    }
+
+    /// The main scrollable of the view, whose position is remembered when
+    /// switching to another view
+    #[must_use]
+    pub const fn scrollable_id(&self) -> Option<&'static str> {
         match self {
-            Self::Server | Self::History => &[SidePanel::ChatKills, SidePanel::Votes],
-            Self::Demos => &[SidePanel::DemoFilters],
-            Self::Settings | Self::Records | Self::AnalysedDemo(_) | Self::Replay => &[],
+            Self::History => Some(history::SCROLLABLE_ID),
+            Self::Settings => Some(settings::SCROLLABLE_ID),
+            Self::Records => Some(records::SCROLLABLE_ID),
+            Self::Demos => Some(demos::SCROLLABLE_ID),
+            Self::AnalysedDemo(_) => Some(demos_analyzed::KDA_SCROLLABLE_ID),
+            // Logs keep to the bottom instead while auto-scrolling
+            Self::Server | Self::Replay | Self::Logs => None,
         }
     }
 }
 
+/// UI state that is kept for each view while switching between them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ViewUiState {
+    pub scroll: Option<RelativeOffset>,
+    /// Which tab was open, only used by analysed demos
+    pub analysed_demo_tab: Option<AnalysedDemoView>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
 pub enum SidePanel {
     ChatKills,
+    Parties,
     Votes,
     DemoFilters,
 }
@@ -70,6 +163,7 @@ impl Display for SidePanel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
             Self::ChatKills => "Chat & Killfeed",
+            Self::Parties => "Parties",
             Self::Votes => "Votes",
             Self::DemoFilters => "Filters",
         };
@@ -81,12 +175,27 @@ impl SidePanel {
     pub fn view<'b>(&self, state: &'b App) -> IcedElement<'b> {
         match self {
             Self::ChatKills => chat_killfeed_view(state),
+            Self::Parties => parties::view(state),
             Self::Votes => coming_soon(),
             Self::DemoFilters => demos::filters_view(state),
         }
     }
 }
 
+/// A message shown at the top of the window for a while
+pub struct Notification {
+    pub text: String,
+    pub created: Instant,
+    pub duration: Duration,
+    /// A button to show on the notification, e.g. to undo a change
+    pub action: Option<(&'static str, Message)>,
+}
+
+/// How long notifications are shown for before being dismissed automatically
+pub const NOTIFICATION_DURATION: Duration = Duration::from_secs(15);
+/// How long the option to undo a change is shown for
+pub const UNDO_NOTIFICATION_DURATION: Duration = Duration::from_secs(5);
+
 pub const FONT_SIZE: u16 = 13;
 pub const FONT_SIZE_HEADING: u16 = 20;
 pub const PFP_FULL_SIZE: u16 = 184;
@@ -177,46 +286,63 @@ pub fn verdict_picker<'a>(
 }
 
 #[must_use]
-pub fn main_window(state: &App) -> impl Into<IcedElement<'_>> {
-    const SPLIT: [u16; 2] = [7, 3];
-
-    let side_panel = state
-        .selected_player
-        .map(|p| player::detailed_player_view(state, p))
-        .or_else(|| {
-            state
-                .settings
-                .view
-                .side_panels()
-                .iter()
-                .find(|p| state.settings.sidepanels.contains(*p))
-                .map(|sp| sp.view(state))
-        });
-
+pub fn main_window(state: &App) -> IcedElement<'_> {
     // Rest of the view
-    let mut content = widget::row![widget::column![
+    let content = widget::column![
         view_select(state),
         Rule::horizontal(1),
+        notifications_view(state),
         state.settings.view.view(state),
     ]
-    .width(Length::FillPortion(SPLIT[0]))
-    .height(Length::Fill)];
-
-    if let Some(side_panel) = side_panel {
-        let panel = widget::Container::new(side_panel)
-            .width(Length::FillPortion(SPLIT[1]))
-            .height(Length::Fill);
-        if state.settings.panel_side == PanelSide::Left {
-            content = widget::row![panel, Rule::vertical(1), content,];
-        } else {
-            content = widget::row![content, Rule::vertical(1), panel,];
-        }
-    }
+    .width(Length::Fill)
+    .height(Length::Fill);
 
-    content
+    let Some(side_panel) = side_panel(state) else {
+        return content.into();
+    };
+
+    let panel = widget::Container::new(side_panel)
         .width(Length::Fill)
-        .height(Length::Fill)
-        .align_items(iced::Alignment::Center)
+        .height(Length::Fill);
+    let ratio = state.settings.side_panel_ratio;
+    let reset = Message::SetSidePanelRatio(DEFAULT_SIDE_PANEL_RATIO);
+    if state.settings.panel_side == PanelSide::Left {
+        split::split(panel, content, ratio, Message::SetSidePanelRatio, reset)
+    } else {
+        split::split(
+            content,
+            panel,
+            1.0 - ratio,
+            |r| Message::SetSidePanelRatio(1.0 - r),
+            reset,
+        )
+    }
+}
+
+/// The selected player's details take the place of the view's side panel,
+/// unless the side panel is split to show both.
+fn side_panel(state: &App) -> Option<IcedElement<'_>> {
+    let player = state
+        .active_selection()
+        .map(|p| player::detailed_player_view(state, p));
+    let panel = state.settings.side_panel().map(|sp| sp.view(state));
+
+    match (player, panel) {
+        (Some(player), Some(panel)) if state.settings.split_side_panel => {
+            let top = (state.settings.split_ratio * 100.0).round() as u16;
+            Some(
+                widget::column![
+                    widget::Container::new(player).height(Length::FillPortion(top)),
+                    Rule::horizontal(1),
+                    widget::Container::new(panel)
+                        .height(Length::FillPortion(100u16.saturating_sub(top).max(1))),
+                ]
+                .into(),
+            )
+        }
+        (Some(player), _) => Some(player),
+        (None, panel) => panel,
+    }
 }
 
 #[must_use]
@@ -227,24 +353,47 @@ pub fn view_select(state: &App) -> IcedElement<'_> {
         ("Records", View::Records),
         ("Demos", View::Demos),
         ("Replay", View::Replay),
+        ("Logs", View::Logs),
         ("Settings", View::Settings),
     ];
 
     let mut views = row![].spacing(10);
     for &(name, v) in VIEWS {
-        let mut button = Button::new(name);
+        let mut button = match view_badge(state, v) {
+            Some(badge) => Button::new(
+                row![
+                    widget::text(name),
+                    // Fixed width so the header doesn't move as the count changes
+                    widget::container(badge)
+                        .width(VIEW_BADGE_WIDTH)
+                        .center_x(),
+                ]
+                .align_items(iced::Alignment::Center),
+            ),
+            None => Button::new(name),
+        };
         if state.settings.view != v {
             button = button.on_press(Message::SetView(v));
         }
+        // So cheaters joining are noticed from any view
+        if v == View::Server && server::needs_attention(state) {
+            button = button.style(theme::Button::custom(styles::ButtonColor(
+                styles::colours::red(),
+            )));
+        }
         views = views.push(button);
     }
 
     let mut side_panels = widget::row![].spacing(10);
     for sp in state.settings.view.side_panels() {
-        side_panels =
-            side_panels.push(widget::Button::new(widget::text(format!("{sp}"))).on_press(
-                Message::ToggleSidePanel(state.settings.view.side_panels(), *sp),
-            ));
+        side_panels = side_panels.push(
+            widget::Button::new(widget::text(format!("{sp}")))
+                .on_press(Message::ToggleSidePanel(*sp)),
+        );
+    }
+
+    if state.mac.settings.upload_demos {
+        views = views.push(upload_status_icon(&state.mac.upload_status));
     }
 
     let content = if state.settings.panel_side == PanelSide::Left {
@@ -256,6 +405,117 @@ pub fn view_select(state: &App) -> IcedElement<'_> {
     content.width(Length::Fill).padding(10).into()
 }
 
+const VIEW_BADGE_WIDTH: f32 = 50.0;
+
+/// Background work or totals shown on a view's button, so they can be kept
+/// an eye on from other views
+fn view_badge(state: &App, view: View) -> Option<IcedElement<'_>> {
+    match view {
+        View::Records => Some(
+            widget::text(state.mac.players.records.len())
+                .size(FONT_SIZE)
+                .style(Color::from_rgb(0.6, 0.6, 0.6))
+                .into(),
+        ),
+        View::Demos => {
+            let analysing = state.demos.num_analysing();
+            if analysing == 0 {
+                return Some(widget::Space::with_width(0).into());
+            }
+            Some(
+                tooltip(
+                    row![
+                        icons::icon(icons::HOURGLASS).size(FONT_SIZE),
+                        widget::text(analysing).size(FONT_SIZE),
+                    ]
+                    .spacing(3),
+                    widget::text(format!("Analysing {analysing} demos")).size(FONT_SIZE),
+                )
+                .into(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Small indicator of whether demos are being uploaded to the masterbase
+#[must_use]
+pub fn upload_status_icon(status: &UploadStatus) -> IcedElement<'_> {
+    let indicator = if status.last_error.is_some() {
+        icons::icon(icons::CROSS).style(styles::colours::red())
+    } else if status.session_open {
+        icons::icon(icons::TICK).style(styles::colours::green())
+    } else {
+        icons::icon(icons::HOURGLASS)
+    };
+
+    let button = Button::new(indicator)
+        .style(theme::Button::Text)
+        .on_press(Message::SetView(View::Settings));
+
+    tooltip(
+        button,
+        widget::text(upload_status_text(status)).size(FONT_SIZE),
+    )
+    .into()
+}
+
+#[must_use]
+pub fn upload_status_text(status: &UploadStatus) -> String {
+    if let Some(e) = &status.last_error {
+        return e.clone();
+    }
+
+    match (&status.demo_name, status.session_open) {
+        (Some(demo), true) => format!(
+            "Uploading {demo} ({:.2} MB sent)",
+            status.bytes_uploaded as f32 / 1_000_000.0
+        ),
+        (Some(demo), false) => format!(
+            "Finished uploading {demo} ({:.2} MB sent)",
+            status.bytes_uploaded as f32 / 1_000_000.0
+        ),
+        (None, _) => String::from("Waiting for a demo to be recorded"),
+    }
+}
+
+#[must_use]
+pub fn notifications_view(state: &App) -> IcedElement<'_> {
+    let mut notifications = column![].spacing(5);
+
+    for (i, n) in state.notifications.iter().enumerate() {
+        let mut contents = row![
+            widget::text(&n.text).size(FONT_SIZE),
+            widget::horizontal_space(),
+        ]
+        .spacing(5)
+        .align_items(iced::Alignment::Center)
+        .padding(5);
+
+        if let Some((label, action)) = &n.action {
+            contents = contents
+                .push(Button::new(widget::text(label).size(FONT_SIZE)).on_press(action.clone()));
+        }
+
+        contents = contents.push(
+            Button::new(widget::text("Dismiss").size(FONT_SIZE))
+                .on_press(Message::DismissNotification(i)),
+        );
+
+        notifications = notifications.push(
+            widget::Container::new(contents)
+                .style(theme::Container::Box)
+                .width(Length::Fill),
+        );
+    }
+
+    if !state.notifications.is_empty() {
+        notifications = notifications.padding(5);
+    }
+
+    notifications.into()
+}
+
 #[must_use]
 pub fn tooltip<'a>(
     element: impl Into<iced::Element<'a, Message, iced::Theme, iced::Renderer>>,
@@ -347,3 +607,66 @@ pub fn format_time_since(seconds: u64) -> String {
         format!("{} days ago", seconds / (60 * 60 * 24))
     }
 }
+
+/// `1234567` as `1,234,567`
+#[must_use]
+pub fn format_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The local time something happened as HH:MM:SS, with how long ago it was
+/// in the tooltip
+#[must_use]
+pub fn timestamp<'a>(time: DateTime<Utc>) -> IcedElement<'a> {
+    let ago = (Utc::now() - time).num_seconds().max(0) as u64;
+    tooltip(
+        widget::text(time.with_timezone(&Local).format("%H:%M:%S"))
+            .size(FONT_SIZE)
+            .style(Color::from_rgb(0.6, 0.6, 0.6)),
+        widget::text(format_time_since(ago)).size(FONT_SIZE),
+    )
+    .into()
+}
+
+/// The flag emoji for a two letter ISO 3166 country code like Steam gives,
+/// e.g. "DE". `None` if the code isn't two letters.
+#[must_use]
+pub fn country_flag(code: &str) -> Option<String> {
+    const REGIONAL_INDICATOR_A: u32 = 0x1F1E6;
+
+    let code = code.trim();
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    code.chars()
+        .map(|c| {
+            char::from_u32(
+                REGIONAL_INDICATOR_A + u32::from(c.to_ascii_uppercase()) - u32::from('A'),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::country_flag;
+
+    #[test]
+    fn country_flags() {
+        assert_eq!(country_flag("DE").as_deref(), Some("\u{1F1E9}\u{1F1EA}"));
+        assert_eq!(country_flag("us").as_deref(), Some("\u{1F1FA}\u{1F1F8}"));
+        assert_eq!(country_flag(""), None);
+        assert_eq!(country_flag("GBR"), None);
+        assert_eq!(country_flag("1A"), None);
+        assert_eq!(country_flag("ÄÖ"), None);
+    }
+}
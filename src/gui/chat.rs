@@ -2,29 +2,74 @@ use iced::{
     widget::{self, scrollable::Id, Scrollable},
     Alignment,
 };
-use tf2_monitor_core::players::game_info::Team;
+use tf2_monitor_core::{
+    console::commands::regexes::ChatMessage,
+    players::{game_info::Team, name_changes::NameChange},
+};
 
 use crate::{App, IcedElement, Message};
 
 use super::{
     styles::{colours, ButtonColor},
-    FONT_SIZE,
+    timestamp, FONT_SIZE,
 };
 
 pub const SCROLLABLE_ID: &str = "Chat";
 
+enum Line<'a> {
+    Chat(&'a ChatMessage),
+    NameChange(&'a NameChange),
+}
+
 #[must_use]
 pub fn view(state: &App) -> IcedElement<'_> {
     // TODO - Virtualise this by using the on_scroll thing
 
-    let contents = state.mac.server.chat_history().iter().fold(
+    // Name changes are shown among the chat in the order they happened
+    let mut name_changes: Vec<&NameChange> = if state.settings.name_changes_in_chat {
+        state.mac.players.name_changes.values().flatten().collect()
+    } else {
+        Vec::new()
+    };
+    name_changes.sort_by_key(|c| c.changed);
+    let mut name_changes = name_changes.into_iter().peekable();
+
+    let mut lines = Vec::new();
+    for chat in state.mac.server.chat_history() {
+        while let Some(change) = name_changes.next_if(|c| c.changed <= chat.timestamp) {
+            lines.push(Line::NameChange(change));
+        }
+        lines.push(Line::Chat(chat));
+    }
+    lines.extend(name_changes.map(Line::NameChange));
+
+    let contents = lines.into_iter().fold(
         widget::Column::new()
             .align_items(Alignment::Start)
             .padding(10)
             .spacing(5),
-        |contents, chat| {
+        |contents, line| {
+            let chat = match line {
+                Line::Chat(chat) => chat,
+                Line::NameChange(change) => {
+                    return contents.push(
+                        widget::button(
+                            widget::text(format!("{} is now known as {}", change.from, change.to))
+                                .size(FONT_SIZE)
+                                .style(colours::orange()),
+                        )
+                        .padding(2)
+                        .style(iced::theme::Button::Text)
+                        .on_press(Message::SelectPlayer(change.steamid)),
+                    );
+                }
+            };
+
             contents.push({
                 let mut row = widget::Row::new().align_items(Alignment::Center).spacing(5);
+                if state.settings.chat_timestamps {
+                    row = row.push(timestamp(chat.timestamp));
+                }
 
                 let mut name =
                     widget::button(widget::text(&chat.player_name).size(FONT_SIZE)).padding(2);
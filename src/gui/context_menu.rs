@@ -0,0 +1,325 @@
+//! Menus that open next to the cursor when something is right-clicked.
+
+use iced::{
+    advanced::{
+        layout, overlay, renderer,
+        widget::{tree, Operation, Tree},
+        Clipboard, Layout, Shell, Widget,
+    },
+    event, keyboard, mouse, Element, Event, Length, Point, Rectangle, Size, Vector,
+};
+
+use crate::{IcedElement, Message};
+
+/// Wrap `content` so right-clicking it opens the menu made by `menu`. The menu
+/// is only made while it's open, and is closed when one of its buttons is
+/// pressed, anything else is clicked, or Escape is pressed.
+pub fn context_menu<'a>(
+    content: impl Into<IcedElement<'a>>,
+    menu: impl Fn() -> IcedElement<'a> + 'a,
+) -> IcedElement<'a> {
+    Element::new(ContextMenu {
+        content: content.into(),
+        menu: Box::new(menu),
+        open_menu: None,
+    })
+}
+
+struct ContextMenu<'a> {
+    content: IcedElement<'a>,
+    menu: Box<dyn Fn() -> IcedElement<'a> + 'a>,
+    /// Kept while the menu is open so the overlay can borrow it
+    open_menu: Option<IcedElement<'a>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Where the menu was opened, if it is
+    open_at: Option<Point>,
+}
+
+impl<'a> Widget<Message, iced::Theme, iced::Renderer> for ContextMenu<'a> {
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &iced::Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        self.content
+            .as_widget()
+            .layout(&mut tree.children[0], renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut iced::Renderer,
+        theme: &iced::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            viewport,
+        );
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    /// The content, and the menu which is only filled in while it's open
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content), Tree::empty()]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        if tree.children.len() == 2 {
+            tree.children[0].diff(&self.content);
+        } else {
+            tree.children = self.children();
+        }
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &iced::Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        self.content
+            .as_widget()
+            .operate(&mut tree.children[0], layout, renderer, operation);
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &iced::Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) = event {
+            if let Some(position) = cursor.position_over(layout.bounds()) {
+                tree.state.downcast_mut::<State>().open_at = Some(position);
+                shell.invalidate_layout();
+                return event::Status::Captured;
+            }
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &iced::Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout,
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &iced::Renderer,
+    ) -> Option<overlay::Element<'b, Message, iced::Theme, iced::Renderer>> {
+        let Tree {
+            state, children, ..
+        } = tree;
+        let state = state.downcast_mut::<State>();
+        let [content_tree, menu_tree] = children.as_mut_slice() else {
+            return None;
+        };
+
+        let Some(position) = state.open_at else {
+            self.open_menu = None;
+            return self
+                .content
+                .as_widget_mut()
+                .overlay(content_tree, layout, renderer);
+        };
+
+        let menu = self.open_menu.insert((self.menu)());
+        menu_tree.diff(&*menu);
+
+        Some(overlay::Element::new(
+            position,
+            Box::new(MenuOverlay {
+                menu,
+                tree: menu_tree,
+                state,
+            }),
+        ))
+    }
+}
+
+struct MenuOverlay<'a, 'b> {
+    menu: &'b mut IcedElement<'a>,
+    tree: &'b mut Tree,
+    state: &'b mut State,
+}
+
+impl MenuOverlay<'_, '_> {
+    fn close(&mut self, shell: &mut Shell<'_, Message>) {
+        self.state.open_at = None;
+        shell.invalidate_layout();
+    }
+}
+
+impl<'a, 'b> overlay::Overlay<Message, iced::Theme, iced::Renderer> for MenuOverlay<'a, 'b> {
+    fn layout(
+        &mut self,
+        renderer: &iced::Renderer,
+        bounds: Size,
+        position: Point,
+        _translation: Vector,
+    ) -> layout::Node {
+        let limits = layout::Limits::new(Size::ZERO, bounds);
+        let node = self.menu.as_widget().layout(self.tree, renderer, &limits);
+        let size = node.size();
+
+        // Keep the menu inside the window, opening it upwards near the bottom
+        let x = position.x.min(bounds.width - size.width).max(0.0);
+        let y = if position.y + size.height > bounds.height {
+            position.y - size.height
+        } else {
+            position.y
+        }
+        .max(0.0);
+
+        node.move_to(Point::new(x, y))
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut iced::Renderer,
+        theme: &iced::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+    ) {
+        self.menu.as_widget().draw(
+            self.tree,
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            &layout.bounds(),
+        );
+    }
+
+    fn operate(
+        &mut self,
+        layout: Layout<'_>,
+        renderer: &iced::Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        self.menu
+            .as_widget()
+            .operate(self.tree, layout, renderer, operation);
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &iced::Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        match &event {
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key: keyboard::Key::Named(keyboard::key::Named::Escape),
+                ..
+            }) => {
+                self.close(shell);
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(_)) if !cursor.is_over(layout.bounds()) => {
+                self.close(shell);
+                return event::Status::Captured;
+            }
+            _ => {}
+        }
+
+        let released = matches!(
+            event,
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+        );
+
+        let status = self.menu.as_widget_mut().on_event(
+            self.tree,
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            &layout.bounds(),
+        );
+
+        // One of the menu's buttons was pressed
+        if released && status == event::Status::Captured {
+            self.close(shell);
+        }
+
+        status
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &iced::Renderer,
+    ) -> mouse::Interaction {
+        self.menu
+            .as_widget()
+            .mouse_interaction(self.tree, layout, cursor, viewport, renderer)
+    }
+}
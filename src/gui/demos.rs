@@ -1,12 +1,20 @@
-use std::time::SystemTime;
+use std::{
+    ops::Range,
+    time::{Instant, SystemTime},
+};
 
 use iced::{
+    alignment::Vertical,
     widget::{self, scrollable::Properties, Scrollable},
     Length,
 };
+use tf2_monitor_core::demos::analyser::progress::Progress;
 
 use crate::{
-    demos::{DemosMessage, MaybeAnalysedDemo, SORT_DIRECTIONS, SORT_OPTIONS},
+    demos::{
+        DemoUpload, DemosMessage, DemosPerPage, ListViewport, MaybeAnalysedDemo, PER_PAGE_OPTIONS,
+        SORT_DIRECTIONS, SORT_OPTIONS, TEAM_SCOPES,
+    },
     App, IcedElement, Message,
 };
 
@@ -17,6 +25,28 @@ use super::{
     tooltip, View, FONT_SIZE, FONT_SIZE_HEADING, PFP_SMALL_SIZE,
 };
 
+pub const SCROLLABLE_ID: &str = "Demos";
+
+/// Every row in the demo list is this tall (including the space below it), so
+/// which rows are on screen can be worked out from the scroll position
+const ROW_HEIGHT: f32 = PFP_SMALL_SIZE as f32 + 3.0;
+const LIST_PADDING: f32 = 15.0;
+/// Rows built above and below the visible ones so scrolling quickly doesn't
+/// show empty space
+const OVERSCAN_ROWS: usize = 10;
+
+/// The range of rows out of `num_rows` that are on screen
+fn visible_rows(viewport: ListViewport, num_rows: usize) -> Range<usize> {
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    let row_at = |y: f32| ((y - LIST_PADDING).max(0.0) / ROW_HEIGHT) as usize;
+
+    let first = row_at(viewport.offset).saturating_sub(OVERSCAN_ROWS);
+    let last = row_at(viewport.offset + viewport.height)
+        .saturating_add(OVERSCAN_ROWS + 1)
+        .min(num_rows);
+    first.min(last)..last
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub fn demos_list_view(state: &App) -> IcedElement<'_> {
     // Pages
@@ -54,6 +84,20 @@ pub fn demos_list_view(state: &App) -> IcedElement<'_> {
             widget::button(widget::text("Refresh")).on_press(DemosMessage::Refresh.into()),
             widget::Space::with_width(5),
             widget::button(widget::text("Analyse all")).on_press(DemosMessage::AnalyseAll.into()),
+            widget::Space::with_width(5),
+            tooltip(
+                widget::button(widget::text("Re-analyse outdated"))
+                    .on_press(DemosMessage::ReanalyseOutdated.into()),
+                "Analyse demos again that were analysed by an older version of the monitor"
+            ),
+            widget::Space::with_width(15),
+            widget::PickList::new(
+                PER_PAGE_OPTIONS,
+                Some(DemosPerPage(state.demos.demos_per_page)),
+                |n| DemosMessage::SetPerPage(n).into()
+            )
+            .text_size(FONT_SIZE),
+            widget::text("per page").size(FONT_SIZE),
             widget::Space::with_width(Length::FillPortion(1)),
             widget::text(format!(
                 "Displaying {displaying_start} - {displaying_end} of {} ({num_pages} {})",
@@ -98,23 +142,52 @@ pub fn demos_list_view(state: &App) -> IcedElement<'_> {
     .spacing(15)
     .padding(15);
 
-    // Actual demos
-    let mut contents = widget::column![].spacing(3).padding(15);
-
-    for &d in state
+    // Actual demos, only building the rows that are on screen and leaving
+    // space for the rest so the scrollbar stays the right size
+    let started = Instant::now();
+    let demos = &state.demos.demos_to_display;
+    let page_start = state
         .demos
-        .demos_to_display
-        .iter()
-        .skip(state.demos.page * state.demos.demos_per_page)
-        .take(state.demos.demos_per_page)
-    {
-        contents = contents.push(demo_list_row(state, d));
+        .page
+        .saturating_mul(state.demos.demos_per_page)
+        .min(demos.len());
+    let page_end = page_start
+        .saturating_add(state.demos.demos_per_page)
+        .min(demos.len());
+    let page = &demos[page_start..page_end];
+    let visible = visible_rows(state.demos.list_viewport, page.len());
+
+    #[allow(clippy::cast_precision_loss)]
+    let space = |rows: usize| widget::Space::with_height(rows as f32 * ROW_HEIGHT);
+    let mut contents = widget::column![space(visible.start)].padding(LIST_PADDING);
+    for &d in &page[visible.clone()] {
+        contents = contents.push(
+            widget::container(demo_list_row(state, d))
+                .height(ROW_HEIGHT)
+                .align_y(Vertical::Top),
+        );
     }
+    contents = contents.push(space(page.len() - visible.end));
+
+    let elapsed = started.elapsed();
+    let mut metrics = state.demos.list_metrics.borrow_mut();
+    metrics.record(elapsed);
+    metrics.outputs += visible.len() as u64;
+    drop(metrics);
+    tracing::trace!(
+        "Built rows {} - {} of {} demos in {:?}",
+        visible.start,
+        visible.end,
+        page.len(),
+        elapsed
+    );
 
     widget::column![
         header,
         widget::horizontal_rule(1),
         Scrollable::new(contents)
+            .id(widget::scrollable::Id::new(SCROLLABLE_ID))
+            .on_scroll(|v| DemosMessage::ScrolledList(v).into())
     ]
     .width(Length::Fill)
     .height(Length::Fill)
@@ -162,11 +235,30 @@ fn demo_list_row(state: &App, demo_index: usize) -> IcedElement<'_> {
             analysed.header.map.clone()
         };
 
-        contents = contents.push(
-            widget::row![widget::button(widget::text(hostname).size(FONT_SIZE))
-                .on_press(Message::SetView(View::AnalysedDemo(demo_index)))]
-            .width(200),
-        );
+        let mut server = widget::row![widget::button(widget::text(hostname).size(FONT_SIZE))
+            .on_press(Message::SetView(View::AnalysedDemo(demo.analysed)))]
+        .spacing(5)
+        .align_items(iced::Alignment::Center)
+        .width(200);
+        if analysed.is_outdated() {
+            server = server.push(tooltip(
+                widget::button(widget::text("Re-analyse").size(FONT_SIZE))
+                    .on_press(Message::Demos(DemosMessage::ReanalyseDemo(demo_index))),
+                "Analysed (outdated). This demo was analysed by an older version of the monitor and may be missing information.",
+            ));
+        }
+        if analysed.truncated {
+            server = server.push(tooltip(
+                widget::text("!").style(colours::yellow()),
+                widget::column![
+                    widget::text(
+                        "This demo couldn't be read to the end, so only part of it was analysed."
+                    ),
+                    widget::text(analysed.parse_warnings.join("\n")),
+                ],
+            ));
+        }
+        contents = contents.push(server);
         contents = contents.push(widget::text(recorded_ago_str).width(100));
         contents = contents.push(widget::text(map).width(Length::FillPortion(4)));
 
@@ -258,10 +350,45 @@ fn demo_list_row(state: &App, demo_index: usize) -> IcedElement<'_> {
         );
     }
 
+    contents = contents.push(widget::container(upload_widget(state, demo_index)).width(120));
+
     // widget::column![top_row, bottom_row]
     contents.width(Length::Fill).into()
 }
 
+fn upload_widget(state: &App, demo_index: usize) -> IcedElement<'_> {
+    let Some(demo) = state.demos.demo_files.get(demo_index) else {
+        return widget::Space::with_width(0).into();
+    };
+
+    let upload_button = |label| {
+        widget::button(widget::text(label).size(FONT_SIZE))
+            .on_press(Message::Demos(DemosMessage::UploadDemo(demo_index)))
+    };
+
+    match state.demos.uploads.get(&demo.analysed) {
+        None => tooltip(
+            upload_button("Upload"),
+            widget::text("Upload this demo to the Masterbase").size(FONT_SIZE),
+        )
+        .into(),
+        Some(DemoUpload::InProgress(checker)) => match checker.check_progress() {
+            Progress::Queued => widget::text("Uploading...").size(FONT_SIZE).into(),
+            Progress::InProgress(amount) => widget::progress_bar(0.0..=1.0, amount).into(),
+            Progress::Finished => widget::text("Finishing...").size(FONT_SIZE).into(),
+        },
+        Some(DemoUpload::Failed(e)) => tooltip(
+            upload_button("Retry upload"),
+            widget::text(e).size(FONT_SIZE).style(colours::red()),
+        )
+        .into(),
+        Some(DemoUpload::Uploaded) => widget::text("Uploaded")
+            .size(FONT_SIZE)
+            .style(colours::green())
+            .into(),
+    }
+}
+
 pub fn filters_view(state: &App) -> IcedElement<'_> {
     let mut contents = widget::column![
         widget::text("Filters").size(FONT_SIZE_HEADING),
@@ -275,6 +402,11 @@ pub fn filters_view(state: &App) -> IcedElement<'_> {
             state.settings.demo_filters.show_non_analysed
         )
         .on_toggle(|v| DemosMessage::FilterShowNonAnalysed(v).into()),
+        widget::checkbox(
+            "Show outdated analysed demos",
+            state.settings.demo_filters.show_outdated
+        )
+        .on_toggle(|v| DemosMessage::FilterShowOutdated(v).into()),
         widget::text("Search (Map, Server, IP, File)").size(FONT_SIZE_HEADING),
         widget::text_input(
             "Search (map, server, ip, file)",
@@ -292,7 +424,7 @@ pub fn filters_view(state: &App) -> IcedElement<'_> {
                     .contains_players
                     .iter()
                     .last()
-                    .map_or("", |s| s.as_str())
+                    .map_or("", |f| f.player.as_str())
             )
             .on_submit(Message::Demos(DemosMessage::FilterContainsPlayerAdd))
             .on_input(|s| DemosMessage::FilterContainsPlayerUpdate(s).into()),
@@ -320,7 +452,17 @@ pub fn filters_view(state: &App) -> IcedElement<'_> {
                         .align_items(iced::Alignment::Center),
                 )
                 .on_press(Message::Demos(DemosMessage::FilterRemovePlayer(i))),
-                widget::text(p),
+                widget::text(&p.player).width(Length::Fill),
+                tooltip(
+                    widget::checkbox("Without", p.exclude)
+                        .on_toggle(move |_| DemosMessage::FilterTogglePlayerExclude(i).into())
+                        .text_size(FONT_SIZE),
+                    widget::text("Only show demos this player is not in"),
+                ),
+                widget::PickList::new(TEAM_SCOPES, Some(p.team), move |t| {
+                    DemosMessage::FilterSetPlayerTeam(i, t).into()
+                })
+                .text_size(FONT_SIZE),
             ]
             .align_items(iced::Alignment::Center)
             .spacing(15),
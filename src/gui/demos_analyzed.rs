@@ -14,23 +14,25 @@ use tf2_monitor_core::{
 };
 
 use crate::{
-    demos::{AnalysedDemoView, CLASSES},
+    demos::{bookmarks, AnalysedDemoID, AnalysedDemoView, DemosMessage, CLASSES},
     App, IcedElement, Message,
 };
 
 use super::{
-    coming_soon, format_time, format_time_since,
+    format_time, format_time_since,
     icons::{self, icon},
     invalid_view,
     styles::colours,
-    tooltip, FONT_SIZE, PFP_SMALL_SIZE,
+    tooltip, verdict_picker, FONT_SIZE, PFP_SMALL_SIZE,
 };
 
 pub const KDA_SCROLLABLE_ID: &str = "kda_table";
+/// Room taken by the verdict picker and badges in the KDA table
+const PLAYER_DETAILS_WIDTH: u16 = 250;
 
 #[allow(clippy::too_many_lines)]
-pub fn analysed_demo_view(state: &App, demo_index: usize) -> IcedElement<'_> {
-    let Some(demo) = state.demos.demo_files.get(demo_index) else {
+pub fn analysed_demo_view(state: &App, demo_id: AnalysedDemoID) -> IcedElement<'_> {
+    let Some(demo) = state.demos.demo_file(demo_id) else {
         return widget::column![
             widget::vertical_space(),
             widget::text("Invalid demo"),
@@ -107,31 +109,181 @@ pub fn analysed_demo_view(state: &App, demo_index: usize) -> IcedElement<'_> {
         .spacing(15),
     );
 
+    contents = contents.push(bookmarks_view(state, demo.analysed, analysed));
+
     // Tab selection
     contents = contents.push(view_select(state));
     contents = contents.push(widget::horizontal_rule(1));
 
-    match state.settings.analysed_demo_view {
+    match state.analysed_demo_tab() {
         AnalysedDemoView::Players => {
+            contents = contents.push(
+                widget::row![
+                    widget::Space::with_width(0),
+                    tooltip(
+                        widget::button(
+                            widget::text("Fetch profiles for all players in this demo")
+                                .size(FONT_SIZE)
+                        )
+                        .on_press(Message::Demos(DemosMessage::FetchProfiles(demo.analysed))),
+                        widget::text("Look up everyone's Steam profile, bans and account age"),
+                    ),
+                    widget::checkbox("Show verdicts and badges", state.demos.show_player_details)
+                        .on_toggle(|show| Message::Demos(DemosMessage::ShowPlayerDetails(show)))
+                        .text_size(FONT_SIZE),
+                ]
+                .spacing(15)
+                .align_items(iced::Alignment::Center),
+            );
+
             if state
-                .selected_player
+                .demos
+                .viewing_player
                 .is_some_and(|p| analysed.players.contains_key(&p))
             {
                 contents = contents.push(widget::row![
-                    kda_table(analysed, false).width(300),
+                    kda_table(state, analysed, false).width(if state.demos.show_player_details {
+                        300 + PLAYER_DETAILS_WIDTH
+                    } else {
+                        300
+                    }),
                     widget::vertical_rule(1),
                     detailed_player_view(state, analysed),
                 ]);
             } else {
-                contents = contents.push(kda_table(analysed, true));
+                contents = contents.push(kda_table(state, analysed, true));
             }
         }
-        AnalysedDemoView::Events => contents = contents.push(coming_soon()),
+        AnalysedDemoView::Events => {
+            contents = contents.push(events_view(state, demo.analysed, analysed));
+        }
     }
 
     contents.into()
 }
 
+fn bookmarks_view<'a>(
+    state: &'a App,
+    demo_id: AnalysedDemoID,
+    analysed: &AnalysedDemo,
+) -> IcedElement<'a> {
+    let new_time = bookmarks::parse_time(&state.demos.new_bookmark_time);
+    let add_bookmark = Message::Demos(DemosMessage::AddBookmark(demo_id));
+
+    let mut contents = widget::column![widget::row![
+        widget::Space::with_width(0),
+        widget::text("Bookmarks"),
+        widget::text_input("Time, e.g. 12:30", &state.demos.new_bookmark_time)
+            .on_input(|s| Message::Demos(DemosMessage::SetNewBookmarkTime(s)))
+            .on_submit(add_bookmark.clone())
+            .size(FONT_SIZE)
+            .width(150),
+        widget::text_input("Label", &state.demos.new_bookmark_label)
+            .on_input(|s| Message::Demos(DemosMessage::SetNewBookmarkLabel(s)))
+            .on_submit(add_bookmark.clone())
+            .size(FONT_SIZE),
+        widget::button(widget::text("Add").size(FONT_SIZE))
+            .on_press_maybe(new_time.map(|_| add_bookmark)),
+        widget::Space::with_width(0),
+    ]
+    .spacing(15)
+    .align_items(iced::Alignment::Center)]
+    .spacing(5);
+
+    for (i, b) in state.demos.bookmarks.get(demo_id).iter().enumerate() {
+        let mut time = widget::text(format_time(bookmarks::tick_to_seconds(analysed, b.tick)))
+            .size(FONT_SIZE)
+            .width(60);
+        if state.demos.highlighted_tick == Some((demo_id, b.tick)) {
+            time = time.style(colours::yellow());
+        }
+
+        contents = contents.push(
+            widget::row![
+                widget::Space::with_width(0),
+                widget::button(
+                    widget::column![icon(icons::MINUS)]
+                        .width(20)
+                        .align_items(iced::Alignment::Center)
+                )
+                .on_press(Message::Demos(DemosMessage::RemoveBookmark(demo_id, i))),
+                time,
+                widget::text_input("Label", &b.label)
+                    .on_input(move |s| Message::Demos(DemosMessage::SetBookmarkLabel(
+                        demo_id, i, s
+                    )))
+                    .size(FONT_SIZE),
+                tooltip(
+                    widget::button(widget::text("Show").size(FONT_SIZE))
+                        .on_press(Message::Demos(DemosMessage::ShowBookmark(demo_id, i))),
+                    widget::text("Highlight on the chart"),
+                ),
+                widget::Space::with_width(0),
+            ]
+            .spacing(15)
+            .align_items(iced::Alignment::Center),
+        );
+    }
+
+    contents.into()
+}
+
+/// Every kill in the demo, which can be bookmarked
+fn events_view<'a>(
+    state: &'a App,
+    demo_id: AnalysedDemoID,
+    analysed: &'a AnalysedDemo,
+) -> IcedElement<'a> {
+    let name = |s: SteamID| {
+        analysed
+            .players
+            .get(&s)
+            .map_or_else(|| s.steam3(), |p| p.name.clone())
+    };
+
+    let mut kills = widget::column![].spacing(2);
+    for (i, death) in analysed.kills.iter().enumerate() {
+        let bookmarked = state
+            .demos
+            .bookmarks
+            .get(demo_id)
+            .iter()
+            .any(|b| b.tick == death.tick.0);
+
+        let time = bookmarks::tick_to_seconds(analysed, death.tick.0);
+        let mut row = widget::row![widget::text(format_time(time)).size(FONT_SIZE).width(60)]
+            .spacing(15)
+            .align_items(iced::Alignment::Center)
+            .height(PFP_SMALL_SIZE);
+
+        if let Some(attacker) = death.attacker.filter(|&a| a != death.victim) {
+            row = row.push(widget::text(name(attacker)).size(FONT_SIZE).width(200));
+            row = row.push(widget::text(&death.weapon).size(FONT_SIZE).width(150));
+        } else {
+            row = row.push(widget::Space::with_width(200));
+            row = row.push(widget::text("Died").size(FONT_SIZE).width(150));
+        }
+        row = row.push(widget::text(name(death.victim)).size(FONT_SIZE).width(200));
+        row = row.push(widget::horizontal_space());
+        row = row.push(
+            widget::button(widget::text("Bookmark").size(FONT_SIZE)).on_press_maybe(
+                (!bookmarked).then_some(Message::Demos(DemosMessage::BookmarkKill(demo_id, i))),
+            ),
+        );
+        row = row.push(widget::Space::with_width(15));
+
+        kills = kills.push(row);
+    }
+
+    widget::row![
+        widget::Space::with_width(15),
+        widget::scrollable(kills).width(Length::Fill).direction(
+            widget::scrollable::Direction::Vertical(Properties::default())
+        ),
+    ]
+    .into()
+}
+
 fn view_select(state: &App) -> IcedElement<'_> {
     const VIEWS: &[(&str, AnalysedDemoView)] = &[
         ("Players", AnalysedDemoView::Players),
@@ -141,7 +293,7 @@ fn view_select(state: &App) -> IcedElement<'_> {
     let mut views = widget::row![widget::Space::with_width(0)].spacing(10);
     for &(name, v) in VIEWS {
         let mut button = widget::Button::new(name);
-        if state.settings.analysed_demo_view != v {
+        if state.analysed_demo_tab() != v {
             button = button.on_press(Message::Demos(
                 crate::demos::DemosMessage::SetAnalysedDemoView(v),
             ));
@@ -153,7 +305,11 @@ fn view_select(state: &App) -> IcedElement<'_> {
 }
 
 fn detailed_player_view<'a>(state: &'a App, analysed: &AnalysedDemo) -> IcedElement<'a> {
-    let Some(p) = state.selected_player.and_then(|p| analysed.players.get(&p)) else {
+    let Some(p) = state
+        .demos
+        .viewing_player
+        .and_then(|p| analysed.players.get(&p))
+    else {
         return invalid_view(state);
     };
 
@@ -230,20 +386,25 @@ fn detailed_player_view<'a>(state: &'a App, analysed: &AnalysedDemo) -> IcedElem
     .into()
 }
 
-fn kda_table(
-    analysed: &AnalysedDemo,
+fn kda_table<'a>(
+    state: &'a App,
+    analysed: &'a AnalysedDemo,
     show_classes: bool,
-) -> widget::Column<'_, Message, iced::Theme, iced::Renderer> {
+) -> widget::Column<'a, Message, iced::Theme, iced::Renderer> {
     // Players heading
     let mut player_classes_heading = widget::row![
         widget::Space::with_width(0),
-        widget::text("Player").width(150),
+        widget::text("Player").width(150 + PFP_SMALL_SIZE),
         widget::text("Total")
             .width(80)
             .horizontal_alignment(iced::alignment::Horizontal::Center),
     ]
     .spacing(15)
     .align_items(iced::Alignment::Center);
+    if state.demos.show_player_details {
+        player_classes_heading = player_classes_heading.push(widget::text("Verdict").width(100));
+        player_classes_heading = player_classes_heading.push(widget::Space::with_width(120));
+    }
 
     if show_classes {
         for c in CLASSES {
@@ -259,7 +420,12 @@ fn kda_table(
 
     // Player list
     let mut player_list = widget::column![].spacing(2);
-    player_list = player_list.push(player_table_row(analysed, analysed.user, show_classes));
+    player_list = player_list.push(player_table_row(
+        state,
+        analysed,
+        analysed.user,
+        show_classes,
+    ));
     for s in analysed
         .players
         .keys()
@@ -267,7 +433,7 @@ fn kda_table(
         .filter(|s| *s != analysed.user)
     {
         player_list = player_list.push(widget::horizontal_rule(1));
-        player_list = player_list.push(player_table_row(analysed, s, show_classes));
+        player_list = player_list.push(player_table_row(state, analysed, s, show_classes));
     }
     player_list = player_list.push(widget::Space::with_height(15));
 
@@ -277,6 +443,7 @@ fn kda_table(
             widget::Space::with_width(15),
             widget::scrollable(player_list)
                 .id(Id::new(KDA_SCROLLABLE_ID))
+                .on_scroll(|v| Message::ScrolledView(v.relative_offset()))
                 .direction(widget::scrollable::Direction::Vertical(
                     Properties::default()
                 ),)
@@ -287,11 +454,12 @@ fn kda_table(
     kda_table
 }
 
-fn player_table_row(
-    analysed: &AnalysedDemo,
+fn player_table_row<'a>(
+    state: &'a App,
+    analysed: &'a AnalysedDemo,
     steamid: SteamID,
     show_classes: bool,
-) -> IcedElement<'_> {
+) -> IcedElement<'a> {
     let Some(player) = analysed.players.get(&steamid) else {
         return widget::row![widget::text("Invalid Player")]
             .height(PFP_SMALL_SIZE)
@@ -299,10 +467,31 @@ fn player_table_row(
             .into();
     };
 
+    // pfp + name
+    let mut name = widget::row![]
+        .spacing(5)
+        .align_items(iced::Alignment::Center)
+        .width(150 + PFP_SMALL_SIZE);
+    if let Some((_, pfp)) = state
+        .mac
+        .players
+        .steam_info
+        .get(&steamid)
+        .and_then(|si| state.pfp_cache.get(&si.pfp_hash))
+    {
+        name = name.push(
+            widget::Image::new(pfp.clone())
+                .width(PFP_SMALL_SIZE)
+                .height(PFP_SMALL_SIZE),
+        );
+    }
+    name = name.push(
+        widget::button(widget::text(&player.name).size(FONT_SIZE))
+            .on_press(Message::SelectPlayer(steamid)),
+    );
+
     let mut contents = widget::row![
-        widget::column![widget::button(widget::text(&player.name).size(FONT_SIZE))
-            .on_press(Message::SelectPlayer(steamid))]
-        .width(150),
+        name,
         widget::column![
             widget::text(format_time(player.time)).size(FONT_SIZE),
             format_kda(
@@ -312,11 +501,16 @@ fn player_table_row(
             ),
         ]
         .align_items(iced::Alignment::Center)
-        .width(80)
+        .width(80),
     ]
     .spacing(15)
     .align_items(iced::Alignment::Center);
 
+    if state.demos.show_player_details {
+        contents = contents.push(verdict_picker(state.mac.players.verdict(steamid), steamid));
+        contents = contents.push(super::player::badges(state, steamid, None).width(120));
+    }
+
     if show_classes {
         for c in CLASSES {
             let details = &player.class_details[c as usize];
@@ -339,7 +533,7 @@ fn player_table_row(
     contents = contents.push(widget::Space::with_width(15));
 
     // contents.width(Length::Fill).into()
-    contents.into()
+    super::player::with_context_menu(state, steamid, contents)
 }
 
 fn format_kda<'a>(k: u32, d: u32, a: u32) -> IcedElement<'a> {
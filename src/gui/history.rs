@@ -1,29 +1,101 @@
+use chrono::Utc;
 use iced::{
-    widget::{self, Scrollable},
-    Length,
+    widget::{self, Button, Scrollable, Space},
+    Alignment, Length,
 };
+use tf2_monitor_core::{players::HistoryEntry, steamid_ng::SteamID};
 
-use crate::{App, IcedElement};
+use crate::{App, IcedElement, Message};
 
-use super::player;
+use super::{
+    format_time_since, player, records::FRIEND_OPTIONS, verdict_picker, FONT_SIZE, PFP_SMALL_SIZE,
+};
+
+pub const SCROLLABLE_ID: &str = "History";
 
 #[must_use]
 pub fn view(state: &App) -> IcedElement<'_> {
+    let filter = widget::row![
+        widget::PickList::new(
+            FRIEND_OPTIONS,
+            Some(state.history_friend_filter),
+            Message::SetHistoryFriendFilter
+        )
+        .text_size(FONT_SIZE),
+        widget::horizontal_space(),
+    ]
+    .padding([15, 15, 0, 15]);
+
     let mut contents = widget::column![].spacing(7);
 
-    for (gi, s) in state
+    for entry in state.mac.players.history.iter().rev().filter(|e| {
+        state
+            .history_friend_filter
+            .matches(state.mac.players.is_friends_with_user(e.steamid))
+    }) {
+        contents = contents.push(state.mac.players.game_info.get(&entry.steamid).map_or_else(
+            || row(state, entry),
+            |gi| player::row(state, gi, entry.steamid),
+        ));
+    }
+
+    widget::column![
+        filter,
+        Scrollable::new(contents.padding(15))
+            .id(widget::scrollable::Id::new(SCROLLABLE_ID))
+            .on_scroll(|v| Message::ScrolledView(v.relative_offset()))
+            .width(Length::Fill)
+            .height(Length::Fill),
+    ]
+    .into()
+}
+
+/// A row for players who left before the app was last started, and so have no
+/// game info to show.
+fn row<'a>(state: &'a App, entry: &HistoryEntry) -> IcedElement<'a> {
+    let steamid = entry.steamid;
+    let mut contents = widget::row![verdict_picker(state.mac.players.verdict(steamid), steamid)]
+        .spacing(5)
+        .align_items(Alignment::Center)
+        .width(Length::Fill);
+
+    if let Some((_, pfp)) = state
         .mac
         .players
-        .history
-        .iter()
-        .rev()
-        .filter_map(|s| state.mac.players.game_info.get(s).map(|gi| (gi, s)))
+        .steam_info
+        .get(&steamid)
+        .and_then(|si| state.pfp_cache.get(&si.pfp_hash))
     {
-        contents = contents.push(player::row(state, gi, *s));
+        contents = contents.push(
+            widget::image(pfp.clone())
+                .width(PFP_SMALL_SIZE)
+                .height(PFP_SMALL_SIZE),
+        );
     }
 
-    Scrollable::new(contents.padding(15))
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .into()
+    contents = contents.push(
+        Button::new(widget::text(name(state, steamid)).size(FONT_SIZE))
+            .on_press(Message::SelectPlayer(steamid)),
+    );
+
+    contents = contents.push(Space::with_width(Length::Fill));
+    contents = contents.push(player::badges(state, steamid, None));
+
+    let seconds = Utc::now()
+        .signed_duration_since(entry.left)
+        .num_seconds()
+        .max(0);
+    contents = contents
+        .push(widget::text(format!("Left {}", format_time_since(seconds as u64))).size(FONT_SIZE));
+    contents = contents.push(Space::with_width(5));
+
+    player::with_context_menu(state, steamid, contents)
+}
+
+fn name(state: &App, steamid: SteamID) -> String {
+    state
+        .mac
+        .players
+        .get_name(steamid)
+        .map_or_else(|| format!("{}", u64::from(steamid)), ToOwned::to_owned)
 }
@@ -8,7 +8,7 @@ use crate::{App, IcedElement, Message};
 
 use super::{
     styles::{colours, ButtonColor},
-    FONT_SIZE,
+    timestamp, FONT_SIZE,
 };
 
 pub const SCROLLABLE_ID: &str = "Kills";
@@ -25,6 +25,9 @@ pub fn view(state: &App) -> impl Into<IcedElement<'_>> {
         |contents, kill| {
             contents.push({
                 let mut row = widget::Row::new().align_items(Alignment::Center).spacing(5);
+                if state.settings.chat_timestamps {
+                    row = row.push(timestamp(kill.timestamp));
+                }
 
                 // Killer name
                 let mut killer_name =
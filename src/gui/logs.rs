@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+
+use iced::{
+    widget::{self, scrollable::Id, text, text_input},
+    Color, Length,
+};
+use tf2_monitor_core::settings::instance;
+use tracing::Level;
+
+use super::{styles::colours, FONT_SIZE};
+use crate::{
+    search,
+    settings::AppSettings,
+    tracing_setup::{LogBuffer, LogFile, LogLimits, LogLine},
+    App, IcedElement, Message,
+};
+
+pub const SCROLLABLE_ID: &str = "Logs";
+
+pub const LEVELS: &[Level] = &[
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+pub struct State {
+    buffer: LogBuffer,
+    file: LogFile,
+    /// Copied out of the buffer whenever it changes so the view can borrow them
+    lines: Vec<LogLine>,
+    /// How many lines had been logged when they were last copied
+    seen: usize,
+    /// Only lines at least this important are shown
+    pub level: Level,
+    pub search: String,
+    /// Keep the newest lines in view as they come in
+    pub auto_scroll: bool,
+    /// The folder the log file is in
+    pub log_dir: Option<PathBuf>,
+}
+
+impl State {
+    #[must_use]
+    pub fn new(buffer: LogBuffer, file: LogFile) -> Self {
+        Self {
+            buffer,
+            log_dir: file.dir(),
+            file,
+            lines: Vec::new(),
+            seen: 0,
+            level: Level::INFO,
+            search: String::new(),
+            auto_scroll: true,
+        }
+    }
+
+    /// Use the log file location and limits from `settings`, starting the log
+    /// file if it hasn't been yet
+    pub fn configure_file(&mut self, settings: &AppSettings) {
+        self.file.set_limits(LogLimits {
+            max_bytes: settings.log_max_megabytes.saturating_mul(1_000_000),
+            max_files: settings.log_max_files,
+        });
+        if let Err(e) = self.file.set_dir(settings.log_directory.clone()) {
+            tracing::error!("Failed to create log file in {:?}: {e}", self.file.dir());
+        }
+        self.log_dir = self.file.dir();
+
+        // The instance with the lock is writing to it
+        if instance::is_read_only() {
+            self.file.disable();
+        } else if let Err(e) = self.file.start() {
+            tracing::error!("Failed to create log file, continuing without persistent logs: {e}");
+        }
+    }
+
+    /// Copies any new lines out of the buffer, returns whether there were any
+    pub fn refresh(&mut self) -> bool {
+        let total = self.buffer.total();
+        if total == self.seen {
+            return false;
+        }
+
+        self.lines = self.buffer.lines();
+        self.seen = total;
+        true
+    }
+
+    /// The lines that match the level and search
+    #[must_use]
+    pub fn visible(&self) -> impl Iterator<Item = &LogLine> {
+        let search = search::normalise(&self.search);
+        self.lines.iter().filter(move |line| {
+            line.level <= self.level
+                && (search.is_empty()
+                    || search::normalise(&line.message).contains(&search)
+                    || search::normalise(&line.target).contains(&search))
+        })
+    }
+
+    /// The visible lines, as they would be written to the log file
+    #[must_use]
+    pub fn visible_text(&self) -> String {
+        self.visible()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn level_colour(level: Level) -> Color {
+    match level {
+        Level::ERROR => colours::red(),
+        Level::WARN => colours::orange(),
+        Level::INFO => colours::green(),
+        Level::DEBUG => colours::team_blu(),
+        _ => colours::pink(),
+    }
+}
+
+#[must_use]
+pub fn view(state: &App) -> IcedElement<'_> {
+    let logs = &state.logs;
+
+    let mut open_folder = widget::button("Open log folder");
+    if let Some(dir) = &logs.log_dir {
+        open_folder = open_folder.on_press(Message::Open(dir.to_string_lossy().to_string()));
+    }
+
+    let header = widget::row![
+        widget::Space::with_width(15),
+        widget::PickList::new(LEVELS, Some(logs.level), Message::SetLogLevel).text_size(FONT_SIZE),
+        text_input("Search", &logs.search).on_input(Message::SetLogSearch),
+        widget::checkbox("Auto-scroll", logs.auto_scroll).on_toggle(Message::SetLogAutoScroll),
+        widget::button("Copy visible lines").on_press(Message::CopyLogs),
+        open_folder,
+        widget::Space::with_width(15),
+    ]
+    .spacing(15)
+    .align_items(iced::Alignment::Center);
+
+    let mut lines = widget::column![].spacing(2);
+    for line in logs.visible() {
+        lines = lines.push(
+            widget::row![
+                text(line.time.format("%H:%M:%S%.3f"))
+                    .size(FONT_SIZE)
+                    .width(90),
+                text(line.level)
+                    .size(FONT_SIZE)
+                    .style(level_colour(line.level))
+                    .width(50),
+                text(&line.target).size(FONT_SIZE).width(220),
+                text(&line.message).size(FONT_SIZE).width(Length::Fill),
+            ]
+            .spacing(10),
+        );
+    }
+
+    widget::column![
+        widget::Space::with_height(15),
+        header,
+        widget::horizontal_rule(1),
+        widget::scrollable(widget::row![
+            widget::Space::with_width(15),
+            lines,
+            widget::Space::with_width(15)
+        ])
+        .id(Id::new(SCROLLABLE_ID))
+        .width(Length::Fill)
+        .height(Length::Fill),
+    ]
+    .spacing(10)
+    .into()
+}
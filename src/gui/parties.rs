@@ -0,0 +1,94 @@
+use iced::{
+    widget::{self, Scrollable},
+    Alignment, Length,
+};
+use tf2_monitor_core::players::records::Verdict;
+
+use crate::{App, IcedElement, Message};
+
+use super::{
+    icons::{self, icon},
+    COLOR_PALETTE, FONT_SIZE, VERDICT_OPTIONS,
+};
+
+#[must_use]
+pub fn view(state: &App) -> IcedElement<'_> {
+    let players = &state.mac.players;
+    let parties = players.parties.parties();
+
+    if parties.is_empty() {
+        return widget::Container::new(widget::text("No parties detected"))
+            .center_x()
+            .center_y()
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into();
+    }
+
+    // Keep the index so the colours still match the icons on the player rows
+    let mut sorted: Vec<_> = parties.iter().enumerate().collect();
+    sorted.sort_by_key(|(_, p)| !players.user.is_some_and(|u| p.contains(&u)));
+
+    let mut contents = widget::column![].spacing(15).padding(10);
+    for (i, party) in sorted {
+        let colour = COLOR_PALETTE[i % COLOR_PALETTE.len()];
+        let title = if players.user.is_some_and(|u| party.contains(&u)) {
+            String::from("Your party")
+        } else {
+            format!("Party {}", i + 1)
+        };
+
+        let mut party_contents = widget::column![widget::row![
+            icon(icons::PARTY).style(colour),
+            widget::text(title),
+            widget::horizontal_space(),
+            widget::text(format!("{} members", party.len())).size(FONT_SIZE),
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center)]
+        .spacing(3);
+
+        // Verdict summary
+        for &verdict in VERDICT_OPTIONS.iter().filter(|&&v| v != Verdict::Player) {
+            let count = party
+                .iter()
+                .filter(|&&s| players.verdict(s) == verdict)
+                .count();
+            if count > 0 {
+                party_contents = party_contents.push(
+                    widget::text(format!(
+                        "{count} {} marked {verdict}",
+                        if count == 1 { "member" } else { "members" }
+                    ))
+                    .size(FONT_SIZE),
+                );
+            }
+        }
+
+        // Members
+        let mut members: Vec<_> = party
+            .iter()
+            .map(|&s| {
+                let name = players
+                    .get_name(s)
+                    .map_or_else(|| format!("{}", u64::from(s)), ToOwned::to_owned);
+                (name, s)
+            })
+            .collect();
+        members.sort_by_key(|(name, _)| name.to_lowercase());
+
+        for (name, s) in members {
+            party_contents = party_contents.push(
+                widget::button(widget::text(name).size(FONT_SIZE))
+                    .on_press(Message::SelectPlayer(s)),
+            );
+        }
+
+        contents = contents.push(party_contents);
+    }
+
+    Scrollable::new(contents)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+}
@@ -1,27 +1,43 @@
-use chrono::{DateTime, Datelike, Utc};
+use chrono::{DateTime, Datelike, Local, Utc};
 use iced::{
     alignment::{Horizontal, Vertical},
+    theme,
     widget::{self, column, Button, Image, Scrollable, Space, TextInput},
-    Alignment, Length,
+    Alignment, Color, Length,
 };
 use tf2_monitor_core::{
     players::{
         game_info::{GameInfo, PlayerState, Team},
-        records::PlayerRecord,
+        records::{PlayerRecord, PreviousName, Verdict, VoteStats},
         steam_info::ProfileVisibility,
+        Players,
     },
     steamid_ng::SteamID,
+    tf_demo_parser::demo::parser::analyser::Class,
 };
 
 use super::{
-    copy_button, format_time,
+    copy_button, country_flag, format_thousands, format_time, format_time_since,
     icons::{self, icon},
     open_profile_button,
     styles::colours,
-    tooltip, verdict_picker, COLOR_PALETTE, FONT_SIZE, PFP_FULL_SIZE, PFP_SMALL_SIZE,
+    timestamp, tooltip, verdict_picker, COLOR_PALETTE, FONT_SIZE, PFP_FULL_SIZE, PFP_SMALL_SIZE,
+    VERDICT_OPTIONS,
 };
 use crate::{App, IcedElement, Message, ALIAS_KEY, NOTES_KEY};
 
+/// So the notes can be focused when adding a note from the context menu
+pub const NOTES_INPUT_ID: &str = "player_notes";
+
+const DEAD_COLOUR: Color = Color::from_rgb(0.5, 0.5, 0.5);
+
+/// Parts of the detailed player view that can be collapsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlayerSection {
+    Chat,
+    Kills,
+}
+
 /// The large player panel to the side of the window
 #[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
 pub fn detailed_player_view(state: &App, player: SteamID) -> IcedElement<'_> {
@@ -66,11 +82,12 @@ pub fn detailed_player_view(state: &App, player: SteamID) -> IcedElement<'_> {
     // Name and previous names
     match maybe_record {
         Some(record) if !record.previous_names().is_empty() => {
-            let mut tooltip_text = String::new();
-            record
+            let tooltip_text = record
                 .previous_names()
                 .iter()
-                .for_each(|n| tooltip_text.push_str(&format!("{n}\n")));
+                .map(previous_name_text)
+                .collect::<Vec<_>>()
+                .join("\n");
 
             name = name.push(tooltip(name_text, widget::text(tooltip_text)));
         }
@@ -104,18 +121,225 @@ pub fn detailed_player_view(state: &App, player: SteamID) -> IcedElement<'_> {
 
     contents = contents.push(steamid);
 
-    // Notes
+    // External links
+    let external_links = state
+        .settings
+        .external_links
+        .iter()
+        .filter_map(|link| Some((&link.name, link.url_for(player)?)))
+        .fold(widget::row![].spacing(5), |row, (name, url)| {
+            row.push(Button::new(widget::text(name).size(FONT_SIZE)).on_press(Message::Open(url)))
+        });
+    contents = contents.push(external_links);
+
+    // Masterbase verdict
+    if let Some(verdict) = state.mac.players.masterbase_verdicts.get(&player) {
+        let text = if verdict.convicted {
+            widget::text("Convicted by the masterbase").style(colours::red())
+        } else {
+            widget::text("Not convicted by the masterbase")
+        };
+        contents = contents.push(tooltip(
+            text,
+            widget::text(format!(
+                "Checked {}",
+                verdict
+                    .fetched
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+            )),
+        ));
+    }
+
+    // Friends with the user
+    match (
+        state.mac.players.is_friends_with_user(player),
+        state
+            .mac
+            .players
+            .friend_since_with_user(player)
+            .and_then(|t| DateTime::from_timestamp(t as i64, 0)),
+    ) {
+        (Some(true), Some(since)) => {
+            contents = contents.push(
+                widget::text(format!(
+                    "Friends since {}",
+                    since.with_timezone(&Local).format("%B %Y")
+                ))
+                .style(colours::green()),
+            );
+        }
+        (Some(true), None) => {
+            contents = contents.push(widget::text("Friends").style(colours::green()));
+        }
+        _ => {}
+    }
+
+    // Encounters
+    if let Some(record) = maybe_record.filter(|r| r.encounters() > 0) {
+        let mut encounters_text = format!(
+            "Seen {} {}",
+            record.encounters(),
+            if record.encounters() == 1 {
+                "time"
+            } else {
+                "times"
+            }
+        );
+        if let Some(first) = record.first_encountered() {
+            encounters_text.push_str(&format!(
+                ", first seen {}",
+                first.with_timezone(&Local).format("%Y-%m-%d")
+            ));
+        }
+
+        let recent: Vec<String> = record
+            .recent_encounters()
+            .iter()
+            .rev()
+            .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+            .collect();
+
+        contents = contents.push(tooltip(
+            widget::text(encounters_text).size(FONT_SIZE),
+            widget::text(format!("Recent encounters:\n{}", recent.join("\n"))),
+        ));
+    }
+
+    // Time in the same matches, from analysed demos
+    let shared = state.mac.players.shared_playtime.seconds_with(player);
+    if shared >= 60 {
+        let time = if shared >= 60 * 60 {
+            format!("{:.1} hours", shared as f32 / (60.0 * 60.0))
+        } else {
+            format!("{} minutes", shared / 60)
+        };
+        contents = contents.push(tooltip(
+            widget::text(format!("You've played {time} in the same matches")).size(FONT_SIZE),
+            widget::text("Counted from your analysed demos"),
+        ));
+    }
+
+    // Seen with marked players
+    if let Some(seen_with) = state.mac.players.co_occurrence.get(player) {
+        let mut marked: Vec<(SteamID, u32)> =
+            seen_with.marked.iter().map(|(&s, &n)| (s, n)).collect();
+        marked.sort_by(|(_, a), (_, b)| b.cmp(a));
+        let marked: Vec<String> = marked
+            .into_iter()
+            .take(10)
+            .map(|(s, n)| {
+                let name = state
+                    .mac
+                    .players
+                    .get_name(s)
+                    .map_or_else(|| format!("{}", u64::from(s)), ToOwned::to_owned);
+                format!("{name} ({n})")
+            })
+            .collect();
+
+        contents = contents.push(tooltip(
+            widget::text(format!(
+                "Has been on a server with marked cheaters {} {}",
+                seen_with.times,
+                if seen_with.times == 1 {
+                    "time"
+                } else {
+                    "times"
+                }
+            ))
+            .size(FONT_SIZE)
+            .style(colours::orange()),
+            widget::text(format!("Seen with:\n{}", marked.join("\n"))),
+        ));
+    }
+
+    // Alias and notes
     contents = contents.push(
-        TextInput::new(
-            "Notes",
-            maybe_record
-                .and_then(|r| r.custom_data().get(NOTES_KEY).and_then(|v| v.as_str()))
-                .unwrap_or(""),
-        )
-        .size(FONT_SIZE)
-        .on_input(move |notes| Message::ChangeNotes(player, notes)),
+        widget::row![
+            TextInput::new(
+                "Alias",
+                maybe_record
+                    .and_then(|r| r.custom_data().get(ALIAS_KEY).and_then(|v| v.as_str()))
+                    .unwrap_or(""),
+            )
+            .size(FONT_SIZE)
+            .width(Length::FillPortion(1))
+            .on_input(move |alias| Message::ChangeAlias(player, alias)),
+            TextInput::new(
+                "Notes",
+                maybe_record
+                    .and_then(|r| r.custom_data().get(NOTES_KEY).and_then(|v| v.as_str()))
+                    .unwrap_or(""),
+            )
+            .id(widget::text_input::Id::new(NOTES_INPUT_ID))
+            .size(FONT_SIZE)
+            .width(Length::FillPortion(2))
+            .on_input(move |notes| Message::ChangeNotes(player, notes)),
+        ]
+        .spacing(5),
     );
 
+    // Linked accounts
+    contents = contents.push(widget::Space::with_height(15));
+    contents = contents.push(linked_accounts(state, player));
+
+    // Previous names
+    if let Some(names) = maybe_record
+        .map(PlayerRecord::previous_names)
+        .filter(|n| !n.is_empty())
+    {
+        contents = contents.push(widget::Space::with_height(15));
+        contents = contents.push(
+            widget::text("Previous Names")
+                .width(Length::Fill)
+                .horizontal_alignment(Horizontal::Center),
+        );
+
+        for previous in names {
+            contents = contents.push(widget::row![
+                widget::text(&previous.name)
+                    .size(FONT_SIZE)
+                    .width(Length::FillPortion(1)),
+                widget::text(previous.last_used.map_or_else(
+                    || String::from("Unknown"),
+                    |t| format!("Last used {}", time_since(t))
+                ))
+                .size(FONT_SIZE)
+                .width(Length::FillPortion(1)),
+            ]);
+        }
+    }
+
+    // Verdict history
+    if let Some(history) = maybe_record
+        .map(PlayerRecord::verdict_history)
+        .filter(|h| !h.is_empty())
+    {
+        contents = contents.push(widget::Space::with_height(15));
+        contents = contents.push(
+            widget::text("Verdict History")
+                .width(Length::Fill)
+                .horizontal_alignment(Horizontal::Center),
+        );
+
+        for &(changed, verdict) in history.iter().rev() {
+            let mut verdict_text = widget::text(verdict)
+                .size(FONT_SIZE)
+                .width(Length::FillPortion(1));
+            if let Some(col) = verdict_colour(verdict) {
+                verdict_text = verdict_text.style(col);
+            }
+
+            contents = contents.push(widget::row![
+                widget::text(changed.with_timezone(&Local).format("%Y-%m-%d %H:%M"))
+                    .size(FONT_SIZE)
+                    .width(Length::FillPortion(1)),
+                verdict_text,
+            ]);
+        }
+    }
+
     // Game info
     if let Some(gi) = state.mac.players.game_info.get(&player) {
         contents = contents.push(widget::Space::with_height(15));
@@ -150,6 +374,10 @@ pub fn detailed_player_view(state: &App, player: SteamID) -> IcedElement<'_> {
             widget::text("Ping").width(Length::FillPortion(1)),
             widget::text(format!("{}ms", gi.ping)).width(Length::FillPortion(1)),
         ]);
+
+        contents = contents.push(widget::Space::with_height(5));
+        contents = contents.push(player_chat(state, player));
+        contents = contents.push(player_kills(state, player));
     }
 
     // Account info
@@ -194,9 +422,11 @@ pub fn detailed_player_view(state: &App, player: SteamID) -> IcedElement<'_> {
 
         // Country
         if let Some(country) = si.country_code.as_ref() {
+            let country_text = country_flag(country)
+                .map_or_else(|| country.clone(), |flag| format!("{flag} {country}"));
             contents = contents.push(widget::row![
                 widget::text("Country").width(Length::FillPortion(1)),
-                widget::text(country).width(Length::FillPortion(1)),
+                widget::text(country_text).width(Length::FillPortion(1)),
             ]);
         }
 
@@ -252,10 +482,27 @@ pub fn detailed_player_view(state: &App, player: SteamID) -> IcedElement<'_> {
         }
 
         // TF playtime
-        if let Some(playtime) = si.playtime {
+        if let Some(hours) = playtime_hours(state, player) {
             contents = contents.push(widget::row![
                 widget::text("TF2 Playtime").width(Length::FillPortion(1)),
-                widget::text(format!("{} hour(s)", playtime / 60)).width(Length::FillPortion(1)),
+                widget::text(hours.map_or_else(
+                    || String::from("hidden"),
+                    |h| format!("{} hrs", format_thousands(h))
+                ))
+                .width(Length::FillPortion(1)),
+            ]);
+        }
+
+        // Steam level
+        if let Some(level) = si.steam_level {
+            let mut level_text = widget::text(level).width(Length::FillPortion(1));
+            if level == 0 {
+                level_text = level_text.style(colours::pink());
+            }
+
+            contents = contents.push(widget::row![
+                widget::text("Steam Level").width(Length::FillPortion(1)),
+                level_text,
             ]);
         }
 
@@ -315,14 +562,28 @@ pub fn row<'a>(state: &'a App, game_info: &'a GameInfo, player: SteamID) -> Iced
         }
     }
 
+    // Dead players are greyed out
+    let dead = game_info.alive == Some(false);
+    let mut name_text = widget::text(&game_info.name).size(FONT_SIZE);
+    if dead {
+        name_text = name_text.style(DEAD_COLOUR);
+    }
+
     name = name
-        .push(
-            Button::new(widget::text(&game_info.name).size(FONT_SIZE))
-                .on_press(Message::SelectPlayer(player)),
-        )
+        .push(Button::new(name_text).on_press(Message::SelectPlayer(player)))
         .align_items(iced::Alignment::Center)
         .spacing(5);
 
+    // Class
+    if let Some(class) = game_info.class.filter(|&c| c != Class::Other) {
+        name = name.push(tooltip(
+            icon(icons::CLASS[class as usize])
+                .size(FONT_SIZE)
+                .style(if dead { DEAD_COLOUR } else { colours::orange() }),
+            widget::text(format!("{class:?}")),
+        ));
+    }
+
     let mut contents = widget::row![
         verdict_picker(
             state
@@ -356,6 +617,26 @@ pub fn row<'a>(state: &'a App, game_info: &'a GameInfo, player: SteamID) -> Iced
 
     contents = contents.push(Space::with_width(Length::Fill));
 
+    // Suspicion, only for players the user hasn't made their mind up about
+    if state.mac.players.verdict(player) == Verdict::Player {
+        let suspicion = state.mac.players.suspicion(player);
+        if suspicion.score > 0 {
+            let breakdown = suspicion.signals.iter().fold(
+                widget::column![widget::text(format!(
+                    "Suspicion score {}/{}",
+                    suspicion.score, suspicion.max
+                ))],
+                |list, s| list.push(widget::text(format!("+{} {}", s.weight, s.signal))),
+            );
+            contents = contents.push(tooltip(
+                widget::progress_bar(0.0..=1.0, suspicion.fraction())
+                    .width(30)
+                    .height(6),
+                breakdown,
+            ));
+        }
+    }
+
     // Badges
     contents = contents.push(badges(state, player, Some(game_info)));
 
@@ -365,18 +646,293 @@ pub fn row<'a>(state: &'a App, game_info: &'a GameInfo, player: SteamID) -> Iced
     contents = contents.push(widget::text(time).size(FONT_SIZE));
     contents = contents.push(widget::Space::with_width(5));
 
-    contents
+    with_context_menu(
+        state,
+        player,
+        contents.width(Length::Fill).align_items(Alignment::Center),
+    )
+}
+
+/// Right-clicking the content opens the player's [`context_menu`]
+pub fn with_context_menu<'a>(
+    state: &'a App,
+    player: SteamID,
+    content: impl Into<IcedElement<'a>>,
+) -> IcedElement<'a> {
+    super::context_menu::context_menu(content, move || context_menu(state, player))
+}
+
+/// Quick actions for a player, without having to open their details
+#[must_use]
+pub fn context_menu(state: &App, player: SteamID) -> IcedElement<'_> {
+    fn item(label: &str, message: Message) -> Button<'_, Message> {
+        Button::new(widget::text(label).size(FONT_SIZE))
+            .style(theme::Button::Text)
+            .width(Length::Fill)
+            .on_press(message)
+    }
+
+    let verdict = state.mac.players.verdict(player);
+    let verdicts = VERDICT_OPTIONS
+        .iter()
+        .fold(widget::row![].spacing(2), |row, &v| {
+            let mut label = widget::text(v).size(FONT_SIZE);
+            if let Some(colour) = verdict_colour(v) {
+                label = label.style(colour);
+            }
+
+            let mut button = Button::new(label).padding(4);
+            if v == verdict {
+                button = button.style(theme::Button::Primary);
+            } else {
+                button = button
+                    .style(theme::Button::Text)
+                    .on_press(Message::ChangeVerdict(player, v));
+            }
+            row.push(button)
+        });
+
+    let steamid64 = format!("{}", u64::from(player));
+    let name = state
+        .mac
+        .players
+        .get_name(player)
+        .map_or_else(|| steamid64.clone(), ToOwned::to_owned);
+    let mut contents = column![
+        widget::text(name).size(FONT_SIZE),
+        widget::horizontal_rule(1),
+        verdicts,
+        widget::horizontal_rule(1),
+        item(
+            "Copy SteamID64",
+            Message::CopyToClipboard(steamid64.clone())
+        ),
+        item("Copy SteamID3", Message::CopyToClipboard(player.steam3())),
+        item(
+            "Open Steam profile",
+            Message::Open(format!("https://steamcommunity.com/profiles/{steamid64}"))
+        ),
+    ]
+    .spacing(2);
+
+    for link in &state.settings.external_links {
+        if let Some(url) = link.url_for(player) {
+            contents = contents.push(item(&link.name, Message::Open(url)));
+        }
+    }
+    contents = contents.push(item("Add note", Message::EditNotes(player)));
+
+    // Only players in the current server can be kicked
+    if state.mac.players.connected.contains(&player)
+        && state
+            .mac
+            .players
+            .game_info
+            .get(&player)
+            .is_some_and(|gi| !gi.userid.is_empty())
+    {
+        contents = contents.push(item("Call votekick", Message::CallVotekick(player)));
+    }
+
+    widget::container(contents)
+        .width(260)
+        .padding(5)
+        .style(theme::Container::Box)
+        .into()
+}
+
+/// Heading that shows or hides a [`PlayerSection`]
+fn section_heading<'a>(state: &App, section: PlayerSection, label: String) -> IcedElement<'a> {
+    let arrow = if state.collapsed_player_sections.contains(&section) {
+        "▸"
+    } else {
+        "▾"
+    };
+
+    Button::new(widget::text(format!("{arrow} {label}")).size(FONT_SIZE))
+        .style(theme::Button::Text)
         .width(Length::Fill)
-        .align_items(Alignment::Center)
+        .on_press(Message::TogglePlayerSection(section))
         .into()
 }
 
-#[allow(clippy::too_many_lines)]
+/// How long ago `time` was
+fn time_since(time: DateTime<Utc>) -> String {
+    format_time_since((Utc::now() - time).num_seconds().max(0) as u64)
+}
+
+fn previous_name_text(previous: &PreviousName) -> String {
+    previous.last_used.map_or_else(
+        || previous.name.clone(),
+        |t| format!("{} - last used {}", previous.name, time_since(t)),
+    )
+}
+
+/// What the player has said in the current server
+fn player_chat(state: &App, player: SteamID) -> IcedElement<'_> {
+    let messages: Vec<_> = state
+        .mac
+        .server
+        .chat_history()
+        .iter()
+        .filter(|m| m.steamid == Some(player))
+        .collect();
+
+    let mut contents = column![section_heading(
+        state,
+        PlayerSection::Chat,
+        format!("Chat ({})", messages.len())
+    )]
+    .spacing(3);
+    if state
+        .collapsed_player_sections
+        .contains(&PlayerSection::Chat)
+    {
+        return contents.into();
+    }
+
+    if messages.is_empty() {
+        contents = contents.push(widget::text("Nothing said yet").size(FONT_SIZE));
+    }
+    for m in messages {
+        contents = contents.push(
+            widget::row![
+                widget::container(timestamp(m.timestamp)).width(60),
+                widget::text(&m.message).size(FONT_SIZE),
+            ]
+            .spacing(5),
+        );
+    }
+
+    contents.into()
+}
+
+/// Kills and deaths the player was part of in the current server
+fn player_kills(state: &App, player: SteamID) -> IcedElement<'_> {
+    let kills: Vec<_> = state
+        .mac
+        .server
+        .kill_history()
+        .iter()
+        .filter(|k| k.killer_steamid == Some(player) || k.victim_steamid == Some(player))
+        .collect();
+
+    let mut contents = column![section_heading(
+        state,
+        PlayerSection::Kills,
+        format!("Kills & Deaths ({})", kills.len())
+    )]
+    .spacing(3);
+    if state
+        .collapsed_player_sections
+        .contains(&PlayerSection::Kills)
+    {
+        return contents.into();
+    }
+
+    if kills.is_empty() {
+        contents = contents.push(widget::text("No kills or deaths yet").size(FONT_SIZE));
+    }
+    for k in kills {
+        // Suicides count as deaths
+        let (mut text, colour) = if k.victim_steamid == Some(player) {
+            (
+                format!("Killed by {} with {}", k.killer_name, k.weapon),
+                colours::red(),
+            )
+        } else {
+            (
+                format!("Killed {} with {}", k.victim_name, k.weapon),
+                colours::green(),
+            )
+        };
+        if k.crit {
+            text.push_str(" (crit)");
+        }
+        let text = widget::text(text).size(FONT_SIZE).style(colour);
+
+        contents = contents.push(
+            widget::row![widget::container(timestamp(k.timestamp)).width(60), text,].spacing(5),
+        );
+    }
+
+    contents.into()
+}
+
+/// The account's TF2 playtime in hours. `Some(None)` if it should be known but
+/// isn't, e.g. the profile is private or the game doesn't show on it, and
+/// `None` if it was never looked up.
+#[must_use]
+pub fn playtime_hours(state: &App, player: SteamID) -> Option<Option<u64>> {
+    let steam = state.mac.players.steam_info.get(&player)?;
+    match steam.playtime {
+        Some(minutes) => Some(Some(minutes / 60)),
+        None if state.mac.settings.request_playtime
+            || steam.profile_visibility != ProfileVisibility::Public =>
+        {
+            Some(None)
+        }
+        None => None,
+    }
+}
+
+/// The parts of a player's badges that take more than a quick lookup to work
+/// out, i.e. matching the name rules and reading the record's custom data.
+/// These only change along with the record, the player's name or the rules.
+#[derive(Debug, Clone, Default)]
+pub struct BadgeData {
+    /// Description of the name rule the player's name matches
+    name_rule: Option<String>,
+    vote_stats: VoteStats,
+    notes: Option<String>,
+}
+
+impl BadgeData {
+    #[must_use]
+    pub fn new(players: &Players, player: SteamID) -> Self {
+        let record = players.records.get(&player);
+
+        Self {
+            name_rule: players
+                .get_name(player)
+                .and_then(|name| players.name_rules.matching(name))
+                .map(|rule| {
+                    format!(
+                        "Name matches the rule \"{}\" ({})",
+                        rule.pattern, rule.action
+                    )
+                }),
+            vote_stats: record.map(PlayerRecord::vote_stats).unwrap_or_default(),
+            notes: record
+                .and_then(|r| r.custom_data().get(NOTES_KEY))
+                .and_then(|v| v.as_str())
+                .map(ToOwned::to_owned),
+        }
+    }
+}
+
 #[must_use]
 pub fn badges<'a>(
     state: &'a App,
     player: SteamID,
     game_info: Option<&'a GameInfo>,
+) -> widget::Row<'a, Message, iced::Theme, iced::Renderer> {
+    badges_with(
+        state,
+        player,
+        game_info,
+        &BadgeData::new(&state.mac.players, player),
+    )
+}
+
+/// Same as [`badges`], but with the [`BadgeData`] already worked out
+#[allow(clippy::too_many_lines)]
+#[must_use]
+pub fn badges_with<'a>(
+    state: &'a App,
+    player: SteamID,
+    game_info: Option<&'a GameInfo>,
+    data: &BadgeData,
 ) -> widget::Row<'a, Message, iced::Theme, iced::Renderer> {
     let mut contents = widget::row![].spacing(15);
 
@@ -395,7 +951,109 @@ pub fn badges<'a>(
         }
     }
 
+    // Name changes
+    if let Some(changes) = state
+        .mac
+        .players
+        .name_changes
+        .get(&player)
+        .filter(|c| !c.is_empty())
+    {
+        let times = if changes.len() == 1 { "time" } else { "times" };
+        let tooltip_element = changes.iter().fold(
+            widget::column![widget::text(format!(
+                "Changed name {} {times} this match",
+                changes.len()
+            ))],
+            |list, c| {
+                list.push(widget::text(format!(
+                    "{} {} -> {}",
+                    c.changed.with_timezone(&Local).format("%H:%M"),
+                    c.from,
+                    c.to
+                )))
+            },
+        );
+
+        contents = contents.push(tooltip(
+            widget::text(format!("N{}", changes.len()))
+                .style(colours::orange())
+                .width(25)
+                .horizontal_alignment(Horizontal::Center),
+            tooltip_element,
+        ));
+    }
+
+    // Name rule
+    if let Some(rule) = &data.name_rule {
+        contents = contents.push(tooltip(
+            widget::text("R")
+                .style(colours::orange())
+                .width(15)
+                .horizontal_alignment(Horizontal::Center),
+            widget::text(rule),
+        ));
+    }
+
+    // Copied name
+    if let Some(steal) = state.mac.players.name_steals.involving(player) {
+        let (col, text) = if steal.stealer == player {
+            (
+                colours::red(),
+                format!(
+                    "Possible name stealer, copying {}",
+                    state.display_name(steal.original)
+                ),
+            )
+        } else {
+            (
+                colours::yellow(),
+                format!(
+                    "Name possibly copied by {}",
+                    state.display_name(steal.stealer)
+                ),
+            )
+        };
+
+        contents = contents.push(tooltip(
+            widget::text("C")
+                .style(col)
+                .width(15)
+                .horizontal_alignment(Horizontal::Center),
+            widget::text(text),
+        ));
+    }
+
+    // Voted to keep cheaters in the server
+    let badge_settings = &state.settings.badges;
+    let stats = data.vote_stats;
+    if badge_settings.cheater_kick_no_votes
+        && stats.voted_no_on_cheater_kicks > 0
+        && stats.voted_no_on_cheater_kicks >= badge_settings.cheater_kick_no_votes_times
+    {
+        contents = contents.push(tooltip(
+            widget::text("K")
+                .style(colours::orange())
+                .width(15)
+                .horizontal_alignment(Horizontal::Center),
+            widget::text(format!(
+                "Voted No on kicking a cheater {} time(s), and Yes on {} votekick(s)",
+                stats.voted_no_on_cheater_kicks, stats.voted_yes
+            )),
+        ));
+    }
+
     if let Some(steam) = state.mac.players.steam_info.get(&player) {
+        // Country
+        if let Some((country, flag)) = steam
+            .country_code
+            .as_ref()
+            .filter(|_| state.settings.country_flags)
+            .and_then(|c| country_flag(c).map(|flag| (c, flag)))
+        {
+            contents = contents.push(tooltip(widget::text(flag), widget::text(country)));
+        }
+
         // Private / Friends only profile
         if matches!(
             steam.profile_visibility,
@@ -436,10 +1094,11 @@ pub fn badges<'a>(
         if let Some(created) = steam
             .time_created
             .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+            .filter(|_| badge_settings.young_account)
         {
             let days = Utc::now().signed_duration_since(created).num_days();
 
-            if days < 100 {
+            if days < i64::from(badge_settings.young_account_days) {
                 contents = contents.push(tooltip(
                     widget::text("Y")
                         .style(colours::pink())
@@ -450,6 +1109,52 @@ pub fn badges<'a>(
             }
         }
 
+        // Level 0 account
+        if badge_settings.level_zero && steam.steam_level == Some(0) {
+            contents = contents.push(tooltip(
+                widget::text("L0")
+                    .style(colours::pink())
+                    .width(20)
+                    .horizontal_alignment(Horizontal::Center),
+                widget::text("Level 0 steam account"),
+            ));
+        }
+
+        // Low TF2 playtime, not known if their games are hidden
+        if let Some(hours) = steam
+            .playtime
+            .map(|minutes| minutes / 60)
+            .filter(|_| badge_settings.low_playtime)
+        {
+            if hours < u64::from(badge_settings.low_playtime_hours) {
+                contents = contents.push(tooltip(
+                    widget::text("H")
+                        .style(colours::pink())
+                        .width(15)
+                        .horizontal_alignment(Horizontal::Center),
+                    widget::text(format!("Only {hours} hour(s) in TF2")),
+                ));
+            }
+        }
+
+        // Watched steam groups
+        let watched_groups: Vec<String> = steam
+            .watched_groups(&state.mac.settings.group_watchlist)
+            .map(|g| g.to_string())
+            .collect();
+        if !watched_groups.is_empty() {
+            contents = contents.push(tooltip(
+                widget::text("G")
+                    .style(colours::orange())
+                    .width(15)
+                    .horizontal_alignment(Horizontal::Center),
+                widget::text(format!(
+                    "Member of watched steam group(s): {}",
+                    watched_groups.join(", ")
+                )),
+            ));
+        }
+
         // Old steam info
     } else {
         // No steam info
@@ -469,15 +1174,45 @@ pub fn badges<'a>(
         contents = contents.push(icon(icons::FRIEND).style(colours::green()).size(FONT_SIZE));
     }
 
-    // Notes
-    if let Some(notes) = state
+    // Subscribed bot lists
+    let bot_lists: Vec<&str> = state
         .mac
         .players
-        .records
+        .bot_lists
+        .lists_containing(player)
+        .collect();
+    if !bot_lists.is_empty() {
+        contents = contents.push(tooltip(
+            widget::text("BL")
+                .style(colours::red())
+                .width(20)
+                .horizontal_alignment(Horizontal::Center),
+            widget::text(format!("Listed as a bot by: {}", bot_lists.join(", "))),
+        ));
+    }
+
+    // Masterbase verdict
+    if let Some(verdict) = state
+        .mac
+        .players
+        .masterbase_verdicts
         .get(&player)
-        .and_then(|r| r.custom_data().get(NOTES_KEY))
-        .and_then(|v| v.as_str())
+        .filter(|v| v.convicted)
     {
+        contents = contents.push(tooltip(
+            widget::text("MB")
+                .style(colours::red())
+                .width(20)
+                .horizontal_alignment(Horizontal::Center),
+            widget::text(format!(
+                "Convicted by the masterbase (checked {})",
+                verdict.fetched.with_timezone(&Local).format("%H:%M")
+            )),
+        ));
+    }
+
+    // Notes
+    if let Some(notes) = &data.notes {
         contents = contents.push(tooltip(icon(icons::NOTES), widget::text(notes)));
     }
 
@@ -507,3 +1242,107 @@ pub fn badges<'a>(
 
     contents
 }
+
+/// A current player that can be picked to link to another account
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LinkOption {
+    steamid: SteamID,
+    name: String,
+}
+
+impl std::fmt::Display for LinkOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Suspected alt accounts linked to the player, with controls to add and
+/// remove links
+fn linked_accounts(state: &App, player: SteamID) -> IcedElement<'_> {
+    let mut contents = column![widget::text("Linked Accounts")
+        .width(Length::Fill)
+        .horizontal_alignment(Horizontal::Center)]
+    .spacing(5);
+
+    let linked = state
+        .mac
+        .players
+        .records
+        .get(&player)
+        .map(PlayerRecord::linked_accounts)
+        .unwrap_or_default();
+
+    for &other in &linked {
+        let name = state
+            .mac
+            .players
+            .get_name(other)
+            .map_or_else(|| format!("{}", u64::from(other)), ToString::to_string);
+
+        contents = contents.push(
+            widget::row![
+                widget::button(
+                    widget::column![icon(icons::MINUS)]
+                        .width(20)
+                        .align_items(iced::Alignment::Center)
+                )
+                .on_press(Message::UnlinkAccounts(player, other)),
+                widget::button(widget::text(name).size(FONT_SIZE))
+                    .on_press(Message::SelectPlayer(other)),
+                widget::horizontal_space(),
+                verdict_picker(state.mac.players.verdict(other), other),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center),
+        );
+    }
+
+    let link_options: Vec<LinkOption> = state
+        .mac
+        .players
+        .connected
+        .iter()
+        .filter(|&&s| s != player && !linked.contains(&s))
+        .filter_map(|&s| {
+            state.mac.players.get_name(s).map(|name| LinkOption {
+                steamid: s,
+                name: name.to_string(),
+            })
+        })
+        .collect();
+
+    let input_steamid = SteamID::try_from(state.link_input.trim()).ok();
+    contents = contents.push(
+        widget::row![
+            TextInput::new("SteamID", &state.link_input)
+                .size(FONT_SIZE)
+                .on_input(Message::SetLinkInput)
+                .on_submit(
+                    input_steamid.map_or(Message::None, |s| Message::LinkAccounts(player, s))
+                ),
+            widget::button(widget::text("Link").size(FONT_SIZE))
+                .on_press_maybe(input_steamid.map(|s| Message::LinkAccounts(player, s))),
+            widget::pick_list(link_options, None::<LinkOption>, move |o| {
+                Message::LinkAccounts(player, o.steamid)
+            })
+            .placeholder("Current players")
+            .text_size(FONT_SIZE),
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center),
+    );
+
+    contents.into()
+}
+
+/// Colour a verdict is shown in, matching the verdict picker
+#[must_use]
+pub fn verdict_colour(verdict: Verdict) -> Option<iced::Color> {
+    match verdict {
+        Verdict::Player => None,
+        Verdict::Bot => Some(colours::red()),
+        Verdict::Suspicious => Some(colours::pink()),
+        Verdict::Cheater => Some(colours::orange()),
+        Verdict::Trusted => Some(colours::green()),
+    }
+}
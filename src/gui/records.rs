@@ -1,27 +1,540 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
 use iced::{
     widget::{self, text, text_input, Button, Scrollable, Space},
     Length,
 };
-use tf2_monitor_core::{players::records::Verdict, steamid_ng::SteamID};
+use serde::{Deserialize, Serialize};
+use tf2_monitor_core::{
+    players::{
+        records::{PlayerRecord, Verdict},
+        tf2bd::{ImportSummary, MergePolicy, Tf2bdPlayerlist},
+        Players,
+    },
+    steamid_ng::SteamID,
+};
+
+use super::{
+    copy_button, country_flag, format_thousands, format_time_since, open_profile_button,
+    player::BadgeData, styles::colours, verdict_picker, FONT_SIZE, PFP_SMALL_SIZE,
+};
+use crate::{demos::SortDirection, search, App, IcedElement, Message, ALIAS_KEY, NOTES_KEY};
+
+pub const SCROLLABLE_ID: &str = "Records";
+
+/// How long ago a ban has to have been detected to count as recent
+pub const RECENTLY_BANNED_DAYS: i64 = 30;
+
+/// How long to wait after the search is typed in before searching
+pub const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+
+pub const PER_PAGE_OPTIONS: &[usize] = &[25, 50, 100, 200];
+
+pub const LAST_SEEN_OPTIONS: &[LastSeenFilter] = &[
+    LastSeenFilter::Any,
+    LastSeenFilter::SeenWithin,
+    LastSeenFilter::NotSeenWithin,
+    LastSeenFilter::Never,
+];
+
+pub const FRIEND_OPTIONS: &[FriendFilter] = &[
+    FriendFilter::Any,
+    FriendFilter::Friends,
+    FriendFilter::NotFriends,
+    FriendFilter::Unknown,
+];
+
+pub const SORT_OPTIONS: &[RecordSort] = &[
+    RecordSort::LastSeen,
+    RecordSort::Created,
+    RecordSort::Modified,
+    RecordSort::Name,
+    RecordSort::Verdict,
+    RecordSort::Encounters,
+    RecordSort::SharedPlaytime,
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub enum RecordSort {
+    LastSeen,
+    Created,
+    #[default]
+    Modified,
+    Name,
+    Verdict,
+    Encounters,
+    /// Time spent in the same matches as the user, from analysed demos
+    SharedPlaytime,
+}
 
-use super::{copy_button, open_profile_button, verdict_picker, FONT_SIZE, PFP_SMALL_SIZE};
-use crate::{App, IcedElement, Message, ALIAS_KEY};
+impl Display for RecordSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::LastSeen => "Last seen",
+            Self::Created => "Created",
+            Self::Modified => "Modified",
+            Self::Name => "Name",
+            Self::Verdict => "Verdict",
+            Self::Encounters => "Encounters",
+            Self::SharedPlaytime => "Time played with",
+        };
+        write!(f, "{str}")
+    }
+}
+
+/// Values records are compared by. Records without a value (e.g. never seen,
+/// or no name could be found) always sort last.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Time(DateTime<Utc>),
+    Name(String),
+    Verdict(u8),
+    Count(u32),
+}
+
+impl RecordSort {
+    fn key(self, players: &Players, steamid: SteamID) -> Option<SortKey> {
+        let record = players.records.get(&steamid)?;
+        match self {
+            Self::LastSeen => record.last_seen().map(SortKey::Time),
+            Self::Created => Some(SortKey::Time(record.created())),
+            Self::Modified => Some(SortKey::Time(record.modified())),
+            Self::Name => players
+                .get_name(steamid)
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(|n| SortKey::Name(n.to_lowercase())),
+            Self::Verdict => Some(SortKey::Verdict(record.verdict().severity())),
+            Self::Encounters => Some(SortKey::Count(record.encounters())),
+            Self::SharedPlaytime => {
+                let seconds = players.shared_playtime.seconds_with(steamid);
+                (seconds > 0).then_some(SortKey::Count(seconds))
+            }
+        }
+    }
+
+    /// Sorts the records in the given direction, with any records missing the
+    /// value being sorted by at the end
+    pub fn sort(self, players: &Players, direction: SortDirection, records: &mut [SteamID]) {
+        let mut keyed: Vec<(Option<SortKey>, SteamID)> =
+            records.iter().map(|&s| (self.key(players, s), s)).collect();
+
+        keyed.sort_by(|(a, a_id), (b, b_id)| {
+            let ordering = match (a, b) {
+                (Some(a), Some(b)) => match direction {
+                    SortDirection::Ascending => a.cmp(b),
+                    SortDirection::Descending => b.cmp(a),
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            ordering.then_with(|| u64::from(*a_id).cmp(&u64::from(*b_id)))
+        });
+
+        for (dst, (_, s)) in records.iter_mut().zip(keyed) {
+            *dst = s;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LastSeenFilter {
+    #[default]
+    Any,
+    SeenWithin,
+    NotSeenWithin,
+    /// Records from before last seen was tracked, or that were never in a
+    /// server with the user
+    Never,
+}
+
+impl Display for LastSeenFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Any => "Seen any time",
+            Self::SeenWithin => "Seen in the last",
+            Self::NotSeenWithin => "Not seen in the last",
+            Self::Never => "Never seen",
+        };
+        write!(f, "{str}")
+    }
+}
+
+impl LastSeenFilter {
+    /// Whether a record last seen at the given time passes the filter. If the
+    /// number of days isn't valid, only [`LastSeenFilter::Never`] filters
+    /// anything.
+    #[must_use]
+    pub fn matches(self, last_seen: Option<DateTime<Utc>>, days: Option<i64>) -> bool {
+        let seen_within = |days: i64| {
+            last_seen.is_some_and(|t| Utc::now().signed_duration_since(t).num_days() < days)
+        };
+
+        match (self, days) {
+            (Self::Never, _) => last_seen.is_none(),
+            (Self::SeenWithin, Some(days)) => seen_within(days),
+            (Self::NotSeenWithin, Some(days)) => !seen_within(days),
+            _ => true,
+        }
+    }
+}
+
+/// Filter accounts by whether they are friends with the user. Friends lists
+/// are only looked up for players that have been in a server with the user,
+/// and can be private, so for many accounts it isn't known either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FriendFilter {
+    #[default]
+    Any,
+    Friends,
+    /// Only accounts which are known not to be friends
+    NotFriends,
+    /// Accounts whose friends lists haven't been fetched or are private
+    Unknown,
+}
+
+impl Display for FriendFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Any => "Friends or not",
+            Self::Friends => "Friends of mine",
+            Self::NotFriends => "Not friends",
+            Self::Unknown => "Friends unknown",
+        };
+        write!(f, "{str}")
+    }
+}
+
+impl FriendFilter {
+    /// Whether an account passes the filter, given the result of
+    /// [`Players::is_friends_with_user`]
+    #[must_use]
+    pub const fn matches(self, friends: Option<bool>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Friends => matches!(friends, Some(true)),
+            Self::NotFriends => matches!(friends, Some(false)),
+            Self::Unknown => friends.is_none(),
+        }
+    }
+}
+
+/// What the records search refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchTarget {
+    /// A steamid in any format, or a profile URL containing one
+    Account(SteamID),
+    /// A custom profile URL which has to be resolved through the Steam API
+    Vanity(String),
+    /// Anything else, which is only matched against names and notes
+    Text,
+}
+
+impl SearchTarget {
+    #[must_use]
+    pub fn parse(search: &str) -> Self {
+        let search = search.trim();
+        let url = search
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_start_matches("www.");
+
+        if let Some(rest) = url.strip_prefix("steamcommunity.com/profiles/") {
+            let id = rest.split('/').next().unwrap_or_default();
+            return id
+                .parse::<u64>()
+                .map_or(Self::Text, |id| Self::Account(SteamID::from(id)));
+        }
+
+        if let Some(rest) = url.strip_prefix("steamcommunity.com/id/") {
+            let vanity = rest.split('/').next().unwrap_or_default();
+            if vanity.is_empty() {
+                return Self::Text;
+            }
+            return Self::Vanity(vanity.to_string());
+        }
+
+        SteamID::try_from(search).map_or(Self::Text, Self::Account)
+    }
+}
+
+/// Looking up the account behind a custom profile URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VanityLookup {
+    Resolving,
+    Found(SteamID),
+    NotFound,
+    /// The request failed, so it is tried again the next time it's searched
+    Failed(String),
+}
+
+/// Why a custom profile URL in the search hasn't turned up an account, if it
+/// hasn't
+#[must_use]
+pub fn vanity_hint(state: &App) -> Option<String> {
+    let SearchTarget::Vanity(vanity) = SearchTarget::parse(&state.records.search) else {
+        return None;
+    };
+
+    match state.records.resolved_vanities.get(&vanity) {
+        Some(VanityLookup::Found(_)) => None,
+        Some(VanityLookup::Resolving) => Some(String::from("Looking up custom URL...")),
+        Some(VanityLookup::NotFound) => {
+            Some(format!("No Steam account has the custom URL {vanity:?}"))
+        }
+        Some(VanityLookup::Failed(e)) => Some(format!("Couldn't look up the custom URL: {e}")),
+        None if state.mac.settings.offline => {
+            Some(String::from("Custom URLs can't be looked up while offline"))
+        }
+        None if state.mac.settings.steam_api_key.is_empty() => Some(String::from(
+            "Set a Steam API key in the settings to look up custom URLs",
+        )),
+        // Waiting for typing to stop
+        None => None,
+    }
+}
+
+/// Adding a record for an account that hasn't been seen in game
+#[derive(Debug, Clone)]
+pub struct AddRecordForm {
+    /// A steamid in any format, or a profile URL
+    pub account: String,
+    pub verdict: Verdict,
+    pub alias: String,
+    pub notes: String,
+    /// The account already has a record, which saving again will update
+    pub existing: Option<SteamID>,
+    /// Waiting for a custom URL to be resolved before saving
+    pub resolving: bool,
+    pub error: Option<String>,
+}
+
+impl AddRecordForm {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            account: String::new(),
+            verdict: Verdict::Suspicious,
+            alias: String::new(),
+            notes: String::new(),
+            existing: None,
+            resolving: false,
+            error: None,
+        }
+    }
+}
+
+impl Default for AddRecordForm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lowercased and accent-stripped names and notes of every record, so the
+/// search doesn't have to normalise all of them again on each keystroke.
+#[derive(Default)]
+pub struct SearchIndex {
+    entries: HashMap<SteamID, IndexEntry>,
+}
+
+struct IndexEntry {
+    /// Enough of the record to tell when it has changed. Adding a previous
+    /// name doesn't change when a record was modified, so those are checked
+    /// separately.
+    modified: DateTime<Utc>,
+    num_names: usize,
+    latest_name: Option<String>,
+    /// The current name as it was given, and normalised
+    current_name: Option<(String, String)>,
+    /// Previous names and alias
+    names: Vec<String>,
+    notes: String,
+}
+
+impl IndexEntry {
+    fn new(record: &PlayerRecord) -> Self {
+        let custom_data = record.custom_data();
+        let alias = custom_data.get(ALIAS_KEY).and_then(|v| v.as_str());
+        let notes = custom_data.get(NOTES_KEY).and_then(|v| v.as_str());
+
+        Self {
+            modified: record.modified(),
+            num_names: record.previous_names().len(),
+            latest_name: record.previous_name_strs().next().map(String::from),
+            current_name: None,
+            names: record
+                .previous_name_strs()
+                .chain(alias)
+                .map(search::normalise)
+                .collect(),
+            notes: notes.map(search::normalise).unwrap_or_default(),
+        }
+    }
+
+    fn is_stale(&self, record: &PlayerRecord) -> bool {
+        self.modified != record.modified()
+            || self.num_names != record.previous_names().len()
+            || self.latest_name.as_deref() != record.previous_name_strs().next()
+    }
+}
+
+impl SearchIndex {
+    /// Catch up with any records that have been added, changed or removed
+    /// since this was last called. Only changed records are normalised again.
+    pub fn update(&mut self, players: &Players) {
+        self.entries.retain(|s, _| players.records.contains_key(s));
+
+        for (steamid, record) in players.records.iter() {
+            let entry = self
+                .entries
+                .entry(*steamid)
+                .and_modify(|e| {
+                    if e.is_stale(record) {
+                        *e = IndexEntry::new(record);
+                    }
+                })
+                .or_insert_with(|| IndexEntry::new(record));
+
+            // Players' current names change without their records changing
+            let name = players.get_name(*steamid);
+            if entry.current_name.as_ref().map(|(n, _)| n.as_str()) != name {
+                entry.current_name = name.map(|n| (n.to_owned(), search::normalise(n)));
+            }
+        }
+    }
+
+    /// Whether a record's current name, previous names or alias match the
+    /// already normalised search, or its notes contain it if `exact` isn't
+    /// set. Records that aren't in the index never match.
+    #[must_use]
+    pub fn matches(&self, steamid: SteamID, search: &str, exact: bool) -> bool {
+        let Some(entry) = self.entries.get(&steamid) else {
+            return false;
+        };
+
+        let mut names = entry
+            .names
+            .iter()
+            .chain(entry.current_name.as_ref().map(|(_, n)| n));
+        if exact {
+            names.any(|n| n == search)
+        } else {
+            names.any(|n| n.contains(search)) || entry.notes.contains(search)
+        }
+    }
+}
+
+/// The parts of a record's row that take a few lookups to work out. These are
+/// kept for the rows on the current page until the record or the account's
+/// steam info changes.
+pub struct RowData {
+    stamp: RowStamp,
+    /// The record's alias if it has one, otherwise the player's name
+    pub name: Option<String>,
+    pub badges: BadgeData,
+}
+
+/// What a row was worked out from, to tell when it has to be done again
+#[derive(PartialEq, Eq)]
+struct RowStamp {
+    modified: Option<DateTime<Utc>>,
+    steam_info_fetched: Option<DateTime<Utc>>,
+    /// Names in the server can change without anything else changing
+    in_server: bool,
+}
+
+impl RowStamp {
+    fn new(players: &Players, steamid: SteamID) -> Self {
+        Self {
+            modified: players.records.get(&steamid).map(PlayerRecord::modified),
+            steam_info_fetched: players.steam_info.get(&steamid).map(|si| si.fetched),
+            in_server: players.game_info.contains_key(&steamid),
+        }
+    }
+}
+
+impl RowData {
+    #[must_use]
+    pub fn new(players: &Players, steamid: SteamID) -> Self {
+        let alias = players
+            .records
+            .get(&steamid)
+            .and_then(|r| r.custom_data().get(ALIAS_KEY))
+            .and_then(|v| v.as_str());
+
+        Self {
+            stamp: RowStamp::new(players, steamid),
+            name: alias
+                .or_else(|| players.get_name(steamid))
+                .map(ToOwned::to_owned),
+            badges: BadgeData::new(players, steamid),
+        }
+    }
+
+    #[must_use]
+    pub fn is_stale(&self, players: &Players, steamid: SteamID) -> bool {
+        self.stamp.in_server || self.stamp != RowStamp::new(players, steamid)
+    }
+}
 
 pub struct State {
     pub to_display: Vec<SteamID>,
+    /// A steamid searched for exactly, which is displayed even if it doesn't
+    /// pass the filters
+    pub pinned: Option<SteamID>,
     pub num_per_page: usize,
     pub current_page: usize,
+    /// Rows on the current page, see [`RowData`]
+    pub rows: HashMap<SteamID, RowData>,
     pub verdict_whitelist: Vec<Verdict>,
+    /// Only show records that have had a ban detected recently
+    pub recently_banned_only: bool,
     pub search: String,
+    /// Increased every time the search changes, so only the latest change
+    /// is searched for once typing stops
+    pub search_generation: usize,
+    pub search_index: SearchIndex,
+    /// Only match names that are exactly the search (ignoring case)
+    pub exact_name: bool,
+    /// Custom profile URLs that have been looked up
+    pub resolved_vanities: HashMap<String, VanityLookup>,
+    pub last_seen_filter: LastSeenFilter,
+    /// Number of days used by the last seen filter
+    pub last_seen_days: String,
+    /// Only show accounts that have been on a server with marked players at
+    /// least this many times. Empty to show all.
+    pub seen_with_marked: String,
+    pub friend_filter: FriendFilter,
+    /// Only show accounts whose Steam profile is set to this country code.
+    /// Empty to show all.
+    pub country: String,
+    /// Waiting for the user to confirm deleting the displayed records
+    pub confirm_delete: bool,
+    /// A TF2BD playerlist that has been read but not merged yet
+    pub pending_import: Option<(PathBuf, Tf2bdPlayerlist)>,
+    pub import_policy: MergePolicy,
+    pub import_preview: Option<ImportSummary>,
+    pub add_record: Option<AddRecordForm>,
 }
 
 impl State {
     #[must_use]
-    pub fn new() -> Self {
+    pub fn new(num_per_page: usize) -> Self {
         Self {
             to_display: Vec::new(),
-            num_per_page: 50,
+            pinned: None,
+            num_per_page: num_per_page.max(1),
             current_page: 0,
+            rows: HashMap::new(),
             verdict_whitelist: vec![
                 Verdict::Trusted,
                 Verdict::Player,
@@ -29,19 +542,36 @@ impl State {
                 Verdict::Cheater,
                 Verdict::Bot,
             ],
+            recently_banned_only: false,
             search: String::new(),
+            search_generation: 0,
+            search_index: SearchIndex::default(),
+            exact_name: false,
+            resolved_vanities: HashMap::new(),
+            last_seen_filter: LastSeenFilter::Any,
+            last_seen_days: String::from("30"),
+            seen_with_marked: String::new(),
+            friend_filter: FriendFilter::Any,
+            country: String::new(),
+            confirm_delete: false,
+            pending_import: None,
+            import_policy: MergePolicy::KeepWorse,
+            import_preview: None,
+            add_record: None,
         }
     }
 }
 
 impl Default for State {
     fn default() -> Self {
-        Self::new()
+        Self::new(50)
     }
 }
 
 #[must_use]
 pub fn view(state: &App) -> IcedElement<'_> {
+    let started = Instant::now();
+
     // Pages
     let num_pages = state.records.to_display.len() / state.records.num_per_page + 1;
     let displaying_start = (state.records.current_page * state.records.num_per_page + 1)
@@ -78,6 +608,19 @@ pub fn view(state: &App) -> IcedElement<'_> {
                 .min(num_pages - 1)
         )),
         button(">>").on_press(Message::SetRecordPage(num_pages - 1)),
+        widget::Space::with_width(15),
+        widget::PickList::new(
+            PER_PAGE_OPTIONS,
+            Some(state.records.num_per_page),
+            Message::SetRecordsPerPage
+        )
+        .text_size(FONT_SIZE),
+        text("per page").size(FONT_SIZE),
+        widget::Space::with_width(15),
+        widget::button("Add record").on_press(Message::OpenAddRecord),
+        widget::button("Import TF2BD").on_press(Message::ImportTF2BD),
+        widget::button("Export TF2BD").on_press(Message::ExportTF2BD),
+        widget::button("Export CSV").on_press(Message::ExportCSV),
         widget::horizontal_space(),
         widget::text(format!(
             "Displaying {displaying_start} - {displaying_end} of {} ({num_pages} {})",
@@ -94,6 +637,52 @@ pub fn view(state: &App) -> IcedElement<'_> {
             .on_toggle(move |_| Message::ToggleVerdictFilter(v))
     };
 
+    let sorting = widget::row![
+        widget::Space::with_width(15),
+        text("Sort by: "),
+        widget::PickList::new(SORT_OPTIONS, Some(state.settings.record_sort_by), |s| {
+            Message::SetRecordSort(s)
+        })
+        .text_size(FONT_SIZE),
+        widget::button(text(state.settings.record_sort_direction).size(FONT_SIZE))
+            .on_press(Message::ToggleRecordSortDirection),
+        widget::Space::with_width(15),
+        widget::PickList::new(
+            LAST_SEEN_OPTIONS,
+            Some(state.records.last_seen_filter),
+            Message::SetLastSeenFilter
+        )
+        .text_size(FONT_SIZE),
+        text_input("Days", &state.records.last_seen_days)
+            .on_input(Message::SetLastSeenDays)
+            .width(60),
+        text("days"),
+        widget::Space::with_width(15),
+        text("Seen with marked players"),
+        text_input("Any", &state.records.seen_with_marked)
+            .on_input(Message::SetSeenWithMarked)
+            .width(60),
+        text("times"),
+        widget::Space::with_width(15),
+        widget::PickList::new(
+            FRIEND_OPTIONS,
+            Some(state.records.friend_filter),
+            Message::SetRecordFriendFilter
+        )
+        .text_size(FONT_SIZE),
+        widget::Space::with_width(15),
+        text("Country"),
+        text_input("Any", &state.records.country)
+            .on_input(Message::SetRecordCountry)
+            .width(50),
+        text(country_flag(&state.records.country).unwrap_or_default()),
+        widget::horizontal_space(),
+        delete_filtered(state),
+        widget::Space::with_width(15),
+    ]
+    .spacing(5)
+    .align_items(iced::Alignment::Center);
+
     let filters = widget::row![
         widget::Space::with_width(0),
         filter_checkbox(Verdict::Trusted),
@@ -101,11 +690,28 @@ pub fn view(state: &App) -> IcedElement<'_> {
         filter_checkbox(Verdict::Suspicious),
         filter_checkbox(Verdict::Cheater),
         filter_checkbox(Verdict::Bot),
+        widget::checkbox("Recently banned", state.records.recently_banned_only)
+            .on_toggle(|_| Message::ToggleRecentlyBannedFilter),
         text_input("Search", &state.records.search).on_input(Message::SetRecordSearch),
+        widget::checkbox("Exact name", state.records.exact_name)
+            .on_toggle(|_| Message::ToggleExactNameSearch),
         widget::Space::with_width(0),
     ]
     .spacing(15)
     .align_items(iced::Alignment::Center);
+    let filters = match vanity_hint(state) {
+        Some(hint) => widget::column![
+            filters,
+            widget::row![
+                widget::horizontal_space(),
+                text(hint).size(FONT_SIZE).style(colours::orange()),
+                widget::Space::with_width(15),
+            ]
+        ]
+        .spacing(5)
+        .into(),
+        None => IcedElement::from(filters),
+    };
 
     // Records
     let mut contents = widget::column![].spacing(3).padding(15);
@@ -119,20 +725,258 @@ pub fn view(state: &App) -> IcedElement<'_> {
         contents = contents.push(row(state, s));
     }
 
-    widget::column![
+    let mut column = widget::column![
         widget::Space::with_height(15),
         header,
         widget::Space::with_height(15),
-        filters,
-        widget::Space::with_height(15),
-        widget::horizontal_rule(1),
+    ];
+    if let Some(preview) = import_preview(state) {
+        column = column.push(preview).push(widget::Space::with_height(15));
+    }
+    if let Some(form) = add_record_form(state) {
+        column = column.push(form).push(widget::Space::with_height(15));
+    }
+
+    column = column.push(sorting);
+    column = column.push(widget::Space::with_height(15));
+    column = column.push(filters);
+    column = column.push(widget::Space::with_height(15));
+    column = column.push(widget::horizontal_rule(1));
+    column = column.push(
         Scrollable::new(contents)
+            .id(widget::scrollable::Id::new(SCROLLABLE_ID))
+            .on_scroll(|v| Message::ScrolledView(v.relative_offset())),
+    );
+
+    tracing::trace!(
+        "Built records page {} ({} rows) in {:?}",
+        state.records.current_page + 1,
+        state
+            .records
+            .to_display
+            .len()
+            .saturating_sub(state.records.current_page * state.records.num_per_page)
+            .min(state.records.num_per_page),
+        started.elapsed()
+    );
+
+    column.width(Length::Fill).height(Length::Fill).into()
+}
+
+const CSV_HEADER: [&str; 9] = [
+    "SteamID64",
+    "Name",
+    "Alias",
+    "Verdict",
+    "Created",
+    "Modified",
+    "Last seen",
+    "Previous names",
+    "Notes",
+];
+
+/// Write the currently displayed records to a CSV file, returning how many
+/// were written.
+///
+/// # Errors
+/// If the file couldn't be written to
+pub fn export_csv(state: &App, path: &Path) -> std::io::Result<usize> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    // BOM so Excel knows it's UTF-8
+    write!(file, "\u{FEFF}")?;
+    write_csv_row(&mut file, &CSV_HEADER)?;
+
+    let mut written = 0;
+    for &steamid in &state.records.to_display {
+        let Some(record) = state.mac.players.records.get(&steamid) else {
+            continue;
+        };
+
+        let custom_data = |key: &str| {
+            record
+                .custom_data()
+                .get(key)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+        };
+
+        write_csv_row(
+            &mut file,
+            &[
+                &u64::from(steamid).to_string(),
+                state.mac.players.get_name(steamid).unwrap_or_default(),
+                custom_data(ALIAS_KEY),
+                &record.verdict().to_string(),
+                &record.created().to_rfc3339(),
+                &record.modified().to_rfc3339(),
+                &record
+                    .last_seen()
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default(),
+                &record.previous_name_strs().collect::<Vec<_>>().join("; "),
+                custom_data(NOTES_KEY),
+            ],
+        )?;
+        written += 1;
+    }
+
+    file.flush()?;
+    Ok(written)
+}
+
+fn write_csv_row(w: &mut impl Write, fields: &[&str]) -> std::io::Result<()> {
+    let fields: Vec<String> = fields.iter().map(|f| escape_csv_field(f)).collect();
+    write!(w, "{}\r\n", fields.join(","))
+}
+
+/// Quotes a field if it contains anything that would break the CSV, with any
+/// quotes inside doubled up. Fields that a spreadsheet would run as a formula
+/// (e.g. a name starting with `=`) are prefixed with `'` so they stay text.
+fn escape_csv_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{field}")
+    } else {
+        field.to_string()
+    };
+
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Button to delete every record matching the current filters, which has to
+/// be confirmed before anything is deleted
+fn delete_filtered(state: &App) -> IcedElement<'_> {
+    let num_records = state
+        .records
+        .to_display
+        .iter()
+        .filter(|&&s| Some(s) != state.records.pinned)
+        .count();
+
+    if !state.records.confirm_delete {
+        return widget::button(text(format!("Delete {num_records} filtered")).size(FONT_SIZE))
+            .on_press_maybe((num_records > 0).then_some(Message::DeleteFilteredRecords))
+            .into();
+    }
+
+    widget::row![
+        text(format!(
+            "Delete {num_records} records? You can undo this straight afterwards."
+        ))
+        .style(colours::red()),
+        widget::button(text("Delete").size(FONT_SIZE)).on_press(Message::ConfirmDeleteRecords),
+        widget::button(text("Cancel").size(FONT_SIZE)).on_press(Message::CancelDeleteRecords),
     ]
-    .width(Length::Fill)
-    .height(Length::Fill)
+    .spacing(5)
+    .align_items(iced::Alignment::Center)
     .into()
 }
 
+/// Summary of what a pending TF2BD import will do, with controls to confirm it
+#[must_use]
+fn import_preview(state: &App) -> Option<IcedElement<'_>> {
+    let (path, list) = state.records.pending_import.as_ref()?;
+    let summary = state.records.import_preview.unwrap_or_default();
+
+    let file_name = path
+        .file_name()
+        .map_or_else(|| path.to_string_lossy(), |f| f.to_string_lossy());
+
+    Some(
+        widget::row![
+            widget::Space::with_width(15),
+            text(format!(
+                "{file_name} ({} players): {} new, {} updated, {} conflicting{}",
+                list.players.len(),
+                summary.added,
+                summary.updated,
+                summary.conflicts,
+                if summary.invalid > 0 {
+                    format!(", {} invalid skipped", summary.invalid)
+                } else {
+                    String::new()
+                }
+            )),
+            widget::horizontal_space(),
+            widget::pick_list(
+                [
+                    MergePolicy::KeepWorse,
+                    MergePolicy::Overwrite,
+                    MergePolicy::SkipExisting
+                ],
+                Some(state.records.import_policy),
+                Message::SetImportPolicy
+            ),
+            widget::button("Import").on_press(Message::ConfirmImport),
+            widget::button("Cancel").on_press(Message::CancelImport),
+            widget::Space::with_width(15),
+        ]
+        .spacing(10)
+        .align_items(iced::Alignment::Center)
+        .into(),
+    )
+}
+
+fn add_record_form(state: &App) -> Option<IcedElement<'_>> {
+    let form = state.records.add_record.as_ref()?;
+
+    let mut save = widget::button(if form.existing.is_some() {
+        "Update"
+    } else {
+        "Add"
+    });
+    if !form.resolving {
+        save = save.on_press(Message::ConfirmAddRecord);
+    }
+
+    let fields = widget::row![
+        widget::Space::with_width(15),
+        text_input("SteamID or profile URL", &form.account)
+            .on_input(Message::SetAddRecordAccount)
+            .on_submit(Message::ConfirmAddRecord)
+            .width(250),
+        widget::PickList::new(
+            super::VERDICT_OPTIONS,
+            Some(form.verdict),
+            Message::SetAddRecordVerdict
+        ),
+        text_input("Alias", &form.alias)
+            .on_input(Message::SetAddRecordAlias)
+            .width(150),
+        text_input("Notes", &form.notes).on_input(Message::SetAddRecordNotes),
+        save,
+        widget::button("Cancel").on_press(Message::CancelAddRecord),
+        widget::Space::with_width(15),
+    ]
+    .spacing(10)
+    .align_items(iced::Alignment::Center);
+
+    let hint = if form.resolving {
+        Some(String::from("Looking up custom URL..."))
+    } else {
+        form.error.clone()
+    };
+    let Some(hint) = hint else {
+        return Some(fields.into());
+    };
+
+    Some(
+        widget::column![
+            fields,
+            widget::row![
+                widget::Space::with_width(15),
+                text(hint).size(FONT_SIZE).style(colours::orange()),
+            ]
+        ]
+        .spacing(5)
+        .into(),
+    )
+}
+
 #[must_use]
 fn row(state: &App, steamid: SteamID) -> IcedElement<'_> {
     let record = state.mac.players.records.get(&steamid);
@@ -168,27 +1012,90 @@ fn row(state: &App, steamid: SteamID) -> IcedElement<'_> {
         );
     }
 
-    #[allow(clippy::option_if_let_else, clippy::manual_map)]
-    let name_text = if let Some(alias) =
-        record.and_then(|r| r.custom_data().get(ALIAS_KEY).and_then(|v| v.as_str()))
-    {
-        Some(alias)
+    // Rows on the page should already have been worked out, but the page can
+    // change before that happens
+    let fallback;
+    let row = if let Some(row) = state.records.rows.get(&steamid) {
+        row
     } else {
-        state.mac.players.get_name(steamid)
+        fallback = RowData::new(&state.mac.players, steamid);
+        &fallback
     };
 
-    if let Some(name_text) = name_text {
+    if let Some(name_text) = &row.name {
         contents = contents.push(Space::with_width(10));
         contents = contents.push(widget::text(name_text));
     }
 
     contents = contents.push(widget::horizontal_space());
-    contents = contents.push(super::player::badges(state, steamid, None));
+    contents = contents.push(super::player::badges_with(
+        state,
+        steamid,
+        None,
+        &row.badges,
+    ));
+    contents = contents.push(widget::Space::with_width(10));
+
+    // TF2 playtime
+    let playtime = super::player::playtime_hours(state, steamid).map_or_else(String::new, |h| {
+        h.map_or_else(
+            || String::from("hidden"),
+            |h| format!("{}h", format_thousands(h)),
+        )
+    });
+    contents = contents.push(
+        text(playtime)
+            .size(FONT_SIZE)
+            .width(60)
+            .horizontal_alignment(iced::alignment::Horizontal::Right),
+    );
+    contents = contents.push(widget::Space::with_width(10));
+
+    // Last seen
+    let last_seen = record.and_then(PlayerRecord::last_seen).map_or_else(
+        || String::from("Never seen"),
+        |t| {
+            let seconds = Utc::now().signed_duration_since(t).num_seconds();
+            format!("Seen {}", format_time_since(seconds.max(0) as u64))
+        },
+    );
+    contents = contents.push(text(last_seen).size(FONT_SIZE).width(140));
     contents = contents.push(widget::Space::with_width(5));
 
-    contents
-        .align_items(iced::Alignment::Center)
-        .height(PFP_SMALL_SIZE)
-        .width(Length::Fill)
-        .into()
+    super::player::with_context_menu(
+        state,
+        steamid,
+        contents
+            .align_items(iced::Alignment::Center)
+            .height(PFP_SMALL_SIZE)
+            .width(Length::Fill),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape_csv_field;
+
+    #[test]
+    fn csv_fields() {
+        assert_eq!(escape_csv_field("Player"), "Player");
+        assert_eq!(escape_csv_field(""), "");
+        assert_eq!(escape_csv_field("a, b"), "\"a, b\"");
+        assert_eq!(escape_csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(escape_csv_field("two\r\nlines"), "\"two\r\nlines\"");
+    }
+
+    #[test]
+    fn csv_formulas_are_escaped() {
+        assert_eq!(escape_csv_field("=1+1"), "'=1+1");
+        assert_eq!(escape_csv_field("+1"), "'+1");
+        assert_eq!(escape_csv_field("-1"), "'-1");
+        assert_eq!(escape_csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(
+            escape_csv_field("=HYPERLINK(\"x\", \"y\")"),
+            "\"'=HYPERLINK(\"\"x\"\", \"\"y\"\")\""
+        );
+        assert_eq!(escape_csv_field("a=b"), "a=b");
+    }
 }
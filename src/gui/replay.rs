@@ -3,14 +3,20 @@ use iced::{
     Length,
 };
 
-use crate::{replay::ReplayMessage, App, IcedContainer, IcedElement, Message};
+use super::{styles::colours, FONT_SIZE_HEADING};
+use crate::{
+    replay::{library::ExistingReplay, ReplayMessage},
+    App, IcedContainer, IcedElement, Message,
+};
 
 #[must_use]
 pub fn view(app: &App) -> IcedElement<'_> {
     let content = widget::column![
         path_selection(app),
         widget::horizontal_rule(1),
-        details(app)
+        details(app),
+        widget::horizontal_rule(1),
+        existing_replays(app),
     ]
     .padding(15)
     .spacing(15);
@@ -45,6 +51,12 @@ pub fn path_selection(app: &App) -> IcedContainer<'_> {
             widget::button("Select thumbnail")
                 .on_press(Message::Replay(ReplayMessage::BrowseThumbnailPath))
                 .width(BUTTON_WIDTH),
+            widget::button("Generate from demo").on_press_maybe(
+                app.replay
+                    .demo
+                    .is_ok()
+                    .then_some(Message::Replay(ReplayMessage::GenerateThumbnail))
+            ),
             widget::button("Clear").on_press(Message::Replay(ReplayMessage::ClearThumbnail)),
             widget::text(
                 app.replay
@@ -117,11 +129,21 @@ pub fn details(app: &App) -> IcedContainer<'_> {
                 ]
                 .spacing(15),
                 // convert
-                widget::row![
-                    widget::button("Create Replay")
-                        .on_press(Message::Replay(ReplayMessage::CreateReplay)),
-                    widget::text(&app.replay.status)
-                ]
+                if app.replay.confirm_overwrite {
+                    widget::row![
+                        widget::button("Replace")
+                            .on_press(Message::Replay(ReplayMessage::ConfirmOverwrite)),
+                        widget::button("Cancel")
+                            .on_press(Message::Replay(ReplayMessage::CancelOverwrite)),
+                        widget::text(&app.replay.status).style(colours::orange())
+                    ]
+                } else {
+                    widget::row![
+                        widget::button("Create Replay")
+                            .on_press(Message::Replay(ReplayMessage::CreateReplay)),
+                        widget::text(&app.replay.status)
+                    ]
+                }
                 .align_items(iced::Alignment::Center)
                 .spacing(15)
             ]
@@ -129,14 +151,97 @@ pub fn details(app: &App) -> IcedContainer<'_> {
 
             widget::Container::new(content)
                 .width(Length::Fill)
-                .height(Length::Fill)
                 .center_x()
                 .align_y(iced::alignment::Vertical::Top)
         }
         Err(e) => widget::Container::new(widget::text(format!("Invalid demo: {e}")))
             .width(Length::Fill)
-            .height(Length::Fill)
             .center_x()
             .center_y(),
     }
 }
+
+#[must_use]
+pub fn existing_replays(app: &App) -> IcedContainer<'_> {
+    let mut list = widget::column![].spacing(5).padding(5);
+    if app.replay.existing.is_empty() {
+        list = list.push(widget::text("No replays found"));
+    }
+    for replay in &app.replay.existing {
+        list = list.push(existing_replay(app, replay));
+    }
+
+    let content = widget::column![
+        widget::row![
+            widget::text("Existing replays").size(FONT_SIZE_HEADING),
+            widget::horizontal_space(),
+            widget::text(&app.replay.existing_status),
+            widget::button("Refresh").on_press(Message::Replay(ReplayMessage::RefreshExisting)),
+        ]
+        .spacing(15)
+        .align_items(iced::Alignment::Center),
+        widget::scrollable(list),
+    ]
+    .spacing(10);
+
+    widget::Container::new(content)
+        .width(Length::Fill)
+        .height(Length::Fill)
+}
+
+fn existing_replay<'a>(app: &'a App, replay: &'a ExistingReplay) -> IcedElement<'a> {
+    const TITLE_WIDTH: u16 = 350;
+    const MAP_WIDTH: u16 = 200;
+
+    let title: IcedElement<'a> = match &app.replay.renaming {
+        Some((path, title)) if path == &replay.dmx_path => widget::text_input("Replay name", title)
+            .on_input(|s| Message::Replay(ReplayMessage::SetRenameTitle(s)))
+            .on_submit(Message::Replay(ReplayMessage::ConfirmRename))
+            .width(TITLE_WIDTH)
+            .into(),
+        _ => widget::text(&replay.title).width(TITLE_WIDTH).into(),
+    };
+
+    let length = replay.length.map_or_else(String::new, |l| {
+        let seconds = l.max(0.0) as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    });
+
+    let actions = if app
+        .replay
+        .renaming
+        .as_ref()
+        .is_some_and(|(p, _)| p == &replay.dmx_path)
+    {
+        widget::row![
+            widget::button("Save").on_press(Message::Replay(ReplayMessage::ConfirmRename)),
+            widget::button("Cancel").on_press(Message::Replay(ReplayMessage::CancelRename)),
+        ]
+    } else if app.replay.confirm_delete.as_ref() == Some(&replay.dmx_path) {
+        widget::row![
+            widget::text("Delete this replay?").style(colours::red()),
+            widget::button("Delete").on_press(Message::Replay(ReplayMessage::ConfirmDelete)),
+            widget::button("Cancel").on_press(Message::Replay(ReplayMessage::CancelDelete)),
+        ]
+    } else {
+        widget::row![
+            widget::button("Rename").on_press(Message::Replay(ReplayMessage::StartRename(
+                replay.dmx_path.clone()
+            ))),
+            widget::button("Delete").on_press(Message::Replay(ReplayMessage::DeleteReplay(
+                replay.dmx_path.clone()
+            ))),
+        ]
+    };
+
+    widget::row![
+        title,
+        widget::text(&replay.map).width(MAP_WIDTH),
+        widget::text(length),
+        widget::horizontal_space(),
+        actions.spacing(5).align_items(iced::Alignment::Center),
+    ]
+    .spacing(15)
+    .align_items(iced::Alignment::Center)
+    .into()
+}
@@ -1,14 +1,103 @@
 use iced::{
-    widget::{column, row, text, Scrollable, Space},
+    theme,
+    widget::{button, column, horizontal_space, row, text, Scrollable, Space},
     Color, Length,
 };
 use tf2_monitor_core::{
-    players::game_info::{GameInfo, Team},
+    players::{
+        game_info::{GameInfo, Team},
+        records::Verdict,
+    },
     steamid_ng::SteamID,
 };
 
-use super::player;
-use crate::{App, IcedElement};
+use super::{player, votes, FONT_SIZE};
+use crate::{App, IcedElement, Message};
+
+/// Verdicts that are counted in the summary, in the order they're shown
+const SUMMARY_VERDICTS: [Verdict; 4] = [
+    Verdict::Cheater,
+    Verdict::Bot,
+    Verdict::Suspicious,
+    Verdict::Trusted,
+];
+
+/// How many of the players on the server have the verdict, not counting ones
+/// that have disconnected
+#[must_use]
+pub fn count_verdict(state: &App, verdict: Verdict) -> usize {
+    state.mac.players.count_present(verdict)
+}
+
+/// Whether there are any cheaters or bots on the server
+#[must_use]
+pub fn needs_attention(state: &App) -> bool {
+    state.mac.players.present().any(|s| {
+        matches!(
+            state.mac.players.verdict(s),
+            Verdict::Cheater | Verdict::Bot
+        )
+    })
+}
+
+fn verdict_label(verdict: Verdict, count: usize) -> &'static str {
+    match (verdict, count) {
+        (Verdict::Cheater, 1) => "Cheater",
+        (Verdict::Cheater, _) => "Cheaters",
+        (Verdict::Bot, 1) => "Bot",
+        (Verdict::Bot, _) => "Bots",
+        (Verdict::Player, 1) => "Player",
+        (Verdict::Player, _) => "Players",
+        (Verdict::Suspicious, _) => "Suspicious",
+        (Verdict::Trusted, _) => "Trusted",
+    }
+}
+
+/// e.g. "2 Cheaters, 1 Suspicious, 3 Trusted on this server", where each count
+/// filters the players to only those with that verdict
+fn verdict_summary(state: &App) -> IcedElement<'_> {
+    let mut summary = row![].spacing(5).align_items(iced::Alignment::Center);
+    let mut any = false;
+
+    for verdict in SUMMARY_VERDICTS {
+        let count = count_verdict(state, verdict);
+        let active = state.server_verdict_filter == Some(verdict);
+        if count == 0 && !active {
+            continue;
+        }
+
+        if any {
+            summary = summary.push(text(",").size(FONT_SIZE));
+        }
+        any = true;
+
+        let mut label = text(format!("{count} {}", verdict_label(verdict, count))).size(FONT_SIZE);
+        if let Some(colour) = player::verdict_colour(verdict) {
+            label = label.style(colour);
+        }
+
+        let (style, on_press) = if active {
+            (
+                theme::Button::Primary,
+                Message::SetServerVerdictFilter(None),
+            )
+        } else {
+            (
+                theme::Button::Text,
+                Message::SetServerVerdictFilter(Some(verdict)),
+            )
+        };
+        summary = summary.push(button(label).padding(2).style(style).on_press(on_press));
+    }
+
+    if any {
+        summary.push(text("on this server").size(FONT_SIZE)).into()
+    } else {
+        text("No marked players on this server")
+            .size(FONT_SIZE)
+            .into()
+    }
+}
 
 #[must_use]
 pub fn view(state: &App) -> IcedElement<'_> {
@@ -17,13 +106,18 @@ pub fn view(state: &App) -> IcedElement<'_> {
         .players
         .connected
         .iter()
+        .filter(|&&p| {
+            state
+                .server_verdict_filter
+                .map_or(true, |v| state.mac.players.verdict(p) == v)
+        })
         .filter_map(|p| state.mac.players.game_info.get(p).map(|gi| (*p, gi)))
         .collect();
     players.sort_by(|&(_, p1), &(_, p2)| p1.time.cmp(&p2.time));
 
     let team_red_players: Vec<(SteamID, &GameInfo)> = players
         .iter()
-        .filter(|&(_, gi)| gi.team == Team::Red)
+        .filter(|&(_, gi)| gi.team_or_lobby() == Team::Red)
         .copied()
         .rev()
         .collect();
@@ -45,7 +139,7 @@ pub fn view(state: &App) -> IcedElement<'_> {
 
     let team_blu_players: Vec<(SteamID, &GameInfo)> = players
         .iter()
-        .filter(|&(_, gi)| gi.team == Team::Blu)
+        .filter(|&(_, gi)| gi.team_or_lobby() == Team::Blu)
         .copied()
         .rev()
         .collect();
@@ -67,7 +161,7 @@ pub fn view(state: &App) -> IcedElement<'_> {
 
     let team_other_players: Vec<(SteamID, &GameInfo)> = players
         .iter()
-        .filter(|&(_, gi)| gi.team != Team::Red && gi.team != Team::Blu)
+        .filter(|&(_, gi)| !matches!(gi.team_or_lobby(), Team::Red | Team::Blu))
         .copied()
         .rev()
         .collect();
@@ -77,7 +171,7 @@ pub fn view(state: &App) -> IcedElement<'_> {
         Some(
             team_other_players
                 .iter()
-                .filter(|&&(_, gi)| gi.team != Team::Red && gi.team != Team::Blu)
+                .filter(|&&(_, gi)| !matches!(gi.team_or_lobby(), Team::Red | Team::Blu))
                 .fold(
                     column![
                         text(format!(
@@ -96,10 +190,31 @@ pub fn view(state: &App) -> IcedElement<'_> {
         )
     };
 
-    let mut contents = column![row![team_red, team_blu]];
+    let header = row![
+        verdict_summary(state),
+        horizontal_space(),
+        button(text("Refresh all players").size(FONT_SIZE)).on_press_maybe(
+            (!state.mac.settings.steam_api_key.is_empty()
+                && !state.mac.players.connected.is_empty())
+            .then_some(Message::RefreshAllProfiles)
+        ),
+    ]
+    .align_items(iced::Alignment::Center)
+    .padding(10);
+
+    let mut contents = column![header, row![team_red, team_blu]];
     if let Some(others) = team_other {
         contents = contents.push(others);
     }
+    let players = Scrollable::new(contents).width(Length::Fill);
 
-    Scrollable::new(contents).width(Length::Fill).into()
+    // Kept out of the scrollable so it can't be scrolled away during the vote
+    match votes::banner(state) {
+        Some(banner) => column![
+            row![Space::with_width(10), banner, Space::with_width(10)],
+            players
+        ]
+        .into(),
+        None => players.into(),
+    }
 }
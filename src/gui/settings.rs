@@ -2,14 +2,41 @@ use iced::{
     widget::{self, scrollable::Id, Scrollable},
     Length,
 };
+use std::fmt::Display;
+
 use tf2_monitor_core::{
+    console::commands::KickReason,
     events::{InternalPreferences, Preferences},
-    settings::FriendsAPIUsage,
+    players::{name_rules::NameRuleAction, suspicion::Signal, user_friends::RefreshUserFriends},
+    settings::{profiles::DEFAULT_PROFILE_NAME, FriendsAPIUsage},
+    steam::launch_options::TF2_REQUIRED_OPTS,
 };
 
-use crate::{gui::{icons::{self, icon}, tooltip}, settings::{PANEL_SIDES, THEMES}, App, IcedElement, Message, MonitorMessage};
+use crate::{gui::{icons::{self, icon}, styles::{colours, custom_theme::{parse_hex, THEME_COLOURS}}, format_time, tooltip, upload_status_text, FONT_SIZE}, replay::template::{DEFAULT_NAME_TEMPLATE, TEMPLATE_VARIABLES}, settings::{Badge, ExternalLink, MAX_SPLIT_RATIO, MAX_UI_SCALE, MIN_SPLIT_RATIO, MIN_UI_SCALE, PANEL_SIDES, PLACEHOLDER_STEAMID3, PLACEHOLDER_STEAMID64}, App, IcedElement, Message, MonitorMessage};
+
+pub const SCROLLABLE_ID: &str = "Settings";
+
+pub const KICK_REASON_OPTIONS: &[KickReasonChoice] = &[
+    KickReasonChoice(None),
+    KickReasonChoice(Some(KickReason::Cheating)),
+    KickReasonChoice(Some(KickReason::Idle)),
+    KickReasonChoice(Some(KickReason::Scamming)),
+    KickReasonChoice(Some(KickReason::None)),
+];
 
-pub const SCROLLABLE_ID: &str = "Chat";
+/// The reason to give for every votekick, or `None` to pick one from the
+/// player's verdict
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KickReasonChoice(pub Option<KickReason>);
+
+impl Display for KickReasonChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(reason) => write!(f, "{reason}"),
+            None => f.write_str("Based on verdict"),
+        }
+    }
+}
 
 #[allow(clippy::too_many_lines)]
 #[must_use]
@@ -52,8 +79,210 @@ pub fn view(state: &App) -> IcedElement<'_> {
         );
     }
 
+    let mut external_links = widget::column![].spacing(5);
+    for (i, link) in state.settings.external_links.iter().enumerate() {
+        external_links = external_links.push(
+            widget::row![
+                widget::button(widget::column![icon(icons::MINUS)].width(20).align_items(iced::Alignment::Center)).on_press(Message::RemoveExternalLink(i)),
+                widget::text(&link.name).width(150),
+                widget::text(&link.url).size(FONT_SIZE),
+            ].align_items(iced::Alignment::Center).spacing(15)
+        );
+    }
+
+    let new_link = ExternalLink::new(&state.new_link_name, &state.new_link_url);
+    let new_link_error = match &new_link {
+        Err(e) if !state.new_link_url.is_empty() => widget::text(e).size(FONT_SIZE).style(colours::red()),
+        _ => widget::text(""),
+    };
+
+    let mut group_watchlist = widget::column![].spacing(5);
+    for (i, group) in state.mac.settings.group_watchlist.iter().enumerate() {
+        group_watchlist = group_watchlist.push(
+            widget::row![
+                widget::button(widget::column![icon(icons::MINUS)].width(20).align_items(iced::Alignment::Center)).on_press(Message::RemoveWatchedGroup(i)),
+                widget::text(group),
+            ].align_items(iced::Alignment::Center).spacing(15)
+        );
+    }
+
+    let mut bot_list_subscriptions = widget::column![].spacing(5);
+    for (i, url) in state.mac.settings.bot_list_subscriptions.iter().enumerate() {
+        let status = state.mac.players.bot_lists.len_of(url).map_or_else(|| String::from("not fetched"), |n| format!("{n} accounts"));
+        bot_list_subscriptions = bot_list_subscriptions.push(
+            widget::row![
+                widget::button(widget::column![icon(icons::MINUS)].width(20).align_items(iced::Alignment::Center)).on_press(Message::RemoveBotList(i)),
+                widget::text(format!("{url} ({status})")),
+            ].align_items(iced::Alignment::Center).spacing(15)
+        );
+    }
+
+    let suspicion = &state.mac.settings.suspicion;
+    let mut suspicion_weights = widget::column![].spacing(5);
+    for &signal in Signal::ALL {
+        suspicion_weights = suspicion_weights.push(
+            widget::row![
+                widget::text(signal).width(HALF_WIDTH),
+                widget::text_input("Weight", &format!("{}", suspicion.weight(signal))).on_input(move |w| Message::SetSuspicionWeight(signal, w)).width(80),
+            ].align_items(iced::Alignment::Center).spacing(ROW_SPACING)
+        );
+    }
+
+    let mut name_patterns = widget::column![].spacing(5);
+    for (i, pattern) in suspicion.name_patterns.iter().enumerate() {
+        let mut pattern_row = widget::row![
+            widget::button(widget::column![icon(icons::MINUS)].width(20).align_items(iced::Alignment::Center)).on_press(Message::RemoveNamePattern(i)),
+            widget::text(pattern),
+        ].align_items(iced::Alignment::Center).spacing(15);
+        if let Some(e) = state.mac.players.scorer.pattern_error(pattern) {
+            pattern_row = pattern_row.push(tooltip(widget::text("Invalid").size(FONT_SIZE).style(colours::red()), widget::text(e)));
+        }
+        name_patterns = name_patterns.push(pattern_row);
+    }
+
+    let mut name_rules = widget::column![].spacing(5);
+    for (i, rule) in state.mac.settings.name_rules.iter().enumerate() {
+        let mut rule_row = widget::row![
+            widget::button(widget::column![icon(icons::MINUS)].width(20).align_items(iced::Alignment::Center)).on_press(Message::RemoveNameRule(i)),
+            widget::text(&rule.pattern),
+            widget::text(rule.action).size(FONT_SIZE),
+        ].align_items(iced::Alignment::Center).spacing(15);
+        if let Some(e) = state.mac.players.name_rules.pattern_error(&rule.pattern) {
+            rule_row = rule_row.push(tooltip(widget::text("Invalid").size(FONT_SIZE).style(colours::red()), widget::text(e)));
+        }
+        if rule.action.verdict().is_some() {
+            let marked = state.mac.players.name_rule_marks(&rule.pattern);
+            rule_row = rule_row.push(widget::text(format!("{marked} marked")).size(FONT_SIZE));
+            if marked > 0 {
+                rule_row = rule_row.push(tooltip(
+                    widget::button(widget::text("Undo marks").size(FONT_SIZE)).on_press(Message::UndoNameRuleMarks(i)),
+                    widget::text("Set the players this rule marked back to Player, unless their verdict has been changed since"),
+                ));
+            }
+        }
+        name_rules = name_rules.push(rule_row);
+    }
+
+    let mut custom_theme_editor = widget::column![].spacing(5);
+    if let (Some(i), Some(theme)) = (state.settings.selected_custom_theme, state.settings.custom_theme()) {
+        custom_theme_editor = custom_theme_editor.push(
+            widget::row![
+                widget::text("Name").width(HALF_WIDTH),
+                widget::row![
+                    widget::text_input("Theme name", &theme.name).on_input(move |name| Message::SetCustomThemeName(i, name)),
+                    widget::button(widget::text("Delete").size(FONT_SIZE)).on_press(Message::RemoveCustomTheme(i)),
+                ].width(HALF_WIDTH).spacing(10).align_items(iced::Alignment::Center),
+            ].align_items(iced::Alignment::Center)
+        );
+
+        for &colour in THEME_COLOURS {
+            let hex = theme.hex(colour);
+            let swatch = if parse_hex(hex).is_some() {
+                widget::text("■").size(20).style(theme.colour(colour))
+            } else {
+                widget::text("Invalid").size(FONT_SIZE).style(colours::red())
+            };
+
+            custom_theme_editor = custom_theme_editor.push(
+                widget::row![
+                    widget::text(colour.label()).size(FONT_SIZE).width(HALF_WIDTH),
+                    widget::row![
+                        widget::text_input("#rrggbb", hex).on_input(move |hex| Message::SetCustomThemeColour(i, colour, hex)).size(FONT_SIZE).width(100),
+                        swatch,
+                    ].width(HALF_WIDTH).spacing(10).align_items(iced::Alignment::Center),
+                ].align_items(iced::Alignment::Center)
+            );
+        }
+    }
+
+    let mut launch_options_prompt = widget::column![].spacing(5);
+    if let Some(missing) = &state.missing_launch_options {
+        launch_options_prompt = launch_options_prompt.push(
+            widget::text(format!("TF2 is missing the launch options {}. Add them to Steam's config for you? Steam must be closed to do this, and a backup of the config is made first.", missing.join(" ")))
+        ).push(
+            widget::row![
+                widget::button("Add and launch").on_press(Message::AddLaunchOptions),
+                widget::button("Launch anyway").on_press(Message::LaunchTF2Anyway),
+                widget::button("Cancel").on_press(Message::CancelLaunchTF2),
+            ].spacing(10)
+        );
+    }
+
+    let mut upload_queue = widget::column![].spacing(5);
+    for upload in &state.mac.upload_queue.uploads {
+        let status = if upload.in_progress {
+            String::from("Uploading...")
+        } else if let Some(wait) = upload.retry_in() {
+            format!("Retrying in {}", format_time(u32::try_from(wait.as_secs()).unwrap_or(u32::MAX)))
+        } else {
+            String::from("Waiting for the demo to finish recording")
+        };
+
+        upload_queue = upload_queue.push(
+            widget::row![
+                tooltip(
+                    widget::text(&upload.demo_name).width(HALF_WIDTH),
+                    widget::text(upload.last_error.as_deref().unwrap_or("Upload was interrupted")),
+                ),
+                widget::text(status).size(FONT_SIZE),
+                widget::horizontal_space(),
+                widget::button("Retry").on_press_maybe((!upload.in_progress).then(|| Message::RetryQueuedUpload(upload.demo_path.clone()))),
+                widget::button("Cancel").on_press(Message::CancelQueuedUpload(upload.demo_path.clone())),
+            ].align_items(iced::Alignment::Center).spacing(ROW_SPACING)
+        );
+    }
+
+    let steam_key_warning = if state.steam_key_rejected {
+        widget::row![
+            widget::text("Your Steam API key was rejected by Steam. Profile lookups are paused until it is changed.").style(colours::red())
+        ]
+    } else {
+        widget::row![]
+    };
+
+    let provision_warning = if let Some(e) = &state.provision_error {
+        widget::row![
+            widget::text(format!("{e}. If your browser has shown you a key, copy it in above.")).style(colours::red())
+        ]
+    } else {
+        widget::row![]
+    };
+
+    let replay_template_help = TEMPLATE_VARIABLES.iter().fold(
+        String::from("How replays are named when a demo is chosen. Available variables:"),
+        |help, (name, description)| format!("{help}\n{{{name}}} - {description}"),
+    );
+
+    let badges = &state.settings.badges;
+
+    let mut profile_choices = vec![String::from(DEFAULT_PROFILE_NAME)];
+    profile_choices.extend(state.profiles.iter().cloned());
+    let current_profile = state.profile.clone().unwrap_or_else(|| String::from(DEFAULT_PROFILE_NAME));
+
     let contents = widget::column![
+        // PROFILE
+        heading("Profile"),
+        widget::row![
+            widget::row![
+                tooltip(
+                    widget::text("Profile"),
+                    widget::text("Each profile has its own settings, player records and steam cache. The current profile is saved before switching."),
+                )
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::PickList::new(profile_choices, Some(current_profile), Message::SetProfile),
+            ].width(HALF_WIDTH).padding(5),
+        ],
+        widget::row![
+            widget::row![].width(HALF_WIDTH),
+            widget::row![
+                widget::text_input("New profile name", &state.new_profile_name).on_input(Message::SetNewProfileName).on_submit(Message::CreateProfile),
+                widget::button(widget::text("New profile").size(FONT_SIZE)).on_press(Message::CreateProfile),
+            ].width(HALF_WIDTH).padding(5).spacing(10).align_items(iced::Alignment::Center),
+        ],
+
         // UI
+        widget::Space::with_height(HEADING_SPACING),
         heading("UI"),
         widget::row![
             widget::row![
@@ -63,21 +292,60 @@ pub fn view(state: &App) -> IcedElement<'_> {
                 )
             ].width(HALF_WIDTH),
             widget::row![
-                widget::PickList::new(THEMES, Some(state.settings.theme.clone()),Message::SetTheme)
-            ].width(HALF_WIDTH).padding(5),
+                widget::PickList::new(state.settings.theme_choices(), Some(state.settings.theme_choice()), Message::SetTheme),
+                widget::button(widget::text("New custom theme").size(FONT_SIZE)).on_press(Message::NewCustomTheme),
+            ].width(HALF_WIDTH).padding(5).spacing(10).align_items(iced::Alignment::Center),
         ],
+        custom_theme_editor,
         widget::row![
             widget::row![
                 tooltip(
                     widget::text("Panel Side"),
-                    widget::text("Which side the side panel opens on (e.g. to display detailed player information or the chat and killfeed)"),
+                    widget::text("Which side the side panel opens on (e.g. to display detailed player information or the chat and killfeed). Drag the line between it and the rest of the window to resize it, or double click the line to reset it."),
                 )
             ].width(HALF_WIDTH),
             widget::row![
                 widget::PickList::new(PANEL_SIDES, Some(state.settings.panel_side), Message::SetPanelSide)
             ].width(HALF_WIDTH).padding(5),
         ],
-        
+        widget::row![
+            widget::row![
+                tooltip(
+                    widget::checkbox("Split Side Panel", state.settings.split_side_panel).on_toggle(Message::SetSplitSidePanel),
+                    widget::text("Show a selected player's details above the chat and killfeed (or other side panel) instead of in place of it"),
+                )
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::slider(MIN_SPLIT_RATIO..=MAX_SPLIT_RATIO, state.settings.split_ratio, Message::SetSplitRatio).step(0.05),
+                widget::text(format!("{:.0}% player", state.settings.split_ratio * 100.0)).size(FONT_SIZE).width(80),
+            ].width(HALF_WIDTH).padding(5).spacing(10).align_items(iced::Alignment::Center),
+        ].align_items(iced::Alignment::Center),
+        widget::row![
+            widget::row![
+                tooltip(
+                    widget::text("UI Scale"),
+                    widget::text("How large text and everything else is displayed, e.g. for high resolution displays"),
+                )
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::slider(MIN_UI_SCALE..=MAX_UI_SCALE, state.settings.ui_scale, Message::SetUiScale).step(0.05),
+                widget::text(format!("{:.0}%", state.settings.ui_scale * 100.0)).size(FONT_SIZE).width(40),
+                widget::button(widget::text("Reset").size(FONT_SIZE)).on_press(Message::SetUiScale(1.0)),
+            ].width(HALF_WIDTH).padding(5).spacing(10).align_items(iced::Alignment::Center),
+        ].align_items(iced::Alignment::Center),
+        widget::row![
+            widget::row![
+                tooltip(
+                    widget::text("Autosave (minutes)"),
+                    widget::text("How often changed settings, player records and cached profiles are saved"),
+                )
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text_input("Autosave (minutes)", &format!("{}", state.settings.autosave_minutes)).on_input(Message::SetAutosaveMinutes),
+                widget::text(state.last_autosave.map_or_else(|| String::from("Not saved yet"), |t| format!("Saved at {}", t.format("%H:%M")))).size(FONT_SIZE),
+            ].width(HALF_WIDTH).padding(5).spacing(10).align_items(iced::Alignment::Center),
+        ].align_items(iced::Alignment::Center),
+
         // RCON
         heading("Rcon"),
 
@@ -98,6 +366,13 @@ pub fn view(state: &App) -> IcedElement<'_> {
                         masterbase_host: None,
                         rcon_port: None,
                         dumb_autokick: None,
+                        steam_info_cache_hours: None,
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: None,
                     }),
                     external: None
                 }))
@@ -122,6 +397,13 @@ pub fn view(state: &App) -> IcedElement<'_> {
                         masterbase_host: None,
                         rcon_port: s.parse::<u16>().ok(),
                         dumb_autokick: None,
+                        steam_info_cache_hours: None,
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: None,
                     }),
                     external: None
                 }))
@@ -152,12 +434,20 @@ pub fn view(state: &App) -> IcedElement<'_> {
                         masterbase_host: None,
                         rcon_port: None,
                         dumb_autokick: None,
+                        steam_info_cache_hours: None,
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: None,
                     }),
                     external: None
                 }))
             ).width(HALF_WIDTH),
         ].align_items(iced::Alignment::Center)
         .spacing(ROW_SPACING),
+        steam_key_warning,
 
         // Friend lookups
         widget::row![
@@ -175,11 +465,24 @@ pub fn view(state: &App) -> IcedElement<'_> {
                         masterbase_host: None,
                         rcon_port: None,
                         dumb_autokick: None,
+                        steam_info_cache_hours: None,
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: None,
                     }),
                     external: None
                 }))
             })].width(HALF_WIDTH).padding(5),
         ].align_items(iced::Alignment::Center).spacing(5),
+        widget::row![
+            widget::row![tooltip("Your friends", "Your friends list is read again every 10 minutes, using the Steam Web API as well if you have a key.")].width(HALF_WIDTH),
+            widget::row![
+                widget::button("Refresh friends").on_press(Message::MAC(MonitorMessage::RefreshUserFriends(RefreshUserFriends)))
+            ].width(HALF_WIDTH).padding(5),
+        ].align_items(iced::Alignment::Center).spacing(5),
 
         // Playtime lookups
 
@@ -197,6 +500,13 @@ pub fn view(state: &App) -> IcedElement<'_> {
                             masterbase_host: None,
                             rcon_port: None,
                             dumb_autokick: None,
+                            steam_info_cache_hours: None,
+                            request_steam_level: None,
+                            request_groups: None,
+                            group_watchlist: None,
+                            mark_group_members_suspicious: None,
+                            bot_list_subscriptions: None,
+                            max_history_len: None,
                         }),
                         external: None
                     }))),
@@ -204,6 +514,185 @@ pub fn view(state: &App) -> IcedElement<'_> {
             ),
         ].align_items(iced::Alignment::Center).spacing(5),
 
+        // Steam level lookups
+        widget::row![
+            tooltip(
+                widget::Checkbox::new("Lookup Steam Level", state.mac.settings.request_steam_level)
+                    .on_toggle(|v| Message::MAC(MonitorMessage::Preferences(Preferences {
+                        internal: Some(InternalPreferences {
+                            friends_api_usage: None,
+                            request_playtime: None,
+                            tf2_directory: None,
+                            rcon_password: None,
+                            steam_api_key: None,
+                            masterbase_key: None,
+                            masterbase_host: None,
+                            rcon_port: None,
+                            dumb_autokick: None,
+                            steam_info_cache_hours: None,
+                            request_steam_level: Some(v),
+                            request_groups: None,
+                            group_watchlist: None,
+                            mark_group_members_suspicious: None,
+                            bot_list_subscriptions: None,
+                            max_history_len: None,
+                        }),
+                        external: None
+                    }))),
+                "Should steam profile lookups include the account's Steam level?\nSteam level lookups can only be requested on an individual account basis and may use up a larger number of API requests."
+            ),
+        ].align_items(iced::Alignment::Center).spacing(5),
+
+        // Steam group lookups
+        widget::row![
+            tooltip(
+                widget::Checkbox::new("Lookup Steam Groups", state.mac.settings.request_groups)
+                    .on_toggle(|v| Message::MAC(MonitorMessage::Preferences(Preferences {
+                        internal: Some(InternalPreferences {
+                            friends_api_usage: None,
+                            request_playtime: None,
+                            tf2_directory: None,
+                            rcon_password: None,
+                            steam_api_key: None,
+                            masterbase_key: None,
+                            masterbase_host: None,
+                            rcon_port: None,
+                            dumb_autokick: None,
+                            steam_info_cache_hours: None,
+                            request_steam_level: None,
+                            request_groups: Some(v),
+                            group_watchlist: None,
+                            mark_group_members_suspicious: None,
+                            bot_list_subscriptions: None,
+                            max_history_len: None,
+                        }),
+                        external: None
+                    }))),
+                "Should steam profile lookups include the public steam groups the account is a member of?\nGroup lookups can only be requested on an individual account basis and may use up a larger number of API requests."
+            ),
+        ].align_items(iced::Alignment::Center).spacing(5),
+
+        // Mark members of watched groups
+        widget::row![
+            tooltip(
+                widget::Checkbox::new("Mark watched group members as Suspicious", state.mac.settings.mark_group_members_suspicious)
+                    .on_toggle(|v| Message::MAC(MonitorMessage::Preferences(Preferences {
+                        internal: Some(InternalPreferences {
+                            friends_api_usage: None,
+                            request_playtime: None,
+                            tf2_directory: None,
+                            rcon_password: None,
+                            steam_api_key: None,
+                            masterbase_key: None,
+                            masterbase_host: None,
+                            rcon_port: None,
+                            dumb_autokick: None,
+                            steam_info_cache_hours: None,
+                            request_steam_level: None,
+                            request_groups: None,
+                            group_watchlist: None,
+                            mark_group_members_suspicious: Some(v),
+                            bot_list_subscriptions: None,
+                            max_history_len: None,
+                        }),
+                        external: None
+                    }))),
+                "Automatically mark players without a verdict as Suspicious if they are a member of one of the watched groups below."
+            ),
+        ].align_items(iced::Alignment::Center).spacing(5),
+
+        // Group watchlist
+        widget::row![
+            widget::row![
+                tooltip("Watched groups", "Steam group IDs to watch for. Players in these groups will be flagged (requires steam group lookups)."),
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text_input("Group ID", &state.new_watched_group)
+                    .on_input(Message::SetNewWatchedGroup)
+                    .on_submit(Message::AddWatchedGroup),
+                widget::button("Add").on_press(Message::AddWatchedGroup),
+            ].align_items(iced::Alignment::Center).spacing(5).width(HALF_WIDTH),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+        group_watchlist,
+
+        // Bot list subscriptions
+        widget::row![
+            widget::row![
+                tooltip("Bot lists", "URLs of bot lists to subscribe to, either TF2BD playerlists or one SteamID per line. Listed players are treated as bots unless you have given them a verdict, and are refreshed periodically."),
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text_input("Bot list URL", &state.new_bot_list)
+                    .on_input(Message::SetNewBotList)
+                    .on_submit(Message::AddBotList),
+                widget::button("Add").on_press(Message::AddBotList),
+            ].align_items(iced::Alignment::Center).spacing(5).width(HALF_WIDTH),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+        bot_list_subscriptions,
+
+        // Steam info cache duration
+        widget::row![
+            widget::row![
+                tooltip("Profile cache (hours)", "How many hours fetched steam profiles are kept before they are looked up again."),
+            ].width(HALF_WIDTH),
+            widget::text_input("Profile cache (hours)", &format!("{}", state.mac.settings.steam_info_cache_hours)).on_input(
+                |s| Message::MAC(MonitorMessage::Preferences(Preferences {
+                    internal: Some(InternalPreferences {
+                        friends_api_usage: None,
+                        request_playtime: None,
+                        tf2_directory: None,
+                        rcon_password: None,
+                        steam_api_key: None,
+                        masterbase_key: None,
+                        masterbase_host: None,
+                        rcon_port: None,
+                        dumb_autokick: None,
+                        steam_info_cache_hours: s.parse::<u32>().ok(),
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: None,
+                    }),
+                    external: None
+                }))
+            ).width(HALF_WIDTH),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+
+        // History length
+        widget::row![
+            widget::row![
+                tooltip("History length", "How many players who have left the server are kept in the History view."),
+            ].width(HALF_WIDTH),
+            widget::text_input("History length", &format!("{}", state.mac.settings.max_history_len)).on_input(
+                |s| Message::MAC(MonitorMessage::Preferences(Preferences {
+                    internal: Some(InternalPreferences {
+                        friends_api_usage: None,
+                        request_playtime: None,
+                        tf2_directory: None,
+                        rcon_password: None,
+                        steam_api_key: None,
+                        masterbase_key: None,
+                        masterbase_host: None,
+                        rcon_port: None,
+                        dumb_autokick: None,
+                        steam_info_cache_hours: None,
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: s.parse::<usize>().ok(),
+                    }),
+                    external: None
+                }))
+            ).width(HALF_WIDTH),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+
         // MASTERBASE
         widget::Space::with_height(HEADING_SPACING),
         heading("MAC Integration"),
@@ -219,8 +708,13 @@ pub fn view(state: &App) -> IcedElement<'_> {
             widget::row![
                 tooltip("Masterbase key", "Your personal key for authenticating with the Masterbase."),
                 widget::horizontal_space(),
+                tooltip(
+                    widget::button(if state.provisioning_key { "Waiting for browser..." } else { "Provision key" })
+                        .on_press_maybe((!state.provisioning_key).then_some(Message::ProvisionMasterbaseKey)),
+                    "Log in through Steam in your browser and the key will be filled in automatically.",
+                ),
                 widget::button("Get yours here").on_press(Message::Open(format!("{}://{}/provision", if state.mac.settings.masterbase_http {"http"} else {"https"}, state.mac.settings.masterbase_host ))),
-            ].width(HALF_WIDTH),
+            ].align_items(iced::Alignment::Center).spacing(5).width(HALF_WIDTH),
             widget::text_input("Masterbase key", &state.mac.settings.masterbase_key).on_input(
                 |s| Message::MAC(MonitorMessage::Preferences(Preferences {
                     internal: Some(InternalPreferences {
@@ -233,12 +727,20 @@ pub fn view(state: &App) -> IcedElement<'_> {
                         masterbase_host: None,
                         rcon_port: None,
                         dumb_autokick: None,
+                        steam_info_cache_hours: None,
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: None,
                     }),
                     external: None
                 }))
             ).width(HALF_WIDTH),
         ].align_items(iced::Alignment::Center)
         .spacing(ROW_SPACING),
+        provision_warning,
 
         // Masterbase host
         widget::row![
@@ -257,6 +759,13 @@ pub fn view(state: &App) -> IcedElement<'_> {
                         masterbase_host: Some(s),
                         rcon_port: None,
                         dumb_autokick: None,
+                        steam_info_cache_hours: None,
+                        request_steam_level: None,
+                        request_groups: None,
+                        group_watchlist: None,
+                        mark_group_members_suspicious: None,
+                        bot_list_subscriptions: None,
+                        max_history_len: None,
                     }),
                     external: None
                 }))
@@ -264,10 +773,38 @@ pub fn view(state: &App) -> IcedElement<'_> {
         ].align_items(iced::Alignment::Center)
         .spacing(ROW_SPACING),
 
+        // Upload status
+        widget::row![
+            widget::row![
+                tooltip("Upload status", "Whether the demo currently being recorded is being uploaded to the Masterbase."),
+            ].width(HALF_WIDTH),
+            if state.mac.upload_status.last_error.is_some() {
+                widget::text(upload_status_text(&state.mac.upload_status)).style(colours::red())
+            } else {
+                widget::text(upload_status_text(&state.mac.upload_status))
+            },
+            widget::horizontal_space(),
+            tooltip(
+                widget::button("Retry").on_press_maybe(state.mac.upload_status.last_error.is_some().then_some(Message::RetryMasterbase)),
+                "Close any session left open on the Masterbase and check your key again.",
+            ),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+        upload_queue,
+
         // OTHER
         widget::Space::with_height(HEADING_SPACING),
         heading("Other"),
 
+        // Launch TF2
+        widget::row![
+            tooltip(
+                widget::button("Launch TF2").on_press(Message::LaunchTF2),
+                widget::text(format!("Start TF2 through Steam, after checking it has the launch options needed by the monitor ({}).", TF2_REQUIRED_OPTS.join(" "))),
+            )
+        ].align_items(iced::Alignment::Center).spacing(5),
+        launch_options_prompt,
+
         // Autokick bots
         widget::row![
             tooltip(
@@ -276,6 +813,138 @@ pub fn view(state: &App) -> IcedElement<'_> {
             )
         ].align_items(iced::Alignment::Center).spacing(5),
 
+        // Votekick reason
+        widget::row![
+            tooltip(
+                widget::text("Votekick reason"),
+                widget::text("The reason given when calling a votekick, from the app or by autokick. Based on verdict gives cheating for bots and cheaters, and other for anyone else."),
+            ),
+            widget::PickList::new(KICK_REASON_OPTIONS, Some(KickReasonChoice(state.mac.settings.kick_reason_override)), |c| Message::SetKickReason(c.0)),
+        ].align_items(iced::Alignment::Center).spacing(10),
+
+        // Close to tray
+        widget::row![
+            tooltip(
+                widget::checkbox("Close to tray", state.settings.close_to_tray).on_toggle_maybe(state.tray.is_some().then_some(Message::SetCloseToTray)),
+                widget::text(if state.tray.is_some() {
+                    "Closing the window hides it in the system tray instead of quitting, so the monitor keeps running in the background."
+                } else {
+                    "The system tray isn't available."
+                }),
+            )
+        ].align_items(iced::Alignment::Center).spacing(5),
+
+        // Name changes in chat
+        widget::row![
+            tooltip(
+                widget::checkbox("Show name changes in chat", state.settings.name_changes_in_chat).on_toggle(Message::SetNameChangesInChat),
+                widget::text("Add a line to the chat when a player in the server changes their name, which cheaters often do to dodge votekicks."),
+            )
+        ].align_items(iced::Alignment::Center).spacing(5),
+
+        // Chat timestamps
+        widget::row![
+            tooltip(
+                widget::checkbox("Show times in chat and killfeed", state.settings.chat_timestamps).on_toggle(Message::SetChatTimestamps),
+                widget::text("Show when each chat message was sent and each kill happened. Hover over a time to see how long ago it was."),
+            )
+        ].align_items(iced::Alignment::Center).spacing(5),
+
+        // Country flags
+        widget::row![
+            tooltip(
+                widget::checkbox("Show country flags", state.settings.country_flags).on_toggle(Message::SetCountryFlags),
+                widget::text("Show the flag of the country set on each player's Steam profile next to them"),
+            )
+        ].align_items(iced::Alignment::Center).spacing(5),
+
+        // BADGES
+        widget::Space::with_height(HEADING_SPACING),
+        heading("Badges"),
+
+        widget::row![
+            widget::row![
+                tooltip(
+                    widget::checkbox("Young account", badges.young_account).on_toggle(|v| Message::SetBadgeEnabled(Badge::YoungAccount, v)),
+                    widget::text("Show Y next to accounts created recently"),
+                )
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text("Younger than"),
+                widget::text_input("Days", &format!("{}", badges.young_account_days)).on_input(Message::SetYoungAccountDays).width(80),
+                widget::text("days"),
+            ].width(HALF_WIDTH).spacing(10).align_items(iced::Alignment::Center),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+        widget::row![
+            tooltip(
+                widget::checkbox("Level 0", badges.level_zero).on_toggle(|v| Message::SetBadgeEnabled(Badge::LevelZero, v)),
+                widget::text("Show L0 next to accounts that are Steam level 0"),
+            )
+        ].align_items(iced::Alignment::Center),
+        widget::row![
+            widget::row![
+                tooltip(
+                    widget::checkbox("Low TF2 playtime", badges.low_playtime).on_toggle(|v| Message::SetBadgeEnabled(Badge::LowPlaytime, v)),
+                    widget::text("Show H next to accounts with little time in TF2. Needs \"Lookup TF2 Playtime\" to be on, and accounts whose playtime is hidden are never marked."),
+                )
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text("Less than"),
+                widget::text_input("Hours", &format!("{}", badges.low_playtime_hours)).on_input(Message::SetLowPlaytimeHours).width(80),
+                widget::text("hours"),
+            ].width(HALF_WIDTH).spacing(10).align_items(iced::Alignment::Center),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+        widget::row![
+            widget::row![
+                tooltip(
+                    widget::checkbox("Votes to keep cheaters", badges.cheater_kick_no_votes).on_toggle(|v| Message::SetBadgeEnabled(Badge::CheaterKickNoVotes, v)),
+                    widget::text("Show K next to players that have voted No on kicking players marked as cheaters. Votes are only seen while a demo is being recorded."),
+                )
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text("At least"),
+                widget::text_input("Times", &format!("{}", badges.cheater_kick_no_votes_times)).on_input(Message::SetCheaterKickNoVotes).width(80),
+                widget::text("times"),
+            ].width(HALF_WIDTH).spacing(10).align_items(iced::Alignment::Center),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+
+        // SUSPICION SCORE
+        widget::Space::with_height(HEADING_SPACING),
+        heading("Suspicion score"),
+
+        widget::text("Players without a verdict get a score from how suspicious their account looks, shown as a meter next to them. It never marks anyone by itself. Set a weight to 0 to ignore that signal."),
+        suspicion_weights,
+        widget::row![
+            widget::row![
+                tooltip("Name patterns", "Regexes to match player names against, e.g. twitch\\.tv/ for bots advertising a channel."),
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text_input("Pattern", &state.new_name_pattern)
+                    .on_input(Message::SetNewNamePattern)
+                    .on_submit(Message::AddNamePattern),
+                widget::button("Add").on_press(Message::AddNamePattern),
+            ].align_items(iced::Alignment::Center).spacing(5).width(HALF_WIDTH),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+        name_patterns,
+
+        // NAME RULES
+        widget::Space::with_height(HEADING_SPACING),
+        heading("Name rules"),
+
+        widget::text("Regexes checked against players' names when they join or change name. Players without a verdict whose name matches are marked, or just get a badge. The first rule that matches is used."),
+        widget::row![
+            widget::text_input("Pattern", &state.new_name_rule)
+                .on_input(Message::SetNewNameRule)
+                .on_submit(Message::AddNameRule),
+            widget::PickList::new(NameRuleAction::ALL, Some(state.new_name_rule_action), Message::SetNewNameRuleAction),
+            widget::button("Add").on_press(Message::AddNameRule),
+        ].align_items(iced::Alignment::Center).spacing(5),
+        name_rules,
+
         // DEMOS
         widget::Space::with_height(HEADING_SPACING),
         heading("Demos"),
@@ -285,12 +954,89 @@ pub fn view(state: &App) -> IcedElement<'_> {
             "Add a folder to search for recorded demos in (for use in the Demos tab)"
         ),
         demo_dir_list,
+        tooltip(
+            widget::checkbox("Analyse demos when they finish recording", state.settings.auto_analyse_demos).on_toggle(Message::SetAutoAnalyseDemos),
+            "Demos are analysed once TF2 has finished writing them, so they're ready in the Demos tab"
+        ),
+        tooltip(
+            widget::checkbox("Summarise demos when they finish analysing", state.settings.demo_summaries).on_toggle(Message::SetDemoSummaries),
+            "Shows the map, your K/D/A, who killed you the most and any marked cheaters. When several demos finish together, only how many is shown."
+        ),
 
-        // External section? Probably not
+        // Replay name template
+        widget::row![
+            widget::row![
+                tooltip("Replay name template", widget::text(replay_template_help)),
+            ].width(HALF_WIDTH),
+            widget::text_input(DEFAULT_NAME_TEMPLATE, &state.settings.replay_name_template).on_input(Message::SetReplayNameTemplate).width(HALF_WIDTH),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+
+        // EXTERNAL LINKS
+        widget::Space::with_height(HEADING_SPACING),
+        heading("External links"),
+
+        widget::row![
+            widget::row![
+                tooltip("Add link", widget::text(format!("Sites to look players up on, shown on their details and when right-clicking them. {PLACEHOLDER_STEAMID64} or {PLACEHOLDER_STEAMID3} in the URL is replaced with the player's SteamID."))),
+            ].width(HALF_WIDTH),
+            widget::row![
+                widget::text_input("Name", &state.new_link_name)
+                    .on_input(Message::SetNewLinkName)
+                    .width(120),
+                widget::text_input(&format!("https://example.com/{PLACEHOLDER_STEAMID64}"), &state.new_link_url)
+                    .on_input(Message::SetNewLinkUrl)
+                    .on_submit(Message::AddExternalLink),
+                widget::button("Add").on_press_maybe(new_link.is_ok().then_some(Message::AddExternalLink)),
+            ].align_items(iced::Alignment::Center).spacing(5).width(HALF_WIDTH),
+        ].align_items(iced::Alignment::Center)
+        .spacing(ROW_SPACING),
+        new_link_error,
+        external_links,
+
+        // DIAGNOSTICS
+        widget::Space::with_height(HEADING_SPACING),
+        heading("Diagnostics"),
+        handler_metrics(state),
     ]
     .width(Length::Fill)
     .spacing(5)
     .padding(15);
 
-    Scrollable::new(contents).id(Id::new(SCROLLABLE_ID)).into()
+    Scrollable::new(contents).id(Id::new(SCROLLABLE_ID)).on_scroll(|v| Message::ScrolledView(v.relative_offset())).into()
+}
+
+/// How long each of the core's event handlers has taken, to find which one is
+/// slowing things down. Building the demo list is timed the same way.
+fn handler_metrics(state: &App) -> IcedElement<'_> {
+    let cell = |contents: String| widget::text(contents).size(FONT_SIZE).width(Length::FillPortion(1));
+
+    let mut table = widget::column![
+        widget::text("Times only count the handlers themselves, not the requests they start in the background.").size(FONT_SIZE),
+        widget::row![
+            cell(String::from("Handler")),
+            cell(String::from("Messages")),
+            cell(String::from("Outputs")),
+            cell(String::from("Average")),
+            cell(String::from("Longest")),
+            cell(String::from("Total")),
+            cell(String::from("Panics")),
+        ].spacing(5),
+        widget::horizontal_rule(1),
+    ].spacing(5);
+
+    let demo_list = state.demos.list_metrics.borrow().clone();
+    for h in state.event_loop.metrics().handlers.iter().chain([&demo_list]) {
+        table = table.push(widget::row![
+            cell(h.name.to_string()),
+            cell(h.messages.to_string()),
+            cell(h.outputs.to_string()),
+            cell(format!("{:.1?}", h.average_time())),
+            cell(format!("{:.1?}", h.max_time)),
+            cell(format!("{:.1?}", h.total_time)),
+            widget::text(h.panics).size(FONT_SIZE).width(Length::FillPortion(1)).style(if h.panics > 0 { colours::red() } else { colours::green() }),
+        ].spacing(5));
+    }
+
+    table.into()
 }
@@ -0,0 +1,281 @@
+//! Two panes side by side with a divider between them that can be dragged to
+//! resize them.
+
+use std::time::{Duration, Instant};
+
+use iced::{
+    advanced::{
+        layout, overlay, renderer,
+        widget::{tree, Operation, Tree},
+        Clipboard, Layout, Renderer as _, Shell, Widget,
+    },
+    event, mouse, Border, Element, Event, Length, Point, Rectangle, Shadow, Size,
+};
+
+use crate::{IcedElement, Message};
+
+/// How wide the area that can be grabbed to drag the divider is
+const HANDLE_WIDTH: f32 = 7.0;
+/// Panes aren't made narrower than this unless the window is too small for
+/// both of them
+const MIN_PANE_WIDTH: f32 = 250.0;
+/// Two presses of the divider within this long are a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// `ratio` is how much of the width `first` takes. While the divider is
+/// dragged `on_resize` is given the new ratio, and double clicking it gives
+/// `on_reset`.
+pub fn split<'a>(
+    first: impl Into<IcedElement<'a>>,
+    second: impl Into<IcedElement<'a>>,
+    ratio: f32,
+    on_resize: impl Fn(f32) -> Message + 'a,
+    on_reset: Message,
+) -> IcedElement<'a> {
+    Element::new(Split {
+        panes: [first.into(), second.into()],
+        ratio,
+        on_resize: Box::new(on_resize),
+        on_reset,
+    })
+}
+
+struct Split<'a> {
+    panes: [IcedElement<'a>; 2],
+    ratio: f32,
+    on_resize: Box<dyn Fn(f32) -> Message + 'a>,
+    on_reset: Message,
+}
+
+#[derive(Default)]
+struct State {
+    dragging: bool,
+    last_press: Option<Instant>,
+}
+
+impl Split<'_> {
+    /// The widths of the two panes
+    fn widths(&self, width: f32) -> (f32, f32) {
+        let available = (width - HANDLE_WIDTH).max(0.0);
+        let mut first = available * self.ratio.clamp(0.0, 1.0);
+        if available >= MIN_PANE_WIDTH * 2.0 {
+            first = first.clamp(MIN_PANE_WIDTH, available - MIN_PANE_WIDTH);
+        }
+
+        (first, available - first)
+    }
+
+    fn handle_bounds(layout: Layout<'_>) -> Rectangle {
+        let bounds = layout.bounds();
+        let first = layout.children().next().map_or(bounds, |l| l.bounds());
+        Rectangle {
+            x: first.x + first.width,
+            y: bounds.y,
+            width: HANDLE_WIDTH,
+            height: bounds.height,
+        }
+    }
+}
+
+impl<'a> Widget<Message, iced::Theme, iced::Renderer> for Split<'a> {
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Fill, Length::Fill)
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &iced::Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let size = limits.width(Length::Fill).height(Length::Fill).max();
+        let (first_width, second_width) = self.widths(size.width);
+
+        let [first, second] = &self.panes;
+        let first = first.as_widget().layout(
+            &mut tree.children[0],
+            renderer,
+            &layout::Limits::new(Size::ZERO, Size::new(first_width, size.height)),
+        );
+        let second = second
+            .as_widget()
+            .layout(
+                &mut tree.children[1],
+                renderer,
+                &layout::Limits::new(Size::ZERO, Size::new(second_width, size.height)),
+            )
+            .move_to(Point::new(first_width + HANDLE_WIDTH, 0.0));
+
+        layout::Node::with_children(size, vec![first, second])
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut iced::Renderer,
+        theme: &iced::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        for ((pane, tree), layout) in self.panes.iter().zip(&tree.children).zip(layout.children()) {
+            pane.as_widget()
+                .draw(tree, renderer, theme, style, layout, cursor, viewport);
+        }
+
+        // A thin rule, which gets thicker while it can be grabbed
+        let handle = Self::handle_bounds(layout);
+        let palette = theme.extended_palette();
+        let (width, colour) =
+            if tree.state.downcast_ref::<State>().dragging || cursor.is_over(handle) {
+                (3.0, palette.primary.base.color)
+            } else {
+                (1.0, palette.background.strong.color)
+            };
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: Rectangle {
+                    x: handle.center_x() - width / 2.0,
+                    width,
+                    ..handle
+                },
+                border: Border::default(),
+                shadow: Shadow::default(),
+            },
+            colour,
+        );
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        self.panes.iter().map(Tree::new).collect()
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(self.panes.as_slice());
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &iced::Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        operation.container(None, layout.bounds(), &mut |operation| {
+            for ((pane, tree), layout) in self
+                .panes
+                .iter()
+                .zip(&mut tree.children)
+                .zip(layout.children())
+            {
+                pane.as_widget().operate(tree, layout, renderer, operation);
+            }
+        });
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &iced::Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        let state = tree.state.downcast_mut::<State>();
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if cursor.is_over(Self::handle_bounds(layout)) =>
+            {
+                if state.last_press.is_some_and(|t| t.elapsed() < DOUBLE_CLICK) {
+                    state.last_press = None;
+                    state.dragging = false;
+                    shell.publish(self.on_reset.clone());
+                } else {
+                    state.last_press = Some(Instant::now());
+                    state.dragging = true;
+                }
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) if state.dragging => {
+                let bounds = layout.bounds();
+                let available = bounds.width - HANDLE_WIDTH;
+                if available > 0.0 {
+                    let ratio = (position.x - bounds.x - HANDLE_WIDTH / 2.0) / available;
+                    shell.publish((self.on_resize)(ratio.clamp(0.0, 1.0)));
+                }
+                return event::Status::Captured;
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if state.dragging => {
+                state.dragging = false;
+                return event::Status::Captured;
+            }
+            _ => {}
+        }
+
+        self.panes
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(layout.children())
+            .map(|((pane, tree), layout)| {
+                pane.as_widget_mut().on_event(
+                    tree,
+                    event.clone(),
+                    layout,
+                    cursor,
+                    renderer,
+                    clipboard,
+                    shell,
+                    viewport,
+                )
+            })
+            .fold(event::Status::Ignored, event::Status::merge)
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &iced::Renderer,
+    ) -> mouse::Interaction {
+        if tree.state.downcast_ref::<State>().dragging
+            || cursor.is_over(Self::handle_bounds(layout))
+        {
+            return mouse::Interaction::ResizingHorizontally;
+        }
+
+        self.panes
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+            .map(|((pane, tree), layout)| {
+                pane.as_widget()
+                    .mouse_interaction(tree, layout, cursor, viewport, renderer)
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &iced::Renderer,
+    ) -> Option<overlay::Element<'b, Message, iced::Theme, iced::Renderer>> {
+        overlay::from_children(self.panes.as_mut_slice(), tree, layout, renderer)
+    }
+}
@@ -1,5 +1,6 @@
 use iced::{widget::button, Color};
 
+pub mod custom_theme;
 pub mod picklist;
 
 pub struct ButtonColor(pub iced::Color);
@@ -17,49 +18,104 @@ impl button::StyleSheet for ButtonColor {
     // other methods in Stylesheet have a default impl
 }
 pub mod colours {
+    use std::sync::RwLock;
+
     use iced::Color;
 
+    /// The colours currently in use, which can be changed by a custom theme
+    static PALETTE: RwLock<Palette> = RwLock::new(Palette::DEFAULT);
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Palette {
+        pub red: Color,
+        pub pink: Color,
+        pub green: Color,
+        pub yellow: Color,
+        pub orange: Color,
+        pub team_red: Color,
+        pub team_blu: Color,
+        pub team_red_darker: Color,
+        pub team_blu_darker: Color,
+    }
+
+    impl Palette {
+        pub const DEFAULT: Self = Self {
+            red: Color::from_rgb(1.0, 0.2, 0.2),
+            pink: Color::from_rgb(1.0, 0.6, 0.6),
+            green: Color::from_rgb(0.2, 8.0, 0.2),
+            yellow: Color::from_rgb(1.0, 1.0, 0.4),
+            orange: Color::from_rgb(1.0, 0.75, 0.25),
+            team_red: Color::from_rgb(184.0 / 255.0, 56.0 / 255.0, 59.0 / 255.0),
+            team_blu: Color::from_rgb(88.0 / 255.0, 133.0 / 255.0, 162.0 / 255.0),
+            team_red_darker: Color::from_rgb(164.0 / 255.0, 36.0 / 255.0, 39.0 / 255.0),
+            team_blu_darker: Color::from_rgb(68.0 / 255.0, 113.0 / 255.0, 162.0 / 255.0),
+        };
+    }
+
+    /// Change the colours returned by the functions in this module
+    pub fn set_palette(palette: Palette) {
+        if let Ok(mut p) = PALETTE.write() {
+            *p = palette;
+        }
+    }
+
+    fn palette() -> Palette {
+        PALETTE.read().map_or(Palette::DEFAULT, |p| *p)
+    }
+
+    /// A slightly darker version of a colour, e.g. for button backgrounds
     #[must_use]
-    pub const fn red() -> Color {
-        Color::from_rgb(1.0, 0.2, 0.2)
+    pub fn darken(colour: Color) -> Color {
+        const AMOUNT: f32 = 20.0 / 255.0;
+        Color::from_rgb(
+            (colour.r - AMOUNT).max(0.0),
+            (colour.g - AMOUNT).max(0.0),
+            (colour.b - AMOUNT).max(0.0),
+        )
     }
 
     #[must_use]
-    pub const fn pink() -> Color {
-        Color::from_rgb(1.0, 0.6, 0.6)
+    pub fn red() -> Color {
+        palette().red
     }
 
     #[must_use]
-    pub const fn green() -> Color {
-        Color::from_rgb(0.2, 8.0, 0.2)
+    pub fn pink() -> Color {
+        palette().pink
     }
 
     #[must_use]
-    pub const fn yellow() -> Color {
-        Color::from_rgb(1.0, 1.0, 0.4)
+    pub fn green() -> Color {
+        palette().green
     }
 
     #[must_use]
-    pub const fn orange() -> Color {
-        Color::from_rgb(1.0, 0.75, 0.25)
+    pub fn yellow() -> Color {
+        palette().yellow
+    }
+
+    #[must_use]
+    pub fn orange() -> Color {
+        palette().orange
     }
 
     #[must_use]
     pub fn team_red() -> Color {
-        Color::from_rgb(184.0 / 255.0, 56.0 / 255.0, 59.0 / 255.0)
+        palette().team_red
     }
 
     #[must_use]
     pub fn team_blu() -> Color {
-        Color::from_rgb(88.0 / 255.0, 133.0 / 255.0, 162.0 / 255.0)
+        palette().team_blu
     }
+
     #[must_use]
     pub fn team_red_darker() -> Color {
-        Color::from_rgb(164.0 / 255.0, 36.0 / 255.0, 39.0 / 255.0)
+        palette().team_red_darker
     }
 
     #[must_use]
     pub fn team_blu_darker() -> Color {
-        Color::from_rgb(68.0 / 255.0, 113.0 / 255.0, 162.0 / 255.0)
+        palette().team_blu_darker
     }
 }
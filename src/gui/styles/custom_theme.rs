@@ -0,0 +1,193 @@
+use iced::Color;
+use serde::{Deserialize, Serialize};
+
+use super::colours::{self, Palette};
+
+pub const THEME_COLOURS: &[ThemeColour] = &[
+    ThemeColour::Background,
+    ThemeColour::Text,
+    ThemeColour::Primary,
+    ThemeColour::Success,
+    ThemeColour::Danger,
+    ThemeColour::Red,
+    ThemeColour::Pink,
+    ThemeColour::Green,
+    ThemeColour::Yellow,
+    ThemeColour::Orange,
+    ThemeColour::TeamRed,
+    ThemeColour::TeamBlu,
+];
+
+/// The colours a custom theme can change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeColour {
+    Background,
+    Text,
+    Primary,
+    Success,
+    Danger,
+    Red,
+    Pink,
+    Green,
+    Yellow,
+    Orange,
+    TeamRed,
+    TeamBlu,
+}
+
+impl ThemeColour {
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Background => "Background",
+            Self::Text => "Text",
+            Self::Primary => "Accent",
+            Self::Success => "Success",
+            Self::Danger => "Danger",
+            Self::Red => "Bot / errors",
+            Self::Pink => "Suspicious",
+            Self::Green => "Trusted",
+            Self::Yellow => "Warnings",
+            Self::Orange => "Cheater",
+            Self::TeamRed => "Team RED",
+            Self::TeamBlu => "Team BLU",
+        }
+    }
+}
+
+/// A user-defined palette. Colours are kept as the hex strings the user typed
+/// so they can be edited freely, and any that aren't valid fall back to the
+/// default colour.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomTheme {
+    pub name: String,
+    pub background: String,
+    pub text: String,
+    pub primary: String,
+    pub success: String,
+    pub danger: String,
+    pub red: String,
+    pub pink: String,
+    pub green: String,
+    pub yellow: String,
+    pub orange: String,
+    pub team_red: String,
+    pub team_blu: String,
+}
+
+impl Default for CustomTheme {
+    fn default() -> Self {
+        let base = iced::Theme::CatppuccinMocha.palette();
+        let colours = Palette::DEFAULT;
+        Self {
+            name: String::from("Custom"),
+            background: to_hex(base.background),
+            text: to_hex(base.text),
+            primary: to_hex(base.primary),
+            success: to_hex(base.success),
+            danger: to_hex(base.danger),
+            red: to_hex(colours.red),
+            pink: to_hex(colours.pink),
+            green: to_hex(colours.green),
+            yellow: to_hex(colours.yellow),
+            orange: to_hex(colours.orange),
+            team_red: to_hex(colours.team_red),
+            team_blu: to_hex(colours.team_blu),
+        }
+    }
+}
+
+impl CustomTheme {
+    #[must_use]
+    pub fn hex(&self, colour: ThemeColour) -> &str {
+        match colour {
+            ThemeColour::Background => &self.background,
+            ThemeColour::Text => &self.text,
+            ThemeColour::Primary => &self.primary,
+            ThemeColour::Success => &self.success,
+            ThemeColour::Danger => &self.danger,
+            ThemeColour::Red => &self.red,
+            ThemeColour::Pink => &self.pink,
+            ThemeColour::Green => &self.green,
+            ThemeColour::Yellow => &self.yellow,
+            ThemeColour::Orange => &self.orange,
+            ThemeColour::TeamRed => &self.team_red,
+            ThemeColour::TeamBlu => &self.team_blu,
+        }
+    }
+
+    pub fn set_hex(&mut self, colour: ThemeColour, hex: String) {
+        let field = match colour {
+            ThemeColour::Background => &mut self.background,
+            ThemeColour::Text => &mut self.text,
+            ThemeColour::Primary => &mut self.primary,
+            ThemeColour::Success => &mut self.success,
+            ThemeColour::Danger => &mut self.danger,
+            ThemeColour::Red => &mut self.red,
+            ThemeColour::Pink => &mut self.pink,
+            ThemeColour::Green => &mut self.green,
+            ThemeColour::Yellow => &mut self.yellow,
+            ThemeColour::Orange => &mut self.orange,
+            ThemeColour::TeamRed => &mut self.team_red,
+            ThemeColour::TeamBlu => &mut self.team_blu,
+        };
+        *field = hex;
+    }
+
+    /// The colour, or the default for it if the hex is invalid
+    #[must_use]
+    pub fn colour(&self, colour: ThemeColour) -> Color {
+        parse_hex(self.hex(colour)).unwrap_or_else(|| Self::default().colour(colour))
+    }
+
+    #[must_use]
+    pub fn theme(&self) -> iced::Theme {
+        iced::Theme::custom(
+            self.name.clone(),
+            iced::theme::Palette {
+                background: self.colour(ThemeColour::Background),
+                text: self.colour(ThemeColour::Text),
+                primary: self.colour(ThemeColour::Primary),
+                success: self.colour(ThemeColour::Success),
+                danger: self.colour(ThemeColour::Danger),
+            },
+        )
+    }
+
+    /// The colours used by [`colours`] while this theme is active
+    #[must_use]
+    pub fn palette(&self) -> Palette {
+        let team_red = self.colour(ThemeColour::TeamRed);
+        let team_blu = self.colour(ThemeColour::TeamBlu);
+        Palette {
+            red: self.colour(ThemeColour::Red),
+            pink: self.colour(ThemeColour::Pink),
+            green: self.colour(ThemeColour::Green),
+            yellow: self.colour(ThemeColour::Yellow),
+            orange: self.colour(ThemeColour::Orange),
+            team_red,
+            team_blu,
+            team_red_darker: colours::darken(team_red),
+            team_blu_darker: colours::darken(team_blu),
+        }
+    }
+}
+
+/// Parses colours like `#ff8800` or `ff8800`
+#[must_use]
+pub fn parse_hex(hex: &str) -> Option<Color> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color::from_rgb8(channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[must_use]
+pub fn to_hex(colour: Color) -> String {
+    let [r, g, b, _] = colour.into_rgba8();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
@@ -0,0 +1,60 @@
+use iced::{
+    theme,
+    widget::{container, horizontal_space, row, text},
+    Length,
+};
+
+use super::{styles::colours, FONT_SIZE};
+use crate::{App, IcedElement};
+
+/// The vote going on in the server, with how many have voted each way and
+/// whether the user has voted yet. `None` when there isn't one.
+#[must_use]
+pub fn banner(state: &App) -> Option<IcedElement<'_>> {
+    let vote = state.mac.server.active_vote()?;
+
+    let issue = match vote.kick_target() {
+        Some(target) => format!(
+            "Votekick against {}",
+            state
+                .mac
+                .players
+                .get_name(target)
+                .unwrap_or("unknown player")
+        ),
+        None => format!("Vote: {}", vote.options.join(" / ")),
+    };
+
+    let mut contents = row![text(issue)]
+        .spacing(15)
+        .align_items(iced::Alignment::Center);
+    for option in &vote.options {
+        let mut tally = text(format!("{option} {}", vote.count(option))).size(FONT_SIZE);
+        match option.as_str() {
+            "Yes" => tally = tally.style(colours::green()),
+            "No" => tally = tally.style(colours::red()),
+            _ => {}
+        }
+        contents = contents.push(tally);
+    }
+    contents = contents.push(text(format!("{}s left", vote.seconds_left())).size(FONT_SIZE));
+    contents = contents.push(horizontal_space());
+
+    let user_vote = state
+        .mac
+        .settings
+        .steam_user
+        .and_then(|user| vote.voted(user));
+    contents = contents.push(match user_vote {
+        Some(option) => text(format!("You voted {option}")),
+        None => text("You haven't voted").style(colours::orange()),
+    });
+
+    Some(
+        container(contents)
+            .style(theme::Container::Box)
+            .width(Length::Fill)
+            .padding(10)
+            .into(),
+    )
+}
@@ -5,13 +5,14 @@
 #![allow(clippy::redundant_pub_crate)]
 
 use std::{
-    any::TypeId, cell::RefCell, collections::{HashMap, HashSet}, io::Cursor, path::PathBuf, time::Duration
+    any::TypeId, cell::RefCell, collections::{HashMap, HashSet}, ops::ControlFlow, path::PathBuf, process::ExitCode, sync::OnceLock, time::{Duration, Instant}
 };
-use bytes::Bytes;
-use demos::DemosMessage;
+use chrono::{DateTime, Local, Utc};
+use demos::{AnalysedDemoView, DemosMessage, SortDirection};
 use graph::KDAChart;
 use replay::{ReplayMessage, ReplayState};
-use gui::{chat, icons::FONT_FILE, killfeed, records, SidePanel, View, PFP_FULL_SIZE, PFP_SMALL_SIZE};
+use pfp_cache::{DecodedPfp, FetchError, PfpQueue};
+use gui::{chat, icons::FONT_FILE, killfeed, logs, player::PlayerSection, records::{self, FriendFilter, LastSeenFilter, AddRecordForm, RecordSort, SearchTarget, VanityLookup}, styles::{colours, custom_theme::{CustomTheme, ThemeColour}}, Notification, SidePanel, View, ViewUiState, NOTIFICATION_DURATION, UNDO_NOTIFICATION_DURATION, PFP_FULL_SIZE, PFP_SMALL_SIZE};
 use iced::{
     event::Event,
     futures::{FutureExt, SinkExt},
@@ -21,16 +22,18 @@ use iced::{
     },
     Application,
 };
-use image::{io::Reader, EncodableLayout, ImageBuffer};
 use reqwest::StatusCode;
 use serde_json::Map;
-use settings::{AppSettings, PanelSide, SETTINGS_IDENTIFIER};
+use startup::StartupError;
+use settings::{clamp_window_position, AppSettings, Badge, ExternalLink, PanelSide, ThemeChoice, SETTINGS_IDENTIFIER};
 use tokio::sync::broadcast::{Receiver, Sender};
+use tracing_setup::{LogBuffer, LogFile};
+use tray::{Tray, TrayAction};
+use undo::{ChangeKind, RecordChange, UndoStack};
 
 use tf2_monitor_core::{
-    console::{commands::{Command, CommandManager, DumbAutoKick}, ConsoleLog, ConsoleOutput, ConsoleParser, RawConsoleOutput}, demos::{analyser::AnalysedDemo, DemoBytes, DemoManager, DemoMessage, DemoWatcher}, event_loop::{self, define_events, EventLoop, MessageSource}, events::{Preferences, Refresh, UserUpdates}, masterbase, players::{new_players::{ExtractNewPlayers, NewPlayers}, records::{Records, Verdict}, Players}, server::Server, settings::{AppDetails, Settings}, steam::{self, api::{
-        FriendLookupResult, LookupFriends, LookupProfiles, ProfileLookupBatchTick,
-        ProfileLookupRequest, ProfileLookupResult,
+    builder::{BuildError, MonitorBuilder, MonitorHandler, MonitorMessage}, console::{commands::{Command, KickReason}, ConsoleLog, ConsoleOutput, RawConsoleOutput}, demos::{analyser::AnalysedDemo, DemoWatcher}, event_loop::{EventLoop, MessageSource}, events::{InternalPreferences, Preferences, Refresh}, masterbase::{self, provision, queue, UploadUpdate}, players::{bans::NewBansDetected, name_changes::NamesChanged, new_players::NewPlayers, records::{Records, Verdict}, name_rules::{NameRule, NameRuleAction, NameRules}, suspicion::{Scorer, Signal}, tf2bd::MergePolicy}, settings::{instance, locate_portable_config_directory, profiles::{self, DEFAULT_PROFILE_NAME}, watcher::{ConfigFile, ConfigWatcher}, AppDetails, ConfigFilesError, Settings, PORTABLE_CONFIG_DIR_NAME, PORTABLE_MARKER_FILE_NAME}, steam::{self, launch_options::LaunchOptions, TF2_GAME_ID, api::{
+        ProfileLookupBatchTick, ProfileLookupRequest, ProfileLookupResult,
     }}, steamid_ng::SteamID, MonitorState
 };
 
@@ -40,63 +43,42 @@ pub mod replay;
 pub mod demos;
 pub mod graph;
 mod tracing_setup;
+pub mod undo;
+pub mod tray;
+pub mod pfp_cache;
+pub mod search;
+pub mod startup;
 
 /// Changing this will change where config files are stored,
 /// so I'm just leaving it as-is for compatibility's sake
-pub const APP: AppDetails<'static> = AppDetails {
+const APP_DETAILS: AppDetails<'static> = AppDetails {
     qualifier: "com.megascatterbomb",
     organization: "MAC",
     application: "MACClient",
+    profile: None,
+    config_dir: None,
 };
+/// Set once at startup, `Some` in portable mode
+static PORTABLE_CONFIG_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Where config files are kept. This is the only way to get the
+/// [`AppDetails`] so every file ends up in the same place, whether or not the
+/// app is in portable mode.
+#[must_use]
+pub fn app() -> AppDetails<'static> {
+    let config_dir = PORTABLE_CONFIG_DIR.get_or_init(|| locate_portable_config_directory(false).unwrap_or_else(|e| {
+        tracing::error!("Failed to set up the portable config directory: {e}");
+        None
+    }));
+    AppDetails {
+        config_dir: config_dir.as_deref(),
+        ..APP_DETAILS
+    }
+}
 
 pub const ALIAS_KEY: &str = "alias";
 pub const NOTES_KEY: &str = "playerNote";
 
-define_events!(
-    MonitorState,
-    MonitorMessage {
-        Refresh,
-
-        Command,
-
-        RawConsoleOutput,
-        ConsoleOutput,
-
-        NewPlayers,
-
-        ProfileLookupRequest,
-        ProfileLookupBatchTick,
-        ProfileLookupResult,
-        FriendLookupResult,
-
-        Preferences,
-        UserUpdates,
-
-        DemoBytes,
-        DemoMessage,
-    },
-    MonitorHandler {
-        CommandManager,
-
-        ConsoleParser,
-
-        ExtractNewPlayers,
-
-        LookupProfiles,
-        LookupFriends,
-
-        DemoManager,
-        DumbAutoKick,
-    },
-);
-
-impl Clone for MonitorMessage {
-    fn clone(&self) -> Self {
-        tracing::error!("Shouldn't be cloning MACMessages!");
-        Self::None
-    }
-}
-
 pub struct Client {
     pub mac: MonitorState,
     pub mac_event_handler: EventLoop<MonitorState, MonitorMessage, MonitorHandler>,
@@ -111,17 +93,70 @@ pub struct App {
     settings: AppSettings,
 
     // UI State
+    /// The player selected outside of analysed demos, which have their own
+    /// (see [`App::active_selection`])
     selected_player: Option<SteamID>,
+    /// Only show players with this verdict in the server view
+    server_verdict_filter: Option<Verdict>,
+    history_friend_filter: FriendFilter,
+    /// Sections of the selected player's details that have been hidden
+    collapsed_player_sections: HashSet<PlayerSection>,
+    /// The Steam API rejected the current key, shown as a warning in the settings
+    steam_key_rejected: bool,
+    /// Waiting for the masterbase to send back a key after opening the provision page
+    provisioning_key: bool,
+    /// Why provisioning a masterbase key failed, shown next to the key field
+    provision_error: Option<String>,
+    /// Steam group ID being typed into the settings
+    new_watched_group: String,
+    /// Bot list URL being typed into the settings
+    new_bot_list: String,
+    /// Suspicious name pattern being typed into the settings
+    new_name_pattern: String,
+    /// Name rule being typed into the settings
+    new_name_rule: String,
+    new_name_rule_action: NameRuleAction,
+    /// External link being typed into the settings
+    new_link_name: String,
+    new_link_url: String,
+    /// SteamID being typed in to link to the selected player
+    link_input: String,
+    notifications: Vec<Notification>,
+    /// Recent changes to records that can be undone
+    undo_stack: UndoStack,
+    /// When everything was last autosaved
+    last_autosave: Option<DateTime<Local>>,
+    /// `None` if the tray icon couldn't be created, in which case the window is never hidden
+    tray: Option<Tray>,
+    /// The window is hidden in the tray, the monitor keeps running in the background
+    window_hidden: bool,
+    /// The settings profile in use, `None` for the default one
+    profile: Option<String>,
+    /// Named profiles that can be switched to
+    profiles: Vec<String>,
+    /// Profile name being typed into the settings
+    new_profile_name: String,
+    /// Notices when the settings or records are changed by something else.
+    /// `None` if it couldn't be started.
+    config_watcher: Option<ConfigWatcher>,
+    /// Required launch options that were missing when trying to launch TF2,
+    /// waiting for the user to decide whether to add them
+    missing_launch_options: Option<Vec<String>>,
 
     snap_chat_to_bottom: bool,
     snap_kills_to_bottom: bool,
+    /// Scroll positions and tabs of each view, restored when switching back to it.
+    /// Each analysed demo has its own entry.
+    view_ui: HashMap<View, ViewUiState>,
 
     // records
     records: records::State,
 
+    logs: logs::State,
+
     // (High res, Low res)
     pfp_cache: HashMap<String, (iced::widget::image::Handle, iced::widget::image::Handle)>,
-    pfp_in_progess: HashSet<String>,
+    pfp_queue: PfpQueue,
 
     // Replay
     replay: ReplayState,
@@ -129,9 +164,9 @@ pub struct App {
     // Demos
     demos: demos::State,
 
-    // Change TF2 directory
-    change_tf2_dir: Sender<PathBuf>,
-    _tf2_dir_changed: RefCell<Option<Receiver<PathBuf>>>,
+    // Restart the console and demo watchers, e.g. when the TF2 directory or profile changes
+    reload_sources: Sender<PathBuf>,
+    _sources_reloaded: RefCell<Option<Receiver<PathBuf>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -139,41 +174,173 @@ pub enum Message {
     None,
 
     EventOccurred(Event),
-    PfpLookupResponse(String, Result<Bytes, ()>),
+    WindowMoved { x: i32, y: i32, maximized: bool },
+    WindowResized { width: u32, height: u32, maximized: bool },
+    PfpLookupResponse(String, Result<DecodedPfp, FetchError>),
+    /// Try fetching pfps that failed again once their backoff is over
+    RetryPfpLookups,
     ProfileLookupRequest(SteamID),
-
-    SetTheme(iced::Theme),
+    /// Fetch steam info for everyone in the server, even if it isn't stale yet
+    RefreshAllProfiles,
+
+    SetTheme(ThemeChoice),
+    NewCustomTheme,
+    RemoveCustomTheme(usize),
+    SetCustomThemeName(usize, String),
+    SetCustomThemeColour(usize, ThemeColour, String),
     SetView(View),
     SelectPlayer(SteamID),
     UnselectPlayer,
+    SetServerVerdictFilter(Option<Verdict>),
+    TogglePlayerSection(PlayerSection),
     SetReplay(PathBuf),
     /// Toggle whether a particular sidepanel is visible 
-    ToggleSidePanel(&'static [SidePanel], SidePanel),
+    ToggleSidePanel(SidePanel),
     SetPanelSide(PanelSide),
+    SetUiScale(f64),
+    SetSidePanelRatio(f32),
+    SetSplitSidePanel(bool),
+    SetSplitRatio(f32),
 
     CopyToClipboard(String),
     ChangeVerdict(SteamID, Verdict),
     ChangeNotes(SteamID, String),
+    ChangeAlias(SteamID, String),
+    /// Select the player and put the cursor in their notes
+    EditNotes(SteamID),
+    CallVotekick(SteamID),
+    /// SteamID being typed in to link to the selected player
+    SetLinkInput(String),
+    LinkAccounts(SteamID, SteamID),
+    UnlinkAccounts(SteamID, SteamID),
     Open(String),
     MAC(MonitorMessage),
     ToggleMACEnabled(bool),
+    /// Close any open masterbase session and check the key again
+    RetryMasterbase,
+    /// Retry an interrupted demo upload now instead of waiting
+    RetryQueuedUpload(PathBuf),
+    CancelQueuedUpload(PathBuf),
+    QueuedUploadFinished(PathBuf, Result<(), String>),
+    /// Open the provision page and wait for the masterbase to send the key back
+    ProvisionMasterbaseKey,
+    MasterbaseKeyProvisioned(Result<String, String>),
     BrowseTF2Dir,
 
     AddDemoDir,
     RemoveDemoDir(usize),
 
+    SetNewLinkName(String),
+    SetNewLinkUrl(String),
+    AddExternalLink,
+    RemoveExternalLink(usize),
+
     /// Which page of records to display
     SetRecordPage(usize),
+    SetRecordsPerPage(usize),
     ToggleVerdictFilter(Verdict),
+    ToggleRecentlyBannedFilter,
     /// Records search bar
     SetRecordSearch(String),
+    /// Search for what was typed, if nothing has been typed since
+    ApplyRecordSearch(usize),
+    /// Only match names exactly in the records search
+    ToggleExactNameSearch,
+    /// Copy any new lines out of the log buffer
+    RefreshLogs,
+    SetLogLevel(tracing::Level),
+    SetLogSearch(String),
+    SetLogAutoScroll(bool),
+    /// Copy the log lines that are shown to the clipboard
+    CopyLogs,
+    VanityResolved(String, VanityLookup),
+    OpenAddRecord,
+    SetAddRecordAccount(String),
+    SetAddRecordVerdict(Verdict),
+    SetAddRecordAlias(String),
+    SetAddRecordNotes(String),
+    ConfirmAddRecord,
+    CancelAddRecord,
+    SetRecordSort(RecordSort),
+    ToggleRecordSortDirection,
+    SetLastSeenFilter(LastSeenFilter),
+    SetRecordFriendFilter(FriendFilter),
+    SetRecordCountry(String),
+    SetHistoryFriendFilter(FriendFilter),
+    SetLastSeenDays(String),
+    SetSeenWithMarked(String),
+    /// Ask to delete all records that match the current filters
+    DeleteFilteredRecords,
+    ConfirmDeleteRecords,
+    CancelDeleteRecords,
+    /// Pick a TF2 Bot Detector playerlist and preview importing it
+    ImportTF2BD,
+    SetImportPolicy(MergePolicy),
+    ConfirmImport,
+    CancelImport,
+    ExportTF2BD,
+    /// Write the displayed records to a CSV file
+    ExportCSV,
 
     Demos(DemosMessage),
 
     ScrolledChat(RelativeOffset),
     ScrolledKills(RelativeOffset),
+    /// The main scrollable of the current view was scrolled
+    ScrolledView(RelativeOffset),
 
     SetKickBots(bool),
+    /// `None` to pick the reason from the player's verdict
+    SetKickReason(Option<KickReason>),
+
+    DismissNotification(usize),
+    /// Revert the last change made to the records
+    Undo,
+    /// Save anything that has changed since it was last saved
+    Autosave,
+    SetAutosaveMinutes(String),
+    SetReplayNameTemplate(String),
+    Tray(TrayAction),
+    SetCloseToTray(bool),
+    SetAutoAnalyseDemos(bool),
+    SetDemoSummaries(bool),
+    SetNameChangesInChat(bool),
+    SetChatTimestamps(bool),
+    SetCountryFlags(bool),
+    SetBadgeEnabled(Badge, bool),
+    SetYoungAccountDays(String),
+    SetLowPlaytimeHours(String),
+    SetCheaterKickNoVotes(String),
+    SetSuspicionWeight(Signal, String),
+    /// Check the launch options, then start TF2 through Steam
+    LaunchTF2,
+    /// Add the missing launch options to Steam's config, then launch
+    AddLaunchOptions,
+    LaunchTF2Anyway,
+    CancelLaunchTF2,
+    /// Save the current profile and load another one
+    SetProfile(String),
+    SetNewProfileName(String),
+    CreateProfile,
+
+    SetNewWatchedGroup(String),
+    AddWatchedGroup,
+    RemoveWatchedGroup(usize),
+
+    SetNewBotList(String),
+    AddBotList,
+    RemoveBotList(usize),
+
+    SetNewNamePattern(String),
+    AddNamePattern,
+    RemoveNamePattern(usize),
+
+    SetNewNameRule(String),
+    SetNewNameRuleAction(NameRuleAction),
+    AddNameRule,
+    RemoveNameRule(usize),
+    /// Set players the rule marked back to Player
+    UndoNameRuleMarks(usize),
 
     Replay(ReplayMessage),
 }
@@ -186,9 +353,12 @@ impl Application for App {
         MonitorState,
         EventLoop<MonitorState, MonitorMessage, MonitorHandler>,
         AppSettings,
+        Option<String>,
+        LogBuffer,
+        LogFile,
     );
 
-    fn new((mut mac, event_loop, settings): Self::Flags) -> (Self, iced::Command<Self::Message>) {
+    fn new((mut mac, event_loop, settings, profile, log_buffer, log_file): Self::Flags) -> (Self, iced::Command<Self::Message>) {
 
         mac.settings.upload_demos = settings.enable_mac_integration;
         let mut commands = Vec::new();
@@ -196,51 +366,104 @@ impl Application for App {
             commands.push(verify_masterbase_connection(&mac.settings));
         };
 
-        let (tf2_dir_tx, tf2_dir_rx) = tokio::sync::broadcast::channel(1);
+        let profiles = profiles::list_profiles(app()).unwrap_or_else(|e| {
+            tracing::error!("Failed to list settings profiles: {e}");
+            Vec::new()
+        });
+
+        let (reload_tx, reload_rx) = tokio::sync::broadcast::channel(1);
+        let records_per_page = settings.records_per_page;
+        let demos_per_page = settings.demos_per_page;
         let mut app = Self {
             mac,
             event_loop,
             settings,
 
             selected_player: None,
+            server_verdict_filter: None,
+            history_friend_filter: FriendFilter::Any,
+            collapsed_player_sections: HashSet::new(),
+            steam_key_rejected: false,
+            provisioning_key: false,
+            provision_error: None,
+            new_watched_group: String::new(),
+            new_bot_list: String::new(),
+            new_name_pattern: String::new(),
+            new_name_rule: String::new(),
+            new_name_rule_action: NameRuleAction::MarkBot,
+            new_link_name: String::new(),
+            new_link_url: String::new(),
+            link_input: String::new(),
+            notifications: Vec::new(),
+            undo_stack: UndoStack::new(),
+            last_autosave: None,
+            tray: Tray::new().map_err(|e| {
+                tracing::error!("Couldn't create tray icon: {e}");
+            }).ok(),
+            window_hidden: false,
+            profile,
+            profiles,
+            new_profile_name: String::new(),
+            config_watcher: None,
+            missing_launch_options: None,
 
             snap_chat_to_bottom: true,
             snap_kills_to_bottom: true,
+            view_ui: HashMap::new(),
+
+            records: records::State::new(records_per_page),
 
-            records: records::State::new(),
+            logs: logs::State::new(log_buffer, log_file),
 
             pfp_cache: HashMap::new(),
-            pfp_in_progess: HashSet::new(),
+            pfp_queue: PfpQueue::new(),
 
             replay: ReplayState::new(),
 
-            demos: demos::State::new(),
+            demos: demos::State::new(demos_per_page),
 
-            change_tf2_dir: tf2_dir_tx,
-            _tf2_dir_changed: RefCell::new(Some(tf2_dir_rx)),
+            reload_sources: reload_tx,
+            _sources_reloaded: RefCell::new(Some(reload_rx)),
         };
 
         app.update_displayed_records();
+        app.apply_palette();
+        app.watch_config_files();
+        app.logs.configure_file(&app.settings);
 
         commands.push(demos::State::refresh_demos(&app));
+        if app.settings.window_maximized {
+            commands.push(iced::window::maximize(iced::window::Id::MAIN, true));
+        }
 
         (app, iced::Command::batch(commands))
     }
 
     fn title(&self) -> String {
-        String::from("Bash's TF2 Monitor")
+        let title = self.profile.as_ref().map_or_else(
+            || String::from("Bash's TF2 Monitor"),
+            |profile| format!("Bash's TF2 Monitor ({profile})"),
+        );
+        if instance::is_read_only() {
+            return format!("{title} - Read-only");
+        }
+        title
     }
 
     fn theme(&self) -> iced::Theme {
-        self.settings.theme.clone()
+        self.settings.current_theme()
+    }
+
+    fn scale_factor(&self) -> f64 {
+        self.settings.ui_scale.clamp(settings::MIN_UI_SCALE, settings::MAX_UI_SCALE)
     }
 
     fn subscription(&self) -> iced::Subscription<Self::Message> {
-        let mut tf2_dir_changed_log = self.change_tf2_dir.subscribe();
-        let mut tf2_dir_changed_con = self.change_tf2_dir.subscribe();
+        let mut tf2_dir_changed_log = self.reload_sources.subscribe();
+        let mut tf2_dir_changed_con = self.reload_sources.subscribe();
 
         #[allow(clippy::used_underscore_binding)]
-        let _ = self._tf2_dir_changed.replace(None);
+        let _ = self._sources_reloaded.replace(None);
         
         let log_file_path = self.mac.settings.tf2_directory.clone().map(|path| path.join("tf/console.log"));
         let demo_path = self.mac.settings.tf2_directory.clone().map(|path| path.join("tf"));
@@ -248,12 +471,22 @@ impl Application for App {
         #[allow(clippy::used_underscore_binding)]
         let analysed_demo_rx = self.demos._demo_analysis_output.replace(None);
 
+        // Only needed while they're being looked at
+        let logs = if matches!(self.settings.view, View::Logs) {
+            iced::time::every(Duration::from_millis(500)).map(|_| Message::RefreshLogs)
+        } else {
+            iced::Subscription::none()
+        };
+
         iced::Subscription::batch([
             iced::event::listen().map(Message::EventOccurred),
+            logs,
             iced::time::every(Duration::from_secs(2))
                 .map(|_| Message::MAC(MonitorMessage::Refresh(Refresh))),
             iced::time::every(Duration::from_millis(500))
                 .map(|_| Message::MAC(MonitorMessage::ProfileLookupBatchTick(ProfileLookupBatchTick))),
+            iced::time::every(Duration::from_secs(self.settings.autosave_minutes.max(1) * 60)).map(|_| Message::Autosave),
+            iced::time::every(Duration::from_secs(5)).map(|_| Message::RetryPfpLookups),
             iced::subscription::channel(TypeId::of::<ConsoleLog>(), 100, |mut output| async move {
                 let mut console_log = if let Some(path) = log_file_path {
                     ConsoleLog::new(path)
@@ -304,6 +537,15 @@ impl Application for App {
                     
                 },
             ),
+            iced::subscription::channel(TypeId::of::<Tray>(), 10, |mut output| async move {
+                loop {
+                    if let Some(action) = TrayAction::poll() {
+                        output.send(Message::Tray(action)).await.ok();
+                    }
+
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }),
             iced::subscription::channel(
                 TypeId::of::<AnalysedDemo>(), 
                 50, 
@@ -323,53 +565,141 @@ impl Application for App {
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
         match message {
             Message::None => {}
-            Message::EventOccurred(Event::Window(_, iced::window::Event::Moved { x, y })) => {
-                self.settings.window_pos = Some((x, y));
+            // Maximizing moves and resizes the window, so only remember the position and size
+            // it had while it wasn't maximized
+            Message::EventOccurred(Event::Window(id, iced::window::Event::Moved { x, y })) => {
+                return iced::window::fetch_maximized(id, move |maximized| Message::WindowMoved { x, y, maximized });
             }
-            Message::EventOccurred(Event::Window(_, iced::window::Event::Resized {
+            Message::EventOccurred(Event::Window(id, iced::window::Event::Resized {
                 width,
                 height,
             })) => {
-                self.settings.window_size = Some((width, height));
+                return iced::window::fetch_maximized(id, move |maximized| Message::WindowResized { width, height, maximized });
+            }
+            Message::WindowMoved { x, y, maximized } => {
+                self.settings.window_maximized = maximized;
+                if !maximized {
+                    self.settings.window_pos = Some((x, y));
+                }
+            }
+            Message::WindowResized { width, height, maximized } => {
+                self.settings.window_maximized = maximized;
+                if !maximized {
+                    self.settings.window_size = Some((width, height));
+                }
             }
+            Message::EventOccurred(Event::Window(_, iced::window::Event::CloseRequested)) => {
+                if self.settings.close_to_tray && self.tray.is_some() {
+                    return self.set_window_hidden(true);
+                }
+                return self.quit();
+            }
+            Message::EventOccurred(Event::Keyboard(iced::keyboard::Event::KeyPressed {
+                key: iced::keyboard::Key::Character(c),
+                modifiers,
+                ..
+            })) if modifiers.command() && c.as_str() == "z" => self.undo(),
             #[allow(clippy::match_same_arms)]
             Message::EventOccurred(_) => {}
             Message::SetView(v) => {
                 self.settings.view = v;
                 if matches!(self.settings.view, View::Records) {
                     self.update_displayed_records();
+                    return iced::Command::batch([self.restore_scroll(), self.start_pfp_lookups()]);
                 } 
                 if matches!(self.settings.view, View::Demos) {
                     self.update_demo_list();
                 } 
+                if matches!(self.settings.view, View::Replay) {
+                    self.replay.refresh_existing(&self.mac);
+                }
                 if let View::AnalysedDemo(id) = self.settings.view {
-                    self.demos.chart = KDAChart::new(self, id, self.selected_player);
+                    self.demos.chart = KDAChart::new(self, id, self.demos.viewing_player);
+                }
+                if matches!(self.settings.view, View::Logs) {
+                    self.logs.refresh();
+                    return self.scroll_logs_to_bottom();
                 }
+                return self.restore_scroll();
             }
             Message::ChangeVerdict(steamid, verdict) => self.update_verdict(steamid, verdict),
             Message::ChangeNotes(steamid, notes) => self.update_notes(steamid, notes),
+            Message::ChangeAlias(steamid, alias) => self.update_alias(steamid, alias),
+            Message::SetLinkInput(input) => self.link_input = input,
+            Message::LinkAccounts(a, b) => {
+                self.link_input.clear();
+                self.mac.players.records.link_accounts(a, b);
+                self.save_records();
+            }
+            Message::UnlinkAccounts(a, b) => {
+                self.mac.players.records.unlink_accounts(a, b);
+                self.mac.players.records.prune();
+                self.save_records();
+            }
             Message::SelectPlayer(steamid) => {
-                self.selected_player = Some(steamid);
-
                 if let View::AnalysedDemo(demo) = self.settings.view {
+                    self.demos.viewing_player = Some(steamid);
                     self.demos.chart = KDAChart::new(self, demo, Some(steamid)); 
+                } else {
+                    self.selected_player = Some(steamid);
                 }
 
+                // The chat shrinks to fit the player's details above it
+                let snap = if self.settings.split_side_panel { self.snap_chat_to_bottom() } else { iced::Command::none() };
+
                 // Fetch their pfp if we don't have it currently but have the steam info
                 if self.mac.players.steam_info.contains_key(&steamid) {
-                    return self.request_pfp_lookup_for_existing_player(steamid);
+                    return iced::Command::batch([snap, self.request_pfp_lookup_for_existing_player(steamid)]);
                 }
 
                 // Request steam lookup of player if we don't have it currently,
-                return self.request_profile_lookup(vec![steamid]);
+                return iced::Command::batch([snap, self.request_profile_lookup(vec![steamid])]);
             }
             Message::UnselectPlayer => {
                 return self.unselect_player();
             }
+            Message::EditNotes(steamid) => {
+                let select = self.update(Message::SelectPlayer(steamid));
+                let focus = widget::text_input::focus(widget::text_input::Id::new(gui::player::NOTES_INPUT_ID));
+                return iced::Command::batch([select, focus]);
+            }
+            Message::CallVotekick(steamid) => {
+                let Some(userid) = self.mac.players.game_info.get(&steamid).map(|gi| gi.userid.clone()).filter(|u| !u.is_empty()) else {
+                    tracing::warn!("Can't votekick {}, they aren't in the server", u64::from(steamid));
+                    return iced::Command::none();
+                };
+
+                let command = Command::Kick { player: userid, reason: self.mac.settings.kick_reason(self.mac.players.verdict(steamid)) };
+                let name = self.mac.players.get_name(steamid).unwrap_or("this player");
+                let confirmed = rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Warning)
+                    .set_title("Call votekick")
+                    .set_description(format!("Call a vote to kick {name}? This will send the command:\n\n{command}"))
+                    .set_buttons(rfd::MessageButtons::OkCancel)
+                    .show();
+                if !matches!(confirmed, rfd::MessageDialogResult::Ok) {
+                    return iced::Command::none();
+                }
+
+                return self.handle_mac_message(MonitorMessage::Command(command));
+            }
+            Message::SetServerVerdictFilter(verdict) => self.server_verdict_filter = verdict,
+            Message::TogglePlayerSection(section) => {
+                if !self.collapsed_player_sections.remove(&section) {
+                    self.collapsed_player_sections.insert(section);
+                }
+            }
             Message::PfpLookupResponse(pfp_hash, response) => {
-                if let Ok(bytes) = response {
-                    self.insert_new_pfp(pfp_hash, &bytes);
+                self.pfp_queue.finished(&pfp_hash, response.as_ref().err().copied());
+                let mut commands = vec![self.start_pfp_lookups()];
+                if let Ok(pfp) = response {
+                    commands.push(self.insert_new_pfp(pfp_hash, pfp));
                 }
+                return iced::Command::batch(commands);
+            }
+            Message::RetryPfpLookups => {
+                self.pfp_queue.retry_due();
+                return self.start_pfp_lookups();
             }
             Message::CopyToClipboard(contents) => return iced::clipboard::write(contents),
             Message::Open(to_open) => {
@@ -380,7 +710,20 @@ impl Application for App {
             Message::MAC(m) => {
                 return self.handle_mac_message(m);
             }
-            Message::SetRecordPage(p) => self.records.current_page = p,
+            Message::SetRecordPage(p) => {
+                self.records.current_page = p;
+                self.refresh_record_rows();
+                return self.start_pfp_lookups();
+            }
+            Message::SetRecordsPerPage(n) => {
+                // Stay around the same records
+                let first = self.records.current_page * self.records.num_per_page;
+                self.settings.records_per_page = n;
+                self.records.num_per_page = n.max(1);
+                self.records.current_page = first / self.records.num_per_page;
+                self.refresh_record_rows();
+                return self.start_pfp_lookups();
+            }
             Message::ToggleVerdictFilter(v) => {
                 if self.records.verdict_whitelist.contains(&v) {
                     self.records.verdict_whitelist.retain(|&vv| vv != v);
@@ -393,22 +736,447 @@ impl Application for App {
                 let max_page = self.records.to_display.len() / self.records.num_per_page;
                 self.records.current_page = self.records.current_page.min(max_page);
             }
+            Message::ToggleRecentlyBannedFilter => {
+                self.records.recently_banned_only = !self.records.recently_banned_only;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
             Message::SetRecordSearch(search) => {
                 self.records.search = search;
+                self.records.search_generation += 1;
+                let generation = self.records.search_generation;
+                return iced::Command::perform(tokio::time::sleep(records::SEARCH_DEBOUNCE), move |()| Message::ApplyRecordSearch(generation));
+            }
+            Message::RefreshLogs => {
+                if self.logs.refresh() {
+                    return self.scroll_logs_to_bottom();
+                }
+            }
+            Message::SetLogLevel(level) => self.logs.level = level,
+            Message::SetLogSearch(search) => self.logs.search = search,
+            Message::SetLogAutoScroll(auto_scroll) => {
+                self.logs.auto_scroll = auto_scroll;
+                return self.scroll_logs_to_bottom();
+            }
+            Message::CopyLogs => return iced::clipboard::write(self.logs.visible_text()),
+            Message::ApplyRecordSearch(generation) => {
+                // Still typing
+                if generation != self.records.search_generation {
+                    return iced::Command::none();
+                }
+
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+
+                // Look up custom profile URLs that haven't been seen yet
+                if let SearchTarget::Vanity(vanity) = SearchTarget::parse(&self.records.search) {
+                    return self.resolve_vanity(vanity);
+                }
+            }
+            Message::OpenAddRecord => self.records.add_record = Some(AddRecordForm::new()),
+            Message::SetAddRecordAccount(account) => {
+                if let Some(form) = &mut self.records.add_record {
+                    form.account = account;
+                    form.existing = None;
+                    form.error = None;
+                }
+            }
+            Message::SetAddRecordVerdict(verdict) => {
+                if let Some(form) = &mut self.records.add_record {
+                    form.verdict = verdict;
+                }
+            }
+            Message::SetAddRecordAlias(alias) => {
+                if let Some(form) = &mut self.records.add_record {
+                    form.alias = alias;
+                }
+            }
+            Message::SetAddRecordNotes(notes) => {
+                if let Some(form) = &mut self.records.add_record {
+                    form.notes = notes;
+                }
+            }
+            Message::ConfirmAddRecord => return self.confirm_add_record(),
+            Message::CancelAddRecord => self.records.add_record = None,
+            Message::ToggleExactNameSearch => {
+                self.records.exact_name = !self.records.exact_name;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::VanityResolved(vanity, lookup) => {
+                self.records.resolved_vanities.insert(vanity.clone(), lookup);
+
+                // The add record form was waiting for it
+                if let Some(form) = &mut self.records.add_record {
+                    if form.resolving && SearchTarget::parse(&form.account) == SearchTarget::Vanity(vanity.clone()) {
+                        form.resolving = false;
+                        return self.confirm_add_record();
+                    }
+                }
+
+                if SearchTarget::parse(&self.records.search) == SearchTarget::Vanity(vanity) {
+                    self.update_displayed_records();
+                    let max_page = self.records.to_display.len() / self.records.num_per_page;
+                    self.records.current_page = self.records.current_page.min(max_page);
+                }
+            }
+            Message::SetRecordSort(sort_by) => {
+                self.settings.record_sort_by = sort_by;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::ToggleRecordSortDirection => {
+                self.settings.record_sort_direction = match self.settings.record_sort_direction {
+                    SortDirection::Ascending => SortDirection::Descending,
+                    SortDirection::Descending => SortDirection::Ascending,
+                };
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::SetLastSeenFilter(filter) => {
+                self.records.last_seen_filter = filter;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::SetRecordFriendFilter(filter) => {
+                self.records.friend_filter = filter;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::SetRecordCountry(country) => {
+                self.records.country = country;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::SetHistoryFriendFilter(filter) => self.history_friend_filter = filter,
+            Message::SetLastSeenDays(days) => {
+                self.records.last_seen_days = days;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::SetSeenWithMarked(times) => {
+                self.records.seen_with_marked = times;
+                self.update_displayed_records();
+                let max_page = self.records.to_display.len() / self.records.num_per_page;
+                self.records.current_page = self.records.current_page.min(max_page);
+            }
+            Message::DeleteFilteredRecords => self.records.confirm_delete = true,
+            Message::CancelDeleteRecords => self.records.confirm_delete = false,
+            Message::ConfirmDeleteRecords => {
+                self.records.confirm_delete = false;
+
+                let pinned = self.records.pinned;
+                let to_delete: Vec<SteamID> = self.records.to_display.iter().copied().filter(|&s| Some(s) != pinned).collect();
+
+                // Records linked to the deleted ones are changed too
+                let mut affected = to_delete.clone();
+                for steamid in &to_delete {
+                    if let Some(record) = self.mac.players.records.get(steamid) {
+                        affected.extend(record.linked_accounts().into_iter().filter(|s| !affected.contains(s)));
+                    }
+                }
+                self.remember_change(ChangeKind::Delete, format!("Deleted {} records", to_delete.len()), &affected);
+
+                for &steamid in &to_delete {
+                    self.mac.players.records.remove_record(steamid);
+                }
+                self.save_records();
+
+                tracing::info!("Deleted {} records", to_delete.len());
+
                 self.update_displayed_records();
                 let max_page = self.records.to_display.len() / self.records.num_per_page;
                 self.records.current_page = self.records.current_page.min(max_page);
             }
+            Message::ImportTF2BD => {
+                let Some(path) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+                    return iced::Command::none();
+                };
+
+                match Records::read_tf2bd(&path) {
+                    Ok(list) => {
+                        self.records.import_preview = Some(self.mac.players.records.preview_tf2bd(&list, self.records.import_policy));
+                        self.records.pending_import = Some((path, list));
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to read playerlist {path:?}: {e}");
+                        self.notify(format!("Failed to read playerlist {}: {e}", path.display()));
+                    }
+                }
+            }
+            Message::SetImportPolicy(policy) => {
+                self.records.import_policy = policy;
+                if let Some((_, list)) = &self.records.pending_import {
+                    self.records.import_preview = Some(self.mac.players.records.preview_tf2bd(list, policy));
+                }
+            }
+            Message::ConfirmImport => {
+                self.records.import_preview = None;
+                let Some((path, list)) = self.records.pending_import.take() else {
+                    return iced::Command::none();
+                };
+
+                let summary = self.mac.players.records.merge_tf2bd(&list, self.records.import_policy);
+                self.save_records();
+                self.update_displayed_records();
+
+                tracing::info!("Imported playerlist {path:?}: {summary:?}");
+                self.notify(format!(
+                    "Imported {}: {} added, {} updated, {} invalid skipped",
+                    path.display(), summary.added, summary.updated, summary.invalid
+                ));
+            }
+            Message::CancelImport => {
+                self.records.pending_import = None;
+                self.records.import_preview = None;
+            }
+            Message::ExportTF2BD => {
+                let Some(path) = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .set_file_name("playerlist.tf2monitor.json")
+                    .save_file() else {
+                    return iced::Command::none();
+                };
+
+                match self.mac.players.records.export_tf2bd(&path) {
+                    Ok(n) => self.notify(format!("Exported {n} players to {}", path.display())),
+                    Err(e) => {
+                        tracing::error!("Failed to export playerlist to {path:?}: {e}");
+                        self.notify(format!("Failed to export playerlist: {e}"));
+                    }
+                }
+            }
+            Message::ExportCSV => {
+                let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV", &["csv"])
+                    .set_file_name("records.csv")
+                    .save_file() else {
+                    return iced::Command::none();
+                };
+
+                match records::export_csv(self, &path) {
+                    Ok(n) => self.notify(format!("Exported {n} records to {}", path.display())),
+                    Err(e) => {
+                        tracing::error!("Failed to export records to {path:?}: {e}");
+                        self.notify(format!("Failed to export records: {e}"));
+                    }
+                }
+            }
             Message::SetKickBots(kick) => self.mac.settings.autokick_bots = kick,
+            Message::SetKickReason(reason) => self.mac.settings.kick_reason_override = reason,
+            Message::Undo => self.undo(),
+            Message::Autosave => self.autosave(),
+            Message::Tray(TrayAction::ToggleWindow) => return self.set_window_hidden(!self.window_hidden),
+            Message::Tray(TrayAction::OpenRecords) => {
+                self.settings.view = View::Records;
+                self.update_displayed_records();
+                return self.set_window_hidden(false);
+            }
+            Message::Tray(TrayAction::Quit) => return self.quit(),
+            Message::SetCloseToTray(close_to_tray) => self.settings.close_to_tray = close_to_tray,
+            Message::SetAutoAnalyseDemos(auto_analyse) => self.settings.auto_analyse_demos = auto_analyse,
+            Message::SetDemoSummaries(show) => self.settings.demo_summaries = show,
+            Message::SetNameChangesInChat(show) => self.settings.name_changes_in_chat = show,
+            Message::SetChatTimestamps(show) => self.settings.chat_timestamps = show,
+            Message::SetCountryFlags(show) => self.settings.country_flags = show,
+            Message::SetBadgeEnabled(badge, enabled) => self.settings.badges.set_enabled(badge, enabled),
+            Message::SetYoungAccountDays(days) => {
+                if let Ok(days) = days.parse::<u32>() {
+                    self.settings.badges.young_account_days = days;
+                }
+            }
+            Message::SetLowPlaytimeHours(hours) => {
+                if let Ok(hours) = hours.parse::<u32>() {
+                    self.settings.badges.low_playtime_hours = hours;
+                }
+            }
+            Message::SetCheaterKickNoVotes(times) => {
+                if let Ok(times) = times.parse::<u32>() {
+                    self.settings.badges.cheater_kick_no_votes_times = times;
+                }
+            }
+            Message::SetSuspicionWeight(signal, weight) => {
+                if let Ok(weight) = weight.parse::<u32>() {
+                    self.mac.settings.suspicion.set_weight(signal, weight);
+                    self.mac.players.scorer = Scorer::new(&self.mac.settings.suspicion);
+                }
+            }
+            Message::LaunchTF2 => {
+                let missing = self.mac.settings.steam_user.map(|user| {
+                    LaunchOptions::new(user).and_then(|opts| {
+                        opts.check_missing_args().map(|m| m.into_iter().map(String::from).collect::<Vec<_>>())
+                    })
+                });
+
+                match missing {
+                    Some(Ok(missing)) if !missing.is_empty() => self.missing_launch_options = Some(missing),
+                    Some(Err(e)) => {
+                        tracing::warn!("Couldn't check launch options: {e:?}");
+                        launch_tf2();
+                    }
+                    _ => launch_tf2(),
+                }
+            }
+            Message::AddLaunchOptions => {
+                let Some(user) = self.mac.settings.steam_user else {
+                    return iced::Command::none();
+                };
+
+                let result = LaunchOptions::new(user).and_then(|mut opts| {
+                    opts.add_missing_args()?;
+                    opts.write()
+                });
+                match result {
+                    Ok(()) => {
+                        self.missing_launch_options = None;
+                        self.notify(String::from("Added the missing launch options (the old localconfig.vdf was backed up)"));
+                        launch_tf2();
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to add launch options: {e:?}");
+                        self.notify(format!("Couldn't add launch options: {e}"));
+                    }
+                }
+            }
+            Message::LaunchTF2Anyway => {
+                self.missing_launch_options = None;
+                launch_tf2();
+            }
+            Message::CancelLaunchTF2 => self.missing_launch_options = None,
+            Message::SetProfile(profile) => {
+                let profile = Some(profile).filter(|p| p != DEFAULT_PROFILE_NAME);
+                return self.switch_profile(profile);
+            }
+            Message::SetNewProfileName(name) => self.new_profile_name = name,
+            Message::CreateProfile => {
+                let name = self.new_profile_name.trim().to_owned();
+                if let Err(e) = profiles::validate_profile_name(&name) {
+                    self.notify(format!("{e}"));
+                    return iced::Command::none();
+                }
+
+                self.new_profile_name.clear();
+                return self.switch_profile(Some(name));
+            }
+            Message::SetAutosaveMinutes(minutes) => {
+                if let Ok(minutes) = minutes.parse::<u64>() {
+                    self.settings.autosave_minutes = minutes.max(1);
+                }
+            }
+            Message::SetReplayNameTemplate(template) => self.settings.replay_name_template = template,
+            Message::DismissNotification(idx) => {
+                if idx < self.notifications.len() {
+                    self.notifications.remove(idx);
+                }
+            }
+            Message::SetNewWatchedGroup(group) => self.new_watched_group = group,
+            Message::AddWatchedGroup => {
+                let Ok(group) = self.new_watched_group.trim().parse::<u64>() else {
+                    return iced::Command::none();
+                };
+                self.new_watched_group.clear();
+
+                let mut watchlist = self.mac.settings.group_watchlist.clone();
+                if !watchlist.contains(&group) {
+                    watchlist.push(group);
+                }
+                return self.update_group_watchlist(watchlist);
+            }
+            Message::RemoveWatchedGroup(idx) => {
+                let mut watchlist = self.mac.settings.group_watchlist.clone();
+                if idx < watchlist.len() {
+                    watchlist.remove(idx);
+                }
+                return self.update_group_watchlist(watchlist);
+            }
+            Message::SetNewBotList(url) => self.new_bot_list = url,
+            Message::AddBotList => {
+                let url = self.new_bot_list.trim().to_string();
+                if url.is_empty() {
+                    return iced::Command::none();
+                }
+                self.new_bot_list.clear();
+
+                let mut subscriptions = self.mac.settings.bot_list_subscriptions.clone();
+                if !subscriptions.contains(&url) {
+                    subscriptions.push(url);
+                }
+                return self.update_bot_list_subscriptions(subscriptions);
+            }
+            Message::RemoveBotList(idx) => {
+                let mut subscriptions = self.mac.settings.bot_list_subscriptions.clone();
+                if idx < subscriptions.len() {
+                    subscriptions.remove(idx);
+                }
+                return self.update_bot_list_subscriptions(subscriptions);
+            }
+            Message::SetNewNamePattern(pattern) => self.new_name_pattern = pattern,
+            Message::AddNamePattern => {
+                let pattern = self.new_name_pattern.trim().to_string();
+                if pattern.is_empty() || self.mac.settings.suspicion.name_patterns.contains(&pattern) {
+                    return iced::Command::none();
+                }
+                self.new_name_pattern.clear();
+
+                self.mac.settings.suspicion.name_patterns.push(pattern);
+                self.mac.players.scorer = Scorer::new(&self.mac.settings.suspicion);
+            }
+            Message::RemoveNamePattern(idx) => {
+                if idx < self.mac.settings.suspicion.name_patterns.len() {
+                    self.mac.settings.suspicion.name_patterns.remove(idx);
+                    self.mac.players.scorer = Scorer::new(&self.mac.settings.suspicion);
+                }
+            }
+            Message::SetNewNameRule(pattern) => self.new_name_rule = pattern,
+            Message::SetNewNameRuleAction(action) => self.new_name_rule_action = action,
+            Message::AddNameRule => {
+                let pattern = self.new_name_rule.trim().to_string();
+                if pattern.is_empty() || self.mac.settings.name_rules.iter().any(|r| r.pattern == pattern) {
+                    return iced::Command::none();
+                }
+                self.new_name_rule.clear();
+
+                self.mac.settings.name_rules.push(NameRule { pattern, action: self.new_name_rule_action });
+                self.update_name_rules();
+            }
+            Message::RemoveNameRule(idx) => {
+                if idx < self.mac.settings.name_rules.len() {
+                    self.mac.settings.name_rules.remove(idx);
+                    self.update_name_rules();
+                }
+            }
+            Message::UndoNameRuleMarks(idx) => {
+                if let Some(pattern) = self.mac.settings.name_rules.get(idx).map(|r| r.pattern.clone()) {
+                    let undone = self.mac.players.undo_name_rule_marks(&pattern);
+                    self.save_records();
+                    self.update_displayed_records();
+                    self.notify(format!("Set {undone} player(s) marked by \"{pattern}\" back to Player"));
+                }
+            }
             Message::ScrolledChat(offset) => {
                 self.snap_chat_to_bottom = (offset.y - 1.0).abs() <= f32::EPSILON;
             }
             Message::ScrolledKills(offset) => {
                 self.snap_kills_to_bottom = (offset.y - 1.0).abs() <= f32::EPSILON;
             }
+            Message::ScrolledView(offset) => {
+                self.view_ui.entry(self.settings.view).or_default().scroll = Some(offset);
+            }
             Message::ProfileLookupRequest(s) => {
                 return self.request_profile_lookup(vec![s]);
             }
+            Message::RefreshAllProfiles => {
+                return self.request_profile_lookup(self.mac.players.connected.clone());
+            }
             Message::ToggleMACEnabled(enabled) => {
                 self.settings.enable_mac_integration = enabled;
                 self.mac.settings.upload_demos = enabled;
@@ -416,36 +1184,145 @@ impl Application for App {
                     return verify_masterbase_connection(&self.mac.settings);
                 }
             },
+            Message::RetryMasterbase => {
+                self.mac.upload_status.last_error = None;
+                return verify_masterbase_connection(&self.mac.settings);
+            },
+            Message::RetryQueuedUpload(path) => {
+                self.mac.upload_queue.retry_now(&path);
+                return self.retry_queued_upload();
+            },
+            Message::CancelQueuedUpload(path) => self.mac.upload_queue.cancel(&path),
+            Message::ProvisionMasterbaseKey => {
+                if self.provisioning_key {
+                    return iced::Command::none();
+                }
+
+                if self.mac.settings.offline {
+                    self.provision_error = Some(String::from("Can't get a key while offline"));
+                    return iced::Command::none();
+                }
+
+                self.provisioning_key = true;
+                self.provision_error = None;
+                let host = self.mac.settings.masterbase_host.clone();
+                let http = self.mac.settings.masterbase_http;
+                return iced::Command::perform(
+                    async move {
+                        provision::provision_key(host, http, provision::PROVISION_TIMEOUT)
+                            .await
+                            .map_err(|e| e.to_string())
+                    },
+                    Message::MasterbaseKeyProvisioned,
+                );
+            },
+            Message::MasterbaseKeyProvisioned(result) => {
+                self.provisioning_key = false;
+                match result {
+                    Ok(key) => {
+                        tracing::info!("Received new masterbase key.");
+                        let update = self.update_masterbase_key(key);
+                        self.mac.upload_status.last_error = None;
+                        return iced::Command::batch([update, verify_masterbase_connection(&self.mac.settings)]);
+                    },
+                    Err(e) => {
+                        tracing::error!("Couldn't provision masterbase key: {e}");
+                        self.provision_error = Some(e);
+                    },
+                }
+            },
+            Message::QueuedUploadFinished(path, result) => {
+                if result.is_ok() {
+                    let name = path.file_name().map_or_else(String::new, |n| n.to_string_lossy().to_string());
+                    self.notify(format!("Finished uploading {name} to the Masterbase"));
+                }
+                self.mac.upload_queue.finish(&path, result);
+            },
             Message::Replay(m) => {
-                return self.replay.handle_message(m, &self.mac);
+                return self.replay.handle_message(m, &self.mac, &self.settings, &self.demos);
             },
             Message::BrowseTF2Dir => {
                 let Some(new_tf2_dir) = rfd::FileDialog::new().pick_folder() else {
                     return iced::Command::none();
                 };
                 self.mac.settings.tf2_directory = Some(new_tf2_dir.clone());
-                self.change_tf2_dir.send(new_tf2_dir).map_err(|e| tracing::error!("TF2 Directory could not be update for console and demo watchers: {e}")).ok();
+                self.reload_sources.send(new_tf2_dir).map_err(|e| tracing::error!("TF2 Directory could not be update for console and demo watchers: {e}")).ok();
             },
             Message::Demos(msg) => {
                 return demos::State::handle_message(self, msg);
             },
             Message::SetReplay(path) => {
                 self.settings.view = View::Replay;
-                return self.replay.handle_message(ReplayMessage::SetDemoPath(path), &self.mac);
+                return self.replay.handle_message(ReplayMessage::SetDemoPath(path), &self.mac, &self.settings, &self.demos);
             }
-            Message::SetTheme(theme) => {
+            Message::SetTheme(ThemeChoice::BuiltIn(theme)) => {
                 self.settings.theme = theme;
+                self.settings.selected_custom_theme = None;
+                self.apply_palette();
+            },
+            Message::SetTheme(ThemeChoice::Custom(i, _)) => {
+                self.settings.selected_custom_theme = Some(i);
+                self.apply_palette();
+            },
+            Message::NewCustomTheme => {
+                self.settings.custom_themes.push(CustomTheme::default());
+                self.settings.selected_custom_theme = Some(self.settings.custom_themes.len() - 1);
+                self.apply_palette();
+            },
+            Message::RemoveCustomTheme(i) => {
+                if i < self.settings.custom_themes.len() {
+                    self.settings.custom_themes.remove(i);
+                    self.settings.selected_custom_theme = match self.settings.selected_custom_theme {
+                        Some(s) if s == i => None,
+                        Some(s) if s > i => Some(s - 1),
+                        s => s,
+                    };
+                    self.apply_palette();
+                }
+            },
+            Message::SetCustomThemeName(i, name) => {
+                if let Some(theme) = self.settings.custom_themes.get_mut(i) {
+                    theme.name = name;
+                }
             },
-            Message::ToggleSidePanel(available_panels, panel) => {
-                if self.selected_player.is_some() || !self.settings.sidepanels.contains(&panel) {
-                    for p in available_panels { self.settings.sidepanels.remove(p); }
-                    self.settings.sidepanels.insert(panel);
+            Message::SetCustomThemeColour(i, colour, hex) => {
+                if let Some(theme) = self.settings.custom_themes.get_mut(i) {
+                    theme.set_hex(colour, hex);
+                    self.apply_palette();
+                }
+            },
+            Message::ToggleSidePanel(panel) => {
+                let view = self.settings.view.kind();
+
+                // The player's details stay open above the panel when it's split
+                if self.settings.split_side_panel {
+                    if self.settings.side_panel() == Some(panel) {
+                        self.settings.sidepanels.remove(&view);
+                        return iced::Command::none();
+                    }
+
+                    self.settings.sidepanels.insert(view, panel);
+                    return self.snap_chat_to_bottom();
+                }
+
+                if self.active_selection().is_some() || self.settings.side_panel() != Some(panel) {
+                    self.settings.sidepanels.insert(view, panel);
                     return self.unselect_player();
                 }
 
-                for p in available_panels { self.settings.sidepanels.remove(p); }
+                self.settings.sidepanels.remove(&view);
             }
             Message::SetPanelSide(side) => self.settings.panel_side = side,
+            Message::SetUiScale(scale) => self.settings.ui_scale = scale.clamp(settings::MIN_UI_SCALE, settings::MAX_UI_SCALE),
+            Message::SetSidePanelRatio(ratio) => self.settings.side_panel_ratio = ratio.clamp(settings::MIN_SIDE_PANEL_RATIO, settings::MAX_SIDE_PANEL_RATIO),
+            Message::SetSplitSidePanel(split) => {
+                self.settings.split_side_panel = split;
+                return self.snap_chat_to_bottom();
+            }
+            Message::SetSplitRatio(ratio) => {
+                self.settings.split_ratio = ratio.clamp(settings::MIN_SPLIT_RATIO, settings::MAX_SPLIT_RATIO);
+                return self.snap_chat_to_bottom();
+            }
             Message::AddDemoDir => {
                 let Some(new_demo_dir) = rfd::FileDialog::new().pick_folder() else {
                     return iced::Command::none();
@@ -459,18 +1336,54 @@ impl Application for App {
             Message::RemoveDemoDir(idx) => {
                 self.settings.demo_directories.remove(idx);
             },
-        };
-
-        iced::Command::none()
-    }
+            Message::SetNewLinkName(name) => self.new_link_name = name,
+            Message::SetNewLinkUrl(url) => self.new_link_url = url,
+            Message::AddExternalLink => {
+                let Ok(link) = ExternalLink::new(&self.new_link_name, &self.new_link_url) else {
+                    return iced::Command::none();
+                };
+                self.new_link_name.clear();
+                self.new_link_url.clear();
+                self.settings.external_links.push(link);
+            },
+            Message::RemoveExternalLink(idx) => {
+                if idx < self.settings.external_links.len() {
+                    self.settings.external_links.remove(idx);
+                }
+            },
+        };
+
+        iced::Command::none()
+    }
 
     fn view(&self) -> iced::Element<'_, Self::Message, Self::Theme, iced::Renderer> {
-        gui::main_window(self).into()
+        gui::main_window(self)
     }
 }
 
 impl App {
+    /// Use the colours of the selected custom theme, if there is one
+    fn apply_palette(&self) {
+        colours::set_palette(self.settings.custom_theme().map_or(colours::Palette::DEFAULT, CustomTheme::palette));
+    }
+
+    /// Saves the settings, merging in any changes made to the file by
+    /// something else first so they aren't overwritten
     fn save_settings(&mut self) {
+        self.reload_settings_if_changed();
+        self.store_app_settings();
+        self.mac.settings.save_ok();
+    }
+
+    /// Saves the records, merging in any changes made to the file by something
+    /// else first so they aren't overwritten
+    fn save_records(&mut self) {
+        self.reload_records_if_changed();
+        self.mac.players.records.save_ok();
+    }
+
+    /// Puts the app settings into the core settings so they are saved with them
+    fn store_app_settings(&mut self) {
         let settings = &mut self.mac.settings;
         let mut external_settings = settings.external.clone();
         if !external_settings.is_object() {
@@ -479,18 +1392,284 @@ impl App {
         external_settings[SETTINGS_IDENTIFIER] =
             serde_json::to_value(self.settings.clone()).expect("Epic serialization fail.");
         settings.update_external_preferences(external_settings);
-        settings.save_ok();
+    }
+
+    /// Saves everything and closes the app. Only hiding the window in the tray
+    /// shouldn't come through here.
+    fn quit(&mut self) -> iced::Command<Message> {
+        self.save_all();
+        iced::window::close(iced::window::Id::MAIN)
+    }
+
+    fn save_all(&mut self) {
+        self.save_settings();
+        self.save_records();
+        self.mac.players.save_history_ok();
+        self.mac.players.save_co_occurrence_ok();
+        self.mac.players.save_shared_playtime_ok();
+    }
+
+    fn watch_config_files(&mut self) {
+        let settings_path = self.mac.settings.config_path.as_deref();
+        let records_path = self.mac.players.records.path.as_deref();
+        self.config_watcher = ConfigWatcher::new(settings_path, records_path).map_err(|e| {
+            tracing::error!("Couldn't watch config files for changes: {e}");
+        }).ok();
+    }
+
+    /// Retry the next interrupted demo upload that is due, unless another
+    /// demo is being uploaded at the moment.
+    fn retry_queued_upload(&mut self) -> iced::Command<Message> {
+        if !self.settings.enable_mac_integration || self.mac.upload_status.session_open || self.mac.settings.offline {
+            return iced::Command::none();
+        }
+
+        let Some(upload) = self.mac.upload_queue.start_next() else {
+            return iced::Command::none();
+        };
+
+        tracing::info!("Retrying upload of {:?}", upload.demo_path);
+        let path = upload.demo_path.clone();
+        let host = self.mac.settings.masterbase_host.clone();
+        let key = self.mac.settings.masterbase_key.clone();
+        let http = self.mac.settings.masterbase_http;
+        iced::Command::perform(
+            queue::retry_upload(upload, host, key, http),
+            move |r| Message::QueuedUploadFinished(path, r),
+        )
+    }
+
+    /// Reloads the settings and records if something else has changed them
+    /// (e.g. they are being synced from another computer).
+    fn reload_changed_config_files(&mut self) {
+        let Some(watcher) = &mut self.config_watcher else {
+            return;
+        };
+
+        for file in watcher.changed_files() {
+            match file {
+                ConfigFile::Settings => self.reload_settings_if_changed(),
+                ConfigFile::Records => self.reload_records_if_changed(),
+            }
+        }
+    }
+
+    /// Merges in the settings file if something else has changed it since it
+    /// was last loaded or saved. Settings changed here since then are kept.
+    fn reload_settings_if_changed(&mut self) {
+        if !self.mac.settings.changed_on_disk() {
+            return;
+        }
+
+        self.store_app_settings();
+        if let Err(e) = self.mac.settings.reload() {
+            tracing::error!("Failed to reload settings: {e}");
+            self.notify(format!("Settings were changed outside of the app but couldn't be reloaded: {e}"));
+            return;
+        }
+
+        // The window stays where it is
+        let mut app_settings = app_settings_from(&self.mac.settings);
+        app_settings.window_pos = self.settings.window_pos;
+        app_settings.window_size = self.settings.window_size;
+        app_settings.window_maximized = self.settings.window_maximized;
+        self.settings = app_settings;
+        self.mac.settings.upload_demos = self.settings.enable_mac_integration;
+        self.mac.players.scorer = Scorer::new(&self.mac.settings.suspicion);
+        self.update_name_rules();
+        self.apply_palette();
+
+        tracing::info!("Reloaded settings after they were changed outside of the app.");
+        self.notify(String::from("Reloaded settings, they were changed outside of the app"));
+    }
+
+    /// Merges in the records file if something else has changed it since it
+    /// was last loaded or saved
+    fn reload_records_if_changed(&mut self) {
+        if !self.mac.players.records.changed_on_disk() {
+            return;
+        }
+
+        match self.mac.players.records.reload() {
+            Ok(summary) => {
+                self.update_displayed_records();
+                tracing::info!("Reloaded player records after they were changed outside of the app: {summary:?}");
+                self.notify(format!(
+                    "Reloaded player records changed outside of the app: {} added, {} updated, {} conflicts",
+                    summary.added, summary.updated, summary.conflicts
+                ));
+            }
+            Err(e) => {
+                tracing::error!("Failed to reload player records: {e}");
+                self.notify(format!("Player records were changed outside of the app but couldn't be reloaded: {e}"));
+            }
+        }
+    }
+
+    /// Saves the current profile, then loads the settings, records and steam
+    /// cache of another one and restarts the console and demo watchers for it
+    fn switch_profile(&mut self, profile: Option<String>) -> iced::Command<Message> {
+        if profile == self.profile {
+            return iced::Command::none();
+        }
+
+        self.save_all();
+        let settings = &self.mac.settings;
+        if let Err(e) = self.mac.players.save_steam_info_if_dirty(settings.steam_cache_max_age_days, settings.steam_cache_max_entries) {
+            tracing::error!("Failed to save steam info cache: {e}");
+        }
+
+        let (mac, mut app_settings) = match load_profile(monitor_builder(app().with_profile(profile.as_deref()), false)) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::error!("Failed to load profile {profile:?}: {e}");
+                self.notify(format!("Failed to load profile: {e}"));
+                return iced::Command::none();
+            }
+        };
+
+        // The window stays where it is
+        app_settings.window_pos = self.settings.window_pos;
+        app_settings.window_size = self.settings.window_size;
+        app_settings.window_maximized = self.settings.window_maximized;
+
+        self.mac = mac;
+        self.settings = app_settings;
+        self.profile = profile;
+        if let Err(e) = profiles::save_active_profile(app(), self.profile.as_deref()) {
+            tracing::error!("Failed to remember the active profile: {e}");
+        }
+        self.profiles = profiles::list_profiles(app()).unwrap_or_else(|e| {
+            tracing::error!("Failed to list settings profiles: {e}");
+            Vec::new()
+        });
+
+        self.selected_player = None;
+        self.demos.viewing_player = None;
+        self.steam_key_rejected = false;
+        self.undo_stack = UndoStack::new();
+        self.notifications.retain(|n| !matches!(n.action, Some((_, Message::Undo))));
+        self.missing_launch_options = None;
+        self.records = records::State::new(self.settings.records_per_page);
+        self.update_displayed_records();
+        self.apply_palette();
+        self.logs.configure_file(&self.settings);
+        self.watch_config_files();
+
+        if let Some(tf2_dir) = self.mac.settings.tf2_directory.clone() {
+            self.reload_sources.send(tf2_dir).map_err(|e| tracing::error!("Console and demo watchers could not be restarted: {e}")).ok();
+        }
+
+        self.notify(format!("Switched to the {} profile", self.profile.as_deref().unwrap_or(DEFAULT_PROFILE_NAME)));
+
+        self.demos.reload_uploaded_demos();
+        self.demos.bookmarks = demos::bookmarks::Bookmarks::load();
+        self.demos.highlighted_tick = None;
+        let mut commands = vec![demos::State::refresh_demos(self)];
+        self.mac.settings.upload_demos = self.settings.enable_mac_integration;
+        if self.settings.enable_mac_integration {
+            commands.push(verify_masterbase_connection(&self.mac.settings));
+        }
+        iced::Command::batch(commands)
+    }
+
+    fn set_window_hidden(&mut self, hidden: bool) -> iced::Command<Message> {
+        self.window_hidden = hidden;
+        if hidden {
+            iced::window::change_mode(iced::window::Id::MAIN, iced::window::Mode::Hidden)
+        } else {
+            iced::Command::batch([
+                iced::window::change_mode(iced::window::Id::MAIN, iced::window::Mode::Windowed),
+                iced::window::gain_focus(iced::window::Id::MAIN),
+            ])
+        }
+    }
+
+    /// Also shows a desktop notification while the window is hidden, since the
+    /// ones in the app can't be seen
+    fn notify_cheater_joined(&mut self, steamid: SteamID) {
+        let text = format!("{} joined the server (marked as a cheater)", self.display_name(steamid));
+        if self.window_hidden {
+            if let Err(e) = notify_rust::Notification::new().summary("TF2 Monitor").body(&text).show() {
+                tracing::error!("Failed to show desktop notification: {e}");
+            }
+        }
+        self.notify(text);
+    }
+
+    /// Saves the settings, records and steam cache if they have changed since
+    /// they were last saved
+    fn autosave(&mut self) {
+        self.reload_settings_if_changed();
+        self.reload_records_if_changed();
+        self.store_app_settings();
+        let mut failed = false;
+
+        match self.mac.settings.save_if_dirty() {
+            Ok(true) => tracing::debug!("Autosaved settings."),
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to autosave settings: {e}");
+                failed = true;
+            }
+        }
+
+        match self.mac.players.records.save_if_dirty() {
+            Ok(true) => tracing::debug!("Autosaved player records."),
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to autosave player records: {e}");
+                failed = true;
+            }
+        }
+
+        let settings = &self.mac.settings;
+        match self.mac.players.save_steam_info_if_dirty(settings.steam_cache_max_age_days, settings.steam_cache_max_entries) {
+            Ok(true) => tracing::debug!("Autosaved steam info cache."),
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to autosave steam info cache: {e}");
+                failed = true;
+            }
+        }
+
+        if !failed {
+            self.last_autosave = Some(Local::now());
+        }
     }
 
     fn update_verdict(&mut self, steamid: SteamID, verdict: Verdict) {
+        if self.mac.players.records.get(&steamid).map_or(Verdict::Player, |r| r.verdict()) != verdict {
+            let name = self.display_name(steamid);
+            self.remember_change(ChangeKind::Verdict, format!("Marked {name} as {verdict}"), &[steamid]);
+        }
+
         let record = self.mac.players.records.entry(steamid).or_default();
         record.set_verdict(verdict);
 
         self.mac.players.records.prune();
-        self.mac.players.records.save_ok();
+        self.save_records();
+    }
+
+    fn update_alias(&mut self, steamid: SteamID, alias: String) {
+        let name = self.display_name(steamid);
+        self.remember_change(ChangeKind::Alias, format!("Changed alias of {name}"), &[steamid]);
+
+        let record = self.mac.players.records.entry(steamid).or_default();
+
+        // An empty alias removes the key entirely
+        let mut alias_value = Map::new();
+        alias_value.insert(ALIAS_KEY.to_string(), serde_json::Value::String(alias));
+        record.set_custom_data(serde_json::Value::Object(alias_value));
+
+        self.mac.players.records.prune();
+        self.save_records();
     }
 
     fn update_notes(&mut self, steamid: SteamID, notes: String) {
+        let name = self.display_name(steamid);
+        self.remember_change(ChangeKind::Notes, format!("Changed notes of {name}"), &[steamid]);
+
         let record = self.mac.players.records.entry(steamid).or_default();
 
         let mut notes_value = Map::new();
@@ -498,11 +1677,306 @@ impl App {
         record.set_custom_data(serde_json::Value::Object(notes_value));
 
         self.mac.players.records.prune();
-        self.mac.players.records.save_ok();
+        self.save_records();
+    }
+
+    /// Look up the account behind a custom profile URL, unless it already has
+    /// been or can't be
+    fn resolve_vanity(&mut self, vanity: String) -> iced::Command<Message> {
+        let looked_up = matches!(self.records.resolved_vanities.get(&vanity), Some(lookup) if !matches!(lookup, VanityLookup::Failed(_)));
+        if self.mac.settings.steam_api_key.is_empty() || self.mac.settings.offline || looked_up {
+            return iced::Command::none();
+        }
+
+        self.records.resolved_vanities.insert(vanity.clone(), VanityLookup::Resolving);
+        let key = self.mac.settings.steam_api_key.clone();
+        iced::Command::perform(
+            async move {
+                let result = steam::api::resolve_vanity_url(&key, &vanity).await;
+                (vanity, result)
+            },
+            |(vanity, result)| {
+                let lookup = match result {
+                    Ok(steamid) => VanityLookup::Found(steamid),
+                    Err(steam::api::SteamAPIError::VanityNotFound(_)) => VanityLookup::NotFound,
+                    Err(e) => {
+                        tracing::warn!("Couldn't resolve custom URL {vanity:?}: {e}");
+                        VanityLookup::Failed(e.to_string())
+                    }
+                };
+                Message::VanityResolved(vanity, lookup)
+            },
+        )
+    }
+
+    /// Save the add record form, once the account it's for is known. If the
+    /// account already has a record the form is filled in from it first so
+    /// it's edited rather than replaced.
+    fn confirm_add_record(&mut self) -> iced::Command<Message> {
+        let Some(mut form) = self.records.add_record.take() else {
+            return iced::Command::none();
+        };
+        form.error = None;
+
+        let steamid = match SearchTarget::parse(&form.account) {
+            SearchTarget::Account(steamid) => steamid,
+            SearchTarget::Vanity(vanity) => match self.records.resolved_vanities.get(&vanity) {
+                Some(VanityLookup::Found(steamid)) => *steamid,
+                Some(VanityLookup::NotFound) => {
+                    form.error = Some(format!("No Steam account has the custom URL {vanity:?}"));
+                    self.records.add_record = Some(form);
+                    return iced::Command::none();
+                }
+                _ if self.mac.settings.offline => {
+                    form.error = Some(String::from("Custom URLs can't be looked up while offline, use the SteamID instead"));
+                    self.records.add_record = Some(form);
+                    return iced::Command::none();
+                }
+                _ if self.mac.settings.steam_api_key.is_empty() => {
+                    form.error = Some(String::from("Set a Steam API key in the settings to look up custom URLs, or use the SteamID instead"));
+                    self.records.add_record = Some(form);
+                    return iced::Command::none();
+                }
+                _ => {
+                    form.resolving = true;
+                    self.records.add_record = Some(form);
+                    return self.resolve_vanity(vanity);
+                }
+            },
+            SearchTarget::Text => {
+                form.error = Some(String::from("Enter a SteamID in any format, or a link to a Steam profile"));
+                self.records.add_record = Some(form);
+                return iced::Command::none();
+            }
+        };
+
+        if let Some(record) = self.mac.players.records.get(&steamid) {
+            if form.existing != Some(steamid) {
+                let custom_str = |key: &str| record.custom_data().get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                if form.alias.is_empty() {
+                    form.alias = custom_str(ALIAS_KEY);
+                }
+                if form.notes.is_empty() {
+                    form.notes = custom_str(NOTES_KEY);
+                }
+                form.error = Some(format!(
+                    "{} already has a record (marked as {}). Its alias and notes have been filled in, press Update to save your changes to it.",
+                    self.display_name(steamid),
+                    record.verdict()
+                ));
+                form.existing = Some(steamid);
+                self.records.add_record = Some(form);
+                return iced::Command::none();
+            }
+        }
+
+        if form.verdict == Verdict::Player && form.alias.trim().is_empty() && form.notes.trim().is_empty() && form.existing.is_none() {
+            form.error = Some(String::from("Choose a verdict other than Player, or add an alias or notes"));
+            self.records.add_record = Some(form);
+            return iced::Command::none();
+        }
+
+        let name = self.display_name(steamid);
+        let description = if form.existing.is_some() { format!("Updated record of {name}") } else { format!("Added record for {name}") };
+        self.remember_change(ChangeKind::Add, description, &[steamid]);
+
+        let record = self.mac.players.records.entry(steamid).or_default();
+        record.set_verdict(form.verdict);
+        let mut custom_data = Map::new();
+        custom_data.insert(ALIAS_KEY.to_string(), serde_json::Value::String(form.alias.trim().to_string()));
+        custom_data.insert(NOTES_KEY.to_string(), serde_json::Value::String(form.notes));
+        record.set_custom_data(serde_json::Value::Object(custom_data));
+
+        self.mac.players.records.prune();
+        self.save_records();
+
+        // Jump to it the same way as searching for its steamid
+        self.records.search = u64::from(steamid).to_string();
+        self.records.search_generation += 1;
+        self.records.current_page = 0;
+        self.update_displayed_records();
+
+        iced::Command::batch([
+            self.request_profile_lookup(vec![steamid]),
+            snap_to(widget::scrollable::Id::new(records::SCROLLABLE_ID), RelativeOffset::START),
+        ])
+    }
+
+    fn notify(&mut self, text: String) {
+        self.notifications.push(Notification {
+            text,
+            created: Instant::now(),
+            duration: NOTIFICATION_DURATION,
+            action: None,
+        });
+    }
+
+    fn notify_with_action(&mut self, text: String, action: (&'static str, Message)) {
+        self.notifications.push(Notification {
+            text,
+            created: Instant::now(),
+            duration: NOTIFICATION_DURATION,
+            action: Some(action),
+        });
+    }
+
+    /// Whether the player is in the analysed demo being looked at
+    fn in_viewed_demo(&self, steamid: SteamID) -> bool {
+        let View::AnalysedDemo(demo) = self.settings.view else {
+            return false;
+        };
+        self.demos.analysed_demos.get(&demo).and_then(|d| d.get_demo()).is_some_and(|a| a.players.contains_key(&steamid))
+    }
+
+    fn display_name(&self, steamid: SteamID) -> String {
+        self.mac.players.get_name(steamid).map_or_else(|| format!("{}", u64::from(steamid)), ToOwned::to_owned)
+    }
+
+    /// Stash the current state of the records so the change about to be made
+    /// can be undone, and offer to undo it.
+    fn remember_change(&mut self, kind: ChangeKind, description: String, steamids: &[SteamID]) {
+        let records = steamids.iter().map(|&s| (s, self.mac.players.records.get(&s).cloned())).collect();
+        let added = self.undo_stack.push(RecordChange {
+            kind,
+            description: description.clone(),
+            made: Instant::now(),
+            records,
+        });
+
+        if added {
+            self.notifications.retain(|n| !matches!(n.action, Some((_, Message::Undo))));
+            self.notifications.push(Notification {
+                text: description,
+                created: Instant::now(),
+                duration: UNDO_NOTIFICATION_DURATION,
+                action: Some(("Undo", Message::Undo)),
+            });
+        }
+    }
+
+    fn undo(&mut self) {
+        let Some(change) = self.undo_stack.pop() else {
+            return;
+        };
+
+        let description = change.description.clone();
+        change.restore(&mut self.mac.players.records);
+        self.save_records();
+
+        self.notifications.retain(|n| !matches!(n.action, Some((_, Message::Undo))));
+        self.notify(format!("Undone: {description}"));
+
+        if matches!(self.settings.view, View::Records) {
+            self.update_displayed_records();
+            let max_page = self.records.to_display.len() / self.records.num_per_page;
+            self.records.current_page = self.records.current_page.min(max_page);
+        }
+    }
+
+    fn update_masterbase_key(&mut self, key: String) -> iced::Command<Message> {
+        self.handle_mac_message(MonitorMessage::Preferences(Preferences {
+            internal: Some(InternalPreferences {
+                friends_api_usage: None,
+                tf2_directory: None,
+                rcon_password: None,
+                steam_api_key: None,
+                masterbase_key: Some(key),
+                masterbase_host: None,
+                rcon_port: None,
+                dumb_autokick: None,
+                request_playtime: None,
+                steam_info_cache_hours: None,
+                request_steam_level: None,
+                request_groups: None,
+                group_watchlist: None,
+                mark_group_members_suspicious: None,
+                bot_list_subscriptions: None,
+                max_history_len: None,
+            }),
+            external: None,
+        }))
+    }
+
+    /// Rebuild the name rules from the settings. Rows on the records page have
+    /// the matching rule worked out already, so they are too.
+    fn update_name_rules(&mut self) {
+        self.mac.players.name_rules = NameRules::new(&self.mac.settings.name_rules);
+        self.records.rows.clear();
+    }
+
+    fn update_group_watchlist(&mut self, watchlist: Vec<u64>) -> iced::Command<Message> {
+        self.handle_mac_message(MonitorMessage::Preferences(Preferences {
+            internal: Some(InternalPreferences {
+                friends_api_usage: None,
+                tf2_directory: None,
+                rcon_password: None,
+                steam_api_key: None,
+                masterbase_key: None,
+                masterbase_host: None,
+                rcon_port: None,
+                dumb_autokick: None,
+                request_playtime: None,
+                steam_info_cache_hours: None,
+                request_steam_level: None,
+                request_groups: None,
+                group_watchlist: Some(watchlist),
+                mark_group_members_suspicious: None,
+                bot_list_subscriptions: None,
+                max_history_len: None,
+            }),
+            external: None,
+        }))
+    }
+
+    fn update_bot_list_subscriptions(&mut self, subscriptions: Vec<String>) -> iced::Command<Message> {
+        self.handle_mac_message(MonitorMessage::Preferences(Preferences {
+            internal: Some(InternalPreferences {
+                friends_api_usage: None,
+                tf2_directory: None,
+                rcon_password: None,
+                steam_api_key: None,
+                masterbase_key: None,
+                masterbase_host: None,
+                rcon_port: None,
+                dumb_autokick: None,
+                request_playtime: None,
+                steam_info_cache_hours: None,
+                request_steam_level: None,
+                request_groups: None,
+                group_watchlist: None,
+                mark_group_members_suspicious: None,
+                bot_list_subscriptions: Some(subscriptions),
+                max_history_len: None,
+            }),
+            external: None,
+        }))
     }
 
     fn update_displayed_records(&mut self) {
-        let steamid = SteamID::try_from(self.records.search.as_str()).ok();
+        let steamid = match SearchTarget::parse(&self.records.search) {
+            SearchTarget::Account(steamid) => Some(steamid),
+            SearchTarget::Vanity(vanity) => match self.records.resolved_vanities.get(&vanity) {
+                Some(VanityLookup::Found(steamid)) => Some(*steamid),
+                _ => None,
+            },
+            SearchTarget::Text => None,
+        };
+        self.records.search_index.update(&self.mac.players);
+        let search = search::normalise(self.records.search.trim());
+        let name_matches = |name: &str| {
+            if self.records.exact_name {
+                search::normalise(name) == search
+            } else {
+                search::normalise(name).contains(&search)
+            }
+        };
+        let last_seen_days = self.records.last_seen_days.trim().parse::<i64>().ok();
+        let seen_with_marked = self.records.seen_with_marked.trim().parse::<u32>().ok();
+        let country = self.records.country.trim();
+        let country_matches = |s: SteamID| country.is_empty() || self.mac.players.steam_info.get(&s).and_then(|si| si.country_code.as_deref()).is_some_and(|c| c.eq_ignore_ascii_case(country));
+        self.records.pinned = None;
+        self.records.confirm_delete = false;
+        let previously_displayed = std::mem::take(&mut self.records.to_display);
 
         self.records.to_display = self
             .mac
@@ -511,47 +1985,51 @@ impl App {
             .iter()
             .map(|(s, r)| (*s, r))
             .filter(|(_, r)| self.records.verdict_whitelist.contains(&r.verdict()))
-            .filter(|(s, r)| {
+            .filter(|(_, r)| self.records.last_seen_filter.matches(r.last_seen(), last_seen_days))
+            .filter(|(s, _)| seen_with_marked.map_or(true, |t| self.mac.players.co_occurrence.times_with_marked(*s) >= t))
+            .filter(|(s, _)| self.records.friend_filter.matches(self.mac.players.is_friends_with_user(*s)))
+            .filter(|(s, _)| country_matches(*s))
+            .filter(|(_, r)| !self.records.recently_banned_only || r.bans_detected().iter().any(|b| Utc::now().signed_duration_since(b.detected).num_days() < records::RECENTLY_BANNED_DAYS))
+            .filter(|(s, _)| {
                 // Search bar
-                if self.records.search.is_empty() {
+                if search.is_empty() {
                     return true;
                 }
 
-                // Previous names
-                r.previous_names()
-                    .iter()
-                    .any(|n| n.contains(&self.records.search))
-
-                    // Steamid
+                // Steamid
+                steamid.is_some_and(|id| id == *s)
                     || steamid.is_some_and(|_| {
-                        format!("{}", u64::from(*s)).contains(&self.records.search)
+                        format!("{}", u64::from(*s)).contains(&search)
                     })
 
-                    // Current name
-                    || self
-                        .mac
-                        .players
-                        .get_name(*s)
-                        .is_some_and(|n| n.contains(&self.records.search))
-
-                    // Alias
-                    || r.custom_data().get(ALIAS_KEY).and_then(|v| v.as_str()).is_some_and(|s| s.contains(&self.records.search))
-
-                    // Notes
-                    || r.custom_data().get(NOTES_KEY).and_then(|v| v.as_str()).is_some_and(|s| s.contains(&self.records.search))
-                    
+                    // Current and previous names, alias and notes
+                    || self.records.search_index.matches(*s, &search, self.records.exact_name)
             })
             .map(|(s, _)| s)
             .collect();
 
-        self.records.to_display.sort_by_key(|s| {
-            self.mac
+        // Accounts seen with marked players often don't have a record yet
+        if let Some(threshold) = seen_with_marked {
+            let unrecorded: Vec<SteamID> = self
+                .mac
                 .players
-                .records
-                .get(s)
-                .expect("Only existing records should be in this list")
-                .modified()
-        });
+                .co_occurrence
+                .accounts_over(threshold)
+                .filter(|s| !self.mac.players.records.contains_key(s))
+                .filter(|_| self.records.verdict_whitelist.contains(&Verdict::Player))
+                .filter(|_| !self.records.recently_banned_only && self.records.last_seen_filter.matches(None, last_seen_days))
+                .filter(|s| self.records.friend_filter.matches(self.mac.players.is_friends_with_user(*s)))
+                .filter(|s| country_matches(*s))
+                .filter(|s| {
+                    search.is_empty()
+                        || steamid.is_some_and(|id| id == *s)
+                        || self.mac.players.get_name(*s).is_some_and(name_matches)
+                })
+                .collect();
+            self.records.to_display.extend(unrecorded);
+        }
+
+        self.settings.record_sort_by.sort(&self.mac.players, self.settings.record_sort_direction, &mut self.records.to_display);
 
         // If exact steamid, put it at the top of the list (even if there isn't a record for it)
         if let Some(steamid) = steamid {
@@ -559,46 +2037,116 @@ impl App {
             if u64::from(steamid) >= 76561197960265728 {
                 if let Some(i) = self.records.to_display.iter().position(|s| *s == steamid) {
                     self.records.to_display.remove(i);
+                } else {
+                    self.records.pinned = Some(steamid);
                 }
 
-                self.records.to_display.push(steamid);
+                self.records.to_display.insert(0, steamid);
             }
         }
-        
-        self.records.to_display.reverse();
+
+        // Only start from the top again if the list has actually changed
+        if self.records.to_display != previously_displayed {
+            self.view_ui.remove(&View::Records);
+        }
+
+        self.refresh_record_rows();
     }
 
     /// Updates the list of demos that is being displayed
     pub fn update_demo_list(&mut self) {
-        self.demos.demos_to_display = self.settings.demo_filters.filter(self);
+        let demos_to_display = self.settings.demo_filters.filter(self);
+        if demos_to_display != self.demos.demos_to_display {
+            self.view_ui.remove(&View::Demos);
+        }
+        self.demos.demos_to_display = demos_to_display;
         self.demos.page = self.demos.page.min(self.demos.demos_to_display.len() / self.demos.demos_per_page);
     }
 
+    /// Which tab the analysed demo being viewed was left on
+    #[must_use]
+    pub fn analysed_demo_tab(&self) -> AnalysedDemoView {
+        self.view_ui
+            .get(&self.settings.view)
+            .and_then(|ui| ui.analysed_demo_tab)
+            .unwrap_or(self.settings.analysed_demo_view)
+    }
+
+    /// Scroll the current view back to where it was left
+    fn restore_scroll(&self) -> iced::Command<Message> {
+        let (Some(id), Some(offset)) = (
+            self.settings.view.scrollable_id(),
+            self.view_ui.get(&self.settings.view).and_then(|ui| ui.scroll),
+        ) else {
+            return iced::Command::none();
+        };
+
+        snap_to(widget::scrollable::Id::new(id), offset)
+    }
+
+    /// Show the newest log lines, unless auto-scroll is off
+    fn scroll_logs_to_bottom(&self) -> iced::Command<Message> {
+        if !self.logs.auto_scroll {
+            return iced::Command::none();
+        }
+
+        snap_to(widget::scrollable::Id::new(logs::SCROLLABLE_ID), RelativeOffset::END)
+    }
+
     fn handle_mac_message(&mut self, message: MonitorMessage) -> iced::Command<Message> {
         let mut commands = Vec::new();
 
         let mut messages = vec![message];
+        let mut steam_info_changed = false;
         while let Some(m) = messages.pop() {
             // Get profile pictures
             match &m {
-                MonitorMessage::ProfileLookupResult(ProfileLookupResult(Ok(profiles))) => {
-                    for (_, r) in profiles {
+                MonitorMessage::ProfileLookupResult(ProfileLookupResult { result: Ok(profiles), .. }) => {
+                    // Pfps on the records page are requested once the steam info has been added
+                    steam_info_changed = true;
+                    for (s, r) in profiles {
                         if let Ok(si) = r {
-                            commands.push(self.request_pfp_lookup(&si.pfp_hash, &si.pfp_url));
+                            if self.mac.players.connected.contains(s) || self.selected_player == Some(*s) || self.demos.viewing_player == Some(*s) || self.in_viewed_demo(*s) || self.mac.players.history.iter().any(|h| h.steamid == *s) {
+                                commands.push(self.request_pfp_lookup(&si.pfp_hash, &si.pfp_url));
+                            }
                         }
                     }
                 }
+                MonitorMessage::ProfileLookupResult(ProfileLookupResult { result: Err(e), .. }) if e.is_permanent() && !self.steam_key_rejected => {
+                    tracing::warn!("Your Steam API key is not valid. Profile lookups will resume once it has been changed. You can get a new one at https://steamcommunity.com/dev/apikey");
+                    self.steam_key_rejected = true;
+                }
+                MonitorMessage::Preferences(Preferences { internal: Some(InternalPreferences { steam_api_key: Some(_), .. }), .. }) => {
+                    self.steam_key_rejected = false;
+                }
+                MonitorMessage::NewBansDetected(NewBansDetected(bans)) => {
+                    for (steamid, ban) in bans {
+                        let name = self.mac.players.get_name(*steamid).map_or_else(|| format!("{}", u64::from(*steamid)), ToOwned::to_owned);
+                        self.notify(format!("New ban detected on {name} ({} VAC, {} game bans)", ban.vac_bans, ban.game_bans));
+                    }
+                }
+                MonitorMessage::Refresh(_) => {
+                    self.notifications.retain(|n| n.created.elapsed() < n.duration);
+                    self.reload_changed_config_files();
+                    commands.push(self.retry_queued_upload());
+                }
                 MonitorMessage::NewPlayers(NewPlayers(players)) => {
                     for s in players {
                         commands.push(self.request_pfp_lookup_for_existing_player(*s));
+                        if self.mac.players.verdict(*s) == Verdict::Cheater {
+                            self.notify_cheater_joined(*s);
+                        }
                     }
                 }
-                MonitorMessage::ConsoleOutput(ConsoleOutput::Chat(_)) if self.snap_chat_to_bottom => {
+                MonitorMessage::ConsoleOutput(ConsoleOutput::Chat(_)) | MonitorMessage::NamesChanged(_) if self.snap_chat_to_bottom => {
                     commands.push(snap_to(
                         widget::scrollable::Id::new(chat::SCROLLABLE_ID),
                         RelativeOffset { x: 0.0, y: 1.0 },
                     ));
                 }
+                MonitorMessage::ConsoleOutput(ConsoleOutput::DemoStop(stop)) if self.settings.auto_analyse_demos => {
+                    commands.push(self.demos.queue_auto_analysis(stop.seconds));
+                }
                 MonitorMessage::ConsoleOutput(ConsoleOutput::Kill(_)) if self.snap_kills_to_bottom => {
                     commands.push(snap_to(
                         widget::scrollable::Id::new(killfeed::SCROLLABLE_ID),
@@ -622,47 +2170,55 @@ impl App {
             }
         }
 
+        if steam_info_changed && matches!(self.settings.view, View::Records) {
+            self.refresh_record_rows();
+            commands.push(self.start_pfp_lookups());
+        }
+
         iced::Command::batch(commands)
     }
 
-    fn insert_new_pfp(&mut self, pfp_hash: String, bytes: &[u8]) {
-        fn default_image() -> image::DynamicImage {
-            image::DynamicImage::ImageRgb8(ImageBuffer::new(
-                u32::from(PFP_FULL_SIZE),
-                u32::from(PFP_FULL_SIZE),
-            ))
-        }
-
-        let full_image = Reader::new(Cursor::new(bytes))
-            .with_guessed_format()
-            .ok()
-            .and_then(|r| r.decode().ok())
-            .unwrap_or_else(default_image)
-            .resize(
-                u32::from(PFP_FULL_SIZE),
-                u32::from(PFP_FULL_SIZE),
-                image::imageops::FilterType::Triangle,
-            );
-
-        let smol_image = full_image.resize(
-            u32::from(PFP_SMALL_SIZE),
-            u32::from(PFP_SMALL_SIZE),
-            image::imageops::FilterType::Triangle,
-        );
+    /// Works out the rows on the current records page again where they have
+    /// changed, and queues the pfps they're missing
+    fn refresh_record_rows(&mut self) {
+        let page: Vec<SteamID> = self.records.to_display.iter()
+            .skip(self.records.current_page * self.records.num_per_page)
+            .take(self.records.num_per_page)
+            .copied()
+            .collect();
 
-        let full_handle = iced::widget::image::Handle::from_pixels(
-            u32::from(PFP_FULL_SIZE),
-            u32::from(PFP_FULL_SIZE),
-            Bytes::copy_from_slice(full_image.into_rgba8().as_bytes()),
-        );
-        let smol_handle = iced::widget::image::Handle::from_pixels(
-            u32::from(PFP_SMALL_SIZE),
-            u32::from(PFP_SMALL_SIZE),
-            Bytes::copy_from_slice(smol_image.into_rgba8().as_bytes()),
-        );
+        self.records.rows.retain(|s, _| page.contains(s));
+        for s in page {
+            let players = &self.mac.players;
+            self.records.rows.entry(s)
+                .and_modify(|row| if row.is_stale(players, s) {
+                    *row = records::RowData::new(players, s);
+                })
+                .or_insert_with(|| records::RowData::new(players, s));
+
+            if let Some(si) = players.steam_info.get(&s) {
+                if !self.pfp_cache.contains_key(&si.pfp_hash) {
+                    self.pfp_queue.request(&si.pfp_hash, &si.pfp_url);
+                }
+            }
+        }
+    }
+
+    /// Keeps a decoded pfp for display, returning the command that also saves
+    /// it to disk for next time if it was just downloaded
+    fn insert_new_pfp(&mut self, pfp_hash: String, pfp: DecodedPfp) -> iced::Command<Message> {
+        self.pfp_cache.insert(pfp_hash.clone(), (pfp.full, pfp.small));
+
+        let Some(bytes) = pfp.to_store else {
+            return iced::Command::none();
+        };
 
-        self.pfp_in_progess.remove(&pfp_hash);
-        self.pfp_cache.insert(pfp_hash, (full_handle, smol_handle));
+        iced::Command::perform(pfp_cache::store(pfp_hash, bytes), |r| {
+            if let Err(e) = r {
+                tracing::warn!("Failed to save pfp to disk: {e}");
+            }
+            Message::None
+        })
     }
 
     fn request_profile_lookup(&mut self, accounts: Vec<SteamID>) -> iced::Command<Message> {
@@ -686,22 +2242,25 @@ impl App {
     }
 
     fn request_pfp_lookup(&mut self, pfp_hash: &str, pfp_url: &str) -> iced::Command<Message> {
-        if self.pfp_cache.contains_key(pfp_hash) || self.pfp_in_progess.contains(pfp_hash) {
+        if self.pfp_cache.contains_key(pfp_hash) {
             return iced::Command::none();
         }
 
-        self.pfp_in_progess.insert(pfp_hash.to_string());
-        let pfp_hash = pfp_hash.to_string();
-        let pfp_url = pfp_url.to_string();
-        iced::Command::perform(
-            async move {
-                match reqwest::get(&pfp_url).await {
-                    Ok(resp) => (pfp_hash, resp.bytes().await.map_err(|_| ())),
-                    Err(_) => (pfp_hash, Err(())),
-                }
-            },
-            |(pfp_hash, resp)| Message::PfpLookupResponse(pfp_hash, resp),
-        )
+        self.pfp_queue.request(pfp_hash, pfp_url);
+        self.start_pfp_lookups()
+    }
+
+    /// Start fetching as many of the queued pfps as are allowed at once
+    fn start_pfp_lookups(&mut self) -> iced::Command<Message> {
+        iced::Command::batch(self.pfp_queue.next_fetches().into_iter().map(|(pfp_hash, pfp_url)| {
+            iced::Command::perform(
+                async move {
+                    let pfp = pfp_cache::fetch(pfp_hash.clone(), pfp_url).await;
+                    (pfp_hash, pfp)
+                },
+                |(pfp_hash, pfp)| Message::PfpLookupResponse(pfp_hash, pfp),
+            )
+        }))
     }
 
     fn request_pfp_lookup_for_existing_player(
@@ -712,137 +2271,279 @@ impl App {
             return iced::Command::none();
         };
 
-        let pfp_hash = &si.pfp_hash;
-        let pfp_url = &si.pfp_url;
+        let pfp_hash = si.pfp_hash.clone();
+        let pfp_url = si.pfp_url.clone();
+        self.request_pfp_lookup(&pfp_hash, &pfp_url)
+    }
 
-        if self.pfp_cache.contains_key(pfp_hash) || self.pfp_in_progess.contains(pfp_hash) {
-            return iced::Command::none();
+    /// The player selected in the current view
+    #[must_use]
+    pub const fn active_selection(&self) -> Option<SteamID> {
+        if let View::AnalysedDemo(_) = self.settings.view {
+            self.demos.viewing_player
+        } else {
+            self.selected_player
         }
-
-        self.pfp_in_progess.insert(pfp_hash.to_string());
-        let pfp_hash = pfp_hash.to_string();
-        let pfp_url = pfp_url.to_string();
-        iced::Command::perform(
-            async move {
-                match reqwest::get(&pfp_url).await {
-                    Ok(resp) => (pfp_hash, resp.bytes().await.map_err(|_| ())),
-                    Err(_) => (pfp_hash, Err(())),
-                }
-            },
-            |(pfp_hash, resp)| Message::PfpLookupResponse(pfp_hash, resp),
-        )
     }
 
     fn unselect_player(&mut self) -> iced::Command<Message> {
+        if let View::AnalysedDemo(demo) = self.settings.view {
+            self.demos.viewing_player = None;
+            self.demos.chart = KDAChart::new(self, demo, None);
+            return iced::Command::none();
+        }
         self.selected_player = None;
+        self.snap_chat_to_bottom()
+    }
 
-        if self.settings.sidepanels.contains(&SidePanel::ChatKills) {
-            return iced::Command::batch([
-                snap_to(widget::scrollable::Id::new(chat::SCROLLABLE_ID), RelativeOffset { x: 0.0, y: 1.0 }),
-                snap_to(widget::scrollable::Id::new(killfeed::SCROLLABLE_ID), RelativeOffset { x: 0.0, y: 1.0 }),
-            ]);
+    /// Keeps the chat and killfeed at their latest messages after they've been
+    /// shown or resized
+    fn snap_chat_to_bottom(&self) -> iced::Command<Message> {
+        if self.settings.side_panel() != Some(SidePanel::ChatKills) {
+            return iced::Command::none();
         }
 
-        iced::Command::none()
+        iced::Command::batch([
+            snap_to(widget::scrollable::Id::new(chat::SCROLLABLE_ID), RelativeOffset { x: 0.0, y: 1.0 }),
+            snap_to(widget::scrollable::Id::new(killfeed::SCROLLABLE_ID), RelativeOffset { x: 0.0, y: 1.0 }),
+        ])
     }
 }
 
-impl Drop for App {
-    fn drop(&mut self) {
-        if let View::AnalysedDemo(_) = self.settings.view {
-            self.settings.view = View::Demos;
-        }
-        self.save_settings();
-        self.mac.players.records.save_ok();
-        self.mac.players.save_steam_info_ok();
-    }
+/// Values given on the command line to use instead of the loaded or inferred ones
+#[derive(Debug, Default)]
+struct CliOverrides {
+    tf2_dir: Option<PathBuf>,
+    config: Option<PathBuf>,
+    playerlist: Option<PathBuf>,
+    steam_user: Option<SteamID>,
+    offline: bool,
 }
 
-fn main() {
-    let _guard = tracing_setup::init_tracing();
-
-    // Load Settings
-    let mut settings = Settings::load_or_create(
-        Settings::default_file_location(APP).unwrap_or_else(|e| {
-            tracing::error!("Failed to find a suitable location to store settings ({e}). Settings will be written to {}", tf2_monitor_core::settings::CONFIG_FILE_NAME);
-            tf2_monitor_core::settings::CONFIG_FILE_NAME.into()
-        }
-    )).expect("Failed to load settings. Please fix any issues mentioned and try again.");
-    settings.save_ok();
-
-    if let Err(e) = settings.infer_steam_user() {
-        tracing::error!("Failed to infer steam user: {e}");
+/// Set once at startup
+static CLI_OVERRIDES: OnceLock<CliOverrides> = OnceLock::new();
+
+/// Loads the profile in `app` with the overrides given on the command line.
+/// The config and playerlist files given are only used for the profile the
+/// app was started with, other profiles use their own.
+fn monitor_builder(app: AppDetails, startup_profile: bool) -> MonitorBuilder {
+    let overrides = CLI_OVERRIDES.get_or_init(CliOverrides::default);
+    let mut builder = MonitorBuilder::new(app);
+    if let Some(tf2_dir) = &overrides.tf2_dir {
+        builder = builder.with_tf2_dir(tf2_dir);
     }
-
-    if let Err(e) = settings.infer_tf2_directory() {
-        tracing::error!("Failed to locate TF2 directory: {e}");
+    if let Some(steam_user) = overrides.steam_user {
+        builder = builder.with_steam_user(steam_user);
     }
-
-    // Playerlist
-    let mut playerlist = Records::load_or_create(Records::default_file_location(APP).unwrap_or_else(|e| {
-        tracing::error!("Failed to find a suitable location to store player records ({e}). Records will be written to {}", tf2_monitor_core::players::records::RECORDS_FILE_NAME);
-        tf2_monitor_core::players::records::RECORDS_FILE_NAME.into()
-    })).expect("Failed to load player records. Please fix any issues mentioned and try again.");
-    playerlist.save_ok();
-
-    let mut players = Players::new(
-        playerlist,
-        settings.steam_user,
-        Players::default_steam_cache_path(APP).ok(),
-    );
-
-    // Local friends
-    if let Some(user) = settings.steam_user {
-        match steam::find_steam_user_friends(user) {
-            Ok(friends) => players.update_friends_list(user, friends),
-            Err(e) => tracing::error!("Failed to check local player's friends: {e}"),
+    if overrides.offline {
+        builder = builder.offline();
+    }
+    if startup_profile {
+        if let Some(config) = &overrides.config {
+            builder = builder.with_settings_file(config);
+        }
+        if let Some(playerlist) = &overrides.playerlist {
+            builder = builder.with_records_file(playerlist);
         }
     }
+    builder
+}
 
-    let core = MonitorState {
-        server: Server::new(),
-        settings,
-        players,
-    };
+/// Loads the settings, records and steam cache of a profile
+///
+/// # Errors
+/// If the settings or records exist but could not be loaded
+fn load_profile(builder: MonitorBuilder) -> Result<(MonitorState, AppSettings), BuildError> {
+    let core = builder.load_state()?;
+    let app_settings = app_settings_from(&core.settings);
+    Ok((core, app_settings))
+}
 
-    let app_settings: AppSettings = core
-        .settings
+/// The app settings are stored with the core settings
+fn app_settings_from(settings: &Settings) -> AppSettings {
+    settings
         .external
         .get(SETTINGS_IDENTIFIER)
         .and_then(|v| serde_json::from_value(v.clone()).map_err(|e| {
             tracing::error!("Failed to deserialize app settings: {e}");
         }).ok())
-        .unwrap_or_default();
+        .unwrap_or_default()
+}
 
-    let event_loop = EventLoop::new()
-        .add_handler(CommandManager::new())
-        .add_handler(ConsoleParser::default())
-        .add_handler(ExtractNewPlayers)
-        .add_handler(LookupProfiles::new())
-        .add_handler(DemoManager::new())
-        .add_handler(LookupFriends::new());
+/// Starts TF2 through Steam, which also starts Steam if it isn't running
+fn launch_tf2() {
+    let url = format!("steam://rungameid/{TF2_GAME_ID}");
+    if let Err(e) = open::that(&url) {
+        tracing::error!("Failed to launch TF2 ({url}): {e:?}");
+    }
+}
+
+fn main() -> ExitCode {
+    let args = clap::Command::new("tf2_monitor")
+        .arg(
+            clap::Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("The settings profile to use, instead of the one used last time"),
+        )
+        .arg(
+            clap::Arg::new("portable")
+                .long("portable")
+                .action(clap::ArgAction::SetTrue)
+                .help(format!("Keep all config files in a `{PORTABLE_CONFIG_DIR_NAME}` folder next to the executable (also enabled by a `{PORTABLE_MARKER_FILE_NAME}` file there)")),
+        )
+        .arg(
+            clap::Arg::new("tf2-dir")
+                .long("tf2-dir")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("The Team Fortress 2 folder, instead of finding it through Steam"),
+        )
+        .arg(
+            clap::Arg::new("config")
+                .long("config")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("The settings file to use, instead of the one in the config folder"),
+        )
+        .arg(
+            clap::Arg::new("playerlist")
+                .long("playerlist")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("The player records file to use, instead of the one in the config folder"),
+        )
+        .arg(
+            clap::Arg::new("steam-user")
+                .long("steam-user")
+                .value_name("STEAMID")
+                .value_parser(|s: &str| SteamID::try_from(s.trim()).map_err(|_| "Please provide a valid SteamID"))
+                .help("The Steam account to use, instead of the one currently logged in"),
+        )
+        .arg(
+            clap::Arg::new("offline")
+                .long("offline")
+                .action(clap::ArgAction::SetTrue)
+                .help("Don't make any requests to the Steam API or the masterbase"),
+        )
+        .get_matches();
+
+    CLI_OVERRIDES
+        .set(CliOverrides {
+            tf2_dir: args.get_one::<PathBuf>("tf2-dir").cloned(),
+            config: args.get_one::<PathBuf>("config").cloned(),
+            playerlist: args.get_one::<PathBuf>("playerlist").cloned(),
+            steam_user: args.get_one::<SteamID>("steam-user").copied(),
+            offline: args.get_flag("offline"),
+        })
+        .expect("Command line overrides were used before they were parsed.");
+
+    // Logs go in the config directory, so portable mode has to be decided first
+    let portable = locate_portable_config_directory(args.get_flag("portable"));
+    let log_dir = Settings::locate_config_directory(AppDetails {
+        config_dir: portable.as_ref().ok().and_then(Option::as_deref),
+        ..APP_DETAILS
+    }).ok();
+    let (_guard, log_buffer, log_file) = tracing_setup::init_tracing(log_dir);
+
+    match run(&args, portable, log_buffer, log_file.clone()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Keep the error in the log file, unless it's unknown whether another
+            // instance is using it
+            if !matches!(e, StartupError::PortableConfig(_)) && !instance::is_read_only() {
+                if let Err(e) = log_file.start() {
+                    tracing::error!("Failed to create log file: {e}");
+                }
+            }
+            startup::report(&e, Settings::locate_config_directory(app()).ok());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(
+    args: &clap::ArgMatches,
+    portable: Result<Option<PathBuf>, ConfigFilesError>,
+    log_buffer: LogBuffer,
+    log_file: LogFile,
+) -> Result<(), StartupError> {
+    let portable = portable.map_err(StartupError::PortableConfig)?;
+    if let Some(dir) = &portable {
+        tracing::info!("Running in portable mode, config files are kept in {dir:?}");
+    }
+    PORTABLE_CONFIG_DIR
+        .set(portable)
+        .expect("Config directory was used before portable mode was decided.");
+
+    // Kept until the app closes
+    let _lock = match Settings::locate_config_directory(app()) {
+        Ok(dir) => match startup::lock_instance(&dir) {
+            ControlFlow::Continue(lock) => lock,
+            ControlFlow::Break(()) => return Ok(()),
+        },
+        Err(e) => {
+            tracing::error!("Couldn't check for other instances of the app: {e}");
+            None
+        }
+    };
+
+    let profile = args.get_one::<String>("profile").cloned().or_else(|| {
+        profiles::load_active_profile(app()).unwrap_or_else(|e| {
+            tracing::error!("Failed to read the last used profile: {e}");
+            None
+        })
+    }).filter(|p| p != DEFAULT_PROFILE_NAME);
+    if let Some(profile) = &profile {
+        profiles::validate_profile_name(profile).map_err(StartupError::Profile)?;
+    }
+    if let Err(e) = profiles::save_active_profile(app(), profile.as_deref()) {
+        tracing::error!("Failed to remember the active profile: {e}");
+    }
+
+    let (core, event_loop) = monitor_builder(app().with_profile(profile.as_deref()), true).build()?;
+    let app_settings = app_settings_from(&core.settings);
 
-    let mut iced_settings = iced::Settings::with_flags((core, event_loop, app_settings.clone()));
+    let mut iced_settings = iced::Settings::with_flags((core, event_loop, app_settings.clone(), profile, log_buffer, log_file));
     iced_settings.window.min_size = Some(iced::Size::new(800.0, 450.0));
+    // Closing may only hide the window in the tray, see `App::quit`
+    iced_settings.window.exit_on_close_request = false;
     iced_settings.fonts.push(FONT_FILE.into());
     // iced_settings.fonts.push(&FONT_FILE);
-    if let Some((x, y)) = app_settings.window_pos {
-        iced_settings.window.position = iced::window::Position::Specific(iced::Point::new(x as f32, y as f32));
-    }
     if let Some((width, height)) = app_settings.window_size {
         iced_settings.window.size = iced::Size::new(width as f32, height as f32);
     }
+    if let Some(pos) = app_settings.window_pos {
+        let size = (iced_settings.window.size.width as u32, iced_settings.window.size.height as u32);
+        let monitors: Vec<_> = display_info::DisplayInfo::all()
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to get monitors: {e}");
+                Vec::new()
+            })
+            .into_iter()
+            .map(|d| (d.x, d.y, d.width, d.height))
+            .collect();
 
-    App::run(iced_settings).expect("Failed to run app.");
-}
-
-impl std::fmt::Debug for MonitorMessage {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "MACMessage")
+        iced_settings.window.position = match clamp_window_position(pos, size, &monitors) {
+            Some((x, y)) => iced::window::Position::Specific(iced::Point::new(x as f32, y as f32)),
+            // Couldn't tell where the monitors are, so just trust the saved position
+            None if monitors.is_empty() => iced::window::Position::Specific(iced::Point::new(pos.0 as f32, pos.1 as f32)),
+            None => {
+                tracing::warn!("Saved window position is off-screen, centering the window instead.");
+                iced::window::Position::Centered
+            }
+        };
     }
+
+    App::run(iced_settings)?;
+    Ok(())
 }
 
 fn verify_masterbase_connection(settings: &Settings) -> iced::Command<Message> {
+    if settings.offline {
+        return iced::Command::none();
+    }
+
     let host = settings.masterbase_host.to_string();
     let key = settings.masterbase_key.to_string();
     let http = settings.masterbase_http;
@@ -850,27 +2551,39 @@ fn verify_masterbase_connection(settings: &Settings) -> iced::Command<Message> {
         async move {
             match masterbase::force_close_session(&host, &key, http).await {
                 // Successfully closed existing session
-                Ok(r) if r.status().is_success() => tracing::warn!(
-                    "User was previously in a Masterbase session that has now been closed."
-                ),
+                Ok(r) if r.status().is_success() => {
+                    tracing::warn!("User was previously in a Masterbase session that has now been closed.");
+                    UploadUpdate::Connected
+                },
                 // Server error
-                Ok(r) if r.status().is_server_error() => tracing::error!(
-                    "Server error when trying to close previous Masterbase sessions: Status code {}",
-                    r.status()
-                ),
+                Ok(r) if r.status().is_server_error() => {
+                    tracing::error!(
+                        "Server error when trying to close previous Masterbase sessions: Status code {}",
+                        r.status()
+                    );
+                    UploadUpdate::from_status("Couldn't close previous session", r.status())
+                },
                 // Not authorized, invalid key
                 Ok(r) if r.status() == StatusCode::UNAUTHORIZED => {
                     tracing::warn!("Your Masterbase key is not valid. Please provision a new one at https://megaanticheat.com/provision");
+                    UploadUpdate::from_status("Couldn't authenticate", r.status())
                 }
                 // Forbidden, no session was open
                 Ok(r) if r.status() == StatusCode::FORBIDDEN => {
                     tracing::info!("Successfully authenticated with the Masterbase.");
+                    UploadUpdate::Connected
                 }
                 // Remaining responses will be client failures
-                Ok(r) => tracing::info!("Client error when trying to contact masterbase: Status code {}", r.status()),
-                Err(e) => tracing::error!("Couldn't reach Masterbase: {e}"),
+                Ok(r) => {
+                    tracing::info!("Client error when trying to contact masterbase: Status code {}", r.status());
+                    UploadUpdate::from_status("Couldn't contact Masterbase", r.status())
+                },
+                Err(e) => {
+                    tracing::error!("Couldn't reach Masterbase: {e}");
+                    UploadUpdate::from_error("Couldn't reach Masterbase", &e)
+                },
             }
         },
-        |()| Message::None,
+        |update| Message::MAC(MonitorMessage::UploadUpdate(update)),
     )
 }
@@ -0,0 +1,446 @@
+//! Fetching profile pictures, which are kept on disk between launches. Steam
+//! names avatars by the hash of their contents, so a cached file never goes
+//! stale and is just looked up by that hash.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{Cursor, ErrorKind},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use bytes::Bytes;
+use iced::widget::image::Handle;
+use image::{imageops::FilterType, io::Reader, DynamicImage, EncodableLayout, ImageBuffer};
+use reqwest::StatusCode;
+use tf2_monitor_core::settings::{ConfigFilesError, Settings};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    app,
+    gui::{PFP_FULL_SIZE, PFP_SMALL_SIZE},
+};
+
+const PFP_CACHE_DIR: &str = "pfp_cache";
+/// Once the cache grows past this, the least recently used pictures are removed
+const MAX_CACHE_BYTES: u64 = 64 * 1024 * 1024;
+/// How far under the limit to shrink the cache, so it isn't pruned on every write
+const PRUNE_TO_BYTES: u64 = MAX_CACHE_BYTES / 4 * 3;
+
+/// Total size of the cache directory, counted the first time a picture is stored
+static CACHE_SIZE: Mutex<Option<u64>> = Mutex::const_new(None);
+
+/// How many pictures are fetched at once
+const MAX_CONCURRENT_FETCHES: usize = 4;
+/// Doubled after each failed attempt, up to [`MAX_RETRY_DELAY`]
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+/// After this many failed attempts, a picture is only tried again when it is
+/// next asked for
+const MAX_AUTO_RETRIES: u32 = 4;
+/// Pictures that don't exist aren't asked for again until this long after
+const NOT_FOUND_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum FetchError {
+    #[error("Picture doesn't exist")]
+    NotFound,
+    #[error("Couldn't download picture")]
+    Failed,
+}
+
+#[derive(Debug, Error)]
+pub enum PfpCacheError {
+    #[error("IO: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Config file: {0}")]
+    Config(#[from] ConfigFilesError),
+    #[error("Invalid pfp hash \"{0}\"")]
+    InvalidHash(String),
+}
+
+/// A profile picture decoded into the sizes it's shown at
+#[derive(Debug, Clone)]
+pub struct DecodedPfp {
+    pub full: Handle,
+    pub small: Handle,
+    /// The original image if it was just downloaded, to be saved to disk
+    pub to_store: Option<Bytes>,
+}
+
+impl DecodedPfp {
+    /// Images that can't be decoded are replaced with a blank one
+    #[must_use]
+    pub fn decode(bytes: Bytes, downloaded: bool) -> Self {
+        let decoded = Reader::new(Cursor::new(&bytes[..]))
+            .with_guessed_format()
+            .ok()
+            .and_then(|r| r.decode().ok());
+        // Don't keep e.g. error pages on disk
+        let to_store = (downloaded && decoded.is_some()).then_some(bytes);
+
+        let full_image = decoded
+            .unwrap_or_else(|| {
+                DynamicImage::ImageRgb8(ImageBuffer::new(
+                    u32::from(PFP_FULL_SIZE),
+                    u32::from(PFP_FULL_SIZE),
+                ))
+            })
+            .resize(
+                u32::from(PFP_FULL_SIZE),
+                u32::from(PFP_FULL_SIZE),
+                FilterType::Triangle,
+            );
+        let small_image = full_image.resize(
+            u32::from(PFP_SMALL_SIZE),
+            u32::from(PFP_SMALL_SIZE),
+            FilterType::Triangle,
+        );
+
+        Self {
+            full: Handle::from_pixels(
+                u32::from(PFP_FULL_SIZE),
+                u32::from(PFP_FULL_SIZE),
+                Bytes::copy_from_slice(full_image.into_rgba8().as_bytes()),
+            ),
+            small: Handle::from_pixels(
+                u32::from(PFP_SMALL_SIZE),
+                u32::from(PFP_SMALL_SIZE),
+                Bytes::copy_from_slice(small_image.into_rgba8().as_bytes()),
+            ),
+            to_store,
+        }
+    }
+}
+
+/// Loads the picture from disk, or downloads it if it hasn't been seen before,
+/// and decodes it. Decoding and resizing is slow enough to hitch the UI when a
+/// whole server's worth of pictures arrive at once, so it's done on a
+/// blocking thread.
+///
+/// # Errors
+/// If the picture couldn't be downloaded
+pub async fn fetch(pfp_hash: String, pfp_url: String) -> Result<DecodedPfp, FetchError> {
+    let (bytes, downloaded) = match load(&pfp_hash).await {
+        Some(bytes) => (bytes, false),
+        None => (download(&pfp_url).await?, true),
+    };
+
+    tokio::task::spawn_blocking(move || DecodedPfp::decode(bytes, downloaded))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to decode pfp {pfp_hash}: {e}");
+            FetchError::Failed
+        })
+}
+
+async fn download(pfp_url: &str) -> Result<Bytes, FetchError> {
+    let response = reqwest::get(pfp_url)
+        .await
+        .map_err(|_| FetchError::Failed)?;
+
+    match response.status() {
+        StatusCode::NOT_FOUND | StatusCode::GONE => Err(FetchError::NotFound),
+        s if !s.is_success() => Err(FetchError::Failed),
+        _ => response.bytes().await.map_err(|_| FetchError::Failed),
+    }
+}
+
+/// Keeps track of which pictures are being fetched so each is only fetched
+/// once, and only a few at a time. Failed pictures are retried with backoff.
+#[derive(Default)]
+pub struct PfpQueue {
+    /// Pictures that are waiting or being fetched
+    requested: HashSet<String>,
+    waiting: VecDeque<(String, String)>,
+    /// Hash and url of the pictures being fetched
+    fetching: HashMap<String, String>,
+    failed: HashMap<String, Failure>,
+}
+
+struct Failure {
+    url: String,
+    attempts: u32,
+    retry_at: Instant,
+    /// Whether to try again by itself once `retry_at` has passed, or wait
+    /// until it is asked for again
+    auto_retry: bool,
+}
+
+impl PfpQueue {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a picture to be fetched, unless it already is or it failed too
+    /// recently to try again.
+    pub fn request(&mut self, pfp_hash: &str, pfp_url: &str) {
+        if self.requested.contains(pfp_hash)
+            || self
+                .failed
+                .get(pfp_hash)
+                .is_some_and(|f| f.retry_at > Instant::now())
+        {
+            return;
+        }
+
+        self.requested.insert(pfp_hash.to_owned());
+        self.waiting
+            .push_back((pfp_hash.to_owned(), pfp_url.to_owned()));
+    }
+
+    /// Take as many of the waiting pictures as can be fetched right now,
+    /// as `(hash, url)`
+    pub fn next_fetches(&mut self) -> Vec<(String, String)> {
+        let count = MAX_CONCURRENT_FETCHES
+            .saturating_sub(self.fetching.len())
+            .min(self.waiting.len());
+
+        let next: Vec<(String, String)> = self.waiting.drain(..count).collect();
+        self.fetching.extend(next.iter().cloned());
+        next
+    }
+
+    /// Stop tracking a fetched picture, or remember when to try it again if
+    /// it failed
+    pub fn finished(&mut self, pfp_hash: &str, error: Option<FetchError>) {
+        let Some(url) = self.fetching.remove(pfp_hash) else {
+            return;
+        };
+        self.requested.remove(pfp_hash);
+
+        let Some(e) = error else {
+            self.failed.remove(pfp_hash);
+            return;
+        };
+
+        let attempts = self.failed.get(pfp_hash).map_or(0, |f| f.attempts) + 1;
+        let (delay, auto_retry) = match e {
+            FetchError::NotFound => (NOT_FOUND_EXPIRY, false),
+            FetchError::Failed => (retry_delay(attempts), attempts <= MAX_AUTO_RETRIES),
+        };
+        tracing::debug!(
+            "Failed to fetch pfp {pfp_hash} ({e}), trying again in {}s",
+            delay.as_secs()
+        );
+
+        self.failed.insert(
+            pfp_hash.to_owned(),
+            Failure {
+                url,
+                attempts,
+                retry_at: Instant::now() + delay,
+                auto_retry,
+            },
+        );
+    }
+
+    /// Queue the failed pictures that are due to be tried again
+    pub fn retry_due(&mut self) {
+        let now = Instant::now();
+        for (pfp_hash, failure) in &mut self.failed {
+            if !failure.auto_retry || failure.retry_at > now || self.requested.contains(pfp_hash) {
+                continue;
+            }
+
+            failure.auto_retry = false;
+            self.requested.insert(pfp_hash.clone());
+            self.waiting
+                .push_back((pfp_hash.clone(), failure.url.clone()));
+        }
+    }
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// The cached picture, if there is one. Reading it marks it as recently used.
+pub async fn load(pfp_hash: &str) -> Option<Bytes> {
+    let path = match pfp_path(pfp_hash) {
+        Ok(path) => path,
+        // e.g. players without steam info
+        Err(PfpCacheError::InvalidHash(_)) => return None,
+        Err(e) => {
+            tracing::warn!("Couldn't look up cached pfp: {e}");
+            return None;
+        }
+    };
+
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => {
+            if let Err(e) = touch(path).await {
+                tracing::warn!("Couldn't update cached pfp {pfp_hash}: {e}");
+            }
+            Some(Bytes::from(bytes))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => {
+            tracing::warn!("Couldn't read cached pfp {pfp_hash}: {e}");
+            None
+        }
+    }
+}
+
+/// Write the picture to the cache if it isn't already there, removing the
+/// least recently used ones if the cache has grown too big.
+///
+/// # Errors
+/// If the cache directory couldn't be found or the file couldn't be written
+pub async fn store(pfp_hash: String, bytes: Bytes) -> Result<(), PfpCacheError> {
+    let path = pfp_path(&pfp_hash)?;
+    if tokio::fs::try_exists(&path).await? {
+        return Ok(());
+    }
+
+    let dir = cache_dir()?;
+    tokio::fs::create_dir_all(&dir).await?;
+    tokio::fs::write(&path, &bytes).await?;
+
+    let mut size = CACHE_SIZE.lock().await;
+    let total = match *size {
+        Some(total) => total + bytes.len() as u64,
+        None => cache_entries(&dir).await?.iter().map(|e| e.size).sum(),
+    };
+
+    *size = Some(if total > MAX_CACHE_BYTES {
+        prune(&dir, total).await?
+    } else {
+        total
+    });
+
+    Ok(())
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    used: SystemTime,
+}
+
+async fn cache_entries(dir: &Path) -> std::io::Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+
+        entries.push(CacheEntry {
+            path: entry.path(),
+            size: metadata.len(),
+            used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Removes the least recently used pictures until the cache is back under
+/// [`PRUNE_TO_BYTES`], returning the new size.
+async fn prune(dir: &Path, mut total: u64) -> std::io::Result<u64> {
+    let mut entries = cache_entries(dir).await?;
+    entries.sort_by_key(|e| e.used);
+
+    let mut removed = 0;
+    for entry in entries {
+        if total <= PRUNE_TO_BYTES {
+            break;
+        }
+
+        match tokio::fs::remove_file(&entry.path).await {
+            Ok(()) => {
+                total = total.saturating_sub(entry.size);
+                removed += 1;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                total = total.saturating_sub(entry.size);
+            }
+            Err(e) => tracing::warn!("Couldn't remove cached pfp {:?}: {e}", entry.path),
+        }
+    }
+
+    tracing::debug!("Removed {removed} old pfps from the cache");
+    Ok(total)
+}
+
+/// Mark a cached picture as just used, as its modified time is what decides
+/// which pictures are removed first.
+async fn touch(path: PathBuf) -> std::io::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        std::fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(SystemTime::now())
+    })
+    .await?
+}
+
+fn cache_dir() -> Result<PathBuf, ConfigFilesError> {
+    Ok(Settings::locate_config_directory(app())?.join(PFP_CACHE_DIR))
+}
+
+fn pfp_path(pfp_hash: &str) -> Result<PathBuf, PfpCacheError> {
+    if !is_valid_hash(pfp_hash) {
+        return Err(PfpCacheError::InvalidHash(pfp_hash.to_owned()));
+    }
+
+    Ok(cache_dir()?.join(pfp_hash))
+}
+
+/// The hash is used as the file name, so it can't be allowed to point anywhere
+/// else
+fn is_valid_hash(pfp_hash: &str) -> bool {
+    !pfp_hash.is_empty()
+        && pfp_hash.len() <= 64
+        && pfp_hash.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_valid_hash, FetchError, PfpQueue, MAX_CONCURRENT_FETCHES};
+
+    #[test]
+    fn queue_limits_and_retries_fetches() {
+        let mut queue = PfpQueue::new();
+        for i in 0..MAX_CONCURRENT_FETCHES + 2 {
+            queue.request(&format!("{i}"), "url");
+        }
+        queue.request("0", "url");
+
+        let first = queue.next_fetches();
+        assert_eq!(first.len(), MAX_CONCURRENT_FETCHES);
+        assert!(queue.next_fetches().is_empty());
+
+        queue.finished(&first[0].0, None);
+        queue.finished(&first[1].0, Some(FetchError::NotFound));
+        queue.finished(&first[2].0, Some(FetchError::Failed));
+        assert_eq!(queue.next_fetches().len(), 2);
+
+        // Neither failure is due to be tried again yet
+        queue.request(&first[1].0, "url");
+        queue.retry_due();
+        assert!(queue.next_fetches().is_empty());
+
+        // Successful ones can be fetched again, e.g. after their steam info
+        // was refreshed
+        queue.request(&first[0].0, "url");
+        assert_eq!(queue.next_fetches().len(), 1);
+    }
+
+    #[test]
+    fn hashes_are_safe_file_names() {
+        assert!(is_valid_hash("fef49e7fa7e1997310d705b2a6158ff8dc1cdfeb"));
+        assert!(!is_valid_hash(""));
+        assert!(!is_valid_hash("../settings"));
+        assert!(!is_valid_hash("abc/def"));
+        assert!(!is_valid_hash("C:\\abc"));
+    }
+}
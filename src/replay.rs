@@ -1,7 +1,11 @@
-use std::{io::Cursor, path::PathBuf};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
 use anyhow::{anyhow, Context, Result};
-use chrono::{Datelike, Timelike};
+use chrono::{DateTime, Datelike, Local, Timelike};
 use filenamify::filenamify;
 use iced::widget;
 use image::{io::Reader, DynamicImage, GenericImage, GenericImageView, ImageFormat};
@@ -12,7 +16,17 @@ use tf2_monitor_core::{
 };
 
 use crate::gui::replay::view;
-use crate::{App, IcedElement, Message};
+use crate::{
+    demos::{self, MaybeAnalysedDemo},
+    settings::AppSettings,
+    App, IcedElement, Message,
+};
+use library::ExistingReplay;
+use template::NameContext;
+
+pub mod dmx;
+pub mod library;
+pub mod template;
 
 const DEFAULT_THUMBNAIL: &[u8] = include_bytes!("default.png");
 
@@ -21,6 +35,10 @@ const TEMPLATE_VMT: &str = include_str!("template_vmt.txt");
 const DIR_THUMBNAIL: &str = "tf/materials/vgui/replay/thumbnails";
 const DIR_REPLAY: &str = "tf/replay/client/replays";
 const DEMO_PATH: &str = "tf/demos";
+const DIR_SCREENSHOTS: &str = "tf/screenshots";
+/// Screenshots taken this long either side of the demo are still considered
+/// to be from it
+const SCREENSHOT_MARGIN: Duration = Duration::from_secs(60);
 
 const SUB_NAME: &str = "%replay_name%";
 const SUB_MAP: &str = "%map%";
@@ -42,17 +60,45 @@ pub struct ReplayState {
     pub replay_name: String,
     pub thumbnail: DynamicImage,
     pub thumbnail_handle: widget::image::Handle,
+
+    /// Replays already in the TF2 directory
+    pub existing: Vec<ExistingReplay>,
+    pub existing_status: String,
+    /// The replay being renamed and the title being typed in for it
+    pub renaming: Option<(PathBuf, String)>,
+    /// The replay waiting for the user to confirm it should be deleted
+    pub confirm_delete: Option<PathBuf>,
+    /// A replay with the same name already exists, waiting for the user to
+    /// confirm it should be replaced
+    pub confirm_overwrite: bool,
 }
 
+/// Creating the replay would replace the files of an existing one
+#[derive(Debug, thiserror::Error)]
+#[error("A replay called \"{0}\" already exists")]
+pub struct ReplayExists(pub String);
+
 #[derive(Debug, Clone)]
 #[allow(clippy::module_name_repetitions)]
 pub enum ReplayMessage {
     SetDemoPath(PathBuf),
     BrowseDemoPath,
     BrowseThumbnailPath,
+    GenerateThumbnail,
     ClearThumbnail,
     CreateReplay,
+    ConfirmOverwrite,
+    CancelOverwrite,
     SetReplayName(String),
+
+    RefreshExisting,
+    StartRename(PathBuf),
+    SetRenameTitle(String),
+    ConfirmRename,
+    CancelRename,
+    DeleteReplay(PathBuf),
+    ConfirmDelete,
+    CancelDelete,
 }
 
 impl ReplayState {
@@ -74,6 +120,12 @@ impl ReplayState {
             thumbnail,
             thumbnail_handle,
             status: String::new(),
+
+            existing: Vec::new(),
+            existing_status: String::new(),
+            renaming: None,
+            confirm_delete: None,
+            confirm_overwrite: false,
         };
 
         state
@@ -86,6 +138,8 @@ impl ReplayState {
         &mut self,
         message: ReplayMessage,
         mac: &MonitorState,
+        settings: &AppSettings,
+        demos: &demos::State,
     ) -> iced::Command<Message> {
         match message {
             ReplayMessage::BrowseThumbnailPath => {
@@ -95,6 +149,7 @@ impl ReplayState {
                     }
                 };
             }
+            ReplayMessage::GenerateThumbnail => self.generate_thumbnail(mac),
             ReplayMessage::BrowseDemoPath => {
                 let mut picker = rfd::FileDialog::new();
                 if let Some(tf2_dir) = &mac.settings.tf2_directory {
@@ -103,6 +158,7 @@ impl ReplayState {
 
                 if let Some(new_demo_path) = picker.pick_file() {
                     self.set_demo_path(new_demo_path);
+                    self.apply_name_template(mac, settings, demos);
                 };
             }
             ReplayMessage::ClearThumbnail => {
@@ -110,15 +166,69 @@ impl ReplayState {
                     self.status = format!("Failed to set thumbnail: {e:?}");
                 }
             }
-            ReplayMessage::CreateReplay => {
-                if let Err(e) = self.create_replay(mac) {
-                    self.status = format!("Error creating replay: {e}");
+            ReplayMessage::CreateReplay | ReplayMessage::ConfirmOverwrite => {
+                let overwrite = matches!(message, ReplayMessage::ConfirmOverwrite);
+                self.confirm_overwrite = false;
+                match self.create_replay(mac, overwrite) {
+                    Ok(()) => {
+                        self.status = String::from("Successfully created replay!");
+                        self.refresh_existing(mac);
+                    }
+                    Err(e) if e.downcast_ref::<ReplayExists>().is_some() => {
+                        self.status = format!("{e}, do you want to replace it?");
+                        self.confirm_overwrite = true;
+                    }
+                    Err(e) => self.status = format!("Error creating replay: {e:#}"),
+                }
+            }
+            ReplayMessage::CancelOverwrite => {
+                self.confirm_overwrite = false;
+                self.status = String::new();
+            }
+            ReplayMessage::SetReplayName(name) => {
+                self.replay_name = name;
+                self.confirm_overwrite = false;
+            }
+            ReplayMessage::SetDemoPath(demo_path) => {
+                self.set_demo_path(demo_path);
+                self.apply_name_template(mac, settings, demos);
+            }
+
+            ReplayMessage::RefreshExisting => self.refresh_existing(mac),
+            ReplayMessage::StartRename(dmx_path) => {
+                let title = self
+                    .existing
+                    .iter()
+                    .find(|r| r.dmx_path == dmx_path)
+                    .map(|r| r.title.clone())
+                    .unwrap_or_default();
+                self.renaming = Some((dmx_path, title));
+            }
+            ReplayMessage::SetRenameTitle(title) => {
+                if let Some((_, t)) = &mut self.renaming {
+                    *t = title;
+                }
+            }
+            ReplayMessage::ConfirmRename => {
+                if let Err(e) = self.rename_existing(mac) {
+                    self.existing_status = format!("Error renaming replay: {e}");
+                } else {
+                    self.renaming = None;
+                    self.existing_status = String::from("Renamed replay.");
+                    self.existing.sort_by_cached_key(|r| r.title.to_lowercase());
+                }
+            }
+            ReplayMessage::CancelRename => self.renaming = None,
+            ReplayMessage::DeleteReplay(dmx_path) => self.confirm_delete = Some(dmx_path),
+            ReplayMessage::ConfirmDelete => {
+                if let Err(e) = self.delete_existing(mac) {
+                    self.existing_status = format!("Error deleting replay: {e}");
                 } else {
-                    self.status = String::from("Successfully created replay!");
+                    self.existing_status = String::from("Deleted replay.");
                 }
+                self.confirm_delete = None;
             }
-            ReplayMessage::SetReplayName(name) => self.replay_name = name,
-            ReplayMessage::SetDemoPath(demo_path) => self.set_demo_path(demo_path),
+            ReplayMessage::CancelDelete => self.confirm_delete = None,
         }
 
         iced::Command::none()
@@ -128,8 +238,62 @@ impl ReplayState {
         view(state)
     }
 
+    /// Reload the list of replays already in the TF2 directory
+    pub fn refresh_existing(&mut self, mac: &MonitorState) {
+        let Some(tf2_dir) = &mac.settings.tf2_directory else {
+            self.existing.clear();
+            self.existing_status = String::from("No TF2 directory set");
+            return;
+        };
+
+        match library::list_replays(tf2_dir) {
+            Ok(replays) => {
+                self.existing = replays;
+                self.existing_status = String::new();
+            }
+            Err(e) => {
+                self.existing.clear();
+                self.existing_status = format!("Couldn't load replays: {e}");
+            }
+        }
+
+        self.renaming = None;
+        self.confirm_delete = None;
+    }
+
+    fn rename_existing(&mut self, mac: &MonitorState) -> Result<()> {
+        let Some(tf2_dir) = &mac.settings.tf2_directory else {
+            return Err(anyhow!("No TF2 directory set"));
+        };
+        let Some((dmx_path, title)) = &self.renaming else {
+            return Ok(());
+        };
+        let Some(replay) = self.existing.iter_mut().find(|r| &r.dmx_path == dmx_path) else {
+            return Err(anyhow!("Replay no longer exists"));
+        };
+
+        replay.rename(tf2_dir, title.trim())
+    }
+
+    fn delete_existing(&mut self, mac: &MonitorState) -> Result<()> {
+        let Some(tf2_dir) = &mac.settings.tf2_directory else {
+            return Err(anyhow!("No TF2 directory set"));
+        };
+        let Some(dmx_path) = &self.confirm_delete else {
+            return Ok(());
+        };
+        let Some(index) = self.existing.iter().position(|r| &r.dmx_path == dmx_path) else {
+            return Err(anyhow!("Replay no longer exists"));
+        };
+
+        self.existing[index].delete(tf2_dir)?;
+        self.existing.remove(index);
+        Ok(())
+    }
+
     pub fn set_demo_path(&mut self, path: PathBuf) {
         self.demo_path = Some(path);
+        self.confirm_overwrite = false;
 
         let Some(demo_path) = &self.demo_path else {
             return;
@@ -154,22 +318,94 @@ impl ReplayState {
             }
         };
 
-        let datetime = chrono::offset::Local::now();
-        self.replay_name = format!(
-            "{}-{}-{} {}:{} - {} on {}",
-            datetime.year(),
-            datetime.month(),
-            datetime.day(),
-            datetime.hour(),
-            datetime.minute(),
-            &header.nick,
-            &header.map,
-        );
-
         self.demo = Ok(header);
         self.status = String::new();
     }
 
+    /// Name the replay using the template from the settings, drawing on the
+    /// analysed demo if it has been analysed. The name can still be edited
+    /// afterwards.
+    pub fn apply_name_template(
+        &mut self,
+        mac: &MonitorState,
+        settings: &AppSettings,
+        demos: &demos::State,
+    ) {
+        let (Ok(header), Some(demo_path)) = (&self.demo, &self.demo_path) else {
+            return;
+        };
+
+        let analysed = demos
+            .demo_files
+            .iter()
+            .find(|d| &d.path == demo_path)
+            .and_then(|d| demos.analysed_demos.get(&d.analysed))
+            .and_then(|a| match a {
+                MaybeAnalysedDemo::Analysed(a) => Some(a.as_ref()),
+                _ => None,
+            });
+        let recorded = std::fs::metadata(demo_path)
+            .and_then(|m| m.modified())
+            .map_or_else(|_| Local::now(), DateTime::<Local>::from);
+        let demo_name = demo_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        self.replay_name = template::render(
+            &settings.replay_name_template,
+            &NameContext {
+                header,
+                analysed,
+                demo_name: &demo_name,
+                recorded,
+            },
+        );
+
+        let file_name = filenamify(&self.replay_name);
+        if file_name.trim().is_empty() {
+            tracing::warn!(
+                "Replay name template {:?} gave an empty name",
+                settings.replay_name_template
+            );
+            self.status = String::from("The name template gave an empty name, please enter one");
+        } else if mac
+            .settings
+            .tf2_directory
+            .as_ref()
+            .is_some_and(|d| !existing_replay_files(d, &file_name).is_empty())
+        {
+            tracing::warn!("Replay name template gave {file_name:?}, which already exists");
+            self.status = format!(
+                "A replay called \"{file_name}\" already exists, change the name to avoid replacing it"
+            );
+        }
+    }
+
+    /// Use the last screenshot that was taken while the demo was being
+    /// recorded as the thumbnail, keeping the current one if there aren't any.
+    pub fn generate_thumbnail(&mut self, mac: &MonitorState) {
+        let (Ok(header), Some(demo_path)) = (&self.demo, &self.demo_path) else {
+            self.status = String::from("Choose a demo first");
+            return;
+        };
+        let Some(tf2_dir) = &mac.settings.tf2_directory else {
+            self.status = String::from("No TF2 directory set");
+            return;
+        };
+
+        let Some(screenshot) = find_demo_screenshot(tf2_dir, demo_path, header.duration) else {
+            self.status = String::from("No screenshots were taken during this demo");
+            return;
+        };
+
+        if let Err(e) = self.load_thumbnail(Some(screenshot)) {
+            self.status = format!("Failed to set thumbnail: {e:?}");
+        } else {
+            self.status = String::new();
+        }
+    }
+
     #[allow(clippy::missing_errors_doc)]
     pub fn load_thumbnail(&mut self, new_thumbnail_path: Option<PathBuf>) -> Result<()> {
         let thumbnail_bytes = new_thumbnail_path.as_ref().map_or_else(
@@ -207,9 +443,9 @@ impl ReplayState {
     ///
     /// # Errors
     /// If not all the required fields are present, or some IO error prevented file writeback.
-    ///
-    /// This function will return an error if .
-    pub fn create_replay(&self, mac: &MonitorState) -> Result<()> {
+    /// Returns [`ReplayExists`] if a replay with the same name is already
+    /// there and `overwrite` isn't set.
+    pub fn create_replay(&self, mac: &MonitorState, overwrite: bool) -> Result<()> {
         let Ok(header) = &self.demo else {
             return Err(anyhow!("No valid demo"));
         };
@@ -225,6 +461,12 @@ impl ReplayState {
             return Err(anyhow!("Replay name is not valid"));
         }
 
+        prepare_replay_dirs(tf2_dir)?;
+        if !overwrite && !existing_replay_files(tf2_dir, &file_name).is_empty() {
+            return Err(ReplayExists(file_name).into());
+        }
+        let [dmx_path, dem_path, vmt_path, vtf_path] = replay_files(tf2_dir, &file_name);
+
         let handle = &mut std::fs::read_dir(tf2_dir.join(DIR_REPLAY))
             .context("Reading replay folder")?
             .filter_map(std::result::Result::ok)
@@ -254,40 +496,141 @@ impl ReplayState {
         dmx_contents = dmx_contents.replace(SUB_TIME, &format!("{time}"));
         dmx_contents = dmx_contents.replace(SUB_HANDLE, &format!("{handle}"));
 
-        std::fs::write(
-            tf2_dir.join(DIR_REPLAY).join(format!("{file_name}.dmx")),
-            dmx_contents,
-        )
-        .context("Writing demo DMX")?;
+        std::fs::write(dmx_path, dmx_contents).context("Writing demo DMX")?;
 
-        std::fs::copy(
-            demo_path,
-            tf2_dir.join(DIR_REPLAY).join(format!("{file_name}.dem")),
-        )
-        .context("Copying demo file")?;
+        std::fs::copy(demo_path, dem_path).context("Copying demo file")?;
 
         // Write thumbnail stuff
         let mut thumbnail_vmt = String::from(TEMPLATE_VMT);
         thumbnail_vmt = thumbnail_vmt.replace(SUB_SCREENSHOT, &file_name);
 
-        std::fs::write(
-            tf2_dir.join(DIR_THUMBNAIL).join(format!("{file_name}.vmt")),
-            thumbnail_vmt,
-        )
-        .context("Writing thumbnail VMT")?;
+        std::fs::write(vmt_path, thumbnail_vmt).context("Writing thumbnail VMT")?;
 
-        std::fs::write(
-            tf2_dir.join(DIR_THUMBNAIL).join(format!("{file_name}.vtf")),
-            vtf,
-        )
-        .context("Writing thumbnail VTF")?;
+        std::fs::write(vtf_path, vtf).context("Writing thumbnail VTF")?;
 
         Ok(())
     }
 }
 
+/// The most recent screenshot in the TF2 screenshots folder that was taken
+/// while the demo was being recorded. Demos are written to until they end, so
+/// recording is assumed to have started `duration` seconds before the demo
+/// was last modified.
+fn find_demo_screenshot(tf2_dir: &Path, demo_path: &Path, duration: f32) -> Option<PathBuf> {
+    let demo_end = std::fs::metadata(demo_path).ok()?.modified().ok()?;
+    let demo_start =
+        demo_end.checked_sub(Duration::try_from_secs_f32(duration).unwrap_or_default())?;
+    let earliest = demo_start.checked_sub(SCREENSHOT_MARGIN)?;
+    let latest = demo_end + SCREENSHOT_MARGIN;
+
+    std::fs::read_dir(tf2_dir.join(DIR_SCREENSHOTS))
+        .ok()?
+        .filter_map(std::result::Result::ok)
+        .map(|d| d.path())
+        .filter(|p| {
+            p.extension()
+                .and_then(ImageFormat::from_extension)
+                .is_some()
+        })
+        .filter_map(|p| {
+            let taken: SystemTime = std::fs::metadata(&p).ok()?.modified().ok()?;
+            (earliest..=latest).contains(&taken).then_some((taken, p))
+        })
+        .max_by_key(|(taken, _)| *taken)
+        .map(|(_, p)| p)
+}
+
+/// Make sure the folders replays are written to exist, which they don't on
+/// an install that has never used the replay system.
+///
+/// # Errors
+/// If the directory isn't a TF2 install, or the folders couldn't be created
+pub fn prepare_replay_dirs(tf2_dir: &Path) -> Result<()> {
+    if !tf2_dir.join("tf").is_dir() {
+        return Err(anyhow!(
+            "{} doesn't look like a TF2 directory (it has no \"tf\" folder)",
+            tf2_dir.display()
+        ));
+    }
+
+    for dir in [DIR_REPLAY, DIR_THUMBNAIL] {
+        std::fs::create_dir_all(tf2_dir.join(dir)).with_context(|| format!("Creating {dir}"))?;
+    }
+
+    Ok(())
+}
+
+/// The DMX, demo copy, and thumbnail VMT and VTF of a replay
+#[must_use]
+pub fn replay_files(tf2_dir: &Path, file_name: &str) -> [PathBuf; 4] {
+    [
+        tf2_dir.join(DIR_REPLAY).join(format!("{file_name}.dmx")),
+        tf2_dir.join(DIR_REPLAY).join(format!("{file_name}.dem")),
+        tf2_dir.join(DIR_THUMBNAIL).join(format!("{file_name}.vmt")),
+        tf2_dir.join(DIR_THUMBNAIL).join(format!("{file_name}.vtf")),
+    ]
+}
+
+/// Files of a replay with this name that are already there
+#[must_use]
+pub fn existing_replay_files(tf2_dir: &Path, file_name: &str) -> Vec<PathBuf> {
+    replay_files(tf2_dir, file_name)
+        .into_iter()
+        .filter(|p| p.exists())
+        .collect()
+}
+
 impl Default for ReplayState {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{existing_replay_files, prepare_replay_dirs, DIR_REPLAY, DIR_THUMBNAIL};
+
+    fn temp_tf2_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tf2_monitor_replay_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+        dir
+    }
+
+    #[test]
+    fn creates_missing_replay_dirs() {
+        let tf2_dir = temp_tf2_dir("fresh");
+        assert!(prepare_replay_dirs(&tf2_dir).is_err());
+
+        std::fs::create_dir(tf2_dir.join("tf")).expect("Failed to create tf dir");
+        prepare_replay_dirs(&tf2_dir).expect("Failed to create replay dirs");
+        assert!(tf2_dir.join(DIR_REPLAY).is_dir());
+        assert!(tf2_dir.join(DIR_THUMBNAIL).is_dir());
+
+        // Fine to call again once they exist
+        prepare_replay_dirs(&tf2_dir).expect("Failed on existing replay dirs");
+
+        std::fs::remove_dir_all(&tf2_dir).ok();
+    }
+
+    #[test]
+    fn finds_existing_replay_files() {
+        let tf2_dir = temp_tf2_dir("existing");
+        std::fs::create_dir(tf2_dir.join("tf")).expect("Failed to create tf dir");
+        prepare_replay_dirs(&tf2_dir).expect("Failed to create replay dirs");
+        assert!(existing_replay_files(&tf2_dir, "my replay").is_empty());
+
+        std::fs::write(tf2_dir.join(DIR_THUMBNAIL).join("my replay.vtf"), [])
+            .expect("Failed to write thumbnail");
+        assert_eq!(
+            existing_replay_files(&tf2_dir, "my replay"),
+            vec![tf2_dir.join(DIR_THUMBNAIL).join("my replay.vtf")]
+        );
+        assert!(existing_replay_files(&tf2_dir, "other replay").is_empty());
+
+        std::fs::remove_dir_all(&tf2_dir).ok();
+    }
+}
@@ -0,0 +1,267 @@
+//! Reading and writing the KeyValues text that replay `.dmx` files are made
+//! of. The game's own files can have comments, unquoted tokens and
+//! conditionals, so parsing is lenient about all of those.
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Block(Vec<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub key: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Text(String),
+    Open,
+    Close,
+}
+
+impl Node {
+    /// Parse the first top-level key and its block
+    ///
+    /// # Errors
+    /// If the contents aren't valid `KeyValues`
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut tokens = tokenize(contents)?.into_iter();
+        let Some(Token::Text(key)) = tokens.next() else {
+            return Err(anyhow!("Expected a key at the start of the file"));
+        };
+        let value = parse_value(&mut tokens)?;
+        Ok(Self { key, value })
+    }
+
+    /// Serialize in the same layout the game writes
+    #[must_use]
+    pub fn write(&self) -> String {
+        let mut out = String::new();
+        write_node(self, 0, &mut out);
+        out
+    }
+
+    /// Child with the given key, ignoring case like the game does
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match &self.value {
+            Value::Block(children) => children.iter().find(|c| c.key.eq_ignore_ascii_case(key)),
+            Value::String(_) => None,
+        }
+    }
+
+    #[must_use]
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match &self.get(key)?.value {
+            Value::String(s) => Some(s),
+            Value::Block(_) => None,
+        }
+    }
+
+    /// Set the string value of a child, adding it if it isn't there
+    pub fn set_str(&mut self, key: &str, value: &str) {
+        let Value::Block(children) = &mut self.value else {
+            return;
+        };
+
+        if let Some(child) = children
+            .iter_mut()
+            .find(|c| c.key.eq_ignore_ascii_case(key))
+        {
+            child.value = Value::String(value.to_owned());
+        } else {
+            children.push(Self {
+                key: key.to_owned(),
+                value: Value::String(value.to_owned()),
+            });
+        }
+    }
+
+    /// All the blocks nested anywhere below this one with the given key
+    pub fn find_blocks_mut<'a>(&'a mut self, key: &str, found: &mut Vec<&'a mut Self>) {
+        let Value::Block(children) = &mut self.value else {
+            return;
+        };
+
+        for child in children {
+            if child.key.eq_ignore_ascii_case(key) && matches!(child.value, Value::Block(_)) {
+                found.push(child);
+            } else {
+                child.find_blocks_mut(key, found);
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn find_blocks(&self, key: &str) -> Vec<&Self> {
+        let mut found = Vec::new();
+        let Value::Block(children) = &self.value else {
+            return found;
+        };
+
+        for child in children {
+            if child.key.eq_ignore_ascii_case(key) && matches!(child.value, Value::Block(_)) {
+                found.push(child);
+            } else {
+                found.extend(child.find_blocks(key));
+            }
+        }
+        found
+    }
+}
+
+fn tokenize(contents: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                            text.extend(chars.next());
+                        }
+                        Some(c) => text.push(c),
+                        None => return Err(anyhow!("Unterminated string")),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            // Conditionals like [$WIN32] apply to the previous value, just ignore them
+            '[' => {
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            c => {
+                let mut text = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '{' | '}' | '"') {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Text(text));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_value(tokens: &mut std::vec::IntoIter<Token>) -> Result<Value> {
+    match tokens.next() {
+        Some(Token::Text(s)) => Ok(Value::String(s)),
+        Some(Token::Open) => {
+            let mut children = Vec::new();
+            loop {
+                match tokens.next() {
+                    Some(Token::Close) => break,
+                    Some(Token::Text(key)) => {
+                        let value = parse_value(tokens)?;
+                        children.push(Node { key, value });
+                    }
+                    Some(Token::Open) => return Err(anyhow!("Expected a key but found '{{'")),
+                    // Tolerate files that were cut off
+                    None => break,
+                }
+            }
+            Ok(Value::Block(children))
+        }
+        Some(Token::Close) => Err(anyhow!("Expected a value but found '}}'")),
+        None => Err(anyhow!("Expected a value but reached the end of the file")),
+    }
+}
+
+fn write_node(node: &Node, depth: usize, out: &mut String) {
+    let indent = "\t".repeat(depth);
+    match &node.value {
+        Value::String(s) => {
+            let _ = writeln!(
+                out,
+                "{indent}\"{}\"\t\t\"{}\"",
+                escape(&node.key),
+                escape(s)
+            );
+        }
+        Value::Block(children) => {
+            let _ = writeln!(out, "{indent}\"{}\"\n{indent}{{", escape(&node.key));
+            for child in children {
+                write_node(child, depth + 1, out);
+            }
+            let _ = writeln!(out, "{indent}}}");
+        }
+    }
+}
+
+/// The game doesn't read escape sequences in these files, so quotes can't be
+/// kept
+fn escape(s: &str) -> String {
+    s.replace('"', "'")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Node, Value};
+
+    #[test]
+    fn parses_game_written_dmx() {
+        let contents = r#"// Written by the game
+"replay_7"
+{
+	"handle"		"7"
+	"map"		"cp_process_final"
+	complete		1
+	"length"		"612.345"
+	"title"		"A \"good\" round"
+	"recon_filename"		"replay_7.dem"
+	"screenshots"
+	{
+		"screenshot"
+		{
+			"width"		"512"
+			"base_filename"		"replay_7_0"
+		}
+		"screenshot" { "base_filename" "replay_7_1" }
+	}
+	"spawn_tick"		"-1" [$WIN32]
+}
+"#;
+
+        let node = Node::parse(contents).expect("Failed to parse");
+        assert_eq!(node.key, "replay_7");
+        assert_eq!(node.get_str("Map"), Some("cp_process_final"));
+        assert_eq!(node.get_str("complete"), Some("1"));
+        assert_eq!(node.get_str("title"), Some("A \"good\" round"));
+        assert_eq!(node.get_str("spawn_tick"), Some("-1"));
+        assert_eq!(node.find_blocks("screenshot").len(), 2);
+
+        let written = Node::parse(&node.write()).expect("Failed to parse written dmx");
+        assert_eq!(written.get_str("title"), Some("A 'good' round"));
+        assert!(
+            matches!(written.get("screenshots").map(|n| &n.value), Some(Value::Block(b)) if b.len() == 2)
+        );
+    }
+}
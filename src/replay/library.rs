@@ -0,0 +1,258 @@
+//! Replays that already exist in the TF2 directory, whether they were made
+//! here or by the game itself.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use filenamify::filenamify;
+
+use super::{dmx::Node, DEMO_PATH, DIR_REPLAY, DIR_THUMBNAIL, SUB_SCREENSHOT, TEMPLATE_VMT};
+
+#[derive(Debug, Clone)]
+pub struct ExistingReplay {
+    pub dmx_path: PathBuf,
+    pub title: String,
+    pub map: String,
+    /// Seconds
+    pub length: Option<f32>,
+    /// The copy of the demo in the replay folder
+    pub demo_file: Option<String>,
+    /// Base names of the thumbnail materials
+    pub screenshots: Vec<String>,
+
+    dmx: Node,
+}
+
+/// All the replays that could be read, sorted by title. Ones that fail to
+/// parse are logged and skipped.
+///
+/// # Errors
+/// If the replay folder exists but couldn't be read
+pub fn list_replays(tf2_dir: &Path) -> Result<Vec<ExistingReplay>> {
+    let entries = match std::fs::read_dir(tf2_dir.join(DIR_REPLAY)) {
+        Ok(entries) => entries,
+        // The replay system has never been used
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context("Reading replay folder"),
+    };
+
+    let mut replays: Vec<ExistingReplay> = entries
+        .filter_map(std::result::Result::ok)
+        .map(|d| d.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "dmx"))
+        .filter_map(|p| {
+            ExistingReplay::load(p.clone())
+                .map_err(|e| tracing::warn!("Couldn't read replay {p:?}: {e:?}"))
+                .ok()
+        })
+        .collect();
+    replays.sort_by_cached_key(|r| r.title.to_lowercase());
+
+    Ok(replays)
+}
+
+impl ExistingReplay {
+    /// # Errors
+    /// If the file couldn't be read or isn't valid `KeyValues`
+    pub fn load(dmx_path: PathBuf) -> Result<Self> {
+        let bytes = std::fs::read(&dmx_path).context("Reading replay DMX")?;
+        let dmx = Node::parse(&String::from_utf8_lossy(&bytes)).context("Parsing replay DMX")?;
+
+        let title = dmx
+            .get_str("title")
+            .filter(|t| !t.trim().is_empty())
+            .map(ToOwned::to_owned)
+            .or_else(|| {
+                dmx_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+            })
+            .unwrap_or_default();
+        let map = dmx.get_str("map").unwrap_or_default().to_owned();
+        let length = dmx.get_str("length").and_then(|l| l.trim().parse().ok());
+        let demo_file = dmx
+            .get_str("recon_filename")
+            .filter(|f| !f.is_empty())
+            .map(ToOwned::to_owned);
+        let screenshots = dmx
+            .find_blocks("screenshot")
+            .into_iter()
+            .filter_map(|s| s.get_str("base_filename"))
+            .map(ToOwned::to_owned)
+            .collect();
+
+        Ok(Self {
+            dmx_path,
+            title,
+            map,
+            length,
+            demo_file,
+            screenshots,
+            dmx,
+        })
+    }
+
+    /// Removes the DMX along with the demo copy and thumbnails that belong to
+    /// it. Original demos outside of the replay folder are never touched.
+    ///
+    /// # Errors
+    /// If any of the files exist but couldn't be removed
+    pub fn delete(&self, tf2_dir: &Path) -> Result<()> {
+        if let Some(demo) = self.demo_path(tf2_dir) {
+            if is_replay_copy(tf2_dir, &demo) {
+                remove_if_exists(&demo).context("Removing replay demo")?;
+            } else if demo.exists() {
+                tracing::warn!("Not removing {demo:?} as it isn't a copy in the replay folder");
+            }
+        }
+
+        for name in &self.screenshots {
+            for path in thumbnail_paths(tf2_dir, name) {
+                remove_if_exists(&path).context("Removing thumbnail")?;
+            }
+        }
+
+        remove_if_exists(&self.dmx_path).context("Removing replay DMX")
+    }
+
+    /// Give the replay a new title, renaming all of its files to match.
+    ///
+    /// # Errors
+    /// If the title isn't a valid file name, another replay already has the
+    /// same files, or any of the files couldn't be written.
+    pub fn rename(&mut self, tf2_dir: &Path, new_title: &str) -> Result<()> {
+        let file_name = filenamify(new_title);
+        if file_name.trim().is_empty() {
+            return Err(anyhow!("Replay name is not valid"));
+        }
+
+        let replay_dir = tf2_dir.join(DIR_REPLAY);
+        let new_dmx_path = replay_dir.join(format!("{file_name}.dmx"));
+
+        // Only the copy in the replay folder is renamed
+        let old_demo = self
+            .demo_path(tf2_dir)
+            .filter(|d| is_replay_copy(tf2_dir, d));
+        let new_demo_file = old_demo.as_ref().map(|_| format!("{file_name}.dem"));
+
+        let new_screenshots: Vec<String> = (0..self.screenshots.len())
+            .map(|i| {
+                if i == 0 {
+                    file_name.clone()
+                } else {
+                    format!("{file_name}_{i}")
+                }
+            })
+            .collect();
+
+        // Check nothing would be overwritten before changing anything
+        let mut moves = vec![(self.dmx_path.clone(), new_dmx_path.clone())];
+        if let (Some(old), Some(new)) = (&old_demo, &new_demo_file) {
+            moves.push((old.clone(), replay_dir.join(new)));
+        }
+        for (old, new) in self.screenshots.iter().zip(&new_screenshots) {
+            moves.extend(
+                thumbnail_paths(tf2_dir, old)
+                    .into_iter()
+                    .zip(thumbnail_paths(tf2_dir, new)),
+            );
+        }
+        if let Some((_, new)) = moves.iter().find(|(old, new)| old != new && new.exists()) {
+            return Err(anyhow!("{new:?} already exists"));
+        }
+
+        if let (Some(old), Some(new)) = (&old_demo, &new_demo_file) {
+            std::fs::rename(old, replay_dir.join(new)).context("Renaming replay demo")?;
+        }
+
+        for (old, new) in self.screenshots.iter().zip(&new_screenshots) {
+            let [old_vmt, old_vtf] = thumbnail_paths(tf2_dir, old);
+            let [new_vmt, new_vtf] = thumbnail_paths(tf2_dir, new);
+
+            if old_vtf.exists() {
+                std::fs::rename(&old_vtf, &new_vtf).context("Renaming thumbnail VTF")?;
+            }
+            if old_vmt.exists() {
+                // The material refers to the texture by name
+                std::fs::write(&new_vmt, TEMPLATE_VMT.replace(SUB_SCREENSHOT, new))
+                    .context("Writing thumbnail VMT")?;
+                if old_vmt != new_vmt {
+                    remove_if_exists(&old_vmt).context("Removing old thumbnail VMT")?;
+                }
+            }
+        }
+
+        self.dmx.set_str("title", new_title);
+        if let Some(new) = &new_demo_file {
+            self.dmx.set_str("recon_filename", new);
+        }
+        let mut screenshot_blocks = Vec::new();
+        self.dmx
+            .find_blocks_mut("screenshot", &mut screenshot_blocks);
+        for (block, new) in screenshot_blocks
+            .into_iter()
+            .filter(|b| b.get_str("base_filename").is_some())
+            .zip(&new_screenshots)
+        {
+            block.set_str("base_filename", new);
+        }
+
+        std::fs::write(&new_dmx_path, self.dmx.write()).context("Writing replay DMX")?;
+        if new_dmx_path != self.dmx_path {
+            remove_if_exists(&self.dmx_path).context("Removing old replay DMX")?;
+        }
+
+        self.dmx_path = new_dmx_path;
+        self.title = new_title.to_owned();
+        if new_demo_file.is_some() {
+            self.demo_file = new_demo_file;
+        }
+        self.screenshots = new_screenshots;
+
+        Ok(())
+    }
+
+    /// Where the demo copy should be. Only the file name is used so a
+    /// malformed DMX can't point anywhere else.
+    fn demo_path(&self, tf2_dir: &Path) -> Option<PathBuf> {
+        let file_name = Path::new(self.demo_file.as_ref()?).file_name()?;
+        Some(tf2_dir.join(DIR_REPLAY).join(file_name))
+    }
+}
+
+fn thumbnail_paths(tf2_dir: &Path, name: &str) -> [PathBuf; 2] {
+    let name = Path::new(name)
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().to_string());
+    let dir = tf2_dir.join(DIR_THUMBNAIL);
+    [
+        dir.join(format!("{name}.vmt")),
+        dir.join(format!("{name}.vtf")),
+    ]
+}
+
+/// Whether the demo really is a copy sitting in the replay folder, and not
+/// (e.g. through a link) one of the user's original demos.
+fn is_replay_copy(tf2_dir: &Path, demo: &Path) -> bool {
+    let (Ok(demo), Ok(replay_dir)) = (demo.canonicalize(), tf2_dir.join(DIR_REPLAY).canonicalize())
+    else {
+        return false;
+    };
+
+    let in_demos_dir = tf2_dir
+        .join(DEMO_PATH)
+        .canonicalize()
+        .is_ok_and(|d| demo.starts_with(d));
+
+    demo.parent() == Some(replay_dir.as_path()) && !in_demos_dir
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        r => r,
+    }
+}
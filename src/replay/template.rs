@@ -0,0 +1,123 @@
+//! Naming replays from a template like `"{date} {map} vs {server}"`.
+
+use chrono::{DateTime, Local};
+use tf2_monitor_core::{demos::analyser::AnalysedDemo, tf_demo_parser::demo::header::Header};
+
+pub const DEFAULT_NAME_TEMPLATE: &str = "{date} {time} - {nick} on {map}";
+
+/// Variables that can be used in the template, and what they are replaced
+/// with. The ones marked as analysed are only available once the demo has
+/// been analysed in the Demos tab.
+pub const TEMPLATE_VARIABLES: &[(&str, &str)] = &[
+    ("date", "When the demo was recorded, e.g. 2024-06-30"),
+    ("time", "The time the demo finished recording, e.g. 21:45"),
+    ("nick", "Your name in the demo"),
+    ("map", "The map played"),
+    (
+        "server",
+        "The server's name if analysed, otherwise its address",
+    ),
+    ("length", "How long the demo is, e.g. 23:05"),
+    ("ticks", "How many ticks the demo is"),
+    ("demo", "The demo's file name"),
+    ("kills", "Your kills (analysed)"),
+    ("deaths", "Your deaths (analysed)"),
+    ("assists", "Your assists (analysed)"),
+];
+
+/// Everything the template variables are drawn from
+pub struct NameContext<'a> {
+    pub header: &'a Header,
+    pub analysed: Option<&'a AnalysedDemo>,
+    pub demo_name: &'a str,
+    pub recorded: DateTime<Local>,
+}
+
+impl NameContext<'_> {
+    fn variable(&self, name: &str) -> Option<String> {
+        let user = self.analysed.and_then(|a| a.players.get(&a.user));
+
+        Some(match name {
+            "date" => self.recorded.format("%Y-%m-%d").to_string(),
+            "time" => self.recorded.format("%H:%M").to_string(),
+            "nick" => self.header.nick.clone(),
+            "map" => self.header.map.clone(),
+            "server" => self
+                .analysed
+                .map(|a| a.server_name.clone())
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| self.header.server.clone()),
+            "length" => {
+                let seconds = self.header.duration.max(0.0) as u32;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+            "ticks" => format!("{}", self.header.ticks),
+            "demo" => self.demo_name.to_owned(),
+            "kills" => user.map_or_else(unknown, |p| format!("{}", p.kills.len())),
+            "deaths" => user.map_or_else(unknown, |p| format!("{}", p.deaths.len())),
+            "assists" => user.map_or_else(unknown, |p| format!("{}", p.assists.len())),
+            _ => return None,
+        })
+    }
+}
+
+/// Stands in for analysed values when the demo hasn't been analysed
+fn unknown() -> String {
+    String::from("?")
+}
+
+/// Replace `{variable}`s in the template. Unknown variables and unmatched
+/// braces are left as they are.
+#[must_use]
+pub fn render(template: &str, context: &NameContext) -> String {
+    render_with(template, |name| context.variable(name))
+}
+
+fn render_with(template: &str, variable: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+
+        let name = &rest[1..end];
+        match variable(name.trim()) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::render_with;
+
+    #[test]
+    fn unknown_variables_render_literally() {
+        let variable = |name: &str| match name {
+            "map" => Some(String::from("pl_upward")),
+            "kills" => Some(String::from("?")),
+            _ => None,
+        };
+
+        assert_eq!(
+            render_with("{map} - {kills} kills", variable),
+            "pl_upward - ? kills"
+        );
+        assert_eq!(render_with("{ map } {nope}", variable), "pl_upward {nope}");
+        assert_eq!(
+            render_with("{map} {unclosed", variable),
+            "pl_upward {unclosed"
+        );
+        assert_eq!(render_with("}{}{map}", variable), "}{}pl_upward");
+    }
+}
@@ -0,0 +1,80 @@
+//! Text matching shared by the search bars, so searching for "pootis" finds
+//! "PootÍs" wherever it's typed.
+
+/// Lowercase `text` and strip accents from Latin letters. This isn't full
+/// Unicode normalisation, but covers the accented names that are common in
+/// TF2 lobbies without needing extra tables.
+#[must_use]
+pub fn normalise(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match fold_char(c) {
+            Fold::Char(c) => out.push(c),
+            Fold::Str(s) => out.push_str(s),
+        }
+    }
+    out
+}
+
+/// Whether `needle` appears anywhere in `haystack`, ignoring case and accents.
+#[must_use]
+pub fn search_match(haystack: &str, needle: &str) -> bool {
+    normalise(haystack).contains(&normalise(needle))
+}
+
+enum Fold {
+    Char(char),
+    Str(&'static str),
+}
+
+/// Expects an already lowercased character
+const fn fold_char(c: char) -> Fold {
+    Fold::Char(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => 'e',
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => 'g',
+        'ĥ' | 'ħ' => 'h',
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => 'i',
+        'ĵ' => 'j',
+        'ķ' => 'k',
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => 'l',
+        'ñ' | 'ń' | 'ņ' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => 'o',
+        'ŕ' | 'ŗ' | 'ř' => 'r',
+        'ś' | 'ŝ' | 'ş' | 'š' => 's',
+        'ţ' | 'ť' | 'ŧ' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => 'u',
+        'ŵ' => 'w',
+        'ý' | 'ÿ' | 'ŷ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        'ß' => return Fold::Str("ss"),
+        'æ' => return Fold::Str("ae"),
+        'œ' => return Fold::Str("oe"),
+        c => c,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalise, search_match};
+
+    #[test]
+    fn mixed_case() {
+        assert!(search_match("xXSniperXx", "sniper"));
+        assert!(search_match("sniper", "SNIPER"));
+        assert_eq!(normalise("Heavy Weapons GUY"), "heavy weapons guy");
+        assert!(!search_match("Scout", "spy"));
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert!(search_match("PootÍs Spenser", "pootis"));
+        assert!(search_match("pootis", "pootís"));
+        assert!(search_match("Straße", "STRASSE"));
+        assert!(search_match("ПУЛЕМЁТЧИК", "пулемёт"));
+        assert!(search_match("ニンジャ", "ニン"));
+        assert_eq!(normalise("Ødegaard"), "odegaard");
+    }
+}
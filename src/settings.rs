@@ -1,14 +1,28 @@
-use std::{collections::HashSet, fmt::Display, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::PathBuf,
+};
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tf2_monitor_core::steamid_ng::SteamID;
+use thiserror::Error;
 
 use crate::{
-    demos::{self, AnalysedDemoView},
-    gui::{SidePanel, View},
+    demos::{self, AnalysedDemoView, SortDirection},
+    gui::{records::RecordSort, styles::custom_theme::CustomTheme, SidePanel, View, ViewKind},
+    replay::template::DEFAULT_NAME_TEMPLATE,
 };
 
 pub const SETTINGS_IDENTIFIER: &str = "MACClientSettings";
 pub const PANEL_SIDES: &[PanelSide] = &[PanelSide::Left, PanelSide::Right];
+pub const MIN_UI_SCALE: f64 = 0.5;
+pub const MAX_UI_SCALE: f64 = 3.0;
+pub const MIN_SPLIT_RATIO: f32 = 0.2;
+pub const MAX_SPLIT_RATIO: f32 = 0.8;
+pub const DEFAULT_SIDE_PANEL_RATIO: f32 = 0.3;
+pub const MIN_SIDE_PANEL_RATIO: f32 = 0.15;
+pub const MAX_SIDE_PANEL_RATIO: f32 = 0.7;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -16,16 +30,63 @@ pub const PANEL_SIDES: &[PanelSide] = &[PanelSide::Left, PanelSide::Right];
 pub struct AppSettings {
     pub window_pos: Option<(i32, i32)>,
     pub window_size: Option<(u32, u32)>,
+    pub window_maximized: bool,
     pub enable_mac_integration: bool,
+    #[serde(deserialize_with = "deserialize_view")]
     pub view: View,
-    pub sidepanels: HashSet<SidePanel>,
+    /// The side panel open in each view, if any
+    #[serde(deserialize_with = "deserialize_sidepanels")]
+    pub sidepanels: HashMap<ViewKind, SidePanel>,
     pub panel_side: PanelSide,
+    /// How much of the window's width the side panel takes
+    pub side_panel_ratio: f32,
+    /// Show a selected player's details above the side panel instead of in
+    /// place of it
+    pub split_side_panel: bool,
+    /// How much of the split side panel the player's details take
+    pub split_ratio: f32,
+    /// Scales the whole interface, e.g. for high resolution displays
+    pub ui_scale: f64,
     pub analysed_demo_view: AnalysedDemoView,
     pub demo_filters: demos::Filters,
     pub demo_directories: Vec<PathBuf>,
+    /// Analyse demos by themselves once they finish recording
+    pub auto_analyse_demos: bool,
+    /// Show a summary of the match when a demo finishes being analysed
+    pub demo_summaries: bool,
+    pub record_sort_by: RecordSort,
+    pub record_sort_direction: SortDirection,
+    pub records_per_page: usize,
+    /// See [`demos::ALL_DEMOS`]
+    pub demos_per_page: usize,
+    /// How often changed settings, records and the steam cache are saved
+    pub autosave_minutes: u64,
+    /// Hide the window in the system tray instead of quitting when it is closed
+    pub close_to_tray: bool,
+    /// Show a line in the chat when someone in the server changes their name
+    pub name_changes_in_chat: bool,
+    /// Show the time next to each chat message and kill
+    pub chat_timestamps: bool,
+    /// Show the flag of the country on a player's Steam profile next to them
+    pub country_flags: bool,
+    pub badges: BadgeSettings,
     #[serde(serialize_with = "serialize_theme")]
     #[serde(deserialize_with = "deserialize_theme")]
     pub theme: iced::Theme,
+    pub custom_themes: Vec<CustomTheme>,
+    /// The custom theme in use instead of [`Self::theme`], if any
+    pub selected_custom_theme: Option<usize>,
+    /// How replays are named when a demo is chosen, see
+    /// [`crate::replay::template::TEMPLATE_VARIABLES`]
+    pub replay_name_template: String,
+    /// Other sites players can be looked up on, see [`ExternalLink`]
+    pub external_links: Vec<ExternalLink>,
+    /// Where log files are written instead of the config directory
+    pub log_directory: Option<PathBuf>,
+    /// How big a log file can get before a new one is started
+    pub log_max_megabytes: u64,
+    /// How many log files are kept, including the current one
+    pub log_max_files: usize,
 }
 
 impl Default for AppSettings {
@@ -33,14 +94,270 @@ impl Default for AppSettings {
         Self {
             window_pos: None,
             window_size: Some((1275, 720)),
+            window_maximized: false,
             enable_mac_integration: false,
             view: View::Server,
-            sidepanels: HashSet::new(),
+            sidepanels: HashMap::new(),
             panel_side: PanelSide::Right,
+            side_panel_ratio: DEFAULT_SIDE_PANEL_RATIO,
+            split_side_panel: false,
+            split_ratio: 0.6,
+            ui_scale: 1.0,
             analysed_demo_view: AnalysedDemoView::Players,
             demo_filters: demos::Filters::new(),
             demo_directories: Vec::new(),
+            auto_analyse_demos: false,
+            demo_summaries: true,
+            record_sort_by: RecordSort::Modified,
+            record_sort_direction: SortDirection::Descending,
+            records_per_page: 50,
+            demos_per_page: 50,
+            autosave_minutes: 5,
+            close_to_tray: false,
+            name_changes_in_chat: true,
+            chat_timestamps: true,
+            country_flags: true,
+            badges: BadgeSettings::default(),
             theme: iced::Theme::CatppuccinMocha,
+            custom_themes: Vec::new(),
+            selected_custom_theme: None,
+            replay_name_template: String::from(DEFAULT_NAME_TEMPLATE),
+            external_links: ExternalLink::defaults(),
+            log_directory: None,
+            log_max_megabytes: 10,
+            log_max_files: 5,
+        }
+    }
+}
+
+/// Moves a window at `pos` so it is entirely within the monitor it overlaps
+/// the most. Returns `None` if it isn't on any of the monitors at all (e.g.
+/// it was on a monitor that has since been unplugged).
+///
+/// Monitors are given as `(x, y, width, height)`.
+#[must_use]
+pub fn clamp_window_position(
+    pos: (i32, i32),
+    size: (u32, u32),
+    monitors: &[(i32, i32, u32, u32)],
+) -> Option<(i32, i32)> {
+    let overlap = |start: i32, len: u32, mon_start: i32, mon_len: u32| {
+        let end = i64::from(start) + i64::from(len);
+        let mon_end = i64::from(mon_start) + i64::from(mon_len);
+        (end.min(mon_end) - i64::from(start.max(mon_start))).max(0)
+    };
+
+    let &(mx, my, mw, mh) = monitors
+        .iter()
+        .map(|m| {
+            let area = overlap(pos.0, size.0, m.0, m.2) * overlap(pos.1, size.1, m.1, m.3);
+            (area, m)
+        })
+        .filter(|(area, _)| *area > 0)
+        .max_by_key(|(area, _)| *area)?
+        .1;
+
+    let clamp = |start: i32, len: u32, mon_start: i32, mon_len: u32| {
+        let max_start = i64::from(mon_start) + i64::from(mon_len) - i64::from(len);
+        i64::from(start).min(max_start).max(i64::from(mon_start)) as i32
+    };
+
+    Some((clamp(pos.0, size.0, mx, mw), clamp(pos.1, size.1, my, mh)))
+}
+
+pub const PLACEHOLDER_STEAMID64: &str = "{steamid64}";
+pub const PLACEHOLDER_STEAMID3: &str = "{steamid3}";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExternalLinkError {
+    #[error("The link needs a name")]
+    MissingName,
+    #[error("The URL must contain {PLACEHOLDER_STEAMID64} or {PLACEHOLDER_STEAMID3}")]
+    MissingPlaceholder,
+}
+
+/// Badges shown next to players that are guesses rather than facts, so can be
+/// tuned or turned off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Badge {
+    YoungAccount,
+    LevelZero,
+    LowPlaytime,
+    CheaterKickNoVotes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BadgeSettings {
+    pub young_account: bool,
+    /// Accounts created fewer than this many days ago are young
+    pub young_account_days: u32,
+    pub level_zero: bool,
+    /// Only possible when TF2 playtime is being looked up
+    pub low_playtime: bool,
+    pub low_playtime_hours: u32,
+    /// Voted No on kicking players marked as cheaters
+    pub cheater_kick_no_votes: bool,
+    pub cheater_kick_no_votes_times: u32,
+}
+
+impl BadgeSettings {
+    #[must_use]
+    pub const fn enabled(&self, badge: Badge) -> bool {
+        match badge {
+            Badge::YoungAccount => self.young_account,
+            Badge::LevelZero => self.level_zero,
+            Badge::LowPlaytime => self.low_playtime,
+            Badge::CheaterKickNoVotes => self.cheater_kick_no_votes,
+        }
+    }
+
+    pub fn set_enabled(&mut self, badge: Badge, enabled: bool) {
+        match badge {
+            Badge::YoungAccount => self.young_account = enabled,
+            Badge::LevelZero => self.level_zero = enabled,
+            Badge::LowPlaytime => self.low_playtime = enabled,
+            Badge::CheaterKickNoVotes => self.cheater_kick_no_votes = enabled,
+        }
+    }
+}
+
+impl Default for BadgeSettings {
+    fn default() -> Self {
+        Self {
+            young_account: true,
+            young_account_days: 100,
+            level_zero: true,
+            low_playtime: true,
+            low_playtime_hours: 50,
+            cheater_kick_no_votes: true,
+            cheater_kick_no_votes_times: 2,
+        }
+    }
+}
+
+/// A site to look players up on. The URL has the player's SteamID put in
+/// place of [`PLACEHOLDER_STEAMID64`] or [`PLACEHOLDER_STEAMID3`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExternalLink {
+    pub name: String,
+    pub url: String,
+}
+
+impl ExternalLink {
+    /// # Errors
+    /// If the name is empty or the URL has nowhere to put the SteamID
+    pub fn new(name: &str, url: &str) -> Result<Self, ExternalLinkError> {
+        let name = name.trim();
+        let url = url.trim();
+
+        if name.is_empty() {
+            return Err(ExternalLinkError::MissingName);
+        }
+        if !has_placeholder(url) {
+            return Err(ExternalLinkError::MissingPlaceholder);
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            url: url.to_owned(),
+        })
+    }
+
+    #[must_use]
+    pub fn defaults() -> Vec<Self> {
+        [
+            ("SteamHistory", "https://steamhistory.net/id/{steamid64}"),
+            ("logs.tf", "https://logs.tf/profile/{steamid64}"),
+            ("trends.tf", "https://trends.tf/player/{steamid64}"),
+        ]
+        .into_iter()
+        .map(|(name, url)| Self {
+            name: name.to_owned(),
+            url: url.to_owned(),
+        })
+        .collect()
+    }
+
+    /// The URL for a particular player, or `None` if the URL has no
+    /// placeholder (e.g. it was edited in the settings file by hand).
+    #[must_use]
+    pub fn url_for(&self, steamid: SteamID) -> Option<String> {
+        if !has_placeholder(&self.url) {
+            return None;
+        }
+
+        Some(
+            self.url
+                .replace(PLACEHOLDER_STEAMID64, &format!("{}", u64::from(steamid)))
+                .replace(PLACEHOLDER_STEAMID3, &steamid.steam3()),
+        )
+    }
+}
+
+fn has_placeholder(url: &str) -> bool {
+    url.contains(PLACEHOLDER_STEAMID64) || url.contains(PLACEHOLDER_STEAMID3)
+}
+
+/// An option in the theme picker
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThemeChoice {
+    BuiltIn(iced::Theme),
+    Custom(usize, String),
+}
+
+impl Display for ThemeChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BuiltIn(theme) => write!(f, "{theme}"),
+            Self::Custom(_, name) => write!(f, "{name} (custom)"),
+        }
+    }
+}
+
+impl AppSettings {
+    /// The side panel open in the current view, if any
+    #[must_use]
+    pub fn side_panel(&self) -> Option<SidePanel> {
+        self.sidepanels
+            .get(&self.view.kind())
+            .copied()
+            .filter(|p| self.view.side_panels().contains(p))
+    }
+
+    #[must_use]
+    pub fn custom_theme(&self) -> Option<&CustomTheme> {
+        self.selected_custom_theme
+            .and_then(|i| self.custom_themes.get(i))
+    }
+
+    /// The theme currently in use, which may be a custom one
+    #[must_use]
+    pub fn current_theme(&self) -> iced::Theme {
+        self.custom_theme()
+            .map_or_else(|| self.theme.clone(), CustomTheme::theme)
+    }
+
+    #[must_use]
+    pub fn theme_choices(&self) -> Vec<ThemeChoice> {
+        THEMES
+            .iter()
+            .cloned()
+            .map(ThemeChoice::BuiltIn)
+            .chain(
+                self.custom_themes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, t)| ThemeChoice::Custom(i, t.name.clone())),
+            )
+            .collect()
+    }
+
+    #[must_use]
+    pub fn theme_choice(&self) -> ThemeChoice {
+        match (self.selected_custom_theme, self.custom_theme()) {
+            (Some(i), Some(t)) => ThemeChoice::Custom(i, t.name.clone()),
+            _ => ThemeChoice::BuiltIn(self.theme.clone()),
         }
     }
 }
@@ -134,3 +451,182 @@ fn deserialize_theme<'de, D: Deserializer<'de>>(d: D) -> Result<iced::Theme, D::
 
     Err(serde::de::Error::custom(format!("Invalid theme \"{s}\"")))
 }
+
+/// Views that can't be read go back to the list of demos, as older versions
+/// stored the analysed demo being viewed by its index in that list.
+fn deserialize_view<'de, D: Deserializer<'de>>(d: D) -> Result<View, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MaybeView {
+        View(View),
+        Invalid(serde::de::IgnoredAny),
+    }
+
+    Ok(match MaybeView::deserialize(d)? {
+        MaybeView::View(view) => view,
+        MaybeView::Invalid(_) => View::Demos,
+    })
+}
+
+/// Older versions kept one set of open side panels for every view, each view
+/// gets whichever of its panels was in the set.
+fn deserialize_sidepanels<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<HashMap<ViewKind, SidePanel>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MaybeOld {
+        PerView(HashMap<ViewKind, SidePanel>),
+        Old(HashSet<SidePanel>),
+    }
+
+    Ok(match MaybeOld::deserialize(d)? {
+        MaybeOld::PerView(sidepanels) => sidepanels,
+        MaybeOld::Old(open) => ViewKind::ALL
+            .iter()
+            .filter_map(|&v| {
+                v.side_panels()
+                    .iter()
+                    .find(|p| open.contains(*p))
+                    .map(|&p| (v, p))
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use tf2_monitor_core::md5;
+
+    use crate::gui::{
+        styles::custom_theme::{CustomTheme, ThemeColour},
+        SidePanel, View, ViewKind,
+    };
+
+    use tf2_monitor_core::steamid_ng::SteamID;
+
+    use super::{AppSettings, ExternalLink, ExternalLinkError};
+
+    #[test]
+    fn external_link_urls() {
+        let steamid = SteamID::from(76_561_197_960_287_930);
+
+        let link = ExternalLink::new(" Both ", "https://example.com/{steamid64}?id3={steamid3}")
+            .expect("Link should be valid");
+        assert_eq!(link.name, "Both");
+        assert_eq!(
+            link.url_for(steamid).as_deref(),
+            Some("https://example.com/76561197960287930?id3=[U:1:22202]")
+        );
+
+        assert_eq!(
+            ExternalLink::new("No placeholder", "https://example.com/"),
+            Err(ExternalLinkError::MissingPlaceholder)
+        );
+        assert_eq!(
+            ExternalLink::new("  ", "https://example.com/{steamid64}"),
+            Err(ExternalLinkError::MissingName)
+        );
+
+        let edited = ExternalLink {
+            name: String::from("Edited by hand"),
+            url: String::from("https://example.com/"),
+        };
+        assert_eq!(edited.url_for(steamid), None);
+    }
+
+    #[test]
+    fn analysed_demo_view_by_hash() {
+        let settings = AppSettings {
+            view: View::AnalysedDemo(md5::compute(b"demo")),
+            ..Default::default()
+        };
+
+        let serialized = serde_json::to_value(&settings).expect("Failed to serialize");
+        let deserialized: AppSettings =
+            serde_json::from_value(serialized).expect("Failed to deserialize");
+        assert_eq!(deserialized.view, settings.view);
+
+        // Old configs held an index into the list of demos
+        let old: AppSettings = serde_json::from_value(serde_json::json!({
+            "view": { "AnalysedDemo": 3 },
+            "ui_scale": 1.5,
+        }))
+        .expect("Failed to deserialize old settings");
+        assert_eq!(old.view, View::Demos);
+        assert!((old.ui_scale - 1.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn side_panels_per_view() {
+        let mut settings = AppSettings::default();
+        settings
+            .sidepanels
+            .insert(ViewKind::Server, SidePanel::ChatKills);
+        settings
+            .sidepanels
+            .insert(ViewKind::Demos, SidePanel::DemoFilters);
+
+        let serialized = serde_json::to_value(&settings).expect("Failed to serialize");
+        let deserialized: AppSettings =
+            serde_json::from_value(serialized).expect("Failed to deserialize");
+        assert_eq!(deserialized.sidepanels, settings.sidepanels);
+
+        // Old configs had one set of panels for all views
+        let mut old: AppSettings = serde_json::from_value(serde_json::json!({
+            "sidepanels": ["Parties", "DemoFilters"],
+        }))
+        .expect("Failed to deserialize old settings");
+        assert_eq!(
+            old.sidepanels.get(&ViewKind::Server),
+            Some(&SidePanel::Parties)
+        );
+        assert_eq!(
+            old.sidepanels.get(&ViewKind::History),
+            Some(&SidePanel::Parties)
+        );
+        assert_eq!(
+            old.sidepanels.get(&ViewKind::Demos),
+            Some(&SidePanel::DemoFilters)
+        );
+        assert_eq!(old.sidepanels.get(&ViewKind::Records), None);
+
+        old.view = View::Demos;
+        assert_eq!(old.side_panel(), Some(SidePanel::DemoFilters));
+        old.view = View::Records;
+        assert_eq!(old.side_panel(), None);
+    }
+
+    #[test]
+    fn custom_theme_round_trip() {
+        let mut theme = CustomTheme {
+            name: String::from("High contrast"),
+            ..Default::default()
+        };
+        theme.set_hex(ThemeColour::Background, String::from("#000000"));
+        theme.set_hex(ThemeColour::TeamRed, String::from("not a colour"));
+
+        let settings = AppSettings {
+            custom_themes: vec![theme.clone()],
+            selected_custom_theme: Some(0),
+            ..Default::default()
+        };
+
+        let serialized = serde_json::to_value(&settings).expect("Failed to serialize");
+        let deserialized: AppSettings =
+            serde_json::from_value(serialized).expect("Failed to deserialize");
+
+        assert_eq!(deserialized.custom_themes, vec![theme.clone()]);
+        assert_eq!(deserialized.selected_custom_theme, Some(0));
+        assert_eq!(
+            deserialized.current_theme().palette().background,
+            iced::Color::BLACK
+        );
+
+        // Invalid colours fall back to the defaults instead of failing
+        assert_eq!(
+            theme.colour(ThemeColour::TeamRed),
+            CustomTheme::default().colour(ThemeColour::TeamRed)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Problems that stop the app from starting, and the small window that shows
+//! them so they aren't only written to a console nobody is looking at.
+
+use std::{
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use iced::{widget, Alignment, Application, Length};
+use rfd::{MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+use tf2_monitor_core::{
+    builder::BuildError,
+    settings::{
+        instance::{self, InstanceLock, LockError, LockOwner},
+        ConfigFilesError,
+    },
+};
+use thiserror::Error;
+
+/// How long to wait for another instance to close after ending it
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum StartupError {
+    #[error("Failed to set up the portable config directory: {0}")]
+    PortableConfig(ConfigFilesError),
+    #[error("{0}")]
+    Profile(ConfigFilesError),
+    #[error("{0}")]
+    Load(#[from] BuildError),
+    #[error("Failed to open the window: {0}")]
+    Gui(#[from] iced::Error),
+}
+
+impl StartupError {
+    /// The config file that couldn't be loaded, if it was a config file
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Load(e) => Some(e.path()),
+            _ => None,
+        }
+    }
+
+    /// What the user can do about it
+    #[must_use]
+    pub const fn help(&self) -> &'static str {
+        match self {
+            Self::PortableConfig(_) => "Make sure the folder next to the executable can be written to, or start the app without --portable.",
+            Self::Profile(_) => "Profile names can't be empty, start or end with spaces, or contain any of / \\ : * ? \" < > |. Start the app with a different --profile.",
+            Self::Load(_) => "The file may have been damaged or edited by hand. Fix it, or move it somewhere else to start with a fresh one, then try again.",
+            Self::Gui(_) => "Make sure your graphics drivers are up to date.",
+        }
+    }
+}
+
+/// Takes the lock on the config files in `dir`. If another instance already
+/// has it, the user can close that one, carry on in read-only mode (without a
+/// lock), or quit, in which case this is `Break`.
+pub fn lock_instance(dir: &Path) -> ControlFlow<(), Option<InstanceLock>> {
+    let owner = match InstanceLock::acquire(dir) {
+        Ok(lock) => return ControlFlow::Continue(Some(lock)),
+        Err(LockError::HeldBy(owner)) => owner,
+        Err(e) => {
+            tracing::error!("Couldn't check for other instances of the app: {e}");
+            return ControlFlow::Continue(None);
+        }
+    };
+
+    const CLOSE: &str = "Close it";
+    const READ_ONLY: &str = "Open read-only";
+    const QUIT: &str = "Quit";
+    let choice = MessageDialog::new()
+        .set_level(MessageLevel::Warning)
+        .set_title("Bash's TF2 Monitor is already running")
+        .set_description(format!(
+            "Another copy of the app is already running (process {}). Running two at once makes them save over each other's changes to your settings and player records.\n\nClose the other one, or open this one in read-only mode, which doesn't save anything or send commands to the game?",
+            owner.pid
+        ))
+        .set_buttons(MessageButtons::YesNoCancelCustom(
+            CLOSE.into(),
+            READ_ONLY.into(),
+            QUIT.into(),
+        ))
+        .show();
+
+    match choice {
+        MessageDialogResult::Yes => close_other(dir, owner),
+        MessageDialogResult::Custom(c) if c == CLOSE => close_other(dir, owner),
+        MessageDialogResult::No => read_only(),
+        MessageDialogResult::Custom(c) if c == READ_ONLY => read_only(),
+        _ => ControlFlow::Break(()),
+    }
+}
+
+fn read_only() -> ControlFlow<(), Option<InstanceLock>> {
+    tracing::warn!("Another instance is running, starting in read-only mode");
+    instance::set_read_only();
+    ControlFlow::Continue(None)
+}
+
+fn close_other(dir: &Path, owner: LockOwner) -> ControlFlow<(), Option<InstanceLock>> {
+    if !owner.kill() {
+        tracing::error!("Couldn't close the other instance (process {})", owner.pid);
+        return read_only();
+    }
+
+    let started = Instant::now();
+    while owner.is_running() && started.elapsed() < KILL_TIMEOUT {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    match InstanceLock::acquire(dir) {
+        Ok(lock) => ControlFlow::Continue(Some(lock)),
+        Err(e) => {
+            tracing::error!("Couldn't take over from the other instance: {e}");
+            read_only()
+        }
+    }
+}
+
+/// Prints `error`, then shows it in a window unless it was the window that
+/// couldn't be opened. `config_dir` is opened by the window's button when the
+/// error isn't about a particular file.
+pub fn report(error: &StartupError, config_dir: Option<PathBuf>) {
+    tracing::error!("{error}");
+    eprintln!("\nTF2 Monitor couldn't start.\n{error}");
+    if let Some(path) = error.path() {
+        eprintln!("File: {}", path.display());
+    }
+    eprintln!("{}", error.help());
+
+    if matches!(error, StartupError::Gui(_)) {
+        return;
+    }
+
+    let window = ErrorWindow {
+        message: error.to_string(),
+        path: error.path().map(Path::to_path_buf),
+        help: error.help(),
+        folder: error
+            .path()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .or(config_dir),
+    };
+    let mut settings = iced::Settings::with_flags(window);
+    settings.window.size = iced::Size::new(640.0, 260.0);
+    if let Err(e) = ErrorWindow::run(settings) {
+        tracing::error!("Failed to show the startup error: {e}");
+    }
+}
+
+struct ErrorWindow {
+    message: String,
+    path: Option<PathBuf>,
+    help: &'static str,
+    folder: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+enum ErrorMessage {
+    OpenFolder,
+    Quit,
+}
+
+impl Application for ErrorWindow {
+    type Executor = iced::executor::Default;
+    type Message = ErrorMessage;
+    type Theme = iced::Theme;
+    type Flags = Self;
+
+    fn new(flags: Self) -> (Self, iced::Command<ErrorMessage>) {
+        (flags, iced::Command::none())
+    }
+
+    fn title(&self) -> String {
+        String::from("Bash's TF2 Monitor couldn't start")
+    }
+
+    fn theme(&self) -> iced::Theme {
+        iced::Theme::Dark
+    }
+
+    fn update(&mut self, message: ErrorMessage) -> iced::Command<ErrorMessage> {
+        match message {
+            ErrorMessage::OpenFolder => {
+                if let Some(folder) = &self.folder {
+                    if let Err(e) = open::that(folder) {
+                        tracing::error!("Failed to open {folder:?}: {e}");
+                    }
+                }
+            }
+            ErrorMessage::Quit => return iced::window::close(iced::window::Id::MAIN),
+        }
+
+        iced::Command::none()
+    }
+
+    fn view(&self) -> iced::Element<'_, ErrorMessage> {
+        let mut contents = widget::column![widget::text(&self.message)].spacing(15);
+        if let Some(path) = &self.path {
+            contents = contents.push(widget::text(format!("File: {}", path.display())));
+        }
+        contents = contents.push(widget::text(self.help));
+
+        let mut open_folder = widget::button("Open config folder");
+        if self.folder.is_some() {
+            open_folder = open_folder.on_press(ErrorMessage::OpenFolder);
+        }
+        contents = contents.push(widget::vertical_space());
+        contents = contents.push(
+            widget::row![
+                widget::horizontal_space(),
+                open_folder,
+                widget::button("Quit").on_press(ErrorMessage::Quit),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+
+        widget::container(contents)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(20)
+            .into()
+    }
+}
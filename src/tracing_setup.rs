@@ -1,72 +1,437 @@
-use std::str::FromStr;
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
 
+use chrono::{DateTime, Local};
+use tracing::{
+    field::{Field, Visit},
+    Level, Subscriber,
+};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
-    filter::Directive, fmt::writer::MakeWriterExt, prelude::__tracing_subscriber_SubscriberExt,
-    util::SubscriberInitExt, EnvFilter, Layer,
+    filter::Directive, fmt::writer::MakeWriterExt, layer::Context,
+    prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
 };
 
-pub fn init_tracing() -> Option<WorkerGuard> {
+pub const LOG_FILE_NAME: &str = "macclient.log";
+/// How many lines are kept for the logs view
+pub const LOG_BUFFER_LINES: usize = 2000;
+/// How much is kept from before the log file is started, anything more is only
+/// written to stderr and the logs view
+const MAX_PENDING_BYTES: usize = 1_000_000;
+
+/// Environment variables that take priority over the log settings
+pub const LOG_DIR_ENV: &str = "TF2_MONITOR_LOG_DIR";
+pub const LOG_MAX_MEGABYTES_ENV: &str = "TF2_MONITOR_LOG_MAX_MB";
+pub const LOG_MAX_FILES_ENV: &str = "TF2_MONITOR_LOG_MAX_FILES";
+
+/// Libraries that log far more than is useful
+const QUIET_DIRECTIVES: &[&str] = &[
+    "hyper=warn",
+    "tf_demo_parser=warn",
+    "wgpu_hal=warn",
+    "wgpu_core=warn",
+    "iced_wgpu=warn",
+    "fontdb=error",
+    "naga=warn",
+    "cosmic_text=warn",
+];
+
+fn quieten(mut filter: EnvFilter) -> EnvFilter {
+    for directive in QUIET_DIRECTIVES {
+        filter = filter.add_directive(Directive::from_str(directive).expect("Bad directive"));
+    }
+    filter
+}
+
+fn debug_filter() -> EnvFilter {
+    quieten(EnvFilter::builder().parse("debug").expect("Bad env"))
+}
+
+/// The value of an environment variable, if it is set and valid
+#[must_use]
+pub fn env_override<T: FromStr>(var: &str) -> Option<T> {
+    let value = std::env::var(var).ok()?;
+    value
+        .parse()
+        .map_err(|_| eprintln!("Ignoring invalid {var}: {value:?}"))
+        .ok()
+}
+
+/// Logs go to stderr, the logs view, and a log file in `default_dir` (or
+/// [`LOG_DIR_ENV`]) until [`LogFile::set_dir`] moves it. Nothing is written to
+/// the file until [`LogFile::start`], so another running instance's log isn't
+/// moved out from under it. If the file can't be created the other two still
+/// work.
+pub fn init_tracing(default_dir: Option<PathBuf>) -> (WorkerGuard, LogBuffer, LogFile) {
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "info");
     }
 
-    let hyper = Directive::from_str("hyper=warn").expect("Bad directive");
-    let demo_parser = Directive::from_str("tf_demo_parser=warn").expect("Bad directive");
-    let wgpu_hal = Directive::from_str("wgpu_hal=warn").expect("Bad directive");
-    let wgpu_core = Directive::from_str("wgpu_core=warn").expect("Bad directive");
-    let iced_wgpu = Directive::from_str("iced_wgpu=warn").expect("Bad directive");
-    let fontdb = Directive::from_str("fontdb=error").expect("Bad directive");
-    let naga = Directive::from_str("naga=warn").expect("Bad directive");
-    let cosmic_text = Directive::from_str("cosmic_text=warn").expect("Bad directive");
-    let subscriber = tracing_subscriber::registry().with(
-        tracing_subscriber::fmt::layer()
-            .with_writer(std::io::stderr)
-            .with_filter(
-                EnvFilter::from_default_env()
-                    .add_directive(hyper.clone())
-                    .add_directive(demo_parser.clone())
-                    .add_directive(wgpu_hal.clone())
-                    .add_directive(wgpu_core.clone())
-                    .add_directive(iced_wgpu.clone())
-                    .add_directive(fontdb.clone())
-                    .add_directive(naga.clone())
-                    .add_directive(cosmic_text.clone()),
-            ),
-    );
-
-    match std::fs::File::create("./macclient.log") {
-        Ok(latest_log) => {
-            let (file_writer, guard) = tracing_appender::non_blocking(latest_log);
-            subscriber
-                .with(
-                    tracing_subscriber::fmt::layer()
-                        .with_ansi(false)
-                        .with_writer(file_writer.with_max_level(tracing::Level::TRACE))
-                        .with_filter(
-                            EnvFilter::builder()
-                                .parse("debug")
-                                .expect("Bad env")
-                                .add_directive(hyper)
-                                .add_directive(demo_parser)
-                                .add_directive(wgpu_hal)
-                                .add_directive(wgpu_core)
-                                .add_directive(iced_wgpu)
-                                .add_directive(fontdb)
-                                .add_directive(naga)
-                                .add_directive(cosmic_text),
-                        ),
-                )
-                .init();
-            Some(guard)
+    let buffer = LogBuffer::default();
+    let file = LogFile::new(default_dir);
+    let (file_writer, guard) = tracing_appender::non_blocking(file.clone());
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(quieten(EnvFilter::from_default_env())),
+        )
+        .with(buffer.clone().with_filter(debug_filter()))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(file_writer.with_max_level(tracing::Level::TRACE))
+                .with_filter(debug_filter()),
+        )
+        .init();
+
+    (guard, buffer, file)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogLimits {
+    /// A new file is started once the current one would go over this
+    pub max_bytes: u64,
+    /// How many files are kept, including the current one
+    pub max_files: usize,
+}
+
+impl Default for LogLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10_000_000,
+            max_files: 5,
+        }
+    }
+}
+
+impl LogLimits {
+    /// [`LOG_MAX_MEGABYTES_ENV`] and [`LOG_MAX_FILES_ENV`] take priority
+    fn with_overrides(self) -> Self {
+        Self {
+            max_bytes: env_override::<u64>(LOG_MAX_MEGABYTES_ENV)
+                .map_or(self.max_bytes, |mb| mb.saturating_mul(1_000_000)),
+            max_files: env_override(LOG_MAX_FILES_ENV).unwrap_or(self.max_files),
+        }
+    }
+}
+
+/// The log file, which is moved to `macclient.log.1`, `macclient.log.2`, ...
+/// when it gets too big or a new one is started, keeping only the newest
+/// [`LogLimits::max_files`].
+#[derive(Clone)]
+pub struct LogFile {
+    inner: Arc<Mutex<LogFileInner>>,
+}
+
+struct LogFileInner {
+    /// Used when no other directory is set
+    default_dir: Option<PathBuf>,
+    dir: Option<PathBuf>,
+    file: FileState,
+    written: u64,
+    limits: LogLimits,
+}
+
+enum FileState {
+    /// Not started yet, what was logged so far is kept to write once it is
+    Pending(Vec<u8>),
+    Open(File),
+    /// Couldn't be opened, changing the directory tries again
+    Closed,
+    /// Another instance is writing to it
+    Disabled,
+}
+
+impl LogFile {
+    fn new(default_dir: Option<PathBuf>) -> Self {
+        let dir = env_override::<PathBuf>(LOG_DIR_ENV).or_else(|| default_dir.clone());
+        let inner = LogFileInner {
+            default_dir,
+            dir,
+            file: FileState::Pending(Vec::new()),
+            written: 0,
+            limits: LogLimits::default().with_overrides(),
+        };
+
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LogFileInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The directory the log files are in
+    #[must_use]
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.lock().dir.clone()
+    }
+
+    /// Start a new log file in `dir`, or the default directory if it's `None`.
+    /// [`LOG_DIR_ENV`] takes priority over both. Before [`Self::start`] this
+    /// only changes where the file will go.
+    ///
+    /// # Errors
+    /// If the new file couldn't be created, in which case nothing is written
+    /// to a file until it can be
+    pub fn set_dir(&self, dir: Option<PathBuf>) -> io::Result<()> {
+        let mut inner = self.lock();
+        let dir = env_override::<PathBuf>(LOG_DIR_ENV)
+            .or(dir)
+            .or_else(|| inner.default_dir.clone());
+        if dir == inner.dir {
+            return Ok(());
+        }
+
+        inner.dir = dir;
+        match inner.file {
+            FileState::Pending(_) | FileState::Disabled => Ok(()),
+            FileState::Open(_) | FileState::Closed => inner.open(),
+        }
+    }
+
+    /// [`LOG_MAX_MEGABYTES_ENV`] and [`LOG_MAX_FILES_ENV`] take priority over
+    /// `limits`
+    pub fn set_limits(&self, limits: LogLimits) {
+        self.lock().limits = limits.with_overrides();
+    }
+
+    /// Moves the old log files along and starts writing to a new one, starting
+    /// with what was logged before now. Only the instance holding the lock on
+    /// the config files should do this. Does nothing once started.
+    ///
+    /// # Errors
+    /// If the file couldn't be created, in which case nothing is written to a
+    /// file until the directory is changed
+    pub fn start(&self) -> io::Result<()> {
+        let mut inner = self.lock();
+        let FileState::Pending(pending) = std::mem::replace(&mut inner.file, FileState::Closed)
+        else {
+            return Ok(());
+        };
+
+        inner.open()?;
+        if let FileState::Open(file) = &mut inner.file {
+            file.write_all(&pending)?;
+        }
+        inner.written = pending.len() as u64;
+        Ok(())
+    }
+
+    /// Don't write to a file at all, for when another instance is writing to
+    /// it
+    pub fn disable(&self) {
+        let mut inner = self.lock();
+        if let FileState::Pending(_) = inner.file {
+            inner.file = FileState::Disabled;
         }
-        Err(e) => {
-            subscriber.init();
-            tracing::error!(
-                "Failed to create log file, continuing without persistent logs: {}",
-                e
-            );
-            None
+    }
+}
+
+impl LogFileInner {
+    /// Moves the existing log files along and starts a new one
+    fn open(&mut self) -> io::Result<()> {
+        self.file = FileState::Closed;
+        self.written = 0;
+
+        let Some(dir) = &self.dir else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No directory for log files",
+            ));
+        };
+        std::fs::create_dir_all(dir)?;
+        rotate(dir, self.limits.max_files)?;
+        self.file = FileState::Open(File::create(dir.join(LOG_FILE_NAME))?);
+        Ok(())
+    }
+}
+
+/// Renames each log file in `dir` to the next number up, removing any past
+/// `max_files`
+fn rotate(dir: &Path, max_files: usize) -> io::Result<()> {
+    let path = |n: usize| {
+        if n == 0 {
+            dir.join(LOG_FILE_NAME)
+        } else {
+            dir.join(format!("{LOG_FILE_NAME}.{n}"))
+        }
+    };
+    let ignore_missing = |result: io::Result<()>| match result {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    };
+
+    let keep = max_files.max(1);
+    ignore_missing(std::fs::remove_file(path(keep - 1)))?;
+    for n in (0..keep - 1).rev() {
+        ignore_missing(std::fs::rename(path(n), path(n + 1)))?;
+    }
+    Ok(())
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.lock();
+        let full = inner.written > 0 && inner.written + buf.len() as u64 > inner.limits.max_bytes;
+        if full && matches!(inner.file, FileState::Open(_)) {
+            // Logging here would end up back in this file
+            if let Err(e) = inner.open() {
+                eprintln!("Failed to start a new log file: {e}");
+            }
+        }
+
+        let file = match &mut inner.file {
+            FileState::Open(file) => file,
+            FileState::Pending(pending) => {
+                if pending.len() + buf.len() <= MAX_PENDING_BYTES {
+                    pending.extend_from_slice(buf);
+                }
+                return Ok(buf.len());
+            }
+            // Nowhere to write, but the lines still go to stderr
+            FileState::Closed | FileState::Disabled => return Ok(buf.len()),
+        };
+        let written = file.write(buf)?;
+        inner.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.lock().file {
+            FileState::Open(file) => file.flush(),
+            FileState::Pending(_) | FileState::Closed | FileState::Disabled => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:>5} {}: {}",
+            self.time.format("%H:%M:%S%.3f"),
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+/// Keeps the last [`LOG_BUFFER_LINES`] lines that were logged, so they can be
+/// shown in the app
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<BufferInner>>,
+}
+
+#[derive(Default)]
+struct BufferInner {
+    lines: VecDeque<LogLine>,
+    /// Every line that has ever been logged, to tell when there are new ones
+    total: usize,
+}
+
+impl LogBuffer {
+    /// How many lines have been logged since startup
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .total
+    }
+
+    #[must_use]
+    pub fn lines(&self) -> Vec<LogLine> {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .lines
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    fn push(&self, line: LogLine) {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        if inner.lines.len() >= LOG_BUFFER_LINES {
+            inner.lines.pop_front();
         }
+        inner.lines.push_back(line);
+        inner.total += 1;
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogBuffer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = MessageVisitor(String::new());
+        event.record(&mut message);
+
+        let metadata = event.metadata();
+        self.push(LogLine {
+            time: Local::now(),
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            message: message.0,
+        });
+    }
+}
+
+/// Writes the message of an event, followed by any other fields
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rotate, LOG_FILE_NAME};
+
+    #[test]
+    fn rotation_keeps_newest_files() {
+        let dir =
+            std::env::temp_dir().join(format!("tf2monitor_log_rotation_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = |n: usize| match n {
+            0 => dir.join(LOG_FILE_NAME),
+            n => dir.join(format!("{LOG_FILE_NAME}.{n}")),
+        };
+
+        for session in 0..5 {
+            rotate(&dir, 3).unwrap();
+            std::fs::write(path(0), session.to_string()).unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(path(0)).unwrap(), "4");
+        assert_eq!(std::fs::read_to_string(path(1)).unwrap(), "3");
+        assert_eq!(std::fs::read_to_string(path(2)).unwrap(), "2");
+        assert!(!path(3).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
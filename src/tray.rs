@@ -0,0 +1,142 @@
+//! Icon in the system tray, so the monitor can keep watching the console and
+//! uploading demos while the window is hidden.
+
+use thiserror::Error;
+use tray_icon::{
+    menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    Icon, TrayIcon, TrayIconBuilder,
+};
+
+const SHOW_ID: &str = "show";
+const RECORDS_ID: &str = "records";
+const QUIT_ID: &str = "quit";
+
+const ICON_SIZE: u32 = 32;
+/// Catppuccin Mocha mauve, to match the default theme
+const ICON_COLOUR: [u8; 3] = [0xcb, 0xa6, 0xf7];
+
+#[derive(Debug, Error)]
+pub enum TrayError {
+    #[error("Failed to create tray menu: {0}")]
+    Menu(#[from] tray_icon::menu::Error),
+    #[error("Failed to create tray icon: {0}")]
+    Tray(#[from] tray_icon::Error),
+    #[error("Invalid tray icon: {0}")]
+    Icon(#[from] tray_icon::BadIcon),
+    #[cfg(target_os = "linux")]
+    #[error("Failed to start GTK for the tray icon: {0}")]
+    Gtk(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayAction {
+    ToggleWindow,
+    OpenRecords,
+    Quit,
+}
+
+impl TrayAction {
+    #[must_use]
+    pub fn from_event(event: &MenuEvent) -> Option<Self> {
+        match event.id.0.as_str() {
+            SHOW_ID => Some(Self::ToggleWindow),
+            RECORDS_ID => Some(Self::OpenRecords),
+            QUIT_ID => Some(Self::Quit),
+            _ => None,
+        }
+    }
+
+    /// The next tray menu item that was clicked, if any
+    #[must_use]
+    pub fn poll() -> Option<Self> {
+        while let Ok(event) = MenuEvent::receiver().try_recv() {
+            if let Some(action) = Self::from_event(&event) {
+                return Some(action);
+            }
+        }
+        None
+    }
+}
+
+/// Keeps the tray icon alive, it is removed when this is dropped.
+pub struct Tray {
+    #[cfg(not(target_os = "linux"))]
+    _icon: TrayIcon,
+}
+
+impl Tray {
+    /// Adds the icon to the system tray. Must be called from the main thread.
+    ///
+    /// On Linux the icon needs a GTK event loop, so it is run on its own thread
+    /// and lives until the app exits.
+    ///
+    /// # Errors
+    /// If the tray icon or its menu could not be created
+    pub fn new() -> Result<Self, TrayError> {
+        #[cfg(not(target_os = "linux"))]
+        {
+            Ok(Self { _icon: build()? })
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let (tx, rx) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                if let Err(e) = gtk::init() {
+                    tx.send(Err(TrayError::Gtk(e.to_string()))).ok();
+                    return;
+                }
+
+                match build() {
+                    Ok(_icon) => {
+                        tx.send(Ok(())).ok();
+                        gtk::main();
+                    }
+                    Err(e) => {
+                        tx.send(Err(e)).ok();
+                    }
+                }
+            });
+
+            rx.recv()
+                .map_err(|e| TrayError::Gtk(e.to_string()))
+                .and_then(|r| r)?;
+            Ok(Self {})
+        }
+    }
+}
+
+fn build() -> Result<TrayIcon, TrayError> {
+    let menu = Menu::new();
+    menu.append_items(&[
+        &MenuItem::with_id(SHOW_ID, "Show/Hide", true, None),
+        &MenuItem::with_id(RECORDS_ID, "Open records", true, None),
+        &PredefinedMenuItem::separator(),
+        &MenuItem::with_id(QUIT_ID, "Quit", true, None),
+    ])?;
+
+    Ok(TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_tooltip("TF2 Monitor")
+        .with_icon(icon()?)
+        .build()?)
+}
+
+/// A filled circle, since there isn't an icon image to use
+fn icon() -> Result<Icon, TrayError> {
+    let centre = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 / 2.0;
+
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = (x as f32 - centre).hypot(y as f32 - centre);
+            // Smooth the edge a little
+            let alpha = (radius - distance).clamp(0.0, 1.0);
+            rgba.extend_from_slice(&ICON_COLOUR);
+            rgba.push((alpha * 255.0) as u8);
+        }
+    }
+
+    Ok(Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)?)
+}
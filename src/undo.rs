@@ -0,0 +1,222 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use tf2_monitor_core::{
+    players::records::{PlayerRecord, Records},
+    steamid_ng::SteamID,
+};
+
+/// How many changes can be undone
+pub const MAX_UNDO: usize = 20;
+/// Edits to the same notes or alias within this long of each other are undone
+/// together
+const MERGE_WINDOW: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Verdict,
+    Notes,
+    Alias,
+    Delete,
+    /// A record added or updated all at once
+    Add,
+}
+
+/// The state of some records from before they were changed
+pub struct RecordChange {
+    pub kind: ChangeKind,
+    pub description: String,
+    pub made: Instant,
+    /// The records as they were before the change, or `None` if they didn't
+    /// exist
+    pub records: Vec<(SteamID, Option<PlayerRecord>)>,
+}
+
+impl RecordChange {
+    /// Put the records back the way they were before the change
+    pub fn restore(self, records: &mut Records) {
+        for (steamid, record) in self.records {
+            match record {
+                Some(record) => {
+                    records.insert(steamid, record);
+                }
+                None => {
+                    records.remove(&steamid);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    changes: VecDeque<RecordChange>,
+}
+
+impl UndoStack {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember a change, returning whether it was added as a new change.
+    /// Consecutive edits to the notes or alias of the same record are merged
+    /// so each keystroke doesn't need to be undone separately.
+    pub fn push(&mut self, change: RecordChange) -> bool {
+        if let Some(last) = self.changes.back_mut() {
+            let same_records = last.records.len() == change.records.len()
+                && last
+                    .records
+                    .iter()
+                    .zip(&change.records)
+                    .all(|((a, _), (b, _))| a == b);
+
+            if same_records
+                && last.kind == change.kind
+                && matches!(change.kind, ChangeKind::Notes | ChangeKind::Alias)
+                && last.made.elapsed() < MERGE_WINDOW
+            {
+                last.made = change.made;
+                return false;
+            }
+        }
+
+        self.changes.push_back(change);
+        while self.changes.len() > MAX_UNDO {
+            self.changes.pop_front();
+        }
+
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<RecordChange> {
+        self.changes.pop_back()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use tf2_monitor_core::{
+        players::records::{PlayerRecord, Records, Verdict},
+        serde_json,
+        steamid_ng::SteamID,
+    };
+
+    use super::{ChangeKind, RecordChange, UndoStack, MAX_UNDO};
+
+    fn change(kind: ChangeKind, description: &str, steamid: u64) -> RecordChange {
+        RecordChange {
+            kind,
+            description: description.into(),
+            made: Instant::now(),
+            records: vec![(SteamID::from(steamid), None)],
+        }
+    }
+
+    #[test]
+    fn changes_are_undone_newest_first() {
+        let mut stack = UndoStack::new();
+        assert!(stack.push(change(ChangeKind::Verdict, "first", 1)));
+        assert!(stack.push(change(ChangeKind::Verdict, "second", 2)));
+        assert!(stack.push(change(ChangeKind::Verdict, "third", 1)));
+
+        let undone: Vec<_> = std::iter::from_fn(|| stack.pop())
+            .map(|c| c.description)
+            .collect();
+        assert_eq!(undone, ["third", "second", "first"]);
+    }
+
+    #[test]
+    fn quick_edits_to_notes_are_merged() {
+        let mut stack = UndoStack::new();
+        assert!(stack.push(change(ChangeKind::Notes, "notes", 1)));
+        assert!(!stack.push(change(ChangeKind::Notes, "more notes", 1)));
+        assert!(stack.push(change(ChangeKind::Notes, "other notes", 2)));
+
+        assert_eq!(
+            stack.pop().map(|c| c.description).as_deref(),
+            Some("other notes")
+        );
+        assert_eq!(stack.pop().map(|c| c.description).as_deref(), Some("notes"));
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn oldest_changes_are_forgotten() {
+        let mut stack = UndoStack::new();
+        for i in 0..MAX_UNDO + 5 {
+            stack.push(change(ChangeKind::Verdict, &i.to_string(), 1));
+        }
+
+        let undone: Vec<_> = std::iter::from_fn(|| stack.pop())
+            .map(|c| c.description)
+            .collect();
+        assert_eq!(undone.len(), MAX_UNDO);
+        assert_eq!(undone.first().map(String::as_str), Some("24"));
+        assert_eq!(undone.last().map(String::as_str), Some("5"));
+    }
+
+    #[test]
+    fn undoing_a_delete_restores_the_record() {
+        let steamid = SteamID::from(76_561_198_000_000_001);
+        let linked = SteamID::from(76_561_198_000_000_002);
+
+        let mut records = Records::default();
+        let record = records.entry(steamid).or_default();
+        record.set_verdict(Verdict::Cheater);
+        record.add_previous_name("Cheater");
+        records.link_accounts(steamid, linked);
+
+        let before: Vec<_> = [steamid, linked]
+            .iter()
+            .map(|s| serde_json::to_value(&records[s]).expect("Serializing record"))
+            .collect();
+
+        let mut stack = UndoStack::new();
+        stack.push(RecordChange {
+            kind: ChangeKind::Delete,
+            description: String::from("Deleted 1 records"),
+            made: Instant::now(),
+            records: [steamid, linked]
+                .iter()
+                .map(|&s| (s, records.get(&s).cloned()))
+                .collect(),
+        });
+        records.remove_record(steamid);
+        assert!(records.get(&steamid).is_none());
+
+        stack
+            .pop()
+            .expect("Delete can be undone")
+            .restore(&mut records);
+        let after: Vec<_> = [steamid, linked]
+            .iter()
+            .map(|s| serde_json::to_value(&records[s]).expect("Serializing record"))
+            .collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn undoing_an_add_removes_the_record() {
+        let steamid = SteamID::from(76_561_198_000_000_001);
+        let mut records = Records::default();
+
+        let mut stack = UndoStack::new();
+        stack.push(change(
+            ChangeKind::Add,
+            "Added record",
+            76_561_198_000_000_001,
+        ));
+        records.insert(steamid, PlayerRecord::default());
+
+        stack
+            .pop()
+            .expect("Add can be undone")
+            .restore(&mut records);
+        assert!(records.get(&steamid).is_none());
+    }
+}
@@ -0,0 +1,313 @@
+//! Setting up a [`MonitorState`] and the event loop that drives it, so each
+//! frontend doesn't have to repeat the same loading and handler wiring.
+
+use std::path::{Path, PathBuf};
+
+use event_loop::{define_events, EventLoop};
+use steamid_ng::SteamID;
+use thiserror::Error;
+
+use crate::{
+    console::{
+        commands::{Command, CommandManager, DumbAutoKick},
+        ConsoleOutput, ConsoleParser, RawConsoleOutput,
+    },
+    demos::{DemoBytes, DemoManager, DemoMessage},
+    events::{Preferences, Refresh, UserUpdates},
+    masterbase::{queue::UploadQueue, UploadStatus, UploadUpdate},
+    players::{
+        bans::{DetectNewBans, NewBansDetected},
+        bot_lists::{BotListFetched, FetchBotLists},
+        convictions::{ConvictionsFetched, LookupConvictions},
+        name_changes::{DetectNameChanges, NamesChanged},
+        name_rules::NameRules,
+        new_players::{ExtractNewPlayers, NewPlayers},
+        records::{Records, RECORDS_FILE_NAME},
+        suspicion::Scorer,
+        user_friends::{FetchUserFriends, RefreshUserFriends, UserFriendsFetched},
+        Players,
+    },
+    server::Server,
+    settings::{AppDetails, ConfigFilesError, Settings, CONFIG_FILE_NAME},
+    steam::api::{
+        FriendLookupResult, LookupFriends, LookupProfiles, ProfileLookupBatchTick,
+        ProfileLookupRequest, ProfileLookupResult,
+    },
+    MonitorState,
+};
+
+define_events!(
+    MonitorState,
+    MonitorMessage {
+        Refresh,
+
+        Command,
+
+        RawConsoleOutput,
+        ConsoleOutput,
+
+        NewPlayers,
+
+        ProfileLookupRequest,
+        ProfileLookupBatchTick,
+        ProfileLookupResult,
+        FriendLookupResult,
+        NewBansDetected,
+        NamesChanged,
+        RefreshUserFriends,
+        UserFriendsFetched,
+        BotListFetched,
+        ConvictionsFetched,
+
+        Preferences,
+        UserUpdates,
+
+        DemoBytes,
+        DemoMessage,
+        UploadUpdate,
+    },
+    MonitorHandler {
+        CommandManager,
+
+        ConsoleParser,
+
+        ExtractNewPlayers,
+
+        LookupProfiles,
+        LookupFriends,
+        DetectNewBans,
+        DetectNameChanges,
+        FetchUserFriends,
+        FetchBotLists,
+        LookupConvictions,
+
+        DemoManager,
+        DumbAutoKick,
+    },
+);
+
+impl Clone for MonitorMessage {
+    fn clone(&self) -> Self {
+        tracing::error!("Shouldn't be cloning MACMessages!");
+        Self::None
+    }
+}
+
+impl std::fmt::Debug for MonitorMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MACMessage")
+    }
+}
+
+pub type MonitorEventLoop = EventLoop<MonitorState, MonitorMessage, MonitorHandler>;
+
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("Failed to load settings from {path:?}: {source}")]
+    Settings {
+        path: PathBuf,
+        source: ConfigFilesError,
+    },
+    #[error("Failed to load player records from {path:?}: {source}")]
+    Records {
+        path: PathBuf,
+        source: ConfigFilesError,
+    },
+}
+
+impl BuildError {
+    /// The config file that couldn't be loaded
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Settings { path, .. } | Self::Records { path, .. } => path,
+        }
+    }
+}
+
+/// Loads the config files for an app and sets up the standard handlers.
+///
+/// ```ignore
+/// let (state, event_loop) = MonitorBuilder::new(app)
+///     .with_tf2_dir(tf2_dir)
+///     .without_demos()
+///     .build()?;
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub struct MonitorBuilder<'a> {
+    app: AppDetails<'a>,
+    settings_file: Option<PathBuf>,
+    records_file: Option<PathBuf>,
+    tf2_dir: Option<PathBuf>,
+    steam_user: Option<SteamID>,
+    demos: bool,
+    offline: bool,
+}
+
+impl<'a> MonitorBuilder<'a> {
+    #[must_use]
+    pub const fn new(app: AppDetails<'a>) -> Self {
+        Self {
+            app,
+            settings_file: None,
+            records_file: None,
+            tf2_dir: None,
+            steam_user: None,
+            demos: true,
+            offline: false,
+        }
+    }
+
+    /// Load and save the settings here instead of the config directory
+    #[must_use]
+    pub fn with_settings_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings_file = Some(path.into());
+        self
+    }
+
+    /// Load and save the player records here instead of the config directory
+    #[must_use]
+    pub fn with_records_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.records_file = Some(path.into());
+        self
+    }
+
+    /// Use this TF2 directory instead of trying to find it
+    #[must_use]
+    pub fn with_tf2_dir(mut self, tf2_dir: impl Into<PathBuf>) -> Self {
+        self.tf2_dir = Some(tf2_dir.into());
+        self
+    }
+
+    /// Use this Steam user instead of the one currently logged in
+    #[must_use]
+    pub const fn with_steam_user(mut self, steam_user: SteamID) -> Self {
+        self.steam_user = Some(steam_user);
+        self
+    }
+
+    /// Don't watch for or analyse demos
+    #[must_use]
+    pub const fn without_demos(mut self) -> Self {
+        self.demos = false;
+        self
+    }
+
+    /// Don't make any requests to the Steam API or the masterbase, see
+    /// [`Settings::offline`]
+    #[must_use]
+    pub const fn offline(mut self) -> Self {
+        self.offline = true;
+        self
+    }
+
+    /// Loads the settings, records, steam cache and upload queue, and sets up
+    /// the event loop
+    ///
+    /// # Errors
+    /// If the settings or records exist but could not be loaded
+    pub fn build(self) -> Result<(MonitorState, MonitorEventLoop), BuildError> {
+        Ok((self.load_state()?, self.event_loop()))
+    }
+
+    /// Loads the settings, records, steam cache and upload queue. Missing
+    /// files are created, anything else that can't be loaded is logged and
+    /// left empty.
+    ///
+    /// # Errors
+    /// If the settings or records exist but could not be loaded
+    pub fn load_state(&self) -> Result<MonitorState, BuildError> {
+        let app = self.app;
+
+        let settings_path = self.settings_file.clone().unwrap_or_else(|| {
+            Settings::default_file_location(app).unwrap_or_else(|e| {
+                tracing::error!("Failed to find a suitable location to store settings ({e}). Settings will be written to {CONFIG_FILE_NAME}");
+                CONFIG_FILE_NAME.into()
+            })
+        });
+        let mut settings = Settings::load_or_create(settings_path.clone()).map_err(|source| {
+            BuildError::Settings {
+                path: settings_path,
+                source,
+            }
+        })?;
+        settings.save_ok();
+        settings.offline = self.offline;
+
+        match self.steam_user {
+            Some(steam_user) => settings.steam_user = Some(steam_user),
+            None => {
+                if let Err(e) = settings.infer_steam_user() {
+                    tracing::error!("Failed to infer steam user: {e}");
+                }
+            }
+        }
+
+        match &self.tf2_dir {
+            Some(tf2_dir) => settings.tf2_directory = Some(tf2_dir.clone()),
+            None => {
+                if let Err(e) = settings.infer_tf2_directory() {
+                    tracing::error!("Failed to locate TF2 directory: {e}");
+                }
+            }
+        }
+
+        let records_path = self.records_file.clone().unwrap_or_else(|| {
+            Records::default_file_location(app).unwrap_or_else(|e| {
+                tracing::error!("Failed to find a suitable location to store player records ({e}). Records will be written to {RECORDS_FILE_NAME}");
+                RECORDS_FILE_NAME.into()
+            })
+        });
+        let mut playerlist = Records::load_or_create(records_path.clone()).map_err(|source| {
+            BuildError::Records {
+                path: records_path,
+                source,
+            }
+        })?;
+        playerlist.save_ok();
+
+        let mut players = Players::new(
+            playerlist,
+            settings.steam_user,
+            Players::default_steam_cache_path(app).ok(),
+        );
+        players.scorer = Scorer::new(&settings.suspicion);
+        players.name_rules = NameRules::new(&settings.name_rules);
+
+        // Demo uploads that were interrupted last time
+        let upload_queue = UploadQueue::default_file_location(app)
+            .and_then(UploadQueue::load_or_create)
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to load the demo upload queue: {e}");
+                UploadQueue::default()
+            });
+
+        Ok(MonitorState {
+            server: Server::new(),
+            settings,
+            players,
+            upload_status: UploadStatus::default(),
+            upload_queue,
+        })
+    }
+
+    /// The standard set of handlers, without any of the optional pieces that
+    /// were turned off
+    #[must_use]
+    pub fn event_loop(&self) -> MonitorEventLoop {
+        let mut event_loop = EventLoop::new()
+            .add_handler(CommandManager::new())
+            .add_handler(ConsoleParser::default())
+            .add_handler(ExtractNewPlayers)
+            .add_handler(LookupProfiles::new())
+            .add_handler(DetectNewBans)
+            .add_handler(DetectNameChanges)
+            .add_handler(FetchUserFriends::new())
+            .add_handler(FetchBotLists::new())
+            .add_handler(LookupConvictions::new());
+        if self.demos {
+            event_loop = event_loop.add_handler(DemoManager::new());
+        }
+        event_loop.add_handler(LookupFriends::new())
+    }
+}
@@ -9,9 +9,10 @@ use self::{
     commands::{
         g15::{G15Player, Parser},
         regexes::{
-            ChatMessage, DemoStop, Hostname, Map, PlayerCount, PlayerKill, ServerIP, StatusLine,
-            REGEX_CHAT, REGEX_DEMOSTOP, REGEX_HOSTNAME, REGEX_IP, REGEX_KILL, REGEX_MAP,
-            REGEX_PLAYERCOUNT, REGEX_STATUS,
+            ChatMessage, DemoStop, Hostname, LobbyMember, Map, PlayerCount, PlayerKill, ServerIP,
+            StatusLine, VoteResult, REGEX_CHAT, REGEX_DEMOSTOP, REGEX_HOSTNAME, REGEX_IP,
+            REGEX_KILL, REGEX_LOBBY_MEMBER, REGEX_MAP, REGEX_PLAYERCOUNT, REGEX_STATUS,
+            REGEX_VOTE_RESULT,
         },
     },
     watcher::Watcher,
@@ -73,16 +74,26 @@ pub enum ConsoleOutput {
     PlayerCount(PlayerCount),
     G15(Vec<G15Player>),
     DemoStop(DemoStop),
+    VoteResult(VoteResult),
+    /// Everyone in the matchmaking lobby, from one `tf_lobby_debug`
+    LobbyDebug(Vec<LobbyMember>),
 }
 impl Message<MonitorState> for ConsoleOutput {
     fn preprocess(&mut self, state: &MonitorState) {
         match self {
+            // Only fall back to matching names if the SteamIDs weren't already known
             Self::Chat(m) => {
-                m.steamid = state.players.get_steamid_from_name(&m.player_name);
+                m.steamid = m
+                    .steamid
+                    .or_else(|| state.players.get_steamid_from_name(&m.player_name));
             }
             Self::Kill(m) => {
-                m.killer_steamid = state.players.get_steamid_from_name(&m.killer_name);
-                m.victim_steamid = state.players.get_steamid_from_name(&m.victim_name);
+                m.killer_steamid = m
+                    .killer_steamid
+                    .or_else(|| state.players.get_steamid_from_name(&m.killer_name));
+                m.victim_steamid = m
+                    .victim_steamid
+                    .or_else(|| state.players.get_steamid_from_name(&m.victim_name));
             }
             _ => {}
         }
@@ -103,6 +114,8 @@ pub struct ConsoleParser {
     regex_map: Regex,
     regex_playercount: Regex,
     regex_demostop: Regex,
+    regex_vote_result: Regex,
+    regex_lobby_member: Regex,
 }
 
 impl Default for ConsoleParser {
@@ -117,6 +130,8 @@ impl Default for ConsoleParser {
             regex_map: Regex::new(REGEX_MAP).expect("Compile static regex"),
             regex_playercount: Regex::new(REGEX_PLAYERCOUNT).expect("Compile static regex"),
             regex_demostop: Regex::new(REGEX_DEMOSTOP).expect("Compile static regex"),
+            regex_vote_result: Regex::new(REGEX_VOTE_RESULT).expect("Compile static regex"),
+            regex_lobby_member: Regex::new(REGEX_LOBBY_MEMBER).expect("Compile static regex"),
         }
     }
 }
@@ -130,6 +145,7 @@ where
         let mut out: Vec<Option<Handled<OM>>> = Vec::new();
 
         let RawConsoleOutput(console_out): &RawConsoleOutput = message.try_get()?;
+        let mut lobby = Vec::new();
 
         // Check all the single-line stuff
         for line in console_out.lines() {
@@ -178,6 +194,22 @@ where
                 let demostop = DemoStop::parse(&caps);
                 out.push(Handled::single(ConsoleOutput::DemoStop(demostop)));
             }
+            // Match vote results
+            if let Some(caps) = self.regex_vote_result.captures(line) {
+                let result = VoteResult::parse(&caps);
+                out.push(Handled::single(ConsoleOutput::VoteResult(result)));
+            }
+            // Match lobby members
+            if let Some(caps) = self.regex_lobby_member.captures(line) {
+                match LobbyMember::parse(&caps) {
+                    Ok(member) => lobby.push(member),
+                    Err(e) => tracing::error!("Error parsing lobby member: {:?}", e),
+                }
+            }
+        }
+
+        if !lobby.is_empty() {
+            out.push(Handled::single(ConsoleOutput::LobbyDebug(lobby)));
         }
 
         // Check for G15
@@ -2,12 +2,12 @@ use std::{
     fmt::{Debug, Display},
     io::ErrorKind,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use event_loop::{try_get, Handled, Is, MessageHandler};
 use rcon::Connection;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::{net::TcpStream, sync::Mutex, time::timeout};
 
@@ -17,6 +17,7 @@ use crate::{
         game_info::{PlayerState, Team},
         records::Verdict,
     },
+    settings::instance,
     MonitorState,
 };
 
@@ -25,7 +26,15 @@ use super::RawConsoleOutput;
 pub mod g15;
 pub mod regexes;
 
-#[derive(Debug, Deserialize, Clone)]
+/// Refreshes closer together than this are dropped, so ones that pile up while
+/// the app is busy don't all send status and `g15_dumpplayer` at once
+const MIN_REFRESH_SPACING: Duration = Duration::from_secs(1);
+/// How many refreshes go by between each `tf_lobby_debug` on Valve servers.
+/// The lobby only changes when players join or leave, so it doesn't need
+/// checking as often as status.
+const LOBBY_DEBUG_REFRESHES: u32 = 5;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum KickReason {
     None,
@@ -40,6 +49,19 @@ impl Default for KickReason {
     }
 }
 
+impl KickReason {
+    /// The reason to give for kicking a player with this verdict, see
+    /// [`Settings::kick_reason`](crate::settings::Settings::kick_reason) for
+    /// the one that should actually be used
+    #[must_use]
+    pub const fn for_verdict(verdict: Verdict) -> Self {
+        match verdict {
+            Verdict::Bot | Verdict::Cheater => Self::Cheating,
+            Verdict::Player | Verdict::Suspicious | Verdict::Trusted => Self::None,
+        }
+    }
+}
+
 impl Display for KickReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
@@ -99,6 +121,9 @@ enum ErrorState {
 pub enum Command {
     G15,
     Status,
+    /// Lists the matchmaking lobby, which only exists on Valve servers. It
+    /// prints an error on community servers.
+    LobbyDebug,
     Say(String),
     SayTeam(String),
     Kick {
@@ -117,21 +142,31 @@ impl Display for Command {
         match self {
             Self::G15 => f.write_str("g15_dumpplayer"),
             Self::Status => f.write_str("status"),
+            Self::LobbyDebug => f.write_str("tf_lobby_debug"),
             Self::Kick { player, reason } => {
-                write!(f, "callvote kick \"{player} {reason}\"")
+                write!(f, "callvote kick {}", quoted(&format!("{player} {reason}")))
             }
-            Self::Say(message) => write!(f, "say \"{message}\""),
-            Self::SayTeam(message) => write!(f, "say_team \"{message}\""),
+            Self::Say(message) => write!(f, "say {}", quoted(message)),
+            Self::SayTeam(message) => write!(f, "say_team {}", quoted(message)),
             Self::Custom(command) => write!(f, "{command}"),
         }
     }
 }
 
+/// Wraps a console command's argument in quotes so it's kept together. The
+/// console has no way to escape a quote inside one, so they're swapped for
+/// single quotes.
+fn quoted(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('"', "'"))
+}
+
 // Handlers ****************************
 
 pub struct CommandManager {
     inner: Arc<Mutex<CommandManagerInner>>,
     refresh_status: bool,
+    last_refresh: Option<Instant>,
+    refreshes_since_lobby_debug: u32,
 }
 
 struct CommandManagerInner {
@@ -282,6 +317,8 @@ impl CommandManager {
         Self {
             inner: Arc::new(Mutex::new(CommandManagerInner::new())),
             refresh_status: false,
+            last_refresh: None,
+            refreshes_since_lobby_debug: 0,
         }
     }
 
@@ -320,6 +357,22 @@ where
         let pwd = &state.settings.rcon_password;
 
         if try_get::<Refresh>(message).is_some() {
+            if self
+                .last_refresh
+                .is_some_and(|t| t.elapsed() < MIN_REFRESH_SPACING)
+            {
+                return Handled::none();
+            }
+            self.last_refresh = Some(Instant::now());
+
+            self.refreshes_since_lobby_debug += 1;
+            if state.server.is_valve_server()
+                && self.refreshes_since_lobby_debug >= LOBBY_DEBUG_REFRESHES
+            {
+                self.refreshes_since_lobby_debug = 0;
+                return self.run_command(&Command::LobbyDebug, port, pwd.to_owned());
+            }
+
             self.refresh_status = !self.refresh_status;
             if self.refresh_status {
                 return self.run_command(&Command::Status, port, pwd.to_owned());
@@ -327,7 +380,15 @@ where
             return self.run_command(&Command::G15, port, pwd.to_owned());
         }
 
-        self.run_command(try_get::<Command>(message)?, port, pwd.to_owned())
+        // A read-only instance still asks for status to show who is in the
+        // server, but leaves everything else to the instance that isn't
+        let command = try_get::<Command>(message)?;
+        if instance::is_read_only() {
+            tracing::warn!("Not running {command} in read-only mode");
+            return Handled::none();
+        }
+
+        self.run_command(command, port, pwd.to_owned())
     }
 }
 
@@ -354,26 +415,85 @@ where
             .players
             .connected
             .iter()
-            .filter(|s| {
-                state
-                    .players
-                    .records
-                    .get(*s)
-                    .is_some_and(|r| r.verdict() == Verdict::Bot)
-            })
+            .filter(|&&s| state.players.verdict(s) == Verdict::Bot)
             .filter_map(|s| state.players.game_info.get(s))
             .filter(|gi| {
                 gi.team == user_team
                     && gi.team != Team::Unassigned
                     && gi.state == PlayerState::Active
             })
-            .map(|gi| gi.userid.clone())
-            .map(|id| Command::Kick {
-                player: id,
-                reason: KickReason::Cheating,
+            .map(|gi| Command::Kick {
+                player: gi.userid.clone(),
+                reason: state.settings.kick_reason(Verdict::Bot),
             })
             .map(|c| Handled::single(c));
 
         Handled::multiple(to_kick)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use event_loop::{define_events, Action, EventLoop};
+
+    use super::{Command, CommandManager, KickReason};
+    use crate::{
+        console::RawConsoleOutput,
+        events::Refresh,
+        masterbase::{queue::UploadQueue, UploadStatus},
+        players::{records::Records, Players},
+        server::Server,
+        settings::Settings,
+        MonitorState,
+    };
+
+    define_events!(
+        MonitorState,
+        TestMessage {
+            Refresh,
+            Command,
+            RawConsoleOutput,
+        },
+        TestHandler { CommandManager },
+    );
+
+    #[test]
+    fn refreshes_are_coalesced() {
+        let mut state = MonitorState {
+            server: Server::new(),
+            settings: Settings::default(),
+            players: Players::new(Records::default(), None, None),
+            upload_status: UploadStatus::default(),
+            upload_queue: UploadQueue::default(),
+        };
+        let mut event_loop: EventLoop<MonitorState, TestMessage, TestHandler> =
+            EventLoop::new().add_handler(CommandManager::new());
+
+        let commands_sent: usize = (0..10)
+            .map(|_| {
+                event_loop
+                    .handle_message(Refresh.into(), &mut state)
+                    .iter()
+                    .filter(|a| matches!(a, Action::Future(_)))
+                    .count()
+            })
+            .sum();
+        assert_eq!(commands_sent, 1);
+
+        // Commands asked for directly aren't held back
+        let actions = event_loop.handle_message(Command::Status.into(), &mut state);
+        assert_eq!(actions.len(), 1);
+    }
+
+    #[test]
+    fn arguments_are_quoted() {
+        let kick = Command::Kick {
+            player: String::from("12"),
+            reason: KickReason::Cheating,
+        };
+        assert_eq!(kick.to_string(), "callvote kick \"12 cheating\"");
+
+        let say = Command::Say(String::from("a \"quoted\" message; status"));
+        assert_eq!(say.to_string(), "say \"a 'quoted' message; status\"");
+    }
+}
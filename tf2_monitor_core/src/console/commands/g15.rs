@@ -5,6 +5,7 @@
 use anyhow::Result;
 use regex::{Captures, Regex};
 use steamid_ng::SteamID;
+use tf_demo_parser::demo::parser::analyser::Class;
 use thiserror::Error;
 
 use crate::players::game_info::Team;
@@ -130,6 +131,29 @@ pub fn parse_alive(caps: &Captures, players: &mut [G15Player]) -> Result<()> {
     Ok(())
 }
 
+/// `m_iPlayerClass[5] integer (7)` --> capture groups: `(player idx)` `(class
+/// (0=none, 1=scout, 2=sniper, 3=soldier, 4=demoman, 5=medic, 6=heavy, 7=pyro,
+/// 8=spy, 9=engineer))`
+pub const REGEX_I_CLASS: &str = r"^m_iPlayerClass\[(\d+)\]\s+integer\s+\(([0-9])\)$";
+pub fn parse_class(caps: &Captures, players: &mut [G15Player]) -> Result<()> {
+    let idx: usize = caps[1].parse()?;
+    let class = match &caps[2] {
+        "1" => Class::Scout,
+        "2" => Class::Sniper,
+        "3" => Class::Soldier,
+        "4" => Class::Demoman,
+        "5" => Class::Medic,
+        "6" => Class::Heavy,
+        "7" => Class::Pyro,
+        "8" => Class::Spy,
+        "9" => Class::Engineer,
+        _ => Class::Other,
+    };
+    let player_ref = players.get_mut(idx).ok_or(Error::IndexOutOfBounds)?;
+    player_ref.class = Some(class);
+    Ok(())
+}
+
 /// `m_iHealth[3] integer (125)` --> capture groups: `(player idx)` `(current
 /// health)`
 pub const REGEX_I_HEALTH: &str = r"^m_iHealth\[(\d+)\]\s+integer\s+\((\d+)\)$";
@@ -196,6 +220,7 @@ pub struct G15Player {
     pub connected: Option<bool>,  // eg true
     pub valid: Option<bool>,      // eg true
     pub alive: Option<bool>,      // eg true
+    pub class: Option<Class>,     // eg 7
     pub userid: Option<String>,   // eg "301"
 }
 impl G15Player {
@@ -210,6 +235,7 @@ impl G15Player {
             health: None,
             ammo: None,
             alive: None,
+            class: None,
             connected: None,
             userid: None,
             valid: None,
@@ -241,6 +267,7 @@ impl Parser {
                 RegMatch::new(REGEX_B_CONNECTED, parse_connected),
                 RegMatch::new(REGEX_I_TEAM, parse_team),
                 RegMatch::new(REGEX_B_ALIVE, parse_alive),
+                RegMatch::new(REGEX_I_CLASS, parse_class),
                 RegMatch::new(REGEX_I_HEALTH, parse_health),
                 RegMatch::new(REGEX_I_SID3, parse_sid3),
                 RegMatch::new(REGEX_B_VALID, parse_valid),
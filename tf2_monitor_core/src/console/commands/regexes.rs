@@ -7,12 +7,19 @@ use regex::Captures;
 use serde::{Deserialize, Serialize};
 use steamid_ng::SteamID;
 
-use crate::players::{game_info::PlayerState, serialize_maybe_steamid_as_string};
+use crate::{
+    players::{
+        game_info::{PlayerState, Team},
+        serialize_maybe_steamid_as_string, serialize_steamid_as_string,
+    },
+    server::VoteOutcome,
+};
 
 /*
     Useful commands:
         status
         g15_dumpplayer
+        tf_lobby_debug
 
         callvote kick <userid>
         vote option<1/2> // Can't really use
@@ -88,6 +95,9 @@ pub struct PlayerKill {
     pub weapon: String,
     pub crit: bool,
     pub timestamp: DateTime<Utc>,
+    /// The demo tick of kills that came from the demo rather than the console
+    #[serde(skip)]
+    pub tick: Option<u32>,
 }
 
 impl PlayerKill {
@@ -101,8 +111,29 @@ impl PlayerKill {
             weapon: caps[3].into(),
             crit: caps.get(4).is_some(),
             timestamp: Utc::now(),
+            tick: None,
         }
     }
+
+    /// Whether both kills were between the same players, going by their
+    /// SteamIDs where both are known and their names otherwise
+    #[must_use]
+    pub fn same_players(&self, other: &Self) -> bool {
+        fn same(a: (Option<SteamID>, &str), b: (Option<SteamID>, &str)) -> bool {
+            match (a.0, b.0) {
+                (Some(a), Some(b)) => a == b,
+                _ => a.1 == b.1,
+            }
+        }
+
+        same(
+            (self.killer_steamid, &self.killer_name),
+            (other.killer_steamid, &other.killer_name),
+        ) && same(
+            (self.victim_steamid, &self.victim_name),
+            (other.victim_steamid, &other.victim_name),
+        )
+    }
 }
 
 /// Chat message
@@ -192,6 +223,58 @@ impl DemoStop {
     }
 }
 
+pub const REGEX_VOTE_RESULT: &str = r"^Vote (passed|failed)";
+
+#[derive(Debug, Clone)]
+pub struct VoteResult(pub VoteOutcome);
+
+impl VoteResult {
+    #[must_use]
+    pub fn parse(caps: &Captures) -> Self {
+        Self(if &caps[1] == "passed" {
+            VoteOutcome::Passed
+        } else {
+            VoteOutcome::Failed
+        })
+    }
+}
+
+// Reads the members of the matchmaking lobby from the output of
+// "tf_lobby_debug", which only has any on Valve servers
+// Example:   Member[0] [U:1:1076379306]  team = TF_GC_TEAM_DEFENDERS  type = MATCH_PLAYER
+// Players who are still being matched in show up as Pending instead of Member
+pub const REGEX_LOBBY_MEMBER: &str =
+    r"^\s*(Member|Pending)\[\d+\]\s+(\[U:\d:\d+\])\s+team = (\w+)\s+type = (\w+)";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LobbyMember {
+    #[serde(serialize_with = "serialize_steamid_as_string")]
+    pub steamid: SteamID,
+    pub team: Team,
+    /// e.g. `MATCH_PLAYER`, or `INVALID_PLAYER` for someone who has left
+    pub member_type: String,
+    pub pending: bool,
+}
+
+impl LobbyMember {
+    /// # Errors
+    /// If it contains an invalid `SteamID`
+    pub fn parse(caps: &Captures) -> Result<Self> {
+        let team = match &caps[3] {
+            "TF_GC_TEAM_DEFENDERS" => Team::Red,
+            "TF_GC_TEAM_INVADERS" => Team::Blu,
+            _ => Team::Unassigned,
+        };
+
+        Ok(Self {
+            steamid: SteamID::from_steam3(&caps[2]).context("Failed to decode steamid.")?,
+            team,
+            member_type: caps[4].into(),
+            pending: &caps[1] == "Pending",
+        })
+    }
+}
+
 // Converts a given string time (e.g. 57:48 or 1:14:46) as an integer number of
 // seconds
 #[allow(clippy::cast_possible_truncation)]
@@ -209,8 +292,9 @@ fn get_time(input: &str) -> Option<u32> {
     Some(t)
 }
 
-#[allow(dead_code)]
-const INVIS_CHARS: &[char] = &[
+/// Characters that don't show up in names, which bots add to copied names
+/// so they aren't exactly the same
+pub const INVIS_CHARS: &[char] = &[
     '\u{00a0}',
     '\u{00ad}',
     '\u{034f}',
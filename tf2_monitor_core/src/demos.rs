@@ -1,4 +1,5 @@
 use bitbuffer::{BitError, BitRead, BitReadBuffer, BitReadStream, LittleEndian};
+use chrono::Utc;
 use event_loop::{try_get, Handled, Is, MessageHandler, MessageSource};
 use notify::{event::ModifyKind, Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
 use std::{
@@ -27,9 +28,11 @@ use thiserror::Error;
 use tokio::sync::{Mutex, MutexGuard};
 
 use crate::{
+    console::commands::regexes::PlayerKill,
     events::UserUpdates,
-    masterbase::{DemoSession, ReportReason},
+    masterbase::{self, queue::QueuedUpload, DemoSession, ReportReason, UploadUpdate},
     players::new_players::NewPlayers,
+    server::VoteOutcome,
     settings::Settings,
     MonitorState,
 };
@@ -45,7 +48,9 @@ pub struct DemoMessage {
 }
 impl event_loop::Message<MonitorState> for DemoMessage {
     fn update_state(self, state: &mut MonitorState) {
-        state.server.handle_demo_message(self, &state.players);
+        if let Some(vote) = state.server.handle_demo_message(self, &state.players) {
+            state.players.record_vote(&vote);
+        }
     }
 }
 
@@ -55,6 +60,8 @@ pub enum DemoEvent {
     VoteStarted(VoteStartedEvent),
     VoteOptions(Box<VoteOptionsEvent>),
     VoteCast(VoteCastEvent, Option<SteamID>),
+    VoteEnded(VoteOutcome),
+    Kill(PlayerKill),
     LatestTick,
 }
 
@@ -278,18 +285,23 @@ impl DemoManagerSession {
 
     /// Returns an event which opens a new session.
     /// This event needs to be handled by the event loop to take effect.
-    fn open_new_session<M: Is<DemoMessage>>(
+    fn open_new_session<M: Is<UploadUpdate>>(
         &mut self,
         settings: &Settings,
         header: &Header,
-        demo_name: &str,
+        demo_path: &Path,
     ) -> Option<Handled<M>> {
         let host = settings.masterbase_host.clone();
         let key = settings.masterbase_key.clone();
         let map = header.map.clone();
         let fake_ip = header.server.clone();
         let http = settings.masterbase_http;
-        let demo_name = demo_name.to_owned();
+        let demo_path = demo_path.to_path_buf();
+        let demo_name = demo_path
+            .file_name()
+            .expect("Should be valid file.")
+            .to_string_lossy()
+            .to_string();
         let session = self.0.clone();
 
         Handled::future(async move {
@@ -302,14 +314,28 @@ impl DemoManagerSession {
                 Ok(session) => {
                     tracing::info!("Opened new demo session with Masterbase: {session:?}");
                     *maybe_session = Ok(session);
+                    Some(UploadUpdate::SessionOpened(demo_name).into())
+                }
+                // Couldn't reach the masterbase, so try again later
+                Err(e @ (masterbase::Error::Reqwest(_) | masterbase::Error::WebSocket(_))) => {
+                    tracing::error!("Could not open new demo session: {e}");
+                    *maybe_session = Err(SessionMissingReason::Error);
+                    Some(
+                        UploadUpdate::Interrupted(QueuedUpload::new(
+                            demo_path,
+                            0,
+                            None,
+                            format!("Could not open demo session: {e}"),
+                        ))
+                        .into(),
+                    )
                 }
                 Err(e) => {
                     tracing::error!("Could not open new demo session: {e}");
                     *maybe_session = Err(SessionMissingReason::Error);
+                    Some(UploadUpdate::from_error("Could not open demo session", &e).into())
                 }
             }
-
-            None
         })
     }
 }
@@ -399,7 +425,11 @@ impl DemoManager {
 
     /// Returns an event that uploads the given bytes to the current session.
     /// This event needs to be handled by the event loop to take effect.
-    fn upload_bytes<M: Is<DemoMessage>>(&mut self, bytes: Vec<u8>) -> Option<Handled<M>> {
+    fn upload_bytes<M: Is<UploadUpdate>>(
+        &mut self,
+        demo_path: PathBuf,
+        bytes: Vec<u8>,
+    ) -> Option<Handled<M>> {
         // Loop while session is uninit
         let mut session = self.session.clone();
         Handled::future(async move {
@@ -409,29 +439,48 @@ impl DemoManager {
                     let len = bytes.len();
                     if let Err(e) = session.send_bytes(bytes).await {
                         tracing::error!("Failed to upload demo chunk: {e}");
-                        *guard = Err(SessionMissingReason::Error);
+
+                        // Leave the session open so the upload can be resumed
+                        let Ok(session) =
+                            std::mem::replace(&mut *guard, Err(SessionMissingReason::Error))
+                        else {
+                            unreachable!("Session was just used");
+                        };
                         drop(guard);
-                    } else {
-                        tracing::debug!("Uploaded {len} bytes to masterbase.");
+
+                        let offset = session.bytes_sent();
+                        let session_id = session.detach();
+                        return Some(
+                            UploadUpdate::Interrupted(QueuedUpload::new(
+                                demo_path,
+                                offset,
+                                Some(session_id),
+                                format!("Failed to upload demo: {e}"),
+                            ))
+                            .into(),
+                        );
                     }
+
+                    tracing::debug!("Uploaded {len} bytes to masterbase.");
+                    Some(UploadUpdate::BytesUploaded(len as u64).into())
                 }
                 Err(SessionMissingReason::Closed) => {
                     tracing::error!("Tried to upload bytes after demo session was closed.");
+                    None
                 }
                 Err(
                     SessionMissingReason::Error
                     | SessionMissingReason::Disabled
                     | SessionMissingReason::Uninit,
-                ) => {}
+                ) => None,
             }
-            None
         })
     }
 
     /// Returns an event that checks for and handles the late bytes for the
     /// current demo.
     /// This event needs to be handled by the event loop to take effect.
-    fn handle_late_bytes<M: Is<DemoMessage>>(&self, late_bytes: Vec<u8>) -> Option<Handled<M>> {
+    fn handle_late_bytes<M: Is<UploadUpdate>>(&self, late_bytes: Vec<u8>) -> Option<Handled<M>> {
         let mut session = self.session.clone();
         Handled::future(async move {
             let mut session_lock = session.get().await;
@@ -441,28 +490,31 @@ impl DemoManager {
                 return None;
             };
 
-            match session.send_late_bytes(late_bytes).await {
+            let update = match session.send_late_bytes(late_bytes).await {
                 Ok(send_response) => {
                     let status = send_response.status();
                     if status.is_success() {
                         tracing::debug!(
                             "Uploaded late bytes to masterbase. Attempting to close session..."
                         );
+                        UploadUpdate::SessionClosed
                     } else {
                         let s = status.as_str();
                         tracing::error!(
                             "Failed to upload late bytes to masterbase: Server returned {s}"
                         );
+                        UploadUpdate::from_status("Failed to finish demo upload", status)
                     }
                 }
                 Err(e) => {
                     tracing::error!("Failed to upload late bytes to masterbase: {e}");
+                    UploadUpdate::from_error("Failed to finish demo upload", &e)
                 }
-            }
+            };
 
             // Drop session
             *session_lock = Err(SessionMissingReason::Closed);
-            None
+            Some(update.into())
         })
     }
 
@@ -507,7 +559,7 @@ impl DemoManager {
         }))
     }
 
-    fn handle_demo_bytes<M: Is<DemoMessage>>(
+    fn handle_demo_bytes<M: Is<DemoMessage> + Is<UploadUpdate>>(
         &mut self,
         state: &MonitorState,
         msg: &DemoBytes,
@@ -542,23 +594,16 @@ impl DemoManager {
             );
         }
 
-        if !state.settings.upload_demos {
+        if !state.settings.upload_demos || state.settings.offline {
             return Handled::multiple(events);
         }
 
-        // let file_name: &str = msg.file_path.file_name().unwrap().to_str().unwrap();
-        let file_name = msg
-            .file_path
-            .file_name()
-            .expect("Should be valid file.")
-            .to_string_lossy();
-
         // Open new demo session if we've extracted the header
         if let Some(header) = demo.header.as_ref() {
             if !parsed_header {
                 events.push(
                     self.session
-                        .open_new_session(&state.settings, header, &file_name),
+                        .open_new_session(&state.settings, header, &msg.file_path),
                 );
 
                 // Once a new session is opened, report any bots already on the server
@@ -578,7 +623,7 @@ impl DemoManager {
 
         // Upload bytes
         let bytes = msg.bytes.clone();
-        events.push(self.upload_bytes(bytes));
+        events.push(self.upload_bytes(msg.file_path.clone(), bytes));
 
         // Check for late bytes
         if let Ok(Some(late_bytes)) = self.read_late_bytes() {
@@ -598,7 +643,7 @@ impl Default for DemoManager {
 impl<IM, OM> MessageHandler<MonitorState, IM, OM> for DemoManager
 where
     IM: Is<DemoBytes> + Is<NewPlayers> + Is<UserUpdates>,
-    OM: Is<DemoMessage>,
+    OM: Is<DemoMessage> + Is<UploadUpdate>,
 {
     fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
         // Report newly connecting bots
@@ -712,6 +757,16 @@ impl OpenDemo {
     }
 }
 
+/// The name and SteamID of the player with the given user ID
+fn find_player(state: &GameState, user_id: u16) -> Option<(String, Option<SteamID>)> {
+    state.players.iter().find_map(|p| {
+        p.info
+            .as_ref()
+            .filter(|i| i.user_id == user_id)
+            .map(|i| (i.name.clone(), SteamID::from_steam3(&i.steam_id).ok()))
+    })
+}
+
 #[allow(clippy::cognitive_complexity)]
 fn handle_packet(packet: &Packet, state: &GameState) -> Vec<DemoMessage> {
     let mut out = Vec::new();
@@ -768,15 +823,41 @@ fn handle_packet(packet: &Packet, state: &GameState) -> Vec<DemoMessage> {
                         }),
                     ),
                 }),
+                // Suicides and deaths to the world aren't in the console's killfeed either
+                GameEvent::PlayerDeath(e) if e.attacker != 0 && e.attacker != e.user_id => {
+                    let (Some(killer), Some(victim)) = (
+                        find_player(state, e.attacker),
+                        find_player(state, e.user_id),
+                    ) else {
+                        continue;
+                    };
+
+                    out.push(DemoMessage {
+                        tick: tick.0,
+                        event: DemoEvent::Kill(PlayerKill {
+                            killer_name: killer.0,
+                            killer_steamid: killer.1,
+                            victim_name: victim.0,
+                            victim_steamid: victim.1,
+                            weapon: e.weapon.to_string(),
+                            // Mini-crits are 1
+                            crit: e.crit_type == 2,
+                            timestamp: Utc::now(),
+                            tick: Some(tick.0),
+                        }),
+                    });
+                }
+                GameEvent::VotePassed(_) => out.push(DemoMessage {
+                    tick: tick.0,
+                    event: DemoEvent::VoteEnded(VoteOutcome::Passed),
+                }),
+                GameEvent::VoteFailed(_) => out.push(DemoMessage {
+                    tick: tick.0,
+                    event: DemoEvent::VoteEnded(VoteOutcome::Failed),
+                }),
                 // GameEvent::VoteEnded(e) => {
                 //     tracing::info!("Vote ended: {:?}", e);
                 // }
-                // GameEvent::VotePassed(e) => {
-                //     tracing::info!("Vote passed: {:?}", e);
-                // }
-                // GameEvent::VoteFailed(e) => {
-                //     tracing::info!("Vote failed: {:?}", e);
-                // }
                 // GameEvent::VoteChanged(e) => {
                 //     tracing::info!("Vote changed: {:?}", e);
                 // }
@@ -26,6 +26,11 @@ use tokio::io::AsyncReadExt;
 
 pub mod progress;
 
+/// Bumped whenever the analysis changes in a way that makes demos analysed
+/// before it worth analysing again, e.g. when new fields are added to
+/// [`AnalysedDemo`]
+pub const ANALYSER_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysedDemo {
     pub user: SteamID,
@@ -36,6 +41,18 @@ pub struct AnalysedDemo {
     pub players: HashMap<SteamID, DemoPlayer>,
     pub kills: Vec<Death>,
     pub events: Vec<(DemoTick, Event)>,
+    /// The demo stopped parsing partway through, e.g. because the file is
+    /// corrupted or TF2 was closed while recording. Everything up to that
+    /// point is still included.
+    #[serde(default)]
+    pub truncated: bool,
+    /// The errors that stopped the demo from being read to the end
+    #[serde(default)]
+    pub parse_warnings: Vec<String>,
+    /// The [`ANALYSER_VERSION`] the demo was analysed with. Demos cached before
+    /// versions were added are 0.
+    #[serde(default)]
+    pub analyser_version: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -149,6 +166,13 @@ impl DemoPlayer {
 }
 
 impl AnalysedDemo {
+    /// Whether the demo was analysed by an older version of the analyser and
+    /// may be missing information
+    #[must_use]
+    pub const fn is_outdated(&self) -> bool {
+        self.analyser_version < ANALYSER_VERSION
+    }
+
     /// Takes in a slice of bytes making up a demo and attempts to extract some useful information from it.
     /// Extracted information includes:
     /// * Demo header
@@ -162,8 +186,11 @@ impl AnalysedDemo {
     /// A `progress` field is only for if you would like to be able to check on the progress of
     /// demo analysis, and can safely be given `None` otherwise.
     ///
+    /// If the demo becomes unreadable after the header, analysis stops there and what was read
+    /// so far is returned, with `truncated` set and the error in `parse_warnings`.
+    ///
     /// # Errors
-    /// If the demo failed to parse for some reason
+    /// If the demo header failed to parse
     #[allow(clippy::too_many_lines)]
     pub fn new(demo_bytes: &[u8], mut progress: Option<progress::Updater>) -> Result<Self, Error> {
         let demo = Demo::new(demo_bytes);
@@ -180,6 +207,9 @@ impl AnalysedDemo {
             players: HashMap::new(),
             kills: Vec::new(),
             events: Vec::new(),
+            truncated: false,
+            parse_warnings: Vec::new(),
+            analyser_version: ANALYSER_VERSION,
         };
 
         // Total number of bits in the demo
@@ -200,7 +230,15 @@ impl AnalysedDemo {
         let mut last_tick = ServerTick::from(0u32);
         let mut num_ticks_checked = 0u64;
         let mut last_kills_len = 0;
-        while let Some(packet) = packets.next(&handler.state_handler)? {
+        loop {
+            let packet = match packets.next(&handler.state_handler) {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(e) => {
+                    analysed_demo.stop_early(&e);
+                    break;
+                }
+            };
             let mut newly_connected: Option<(String, u16)> = None;
 
             // Custom packet handling
@@ -241,7 +279,10 @@ impl AnalysedDemo {
                 _ => {}
             }
 
-            handler.handle_packet(packet)?;
+            if let Err(e) = handler.handle_packet(packet) {
+                analysed_demo.stop_early(&e);
+                break;
+            }
 
             if let Some((name, userid)) = newly_connected {
                 if let Some(info) = handler
@@ -449,7 +490,7 @@ impl AnalysedDemo {
         analysed_demo
             .players
             .values_mut()
-            .for_each(|p| p.average_ping /= num_ticks_checked);
+            .for_each(|p| p.average_ping /= num_ticks_checked.max(1));
 
         // User
         if let Some(steamid) = handler
@@ -491,6 +532,12 @@ impl AnalysedDemo {
 
         Ok(analysed_demo)
     }
+
+    fn stop_early(&mut self, error: &ParseError) {
+        tracing::warn!("Demo could not be read to the end: {error}");
+        self.truncated = true;
+        self.parse_warnings.push(error.to_string());
+    }
 }
 
 /// Takes a hash of the header and created time of a demo file
@@ -11,7 +11,7 @@ use crate::{players::records::Verdict, settings::FriendsAPIUsage, MonitorState};
 pub struct Refresh;
 impl Message<MonitorState> for Refresh {
     fn update_state(self, state: &mut MonitorState) {
-        state.players.refresh();
+        state.players.refresh(state.settings.max_history_len);
     }
 
     #[allow(unused_variables)]
@@ -92,6 +92,13 @@ pub struct InternalPreferences {
     pub rcon_port: Option<u16>,
     pub dumb_autokick: Option<bool>,
     pub request_playtime: Option<bool>,
+    pub steam_info_cache_hours: Option<u32>,
+    pub request_steam_level: Option<bool>,
+    pub request_groups: Option<bool>,
+    pub group_watchlist: Option<Vec<u64>>,
+    pub mark_group_members_suspicious: Option<bool>,
+    pub bot_list_subscriptions: Option<Vec<String>>,
+    pub max_history_len: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -132,6 +139,28 @@ impl Message<MonitorState> for Preferences {
             if let Some(request_playtime) = internal.request_playtime {
                 state.settings.request_playtime = request_playtime;
             }
+            if let Some(cache_hours) = internal.steam_info_cache_hours {
+                state.settings.steam_info_cache_hours = cache_hours;
+            }
+            if let Some(request_steam_level) = internal.request_steam_level {
+                state.settings.request_steam_level = request_steam_level;
+            }
+            if let Some(request_groups) = internal.request_groups {
+                state.settings.request_groups = request_groups;
+            }
+            if let Some(group_watchlist) = internal.group_watchlist {
+                state.settings.group_watchlist = group_watchlist;
+            }
+            if let Some(mark_suspicious) = internal.mark_group_members_suspicious {
+                state.settings.mark_group_members_suspicious = mark_suspicious;
+            }
+            if let Some(subscriptions) = internal.bot_list_subscriptions {
+                state.players.bot_lists.retain_subscribed(&subscriptions);
+                state.settings.bot_list_subscriptions = subscriptions;
+            }
+            if let Some(max_history_len) = internal.max_history_len {
+                state.settings.max_history_len = max_history_len;
+            }
         }
 
         if let Some(external) = self.external {
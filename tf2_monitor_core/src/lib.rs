@@ -1,3 +1,4 @@
+pub mod builder;
 pub mod console;
 pub mod demos;
 pub mod events;
@@ -8,10 +9,12 @@ pub mod settings;
 pub mod steam;
 
 use console::ConsoleOutput;
+use masterbase::{queue::UploadQueue, UploadStatus};
 use players::Players;
 use server::Server;
 use settings::Settings;
 
+pub use atomic_write_file;
 pub use bitbuffer;
 pub use event_loop;
 pub use md5;
@@ -25,19 +28,26 @@ pub struct MonitorState {
     pub server: Server,
     pub settings: Settings,
     pub players: Players,
+    pub upload_status: UploadStatus,
+    pub upload_queue: UploadQueue,
 }
 
 impl MonitorState {
     pub fn handle_console_output(&mut self, output: ConsoleOutput) {
         use ConsoleOutput::{
-            Chat, DemoStop, Hostname, Kill, Map, PlayerCount, ServerIP, Status, G15,
+            Chat, DemoStop, Hostname, Kill, LobbyDebug, Map, PlayerCount, ServerIP, Status,
+            VoteResult, G15,
         };
         match output {
             Status(inner) => self.players.handle_status_line(inner),
             G15(inner) => self.players.handle_g15(inner),
+            LobbyDebug(inner) => self.players.handle_lobby_debug(inner),
             DemoStop(_) => {}
-            Chat(_) | Kill(_) | Hostname(_) | ServerIP(_) | Map(_) | PlayerCount(_) => {
-                self.server.handle_console_output(output);
+            Chat(_) | Kill(_) | Hostname(_) | ServerIP(_) | Map(_) | PlayerCount(_)
+            | VoteResult(_) => {
+                if let Some(vote) = self.server.handle_console_output(output) {
+                    self.players.record_vote(&vote);
+                }
             }
         }
     }
@@ -3,15 +3,25 @@ use std::{
     fmt::{Debug, Display, Write},
 };
 
+use bitbuffer::{BitRead, BitReadBuffer, BitReadStream, LittleEndian};
 use futures::SinkExt;
-use reqwest::{Client, RequestBuilder, Response};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use steamid_ng::SteamID;
+use tf_demo_parser::demo::header::Header;
 use thiserror::Error;
 use tokio::{net::TcpStream, sync::mpsc::Sender};
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
-use crate::players::records::Verdict;
+use crate::{
+    demos::analyser::progress::{Progress, Updater},
+    players::records::Verdict,
+    MonitorState,
+};
+use queue::QueuedUpload;
+
+pub mod provision;
+pub mod queue;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -23,6 +33,105 @@ pub enum Error {
     WebSocket(#[from] tokio_tungstenite::tungstenite::error::Error),
     #[error("Request failed: {0}")]
     Failed(String),
+    #[error("Server returned {0}: {1}")]
+    Status(StatusCode, String),
+    #[error("Couldn't read demo header: {0}")]
+    Header(#[from] bitbuffer::BitError),
+}
+
+/// How much of an existing demo file is sent at a time
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+/// Where the bytes that are only written once recording stops are in a demo
+const LATE_BYTES_RANGE: std::ops::Range<usize> = 0x420..0x430;
+
+/// How uploading the current demo to the masterbase is going
+#[derive(Debug, Clone, Default)]
+pub struct UploadStatus {
+    pub session_open: bool,
+    /// The demo currently being uploaded, or the last one that was
+    pub demo_name: Option<String>,
+    pub bytes_uploaded: u64,
+    pub last_error: Option<String>,
+}
+
+impl UploadStatus {
+    pub fn update(&mut self, update: UploadUpdate) {
+        match update {
+            UploadUpdate::SessionOpened(demo_name) => {
+                self.session_open = true;
+                self.demo_name = Some(demo_name);
+                self.bytes_uploaded = 0;
+                self.last_error = None;
+            }
+            UploadUpdate::BytesUploaded(len) => self.bytes_uploaded += len,
+            UploadUpdate::SessionClosed => self.session_open = false,
+            UploadUpdate::Failed(e) => {
+                self.session_open = false;
+                self.last_error = Some(e);
+            }
+            UploadUpdate::Interrupted(upload) => {
+                self.session_open = false;
+                self.last_error = upload
+                    .last_error
+                    .map(|e| format!("{e} (queued to retry later)"));
+            }
+            UploadUpdate::Connected => self.last_error = None,
+        }
+    }
+}
+
+/// Sent by the demo uploader to keep the [`UploadStatus`] up to date
+#[derive(Debug, Clone)]
+pub enum UploadUpdate {
+    SessionOpened(String),
+    BytesUploaded(u64),
+    SessionClosed,
+    Failed(String),
+    /// The connection was lost, so the rest of the demo has to be uploaded
+    /// later
+    Interrupted(QueuedUpload),
+    /// The masterbase accepted the key without a session being open
+    Connected,
+}
+
+impl UploadUpdate {
+    #[must_use]
+    pub fn from_error(context: &str, error: &Error) -> Self {
+        Self::Failed(format!("{context}: {}", error_message(error)))
+    }
+
+    #[must_use]
+    pub fn from_status(context: &str, status: StatusCode) -> Self {
+        Self::Failed(format!("{context}: {}", status_message(status)))
+    }
+}
+
+/// A description of the error that makes sense to show to the user
+#[must_use]
+pub fn error_message(error: &Error) -> String {
+    match error {
+        Error::Status(status, _) => status_message(*status),
+        e => e.to_string(),
+    }
+}
+
+#[must_use]
+pub fn status_message(status: StatusCode) -> String {
+    match status {
+        StatusCode::UNAUTHORIZED => String::from("Your Masterbase key is not valid"),
+        StatusCode::FORBIDDEN => String::from("The Masterbase refused the request"),
+        StatusCode::CONFLICT => String::from("A session is already open for your key"),
+        s => format!("Server returned {s}"),
+    }
+}
+
+impl event_loop::Message<MonitorState> for UploadUpdate {
+    fn update_state(self, state: &mut MonitorState) {
+        if let Self::Interrupted(upload) = &self {
+            state.upload_queue.push(upload.clone());
+        }
+        state.upload_status.update(self);
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -82,6 +191,8 @@ pub type WebSocket = WebSocketStream<MaybeTlsStream<TcpStream>>;
 pub struct DemoSession {
     session_id: SessionID,
     ws_client: WebSocket,
+    /// How many demo bytes have been sent in this session
+    bytes_sent: u64,
 
     host: String,
     key: String,
@@ -94,12 +205,6 @@ impl DemoSession {
     ///   `fake_ip`, or `map`)
     /// * Network error
     /// * Invalid response
-    ///
-    /// # Panics
-    /// This function spawns a task responsible for closing the session out when the
-    /// resulting `DemoSession` is dropped. This task can panic if the `DemoSession`
-    /// fails to notify it, which should only be possible if the async runtime stops
-    /// before the session is dropped, meaning this shouldn't be able to occur.
     pub async fn new(
         host: String,
         key: String,
@@ -127,14 +232,39 @@ impl DemoSession {
         let url = reqwest::Url::parse_with_params(&endpoint, params)?;
         let response = reqwest::get(url).await?;
 
-        if !response.status().is_success() {
-            return Err(Error::Failed(response.text().await?));
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Status(status, response.text().await?));
         }
 
-        let mut session_id: SessionID = response.json().await?;
+        let session_id: SessionID = response.json().await?;
+        Self::connect(host, key, session_id.session_id, http).await
+    }
 
+    /// Reconnect to a session that was [detached](Self::detach) after losing
+    /// connection, to continue uploading the same demo.
+    ///
+    /// # Errors
+    /// * Network error
+    /// * The session has expired or been closed
+    pub async fn resume(
+        host: String,
+        key: String,
+        session_id: u128,
+        http: bool,
+    ) -> Result<Self, Error> {
+        tracing::debug!("Resuming demo session {session_id}");
+        Self::connect(host, key, session_id, http).await
+    }
+
+    /// Opens the websocket for an existing session, and arranges for the
+    /// session to be closed when this is dropped.
+    async fn connect(host: String, key: String, id: u128, http: bool) -> Result<Self, Error> {
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        session_id.close = Some(tx);
+        let session_id = SessionID {
+            session_id: id,
+            close: Some(tx),
+        };
 
         // Open Websocket
         let params: [(&str, &str); 2] =
@@ -148,17 +278,15 @@ impl DemoSession {
 
         // Wait for the dropped `DemoSession` to tell it to close the session.
         // When it receives a message over the channel, it makes the appropriate
-        // request.
+        // request. If the channel closes without a message the session was
+        // detached and is left open.
         {
             let host = host.clone();
             let key = key.clone();
-            let id = session_id.session_id;
-            // let host = host.to_owned();
-            // let key = key.to_owned();
             tokio::task::spawn(async move {
-                rx.recv()
-                    .await
-                    .expect("Didn't get closing message from DemoSession.");
+                if rx.recv().await.is_none() {
+                    return;
+                }
 
                 match force_close_session(&host, &key, http).await {
                     Ok(_) => tracing::info!("Closed session {id}."),
@@ -172,12 +300,31 @@ impl DemoSession {
         Ok(Self {
             session_id,
             ws_client,
+            bytes_sent: 0,
             host,
             key,
             http,
         })
     }
 
+    #[must_use]
+    pub const fn id(&self) -> u128 {
+        self.session_id.session_id
+    }
+
+    #[must_use]
+    pub const fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Drops the connection without closing the session on the masterbase,
+    /// so it can be [resumed](Self::resume) later. Returns the session id.
+    #[must_use]
+    pub fn detach(mut self) -> u128 {
+        self.session_id.close = None;
+        self.session_id.session_id
+    }
+
     /// # Errors
     /// If the client failed to send the bytes
     pub async fn send_bytes(
@@ -185,9 +332,12 @@ impl DemoSession {
         bytes: Vec<u8>,
     ) -> Result<(), tokio_tungstenite::tungstenite::Error> {
         tracing::debug!("Sending demo bytes to masterbase");
+        let len = bytes.len() as u64;
         self.ws_client
             .send(tokio_tungstenite::tungstenite::Message::Binary(bytes))
-            .await
+            .await?;
+        self.bytes_sent += len;
+        Ok(())
     }
 
     /// # Errors
@@ -291,3 +441,90 @@ pub async fn force_close_session(host: &str, key: &str, http: bool) -> Result<Re
 
     Ok(reqwest::get(url).await?)
 }
+
+/// Uploads a demo that has already finished recording, e.g. because the
+/// monitor wasn't running during the match. The session is closed once the
+/// whole file has been sent.
+///
+/// # Errors
+/// * The demo header couldn't be read
+/// * Any of the requests to the masterbase failed
+pub async fn upload_demo_file(
+    host: String,
+    key: String,
+    http: bool,
+    demo_name: &str,
+    bytes: &[u8],
+    mut progress: Updater,
+) -> Result<(), Error> {
+    let mut stream = BitReadStream::new(BitReadBuffer::new(bytes, LittleEndian));
+    let header = Header::read(&mut stream)?;
+
+    let mut session =
+        DemoSession::new(host, key, &header.server, &header.map, demo_name, http).await?;
+    tracing::info!("Opened demo session for uploading {demo_name}: {session:?}");
+
+    let mut sent = 0;
+    for chunk in bytes.chunks(UPLOAD_CHUNK_SIZE) {
+        session.send_bytes(chunk.to_vec()).await?;
+        sent += chunk.len();
+        #[allow(clippy::cast_precision_loss)]
+        progress.update_progress(Progress::InProgress(sent as f32 / bytes.len() as f32));
+    }
+
+    // Demos that weren't stopped properly never get their late bytes written
+    match bytes.get(LATE_BYTES_RANGE) {
+        Some(late_bytes) if late_bytes.iter().take(8).any(|&b| b != 0) => {
+            let response = session.send_late_bytes(late_bytes.to_vec()).await?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(Error::Status(status, response.text().await?));
+            }
+        }
+        _ => tracing::warn!("{demo_name} has no late bytes, it may not have finished recording."),
+    }
+
+    progress.update_progress(Progress::Finished);
+    tracing::info!("Finished uploading {demo_name} to the masterbase.");
+    Ok(())
+}
+
+/// Which of the given accounts have been convicted of cheating by the
+/// masterbase. Accounts it doesn't know about may be left out.
+///
+/// # Errors
+/// * Fails to parse Url (usually indicating a bad host or key was provided)
+/// * Web request failed or the server returned an error
+/// * Invalid response
+pub async fn fetch_convictions(
+    host: &str,
+    key: &str,
+    http: bool,
+    players: &[SteamID],
+) -> Result<HashMap<SteamID, bool>, Error> {
+    let steamids = players
+        .iter()
+        .map(|&s| u64::from(s).to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let params = [("api_key", key), ("steamids", &steamids)];
+
+    let endpoint = if http {
+        format!("http://{host}/convicted")
+    } else {
+        format!("https://{host}/convicted")
+    };
+    let url = reqwest::Url::parse_with_params(&endpoint, params)?;
+
+    let response = reqwest::get(url).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Status(status, response.text().await?));
+    }
+
+    let convicted: HashMap<String, bool> = response.json().await?;
+    Ok(convicted
+        .into_iter()
+        .filter_map(|(s, c)| s.parse::<u64>().ok().map(|s| (SteamID::from(s), c)))
+        .collect())
+}
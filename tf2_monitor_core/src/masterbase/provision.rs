@@ -0,0 +1,266 @@
+//! Getting a masterbase key without having to copy it out of the browser. The
+//! provision page is told to redirect to a local listener, which receives the
+//! key once the user has logged in through Steam. A random `state` is sent
+//! with the request and has to come back with the key, so other pages can't
+//! hand the listener a key of their own.
+
+use std::{sync::Arc, time::Duration};
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use url::Url;
+use uuid::Uuid;
+
+/// How long to wait for the user to log in before giving up
+pub const PROVISION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+const CALLBACK_PATH: &str = "/callback";
+/// The query parameters the key might be passed back in
+const KEY_PARAMS: [&str; 2] = ["api_key", "key"];
+const STATE_PARAM: &str = "state";
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+/// Connections that haven't sent their request by now are dropped
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+const KEY_RECEIVED_PAGE: &str = "<html><body><p>Your masterbase key has been received. You can close this tab and return to the app.</p></body></html>";
+
+#[derive(Debug, Error)]
+pub enum ProvisionError {
+    #[error("Couldn't start local listener: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to build provision url: {0}")]
+    UrlParse(#[from] url::ParseError),
+    #[error("Couldn't open the browser: {0}")]
+    Browser(std::io::Error),
+    #[error("Timed out waiting for the masterbase")]
+    TimedOut,
+}
+
+pub struct ProvisionListener {
+    listener: TcpListener,
+    port: u16,
+    /// Has to be in the callback for its key to be accepted
+    state: Arc<str>,
+}
+
+impl ProvisionListener {
+    /// Start listening on an ephemeral port on localhost
+    ///
+    /// # Errors
+    /// If the listener could not be bound
+    pub async fn bind() -> Result<Self, ProvisionError> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        Ok(Self {
+            listener,
+            port,
+            state: Uuid::new_v4().simple().to_string().into(),
+        })
+    }
+
+    #[must_use]
+    pub fn redirect_url(&self) -> String {
+        format!("http://127.0.0.1:{}{CALLBACK_PATH}", self.port)
+    }
+
+    /// # Errors
+    /// If the host doesn't make a valid url
+    pub fn provision_url(&self, host: &str, http: bool) -> Result<Url, ProvisionError> {
+        let mut url = Url::parse(&format!(
+            "{}://{host}/provision",
+            if http { "http" } else { "https" }
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("redirect", &self.redirect_url())
+            .append_pair(STATE_PARAM, &self.state);
+        Ok(url)
+    }
+
+    /// Wait for the callback with the key. Any other requests (e.g. the
+    /// browser asking for a favicon, or callbacks without the right state)
+    /// are ignored. Each connection is handled on its own task so one that
+    /// never sends anything doesn't hold up the rest.
+    ///
+    /// # Errors
+    /// If the listener stopped accepting connections
+    pub async fn wait_for_key(self) -> Result<String, ProvisionError> {
+        let (tx, mut rx) = mpsc::channel(1);
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, _) = accepted?;
+                    let tx = tx.clone();
+                    let state = self.state.clone();
+                    tokio::spawn(async move {
+                        let handled =
+                            tokio::time::timeout(CONNECTION_TIMEOUT, handle_connection(stream, &state));
+                        match handled.await {
+                            Ok(Ok(Some(key))) => {
+                                tx.send(key).await.ok();
+                            }
+                            Ok(Ok(None)) => {}
+                            Ok(Err(e)) => tracing::warn!("Failed to handle provision callback: {e}"),
+                            Err(_) => tracing::debug!("Dropped an idle provision connection"),
+                        }
+                    });
+                }
+                Some(key) = rx.recv() => return Ok(key),
+            }
+        }
+    }
+}
+
+/// Open the provision page in the browser and wait for the masterbase to send
+/// the new key back.
+///
+/// # Errors
+/// If the browser couldn't be opened, the local listener failed, or the key
+/// wasn't received before the timeout.
+pub async fn provision_key(
+    host: String,
+    http: bool,
+    timeout: Duration,
+) -> Result<String, ProvisionError> {
+    let listener = ProvisionListener::bind().await?;
+    let url = listener.provision_url(&host, http)?;
+    tracing::info!("Waiting for masterbase key on {}", listener.redirect_url());
+    open::that(url.as_str()).map_err(ProvisionError::Browser)?;
+
+    tokio::time::timeout(timeout, listener.wait_for_key())
+        .await
+        .map_err(|_| ProvisionError::TimedOut)?
+}
+
+async fn handle_connection(mut stream: TcpStream, state: &str) -> std::io::Result<Option<String>> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(2).any(|w| w == b"\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let key = parse_callback(&String::from_utf8_lossy(&request), state);
+    let response = if key.is_some() {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{KEY_RECEIVED_PAGE}",
+            KEY_RECEIVED_PAGE.len()
+        )
+    } else {
+        String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(key)
+}
+
+/// The key from the request line of a callback, if it is one and has the
+/// expected `state`
+fn parse_callback(request: &str, state: &str) -> Option<String> {
+    let mut parts = request.lines().next()?.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+
+    let url = Url::parse("http://127.0.0.1")
+        .ok()?
+        .join(parts.next()?)
+        .ok()?;
+    if url.path() != CALLBACK_PATH
+        || !url
+            .query_pairs()
+            .any(|(k, v)| k == STATE_PARAM && v == state)
+    {
+        return None;
+    }
+
+    url.query_pairs()
+        .find(|(k, v)| KEY_PARAMS.contains(&k.as_ref()) && !v.is_empty())
+        .map(|(_, v)| v.into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::{parse_callback, ProvisionListener};
+
+    #[test]
+    fn parses_key_from_callback() {
+        assert_eq!(
+            parse_callback(
+                "GET /callback?state=xyz&api_key=abc%2D123 HTTP/1.1\r\nHost: 127.0.0.1\r\n",
+                "xyz"
+            ),
+            Some(String::from("abc-123"))
+        );
+        assert_eq!(
+            parse_callback("GET /callback?key=abc&state=xyz HTTP/1.1\r\n", "xyz"),
+            Some(String::from("abc"))
+        );
+        assert_eq!(parse_callback("GET /favicon.ico HTTP/1.1\r\n", "xyz"), None);
+        assert_eq!(
+            parse_callback("GET /callback?state=xyz&api_key= HTTP/1.1\r\n", "xyz"),
+            None
+        );
+        assert_eq!(
+            parse_callback("POST /callback?state=xyz&key=abc HTTP/1.1\r\n", "xyz"),
+            None
+        );
+    }
+
+    #[test]
+    fn callbacks_need_the_right_state() {
+        assert_eq!(
+            parse_callback("GET /callback?key=abc HTTP/1.1\r\n", "xyz"),
+            None
+        );
+        assert_eq!(
+            parse_callback("GET /callback?key=abc&state=abc HTTP/1.1\r\n", "xyz"),
+            None
+        );
+        assert_eq!(
+            parse_callback("GET /callback?key=abc&state= HTTP/1.1\r\n", "xyz"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn idle_connections_dont_block_the_callback() {
+        let listener = ProvisionListener::bind().await.unwrap();
+        let port = listener.port;
+        let request = format!(
+            "GET /callback?state={}&key=abc HTTP/1.1\r\n\r\n",
+            listener.state
+        );
+        let waiting = tokio::spawn(listener.wait_for_key());
+
+        // Connects but never sends anything
+        let _idle = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+        let mut callback = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        callback.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        callback.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+
+        let key = tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .expect("Key should be received while the other connection is idle")
+            .unwrap()
+            .unwrap();
+        assert_eq!(key, "abc");
+    }
+}
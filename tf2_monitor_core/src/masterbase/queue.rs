@@ -0,0 +1,295 @@
+//! Demo uploads that were interrupted, e.g. by losing connection mid-match,
+//! kept on disk so they can be retried later.
+
+use std::{
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use atomic_write_file::AtomicWriteFile;
+use serde::{Deserialize, Serialize};
+
+use super::{error_message, force_close_session, upload_demo_file, DemoSession, Error};
+use crate::{
+    demos::analyser::progress,
+    settings::{instance, AppDetails, ConfigFilesError, Settings},
+};
+
+pub const UPLOAD_QUEUE_FILE_NAME: &str = "upload_queue.json";
+
+/// Wait this long before the first retry, doubling after each failure
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Demos that haven't been written to in this long are assumed to be done
+/// recording, even if they never had their late bytes written
+const RECORDING_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedUpload {
+    pub demo_path: PathBuf,
+    pub demo_name: String,
+    /// How much of the demo the masterbase had received
+    pub offset: u64,
+    /// The session the demo was being uploaded in, which might still be open
+    pub session_id: Option<u128>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+
+    /// Not kept between runs, so everything is retried on startup
+    #[serde(skip)]
+    pub next_attempt: Option<Instant>,
+    #[serde(skip)]
+    pub in_progress: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UploadQueue {
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    pub uploads: Vec<QueuedUpload>,
+}
+
+impl QueuedUpload {
+    #[must_use]
+    pub fn new(demo_path: PathBuf, offset: u64, session_id: Option<u128>, error: String) -> Self {
+        let demo_name = demo_path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().to_string());
+
+        Self {
+            demo_path,
+            demo_name,
+            offset,
+            session_id,
+            attempts: 0,
+            last_error: Some(error),
+            next_attempt: Some(Instant::now() + FIRST_RETRY_DELAY),
+            in_progress: false,
+        }
+    }
+
+    /// Time until the next automatic retry, `None` if it's due
+    #[must_use]
+    pub fn retry_in(&self) -> Option<Duration> {
+        self.next_attempt
+            .and_then(|t| t.checked_duration_since(Instant::now()))
+    }
+}
+
+impl UploadQueue {
+    /// # Errors
+    /// If the config directory could not be located
+    pub fn default_file_location(app_details: AppDetails) -> Result<PathBuf, ConfigFilesError> {
+        Ok(Settings::locate_config_directory(app_details)?.join(UPLOAD_QUEUE_FILE_NAME))
+    }
+
+    /// Loads the queue, or starts an empty one if the file doesn't exist yet
+    ///
+    /// # Errors
+    /// If the file could not be read or parsed
+    pub fn load_or_create(path: PathBuf) -> Result<Self, ConfigFilesError> {
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(Self {
+                    path: Some(path),
+                    uploads: Vec::new(),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut queue: Self = serde_json::from_str(&contents)?;
+        queue.path = Some(path);
+        Ok(queue)
+    }
+
+    /// # Errors
+    /// If it failed to serialize or write back to the file.
+    pub fn save(&self) -> Result<(), ConfigFilesError> {
+        if instance::is_read_only() {
+            return Ok(());
+        }
+
+        let path = self.path.as_ref().ok_or(ConfigFilesError::NoConfigSet)?;
+
+        let mut file = AtomicWriteFile::open(path)?;
+        let contents = serde_json::to_string(self)?;
+
+        write!(file, "{contents}")?;
+        file.commit()?;
+        Ok(())
+    }
+
+    pub fn save_ok(&self) {
+        if let Err(e) = self.save() {
+            tracing::error!("Failed to save upload queue to {:?}: {e}", self.path);
+        }
+    }
+
+    /// Add an interrupted upload, replacing any already queued for the same
+    /// demo.
+    pub fn push(&mut self, upload: QueuedUpload) {
+        self.uploads.retain(|u| u.demo_path != upload.demo_path);
+        self.uploads.push(upload);
+        self.save_ok();
+    }
+
+    pub fn cancel(&mut self, demo_path: &Path) {
+        self.uploads.retain(|u| u.demo_path != demo_path);
+        self.save_ok();
+    }
+
+    pub fn retry_now(&mut self, demo_path: &Path) {
+        if let Some(upload) = self.uploads.iter_mut().find(|u| u.demo_path == demo_path) {
+            upload.next_attempt = None;
+        }
+    }
+
+    /// The next upload that is due to be retried, which is marked as in
+    /// progress. Demos that are still being recorded are left until they
+    /// finish.
+    pub fn start_next(&mut self) -> Option<QueuedUpload> {
+        if self.uploads.iter().any(|u| u.in_progress) {
+            return None;
+        }
+
+        let upload = self
+            .uploads
+            .iter_mut()
+            .filter(|u| u.retry_in().is_none())
+            .find(|u| !still_recording(&u.demo_path))?;
+        upload.in_progress = true;
+        Some(upload.clone())
+    }
+
+    /// Remove the upload if it succeeded, otherwise schedule it to be tried
+    /// again later.
+    pub fn finish(&mut self, demo_path: &Path, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                tracing::info!("Finished queued upload of {demo_path:?}");
+                self.uploads.retain(|u| u.demo_path != demo_path);
+            }
+            Err(e) => {
+                let Some(upload) = self.uploads.iter_mut().find(|u| u.demo_path == demo_path)
+                else {
+                    return;
+                };
+
+                upload.attempts += 1;
+                let delay = FIRST_RETRY_DELAY
+                    .saturating_mul(2u32.saturating_pow(upload.attempts.min(16)))
+                    .min(MAX_RETRY_DELAY);
+                tracing::warn!(
+                    "Queued upload of {demo_path:?} failed ({e}), retrying in {}s",
+                    delay.as_secs()
+                );
+
+                upload.in_progress = false;
+                upload.next_attempt = Some(Instant::now() + delay);
+                upload.last_error = Some(e);
+                // A failed attempt will have closed any session that was open
+                upload.session_id = None;
+            }
+        }
+
+        self.save_ok();
+    }
+}
+
+/// Whether TF2 is still writing to the demo. Finished demos have their late
+/// bytes written.
+fn still_recording(demo_path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(demo_path) else {
+        return false;
+    };
+
+    let recently_modified = metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.elapsed().ok())
+        .is_some_and(|e| e < RECORDING_TIMEOUT);
+
+    recently_modified && !has_late_bytes(demo_path)
+}
+
+fn has_late_bytes(demo_path: &Path) -> bool {
+    let mut header = [0u8; super::LATE_BYTES_RANGE.end];
+    std::fs::File::open(demo_path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok()
+        && header[super::LATE_BYTES_RANGE]
+            .iter()
+            .take(8)
+            .any(|&b| b != 0)
+}
+
+/// Try to continue the upload in the session it was in, and if that doesn't
+/// work (usually because the session has expired), upload the whole demo
+/// again in a new session.
+///
+/// # Errors
+/// If the demo could not be read, or both resuming and uploading the whole
+/// demo failed. The message is suitable for showing to the user.
+pub async fn retry_upload(
+    upload: QueuedUpload,
+    host: String,
+    key: String,
+    http: bool,
+) -> Result<(), String> {
+    let bytes = tokio::fs::read(&upload.demo_path)
+        .await
+        .map_err(|e| format!("Couldn't read demo: {e}"))?;
+
+    if let Some(session_id) = upload.session_id {
+        match resume_upload(&host, &key, http, session_id, &bytes, upload.offset).await {
+            Ok(()) => return Ok(()),
+            Err(e) => tracing::warn!(
+                "Couldn't resume session {session_id} for {}, uploading the whole demo again: {e}",
+                upload.demo_name
+            ),
+        }
+    }
+
+    // Make sure the old session isn't in the way of the new one
+    if let Err(e) = force_close_session(&host, &key, http).await {
+        tracing::warn!("Couldn't close previous session: {e}");
+    }
+
+    let (updater, _) = progress::create_pair();
+    upload_demo_file(host, key, http, &upload.demo_name, &bytes, updater)
+        .await
+        .map_err(|e| error_message(&e))
+}
+
+async fn resume_upload(
+    host: &str,
+    key: &str,
+    http: bool,
+    session_id: u128,
+    bytes: &[u8],
+    offset: u64,
+) -> Result<(), Error> {
+    let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+    let remaining = bytes
+        .get(offset..)
+        .ok_or_else(|| Error::Failed(String::from("Demo is shorter than what was uploaded")))?;
+
+    let mut session =
+        DemoSession::resume(host.to_owned(), key.to_owned(), session_id, http).await?;
+    for chunk in remaining.chunks(super::UPLOAD_CHUNK_SIZE) {
+        session.send_bytes(chunk.to_vec()).await?;
+    }
+
+    if let Some(late_bytes) = bytes.get(super::LATE_BYTES_RANGE) {
+        let response = session.send_late_bytes(late_bytes.to_vec()).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Status(status, response.text().await?));
+        }
+    }
+
+    Ok(())
+}
@@ -1,71 +1,157 @@
 use std::{
     collections::{HashMap, VecDeque},
+    io::Write,
     path::{Path, PathBuf},
 };
 
-use serde::{Serialize, Serializer};
+use atomic_write_file::AtomicWriteFile;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, Serializer};
 use steamid_ng::SteamID;
 
 use crate::{
-    console::commands::{g15, regexes::StatusLine},
-    settings::{AppDetails, ConfigFilesError, Settings},
+    console::commands::{
+        g15,
+        regexes::{LobbyMember, StatusLine},
+    },
+    server::VoteEvent,
+    settings::{instance, AppDetails, ConfigFilesError, Settings},
 };
 
 use self::{
+    bot_lists::BotLists,
+    co_occurrence::{CoOccurrence, CO_OCCURRENCE_FILE_NAME},
+    convictions::MasterbaseVerdict,
     friends::{Friend, FriendInfo},
-    game_info::GameInfo,
+    game_info::{GameInfo, PlayerState, Team},
+    name_changes::NameChange,
+    name_rules::NameRules,
+    name_steals::NameSteals,
     parties::Parties,
-    records::{default_custom_data, PlayerRecord, Records, Verdict},
+    records::{default_custom_data, NameRuleMark, PlayerRecord, Records, Verdict},
+    shared_playtime::{SharedPlaytime, SHARED_PLAYTIME_FILE_NAME},
     steam_info::SteamInfo,
+    suspicion::{Scorer, SuspicionScore},
 };
 
+pub mod bans;
+pub mod bot_lists;
+pub mod co_occurrence;
+pub mod convictions;
 pub mod friends;
 pub mod game_info;
+pub mod name_changes;
+pub mod name_rules;
+pub mod name_steals;
 #[allow(clippy::module_name_repetitions)]
 pub mod new_players;
 pub mod parties;
 pub mod records;
+pub mod shared_playtime;
 pub mod steam_info;
+pub mod suspicion;
+pub mod tf2bd;
+pub mod user_friends;
 
 pub const STEAM_CACHE_FILE_NAME: &str = "steam_cache.bin";
-
-// const MAX_HISTORY_LEN: usize = 100;
+/// Appended to the steam cache file name for the previous cache, which is
+/// loaded instead if the current one is corrupted
+const BACKUP_FILE_SUFFIX: &str = ".bak";
+pub const HISTORY_FILE_NAME: &str = "history.bin";
+
+/// A player who has left the server
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub steamid: SteamID,
+    pub left: DateTime<Utc>,
+}
 
 pub struct Players {
     cache_path: Option<PathBuf>,
+    history_path: Option<PathBuf>,
+    co_occurrence_path: Option<PathBuf>,
+    shared_playtime_path: Option<PathBuf>,
 
     pub game_info: HashMap<SteamID, GameInfo>,
     pub steam_info: HashMap<SteamID, SteamInfo>,
     pub friend_info: HashMap<SteamID, FriendInfo>,
     pub records: Records,
+    /// Accounts marked by subscribed remote bot lists
+    pub bot_lists: BotLists,
+    /// Community verdicts fetched from the masterbase
+    pub masterbase_verdicts: HashMap<SteamID, MasterbaseVerdict>,
+    pub co_occurrence: CoOccurrence,
+    /// Time spent in the same matches as the user, from analysed demos
+    pub shared_playtime: SharedPlaytime,
     pub parties: Parties,
+    pub name_steals: NameSteals,
+    /// Should be replaced whenever the suspicion weights in the settings
+    /// change
+    pub scorer: Scorer,
+    /// Should be replaced whenever the name rules in the settings change
+    pub name_rules: NameRules,
 
     pub connected: Vec<SteamID>,
-    pub history: VecDeque<SteamID>,
+    /// [`Self::present`] as of the last refresh
+    last_present: Vec<SteamID>,
+    /// Names players in the server have changed from while they've been in
+    /// it, oldest first
+    pub name_changes: HashMap<SteamID, Vec<NameChange>>,
+    /// Players who have left the server, most recent last
+    pub history: VecDeque<HistoryEntry>,
 
     pub user: Option<SteamID>,
+    /// Teams from the last `tf_lobby_debug`, only on Valve servers
+    lobby_teams: HashMap<SteamID, Team>,
 
     parties_needs_update: bool,
+    name_steals_needs_update: bool,
+    /// Steam info has changed since the cache was last saved
+    steam_info_dirty: bool,
 }
 
 #[allow(dead_code)]
 impl Players {
     #[must_use]
     pub fn new(records: Records, user: Option<SteamID>, cache_path: Option<PathBuf>) -> Self {
+        let history_path = cache_path
+            .as_ref()
+            .map(|p| p.with_file_name(HISTORY_FILE_NAME));
+        let co_occurrence_path = cache_path
+            .as_ref()
+            .map(|p| p.with_file_name(CO_OCCURRENCE_FILE_NAME));
+        let shared_playtime_path = cache_path
+            .as_ref()
+            .map(|p| p.with_file_name(SHARED_PLAYTIME_FILE_NAME));
         let mut players = Self {
             cache_path,
+            history_path,
+            co_occurrence_path,
+            shared_playtime_path,
 
             game_info: HashMap::new(),
             steam_info: HashMap::new(),
             friend_info: HashMap::new(),
             records,
+            bot_lists: BotLists::new(),
+            masterbase_verdicts: HashMap::new(),
+            co_occurrence: CoOccurrence::new(),
+            shared_playtime: SharedPlaytime::new(),
             parties: Parties::new(),
+            name_steals: NameSteals::new(),
+            scorer: Scorer::default(),
+            name_rules: NameRules::default(),
 
             connected: Vec::new(),
+            last_present: Vec::new(),
+            name_changes: HashMap::new(),
             history: VecDeque::new(),
             user,
+            lobby_teams: HashMap::new(),
 
             parties_needs_update: false,
+            name_steals_needs_update: false,
+            steam_info_dirty: false,
         };
 
         if players.cache_path.is_some() {
@@ -81,6 +167,42 @@ impl Players {
             }
         }
 
+        if players.history_path.is_some() {
+            match players.load_history() {
+                Ok(()) => tracing::info!(
+                    "Loaded player history with {} entries.",
+                    players.history.len()
+                ),
+                Err(ConfigFilesError::IO(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                    tracing::warn!("No player history was found, creating a new one.");
+                }
+                Err(e) => tracing::error!("Failed to load player history: {e}"),
+            }
+        }
+
+        if let Some(path) = &players.co_occurrence_path {
+            match players.co_occurrence.load_from(path) {
+                Ok(()) => tracing::info!("Loaded seen-with data."),
+                Err(ConfigFilesError::IO(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                    tracing::warn!("No seen-with data was found, creating a new one.");
+                }
+                Err(e) => tracing::error!("Failed to load seen-with data: {e}"),
+            }
+        }
+
+        if let Some(path) = &players.shared_playtime_path {
+            match players.shared_playtime.load_from(path) {
+                Ok(()) => tracing::info!(
+                    "Loaded shared playtime from {} demos.",
+                    players.shared_playtime.num_demos()
+                ),
+                Err(ConfigFilesError::IO(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                    tracing::warn!("No shared playtime was found, creating a new one.");
+                }
+                Err(e) => tracing::error!("Failed to load shared playtime: {e}"),
+            }
+        }
+
         players
     }
 
@@ -92,12 +214,52 @@ impl Players {
         Ok(Settings::locate_config_directory(app_details)?.join(STEAM_CACHE_FILE_NAME))
     }
 
-    /// Retrieve the local verdict for a player
+    /// Retrieve the local verdict for a player. Players without a verdict of
+    /// their own are considered bots if they are on a subscribed bot list.
     #[must_use]
     pub fn verdict(&self, steamid: SteamID) -> Verdict {
-        self.records
+        match self
+            .records
             .get(&steamid)
             .map_or(Verdict::Player, PlayerRecord::verdict)
+        {
+            Verdict::Player if self.bot_lists.contains(steamid) => Verdict::Bot,
+            verdict => verdict,
+        }
+    }
+
+    /// Counts how each player voted in a votekick that has ended. Voting No on
+    /// kicking a cheater is always kept, otherwise only players that already
+    /// have a record are counted so there isn't a new one for everyone who
+    /// votes.
+    pub fn record_vote(&mut self, vote: &VoteEvent) {
+        let Some(target) = vote.kick_target() else {
+            return;
+        };
+        let against_cheater = self.verdict(target) == Verdict::Cheater;
+
+        for cast in &vote.votes {
+            let Some(steamid) = cast.steamid.filter(|&s| s != target) else {
+                continue;
+            };
+
+            let record = match vote.option(cast) {
+                Some("No") if against_cheater => self.records.entry(steamid).or_default(),
+                Some("Yes") => match self.records.get_mut(&steamid) {
+                    Some(record) => record,
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            let mut stats = record.vote_stats();
+            if vote.option(cast) == Some("Yes") {
+                stats.voted_yes += 1;
+            } else {
+                stats.voted_no_on_cheater_kicks += 1;
+            }
+            record.set_vote_stats(stats);
+        }
     }
 
     /// Updates friends lists of a user
@@ -141,6 +303,15 @@ impl Players {
     fn propagate_friend(&mut self, steamid: SteamID, friend: &Friend) {
         let friend_info = self.friend_info.entry(friend.steamid).or_default();
 
+        if let Some(existing) = friend_info
+            .friends
+            .iter_mut()
+            .find(|f| f.steamid == steamid)
+        {
+            existing.friend_since = friend.friend_since;
+            return;
+        }
+
         friend_info.friends.push(Friend {
             steamid,
             friend_since: friend.friend_since,
@@ -195,6 +366,25 @@ impl Players {
         self.user.and_then(|user| self.are_friends(friend, user))
     }
 
+    /// When an account became friends with the user, as a unix timestamp.
+    /// Returns None if they aren't friends or we don't have enough
+    /// information to tell, see [`Self::is_friends_with_user`] to tell those
+    /// apart.
+    #[must_use]
+    pub fn friend_since_with_user(&self, friend: SteamID) -> Option<u64> {
+        let user = self.user?;
+        let since = |a: SteamID, b: SteamID| {
+            self.friend_info
+                .get(&a)?
+                .friends
+                .iter()
+                .find(|f| f.steamid == b)
+                .map(|f| f.friend_since)
+        };
+
+        since(friend, user).or_else(|| since(user, friend))
+    }
+
     /// Check if two accounts are friends with each other.
     /// Returns None if we don't have enough information to tell.
     #[must_use]
@@ -211,13 +401,15 @@ impl Players {
             }
         }
 
-        // Other friends list is public, so 2-way lookup should have been possible
-        if self
-            .friend_info
-            .get(&friend2)
-            .is_some_and(|f| f.public.is_some_and(|p| p))
-        {
-            return Some(false);
+        if let Some(friends) = self.friend_info.get(&friend2) {
+            if friends.friends.iter().any(|f| f.steamid == friend1) {
+                return Some(true);
+            }
+
+            // Other friends list is public, so 2-way lookup should have been possible
+            if friends.public.is_some_and(|p| p) {
+                return Some(false);
+            }
         }
 
         // Both are private :(
@@ -227,7 +419,8 @@ impl Players {
     /// Moves any old players from the server into history. Any console commands
     /// (status, `g15_dumpplayer`, etc) should be run before calling this
     /// function again to prevent removing all players from the player list.
-    pub fn refresh(&mut self) {
+    /// At most `max_history_len` players are kept in the history.
+    pub fn refresh(&mut self, max_history_len: usize) {
         // Get old players
         let unaccounted_players: Vec<SteamID> = self
             .connected
@@ -238,33 +431,102 @@ impl Players {
 
         if !unaccounted_players.is_empty() {
             self.parties_needs_update = true;
+            self.name_steals_needs_update = true;
         }
 
         self.connected.retain(|s| !unaccounted_players.contains(s));
 
         // Remove any of them from the history as they will be added more recently
         self.history
-            .retain(|p| !unaccounted_players.iter().any(|up| up == p));
+            .retain(|h| !unaccounted_players.contains(&h.steamid));
 
-        // Shrink to not go past max number of players
-        // let num_players = self.history.len() + unaccounted_players.len();
-        // for _ in MAX_HISTORY_LEN..num_players {
-        //     self.history.pop_front();
-        // }
+        let left = Utc::now();
+        for steamid in unaccounted_players {
+            self.name_changes.remove(&steamid);
+            self.history.push_back(HistoryEntry { steamid, left });
+        }
 
-        for p in unaccounted_players {
-            self.history.push_back(p);
+        // Shrink to not go past max number of players
+        while self.history.len() > max_history_len {
+            self.history.pop_front();
         }
 
+        // Also picks up players who have been marked since they joined
+        let marked: Vec<SteamID> = self
+            .connected
+            .iter()
+            .copied()
+            .filter(|&s| matches!(self.verdict(s), Verdict::Cheater | Verdict::Bot))
+            .collect();
+        self.co_occurrence
+            .update(&self.connected, &marked, self.user);
+
         // Mark all remaining players as unaccounted, they will be marked as accounted
         // again when they show up in status or another console command.
         self.game_info.values_mut().for_each(GameInfo::next_cycle);
 
+        // Players who have just disconnected (or come back) change who is
+        // partied up, even though they haven't been pruned yet
+        let present: Vec<SteamID> = self.present().collect();
+        if present != self.last_present {
+            self.parties_needs_update = true;
+            self.name_steals_needs_update = true;
+        }
+
         if self.parties_needs_update {
-            self.parties
-                .find_parties(&self.friend_info, &self.connected);
+            self.parties.find_parties(&self.friend_info, &present);
             self.parties_needs_update = false;
         }
+
+        if self.name_steals_needs_update {
+            self.name_steals.find_name_steals(
+                &present,
+                &self.game_info,
+                &self.steam_info,
+                &self.records,
+            );
+            self.name_steals_needs_update = false;
+        }
+
+        self.last_present = present;
+    }
+
+    /// Connected players that are still showing up in status, without the
+    /// ones that have disconnected but haven't been moved to the history yet
+    pub fn present(&self) -> impl Iterator<Item = SteamID> + '_ {
+        self.connected.iter().copied().filter(|s| {
+            self.game_info
+                .get(s)
+                .is_some_and(|gi| gi.state != PlayerState::Disconnected)
+        })
+    }
+
+    /// How many players still in the server have the verdict, see
+    /// [`Self::present`]
+    #[must_use]
+    pub fn count_present(&self, verdict: Verdict) -> usize {
+        self.present()
+            .filter(|&s| self.verdict(s) == verdict)
+            .count()
+    }
+
+    /// Whether the masterbase has convicted the player of cheating
+    #[must_use]
+    pub fn is_convicted(&self, steamid: SteamID) -> bool {
+        self.masterbase_verdicts
+            .get(&steamid)
+            .is_some_and(|v| v.convicted)
+    }
+
+    /// How suspicious the player looks, only for the user to see
+    #[must_use]
+    pub fn suspicion(&self, steamid: SteamID) -> SuspicionScore {
+        self.scorer.score(
+            self.steam_info.get(&steamid),
+            self.friend_info.get(&steamid),
+            self.game_info.get(&steamid),
+            |friend| matches!(self.verdict(friend), Verdict::Cheater | Verdict::Bot),
+        )
     }
 
     /// Gets a struct containing all the relevant data on a player in a
@@ -280,7 +542,7 @@ impl Players {
         let record = self.records.get(&steamid);
         let previous_names = record
             .as_ref()
-            .map(|r| r.previous_names().iter().map(AsRef::as_ref).collect())
+            .map(|r| r.previous_name_strs().collect())
             .unwrap_or_default();
 
         let friend_info = self.friend_info.get(&steamid);
@@ -301,10 +563,11 @@ impl Players {
             customData: record
                 .as_ref()
                 .map_or_else(default_custom_data, |r| r.custom_data().clone()),
-            convicted: false,
+            convicted: self.is_convicted(steamid),
             previous_names,
             friends,
             friendsIsPublic: friend_info.and_then(|fi| fi.public),
+            suspicion: self.suspicion(steamid),
         }
     }
 
@@ -314,55 +577,159 @@ impl Players {
                 continue;
             };
 
-            if let Some(r) = self.records.get_mut(&steamid) {
-                r.mark_seen();
-            }
+            self.mark_seen(steamid);
 
             // Add to connected players if they aren't already
             if !self.connected.contains(&steamid) {
                 self.connected.push(steamid);
                 self.parties_needs_update = true;
+                self.name_steals_needs_update = true;
             }
 
             // Update game info
+            let mut new_name = None;
             if let Some(game_info) = self.game_info.get_mut(&steamid) {
                 if let Some(name) = g15.name.as_ref() {
                     self.records.update_name(steamid, name);
+                    if *name != game_info.name {
+                        new_name = Some(name.clone());
+                    }
                 }
                 game_info.update_from_g15(g15);
-            } else if let Some(game_info) = GameInfo::new_from_g15(g15) {
+            } else if let Some(mut game_info) = GameInfo::new_from_g15(g15) {
                 // Update name
                 self.records.update_name(steamid, &game_info.name);
+                game_info.lobby_team = self.lobby_teams.get(&steamid).copied();
+                new_name = Some(game_info.name.clone());
                 self.game_info.insert(steamid, game_info);
             }
+
+            if let Some(name) = new_name {
+                self.apply_name_rules(steamid, &name);
+                self.name_steals_needs_update = true;
+            }
         }
     }
 
     pub fn handle_status_line(&mut self, status: StatusLine) {
         let steamid = status.steamid;
 
-        if let Some(r) = self.records.get_mut(&steamid) {
-            r.mark_seen();
-        }
+        self.mark_seen(steamid);
 
         // Add to connected players if they aren't already
         if !self.connected.contains(&steamid) {
             self.connected.push(steamid);
             self.parties_needs_update = true;
+            self.name_steals_needs_update = true;
         }
 
-        if let Some(game_info) = self.game_info.get_mut(&steamid) {
-            if status.name != game_info.name {
+        let new_name = if let Some(game_info) = self.game_info.get_mut(&steamid) {
+            let new_name = (status.name != game_info.name).then(|| status.name.clone());
+            if new_name.is_some() {
                 self.records.update_name(steamid, &status.name);
             }
 
             game_info.update_from_status(status);
+            new_name
         } else {
-            let game_info = GameInfo::new_from_status(status);
+            let mut game_info = GameInfo::new_from_status(status);
 
             // Update name
             self.records.update_name(steamid, &game_info.name);
+            game_info.lobby_team = self.lobby_teams.get(&steamid).copied();
+            let name = game_info.name.clone();
             self.game_info.insert(steamid, game_info);
+            Some(name)
+        };
+
+        if let Some(name) = new_name {
+            self.apply_name_rules(steamid, &name);
+            self.name_steals_needs_update = true;
+        }
+    }
+
+    /// Gives the player the verdict from the first name rule that matches
+    /// their new name, unless they already have one
+    fn apply_name_rules(&mut self, steamid: SteamID, name: &str) {
+        if self.verdict(steamid) != Verdict::Player {
+            return;
+        }
+        let Some(rule) = self.name_rules.matching(name) else {
+            return;
+        };
+        let Some(verdict) = rule.action.verdict() else {
+            return;
+        };
+
+        tracing::info!(
+            "Marking {} as {verdict} for matching the name rule \"{}\"",
+            u64::from(steamid),
+            rule.pattern
+        );
+        let mark = NameRuleMark {
+            pattern: rule.pattern.clone(),
+            verdict,
+        };
+        let record = self.records.entry(steamid).or_default();
+        record.set_verdict(verdict).set_name_rule_mark(Some(&mark));
+        record.add_previous_name(name);
+    }
+
+    /// How many records still have the verdict the name rule gave them
+    #[must_use]
+    pub fn name_rule_marks(&self, pattern: &str) -> usize {
+        self.records
+            .values()
+            .filter(|r| {
+                r.name_rule_mark()
+                    .is_some_and(|m| m.pattern == pattern && m.verdict == r.verdict())
+            })
+            .count()
+    }
+
+    /// Sets everyone the name rule marked back to [`Verdict::Player`], unless
+    /// their verdict has been changed since. Returns how many were undone.
+    pub fn undo_name_rule_marks(&mut self, pattern: &str) -> usize {
+        let mut undone = 0;
+        for record in self.records.values_mut() {
+            let Some(mark) = record.name_rule_mark().filter(|m| m.pattern == pattern) else {
+                continue;
+            };
+
+            if mark.verdict == record.verdict() {
+                record.set_verdict(Verdict::Player);
+                undone += 1;
+            }
+            record.set_name_rule_mark(None);
+        }
+        undone
+    }
+
+    /// Takes the teams the matchmaking lobby has everyone on, replacing the
+    /// last ones since members who have left aren't listed any more
+    pub fn handle_lobby_debug(&mut self, members: Vec<LobbyMember>) {
+        self.lobby_teams = members
+            .into_iter()
+            .filter(|m| m.team != Team::Unassigned)
+            .map(|m| (m.steamid, m.team))
+            .collect();
+
+        for (steamid, game_info) in &mut self.game_info {
+            game_info.lobby_team = self.lobby_teams.get(steamid).copied();
+        }
+    }
+
+    /// Updates when a player was last seen, counting it as a new encounter if
+    /// they have only just joined. Must be called before the player is added
+    /// to `connected`.
+    fn mark_seen(&mut self, steamid: SteamID) {
+        let just_joined = !self.connected.contains(&steamid);
+        if let Some(r) = self.records.get_mut(&steamid) {
+            if just_joined {
+                r.add_encounter();
+            } else {
+                r.mark_seen();
+            }
         }
     }
 
@@ -375,20 +742,28 @@ impl Players {
         } else if let Some(last_name) = self
             .records
             .get(&steamid)
-            .map(|r| r.previous_names().first())
+            .map(|r| r.previous_name_strs().next())
         {
-            return last_name.map(String::as_str);
+            return last_name;
         }
 
         None
     }
 
+    /// The connected player with this name, or `None` if nobody or more than
+    /// one player has it, since it can't be told who it was.
     #[must_use]
     pub fn get_steamid_from_name(&self, name: &str) -> Option<SteamID> {
-        self.connected
+        let mut matching = self
+            .connected
             .iter()
-            .find(|&s| self.game_info.get(s).is_some_and(|gi| gi.name == name))
-            .copied()
+            .filter(|&s| self.game_info.get(s).is_some_and(|gi| gi.name == name));
+
+        let steamid = matching.next().copied();
+        if matching.next().is_some() {
+            return None;
+        }
+        steamid
     }
 
     #[must_use]
@@ -410,35 +785,196 @@ impl Players {
         self.load_steam_info_from(&path)
     }
 
+    /// Prunes the cache with [`Self::prune_steam_info`] and writes it to disk.
+    ///
     /// # Errors
     /// If the data could not be serialized or the file could not be written back to disk
-    pub fn save_steam_info(&self) -> Result<(), ConfigFilesError> {
+    pub fn save_steam_info(
+        &mut self,
+        max_age_days: u32,
+        max_entries: usize,
+    ) -> Result<(), ConfigFilesError> {
+        if instance::is_read_only() {
+            return Ok(());
+        }
+
+        self.prune_steam_info(max_age_days, max_entries);
         let path = self
             .cache_path
             .as_ref()
             .ok_or(ConfigFilesError::NoConfigSet)?;
-        self.save_steam_info_to(path)
+        self.save_steam_info_to(path)?;
+        self.steam_info_dirty = false;
+        Ok(())
+    }
+
+    /// Caches the steam info for a player so it is saved with the next
+    /// autosave.
+    pub fn insert_steam_info(&mut self, steamid: SteamID, steam_info: SteamInfo) {
+        self.steam_info.insert(steamid, steam_info);
+        self.steam_info_dirty = true;
+        // Account age and playtime decide which of a stolen name is the original
+        self.name_steals_needs_update = true;
     }
 
-    pub fn save_steam_info_ok(&self) {
-        if let Err(e) = self.save_steam_info() {
+    /// Same as [`Self::save_steam_info`], but skips writing the cache if no
+    /// steam info has changed since it was last saved. Returns whether the
+    /// cache was written.
+    ///
+    /// # Errors
+    /// If the data could not be serialized or the file could not be written back to disk
+    pub fn save_steam_info_if_dirty(
+        &mut self,
+        max_age_days: u32,
+        max_entries: usize,
+    ) -> Result<bool, ConfigFilesError> {
+        if !self.steam_info_dirty {
+            return Ok(false);
+        }
+
+        self.save_steam_info(max_age_days, max_entries)?;
+        Ok(true)
+    }
+
+    pub fn save_steam_info_ok(&mut self, max_age_days: u32, max_entries: usize) {
+        if let Err(e) = self.save_steam_info(max_age_days, max_entries) {
             tracing::error!("Failed to save steam info cache: {e}");
         } else {
             tracing::debug!("Saved steam info cache.");
         }
     }
 
-    fn load_steam_info_from(&mut self, path: &Path) -> Result<(), ConfigFilesError> {
+    /// # Errors
+    /// If the file could not be read from disk or the data could not be deserialized
+    pub fn load_history(&mut self) -> Result<(), ConfigFilesError> {
+        let path = self
+            .history_path
+            .as_ref()
+            .ok_or(ConfigFilesError::NoConfigSet)?;
         let contents = std::fs::read(path)?;
-        let steam_info = pot::from_slice(&contents)?;
+        self.history = pot::from_slice(&contents)?;
+        Ok(())
+    }
 
-        self.steam_info = steam_info;
+    /// # Errors
+    /// If the data could not be serialized or the file could not be written back to disk
+    pub fn save_history(&self) -> Result<(), ConfigFilesError> {
+        if instance::is_read_only() {
+            return Ok(());
+        }
+
+        let path = self
+            .history_path
+            .as_ref()
+            .ok_or(ConfigFilesError::NoConfigSet)?;
+        let contents = pot::to_vec(&self.history)?;
+
+        let mut file = AtomicWriteFile::open(path)?;
+        file.write_all(&contents)?;
+        file.commit()?;
         Ok(())
     }
 
+    pub fn save_history_ok(&self) {
+        if let Err(e) = self.save_history() {
+            tracing::error!("Failed to save player history: {e}");
+        } else {
+            tracing::debug!("Saved player history.");
+        }
+    }
+
+    pub fn save_co_occurrence_ok(&self) {
+        let Some(path) = &self.co_occurrence_path else {
+            return;
+        };
+        if instance::is_read_only() {
+            return;
+        }
+
+        if let Err(e) = self.co_occurrence.save_to(path) {
+            tracing::error!("Failed to save seen-with data: {e}");
+        } else {
+            tracing::debug!("Saved seen-with data.");
+        }
+    }
+
+    pub fn save_shared_playtime_ok(&self) {
+        let Some(path) = &self.shared_playtime_path else {
+            return;
+        };
+        if instance::is_read_only() {
+            return;
+        }
+
+        if let Err(e) = self.shared_playtime.save_to(path) {
+            tracing::error!("Failed to save shared playtime: {e}");
+        } else {
+            tracing::debug!("Saved shared playtime.");
+        }
+    }
+
+    /// Removes steam info that was fetched more than `max_age_days` ago, then
+    /// the least recently fetched entries beyond `max_entries`.
+    pub fn prune_steam_info(&mut self, max_age_days: u32, max_entries: usize) {
+        let now = Utc::now();
+        self.steam_info.retain(|_, si| {
+            now.signed_duration_since(si.fetched).num_days() < i64::from(max_age_days)
+        });
+
+        if self.steam_info.len() > max_entries {
+            let mut by_age: Vec<(DateTime<Utc>, SteamID)> = self
+                .steam_info
+                .iter()
+                .map(|(&s, si)| (si.fetched, s))
+                .collect();
+            by_age.sort_unstable_by_key(|(fetched, _)| *fetched);
+
+            let excess = self.steam_info.len() - max_entries;
+            for (_, s) in by_age.into_iter().take(excess) {
+                self.steam_info.remove(&s);
+            }
+        }
+    }
+
+    /// Loads the cache from `path`, falling back to the backup of the
+    /// previous cache if it is missing or corrupted.
+    fn load_steam_info_from(&mut self, path: &Path) -> Result<(), ConfigFilesError> {
+        fn read(path: &Path) -> Result<HashMap<SteamID, SteamInfo>, ConfigFilesError> {
+            let contents = std::fs::read(path)?;
+            Ok(pot::from_slice(&contents)?)
+        }
+
+        match read(path) {
+            Ok(steam_info) => {
+                self.steam_info = steam_info;
+                Ok(())
+            }
+            Err(e) => {
+                let backup = with_suffix(path, BACKUP_FILE_SUFFIX);
+                let steam_info = read(&backup).map_err(|_| e)?;
+                tracing::warn!(
+                    "Steam info cache could not be loaded, restored it from {}",
+                    backup.display()
+                );
+
+                self.steam_info = steam_info;
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the cache atomically, keeping the previous cache as a backup in
+    /// case the new one ends up corrupted anyway.
     fn save_steam_info_to(&self, path: &Path) -> Result<(), ConfigFilesError> {
         let contents = pot::to_vec(&self.steam_info)?;
-        std::fs::write(path, contents)?;
+
+        if path.exists() {
+            std::fs::copy(path, with_suffix(path, BACKUP_FILE_SUFFIX))?;
+        }
+
+        let mut file = AtomicWriteFile::open(path)?;
+        file.write_all(&contents)?;
+        file.commit()?;
         Ok(())
     }
 }
@@ -459,6 +995,13 @@ impl Serialize for Players {
 
 // Useful
 
+/// e.g. `steam_cache.bin` -> `steam_cache.bin.bak`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
 #[allow(clippy::trivially_copy_pass_by_ref, clippy::missing_errors_doc)]
 pub fn serialize_steamid_as_string<S: Serializer>(
     steamid: &SteamID,
@@ -495,4 +1038,419 @@ pub struct Player<'a> {
 
     pub friends: Vec<&'a Friend>,
     pub friendsIsPublic: Option<bool>,
+    pub suspicion: SuspicionScore,
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unreadable_literal)]
+
+    use std::path::PathBuf;
+
+    use chrono::{Duration, Utc};
+    use steamid_ng::SteamID;
+    use tf_demo_parser::demo::parser::analyser::Class;
+
+    use super::{
+        friends::{Friend, FriendInfo},
+        g15::G15Player,
+        game_info::{GameInfo, PlayerState},
+        name_rules::{NameRule, NameRuleAction, NameRules},
+        records::{Records, Verdict, VoteStats},
+        steam_info::{ProfileVisibility, SteamInfo},
+        with_suffix, Players, StatusLine, BACKUP_FILE_SUFFIX,
+    };
+    use crate::server::{CastVote, VoteEvent, VoteOutcome};
+
+    fn steam_info(name: &str, days_old: i64) -> SteamInfo {
+        SteamInfo {
+            account_name: name.into(),
+            profile_url: String::new(),
+            pfp_url: String::new(),
+            pfp_hash: String::new(),
+            profile_visibility: ProfileVisibility::Public,
+            time_created: None,
+            country_code: None,
+            vac_bans: 0,
+            game_bans: 0,
+            days_since_last_ban: None,
+            playtime: None,
+            steam_level: None,
+            groups: None,
+            fetched: Utc::now() - Duration::days(days_old),
+        }
+    }
+
+    fn status_line(steamid: SteamID, name: &str) -> StatusLine {
+        StatusLine {
+            userid: String::from("12"),
+            name: name.into(),
+            steamid,
+            time: 120,
+            ping: 30,
+            loss: 0,
+            state: PlayerState::Active,
+        }
+    }
+
+    fn cache_path() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tf2monitor-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+        dir.join(super::STEAM_CACHE_FILE_NAME)
+    }
+
+    fn players_with_cache(path: &std::path::Path) -> Players {
+        let mut players = Players::new(Records::default(), None, None);
+        players.cache_path = Some(path.to_path_buf());
+        players
+    }
+
+    #[test]
+    pub fn status_keeps_class_from_g15() {
+        let steamid = SteamID::from(76561198000000001);
+        let mut players = Players::new(Records::default(), None, None);
+
+        players.handle_status_line(status_line(steamid, "Pyro"));
+        let game_info = players.game_info.get(&steamid).expect("Player was added");
+        assert_eq!(game_info.class, None);
+        assert_eq!(game_info.alive, None);
+
+        players.handle_g15(vec![G15Player {
+            steamid: Some(steamid),
+            userid: Some(String::from("12")),
+            class: Some(Class::Pyro),
+            alive: Some(false),
+            ..Default::default()
+        }]);
+        players.handle_status_line(StatusLine {
+            time: 125,
+            ..status_line(steamid, "Pyro")
+        });
+
+        let game_info = players.game_info.get(&steamid).expect("Player was added");
+        assert_eq!(game_info.class, Some(Class::Pyro));
+        assert_eq!(game_info.alive, Some(false));
+    }
+
+    #[test]
+    pub fn friend_since_either_direction() {
+        let user = SteamID::from(76561198000000001);
+        let friend = SteamID::from(76561198000000002);
+        let stranger = SteamID::from(76561198000000003);
+        let mut players = Players::new(Records::default(), Some(user), None);
+
+        players.friend_info.insert(
+            friend,
+            FriendInfo {
+                public: Some(true),
+                friends: vec![Friend {
+                    steamid: user,
+                    friend_since: 1_552_000_000,
+                }],
+            },
+        );
+
+        assert_eq!(players.friend_since_with_user(friend), Some(1_552_000_000));
+        assert_eq!(players.friend_since_with_user(stranger), None);
+        assert_eq!(players.is_friends_with_user(stranger), None);
+
+        // Only the user's list is known
+        players.friend_info.remove(&friend);
+        players.friend_info.insert(
+            user,
+            FriendInfo {
+                public: Some(true),
+                friends: vec![Friend {
+                    steamid: friend,
+                    friend_since: 1_552_000_000,
+                }],
+            },
+        );
+        assert_eq!(players.friend_since_with_user(friend), Some(1_552_000_000));
+        assert_eq!(players.is_friends_with_user(friend), Some(true));
+        assert_eq!(players.is_friends_with_user(stranger), Some(false));
+    }
+
+    #[test]
+    pub fn ambiguous_names_have_no_steamid() {
+        let mut players = Players::new(Records::default(), None, None);
+        for (id, name) in [
+            (76561198000000001, "Pyro"),
+            (76561198000000002, "Pyro"),
+            (76561198000000003, "Medic"),
+        ] {
+            let steamid = SteamID::from(id);
+            players.connected.push(steamid);
+            players.game_info.insert(
+                steamid,
+                GameInfo {
+                    name: name.into(),
+                    ..Default::default()
+                },
+            );
+        }
+
+        assert_eq!(
+            players.get_steamid_from_name("Medic"),
+            Some(SteamID::from(76561198000000003))
+        );
+        assert_eq!(players.get_steamid_from_name("Pyro"), None);
+        assert_eq!(players.get_steamid_from_name("Spy"), None);
+    }
+
+    #[test]
+    pub fn truncated_cache_falls_back_to_backup() {
+        let path = cache_path();
+        let mut players = players_with_cache(&path);
+        players
+            .steam_info
+            .insert(SteamID::from(76561198000000001), steam_info("First", 0));
+        players.save_steam_info(30, 100).expect("Failed to save");
+
+        players
+            .steam_info
+            .insert(SteamID::from(76561198000000002), steam_info("Second", 0));
+        players.save_steam_info(30, 100).expect("Failed to save");
+
+        // Simulate a write that was cut off part way
+        let contents = std::fs::read(&path).expect("Failed to read cache");
+        std::fs::write(&path, &contents[..contents.len() / 2]).expect("Failed to truncate");
+
+        let mut loaded = players_with_cache(&path);
+        loaded
+            .load_steam_info_from(&path)
+            .expect("Backup should load");
+        assert_eq!(loaded.steam_info.len(), 1);
+        assert!(loaded
+            .steam_info
+            .contains_key(&SteamID::from(76561198000000001)));
+
+        let _ = std::fs::remove_dir_all(path.parent().expect("Cache has a parent"));
+    }
+
+    #[test]
+    pub fn truncated_cache_without_backup_fails() {
+        let path = cache_path();
+        let mut players = players_with_cache(&path);
+        players
+            .steam_info
+            .insert(SteamID::from(76561198000000001), steam_info("First", 0));
+        players.save_steam_info(30, 100).expect("Failed to save");
+        assert!(!with_suffix(&path, BACKUP_FILE_SUFFIX).exists());
+
+        let contents = std::fs::read(&path).expect("Failed to read cache");
+        std::fs::write(&path, &contents[..contents.len() / 2]).expect("Failed to truncate");
+
+        let mut loaded = players_with_cache(&path);
+        assert!(loaded.load_steam_info_from(&path).is_err());
+        assert!(loaded.steam_info.is_empty());
+
+        let _ = std::fs::remove_dir_all(path.parent().expect("Cache has a parent"));
+    }
+
+    #[test]
+    pub fn only_save_when_dirty() {
+        let path = cache_path();
+        let mut players = players_with_cache(&path);
+        assert!(!players
+            .save_steam_info_if_dirty(30, 100)
+            .expect("Failed to save"));
+        assert!(!path.exists());
+
+        players.insert_steam_info(SteamID::from(76561198000000001), steam_info("First", 0));
+        assert!(players
+            .save_steam_info_if_dirty(30, 100)
+            .expect("Failed to save"));
+        assert!(path.exists());
+        assert!(!players
+            .save_steam_info_if_dirty(30, 100)
+            .expect("Failed to save"));
+
+        let _ = std::fs::remove_dir_all(path.parent().expect("Cache has a parent"));
+    }
+
+    #[test]
+    pub fn prune_old_and_excess_entries() {
+        let mut players = Players::new(Records::default(), None, None);
+        for (i, days_old) in (0..5).zip(0..) {
+            players.steam_info.insert(
+                SteamID::from(76561198000000000 + i),
+                steam_info("", days_old),
+            );
+        }
+        players
+            .steam_info
+            .insert(SteamID::from(76561198000000010), steam_info("", 100));
+
+        players.prune_steam_info(30, 3);
+        assert_eq!(players.steam_info.len(), 3);
+        for i in 0..3 {
+            assert!(players
+                .steam_info
+                .contains_key(&SteamID::from(76561198000000000 + i)));
+        }
+    }
+
+    #[test]
+    pub fn votes_on_cheater_kicks_are_counted() {
+        let caller = SteamID::from(76561198000000001);
+        let cheater = SteamID::from(76561198000000002);
+        let friend = SteamID::from(76561198000000003);
+        let recorded = SteamID::from(76561198000000004);
+        let unrecorded = SteamID::from(76561198000000005);
+
+        let mut players = Players::new(Records::default(), None, None);
+        players
+            .records
+            .entry(cheater)
+            .or_default()
+            .set_verdict(Verdict::Cheater);
+        players.records.entry(recorded).or_default();
+
+        let cast = |steamid: SteamID, option: u8| CastVote {
+            steamid: Some(steamid),
+            option,
+        };
+        let vote = VoteEvent {
+            idx: 0,
+            options: vec![String::from("Yes"), String::from("No")],
+            votes: vec![
+                cast(caller, 0),
+                cast(cheater, 1),
+                cast(friend, 1),
+                cast(recorded, 0),
+                cast(unrecorded, 0),
+            ],
+            outcome: Some(VoteOutcome::Passed),
+            started: Utc::now(),
+        };
+        assert_eq!(vote.kick_target(), Some(cheater));
+        players.record_vote(&vote);
+
+        let stats = |steamid: SteamID| players.records.get(&steamid).map(|r| r.vote_stats());
+        assert_eq!(
+            stats(friend),
+            Some(VoteStats {
+                voted_yes: 0,
+                voted_no_on_cheater_kicks: 1
+            })
+        );
+        assert_eq!(
+            stats(recorded),
+            Some(VoteStats {
+                voted_yes: 1,
+                voted_no_on_cheater_kicks: 0
+            })
+        );
+        assert_eq!(stats(cheater), Some(VoteStats::default()));
+        assert_eq!(stats(caller), None);
+        assert_eq!(stats(unrecorded), None);
+    }
+
+    #[test]
+    pub fn name_rules_mark_new_players() {
+        let bot = SteamID::from(76561198000000001);
+        let trusted = SteamID::from(76561198000000002);
+        let renamed = SteamID::from(76561198000000003);
+        let mut players = Players::new(Records::default(), None, None);
+        players.name_rules = NameRules::new(&[
+            NameRule {
+                pattern: String::from("("),
+                action: NameRuleAction::MarkBot,
+            },
+            NameRule {
+                pattern: String::from(r"^\(\d+\)DoesHotter$"),
+                action: NameRuleAction::MarkBot,
+            },
+        ]);
+        players
+            .records
+            .entry(trusted)
+            .or_default()
+            .set_verdict(Verdict::Trusted);
+
+        players.handle_status_line(status_line(bot, "(1)DoesHotter"));
+        players.handle_status_line(status_line(trusted, "(2)DoesHotter"));
+        players.handle_status_line(status_line(renamed, "Pyro"));
+        assert_eq!(players.verdict(bot), Verdict::Bot);
+        assert_eq!(players.verdict(trusted), Verdict::Trusted);
+        assert_eq!(players.verdict(renamed), Verdict::Player);
+
+        // Changing name to match a rule
+        players.handle_status_line(status_line(renamed, "(3)DoesHotter"));
+        assert_eq!(players.verdict(renamed), Verdict::Bot);
+        assert_eq!(players.name_rule_marks(r"^\(\d+\)DoesHotter$"), 2);
+
+        // Marks the user has changed since aren't undone
+        players
+            .records
+            .get_mut(&renamed)
+            .unwrap()
+            .set_verdict(Verdict::Cheater);
+        assert_eq!(players.undo_name_rule_marks(r"^\(\d+\)DoesHotter$"), 1);
+        assert_eq!(players.verdict(bot), Verdict::Player);
+        assert_eq!(players.verdict(renamed), Verdict::Cheater);
+        assert!(players.records[&bot].name_rule_mark().is_none());
+    }
+
+    #[test]
+    pub fn copied_names_are_found() {
+        let original = SteamID::from(76561198000000001);
+        let bot = SteamID::from(76561198000000002);
+        let mut players = Players::new(Records::default(), None, None);
+        players.records.entry(original).or_default();
+
+        players.handle_status_line(status_line(bot, "Pyro Main\u{200f}"));
+        players.handle_status_line(status_line(original, "Pyro Main"));
+        players.refresh(10);
+
+        let steal = players.name_steals.involving(original).unwrap();
+        assert_eq!(steal.stealer, bot);
+        assert_eq!(steal.original, original);
+
+        players.handle_status_line(status_line(bot, "Heavy Main"));
+        players.refresh(10);
+        assert!(players.name_steals.steals().is_empty());
+    }
+
+    #[test]
+    pub fn disconnected_players_are_not_counted() {
+        let cheater = SteamID::from(76561198000000001);
+        let friend = SteamID::from(76561198000000002);
+        let mut players = Players::new(Records::default(), None, None);
+        players
+            .records
+            .entry(cheater)
+            .or_default()
+            .set_verdict(Verdict::Cheater);
+        for (a, b) in [(cheater, friend), (friend, cheater)] {
+            players.friend_info.insert(
+                a,
+                FriendInfo {
+                    public: Some(true),
+                    friends: vec![Friend {
+                        steamid: b,
+                        friend_since: 0,
+                    }],
+                },
+            );
+        }
+
+        players.handle_status_line(status_line(cheater, "Cheater"));
+        players.handle_status_line(status_line(friend, "Friend"));
+        players.refresh(10);
+        assert_eq!(players.count_present(Verdict::Cheater), 1);
+        assert_eq!(players.parties.parties().len(), 1);
+
+        // The cheater stops showing up in status
+        while players.game_info[&cheater].state != PlayerState::Disconnected {
+            players.handle_status_line(status_line(friend, "Friend"));
+            players.refresh(10);
+        }
+
+        assert!(players.connected.contains(&cheater));
+        assert!(players.history.is_empty());
+        assert_eq!(players.count_present(Verdict::Cheater), 0);
+        assert!(players.parties.parties().is_empty());
+    }
 }
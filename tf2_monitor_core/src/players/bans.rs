@@ -0,0 +1,195 @@
+use chrono::Utc;
+use event_loop::{try_get, Handled, Is, Message, MessageHandler};
+use steamid_ng::SteamID;
+
+use super::records::BanDetected;
+use crate::{steam::api::ProfileLookupResult, MonitorState};
+
+// Messages *********************
+
+/// Players whose VAC or game ban count has gone up since their steam info was
+/// last fetched
+#[derive(Debug, Clone)]
+pub struct NewBansDetected(pub Vec<(SteamID, BanDetected)>);
+impl Message<MonitorState> for NewBansDetected {
+    fn update_state(self, state: &mut MonitorState) {
+        for (steamid, ban) in self.0 {
+            let name = state.players.get_name(steamid).map(ToOwned::to_owned);
+
+            let record = state.players.records.entry(steamid).or_default();
+            record.add_ban_detected(ban);
+            if let Some(name) = name {
+                record.add_previous_name(&name);
+            }
+        }
+
+        state.players.records.save_ok();
+    }
+}
+
+// Handlers *********************
+
+/// Compares incoming steam info against what is already cached to find any
+/// new bans. This has to see the result before it updates the state.
+pub struct DetectNewBans;
+impl<IM, OM> MessageHandler<MonitorState, IM, OM> for DetectNewBans
+where
+    IM: Is<ProfileLookupResult>,
+    OM: Is<NewBansDetected>,
+{
+    fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
+        let ProfileLookupResult {
+            result: Ok(results),
+            ..
+        } = try_get(message)?
+        else {
+            return Handled::none();
+        };
+
+        let new_bans: Vec<_> = results
+            .iter()
+            .filter_map(|(steamid, result)| {
+                let new = result.as_ref().ok()?;
+                let old = state.players.steam_info.get(steamid)?;
+
+                // Only trust data that is actually newer than what we had
+                if new.fetched <= old.fetched {
+                    return None;
+                }
+
+                if new.vac_bans <= old.vac_bans && new.game_bans <= old.game_bans {
+                    return None;
+                }
+
+                tracing::warn!(
+                    "New ban detected on {} ({}): {} -> {} VAC bans, {} -> {} game bans",
+                    new.account_name,
+                    u64::from(*steamid),
+                    old.vac_bans,
+                    new.vac_bans,
+                    old.game_bans,
+                    new.game_bans
+                );
+
+                Some((
+                    *steamid,
+                    BanDetected {
+                        detected: Utc::now(),
+                        vac_bans: new.vac_bans,
+                        game_bans: new.game_bans,
+                    },
+                ))
+            })
+            .collect();
+
+        if new_bans.is_empty() {
+            return Handled::none();
+        }
+
+        Handled::single(NewBansDetected(new_bans))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+    use event_loop::{define_events, Action, EventLoop};
+    use steamid_ng::SteamID;
+
+    use super::{DetectNewBans, NewBansDetected};
+    use crate::{
+        masterbase::{queue::UploadQueue, UploadStatus},
+        players::{
+            records::{PlayerRecord, Records},
+            steam_info::{ProfileVisibility, SteamInfo},
+            Players,
+        },
+        server::Server,
+        settings::Settings,
+        steam::api::ProfileLookupResult,
+        MonitorState,
+    };
+
+    const STEAMID: u64 = 76_561_198_000_000_001;
+
+    define_events!(
+        MonitorState,
+        TestMessage {
+            ProfileLookupResult,
+            NewBansDetected,
+        },
+        TestHandler { DetectNewBans },
+    );
+
+    fn steam_info(vac_bans: u32, hours_old: i64) -> SteamInfo {
+        SteamInfo {
+            account_name: String::from("Player"),
+            profile_url: String::new(),
+            pfp_url: String::new(),
+            pfp_hash: String::new(),
+            profile_visibility: ProfileVisibility::Public,
+            time_created: None,
+            country_code: None,
+            vac_bans,
+            game_bans: 0,
+            days_since_last_ban: None,
+            playtime: None,
+            steam_level: None,
+            groups: None,
+            fetched: Utc::now() - Duration::hours(hours_old),
+        }
+    }
+
+    /// Looks up the account again with `vac_bans`, after it was last seen with
+    /// none, and returns the bans that were detected
+    fn detect_bans(vac_bans: u32) -> Option<NewBansDetected> {
+        let steamid = SteamID::from(STEAMID);
+        let mut state = MonitorState {
+            server: Server::new(),
+            settings: Settings::default(),
+            players: Players::new(Records::default(), None, None),
+            upload_status: UploadStatus::default(),
+            upload_queue: UploadQueue::default(),
+        };
+        state.players.steam_info.insert(steamid, steam_info(0, 2));
+
+        let mut event_loop: EventLoop<MonitorState, TestMessage, TestHandler> =
+            EventLoop::new().add_handler(DetectNewBans);
+        let lookup = ProfileLookupResult {
+            requested: vec![steamid],
+            result: Ok(vec![(steamid, Ok(steam_info(vac_bans, 0)))]),
+        };
+
+        let mut detected = event_loop
+            .handle_message(lookup.into(), &mut state)
+            .into_iter()
+            .filter_map(|a| match a {
+                Action::Message(TestMessage::NewBansDetected(bans)) => Some(bans),
+                _ => None,
+            });
+        let bans = detected.next();
+        assert!(detected.next().is_none());
+        bans
+    }
+
+    #[test]
+    pub fn unchanged_bans_are_not_reported() {
+        assert!(detect_bans(0).is_none());
+    }
+
+    #[test]
+    pub fn new_bans_are_reported() {
+        let NewBansDetected(bans) = detect_bans(1).expect("Ban was detected");
+        assert_eq!(bans.len(), 1);
+        let (steamid, ban) = bans[0].clone();
+        assert_eq!(steamid, SteamID::from(STEAMID));
+        assert_eq!(ban.vac_bans, 1);
+
+        // Noting the ban isn't a change the user made to the record
+        let mut record = PlayerRecord::default();
+        let modified = record.modified();
+        record.add_ban_detected(ban);
+        assert_eq!(record.bans_detected().len(), 1);
+        assert_eq!(record.modified(), modified);
+    }
+}
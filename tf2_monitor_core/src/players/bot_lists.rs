@@ -0,0 +1,344 @@
+//! Remote bot lists the user has subscribed to. Accounts on these lists are
+//! treated as bots, but are kept separate from the personal [`Records`] so
+//! they never end up in the playerlist file and can be dropped again by
+//! unsubscribing.
+//!
+//! [`Records`]: super::records::Records
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use event_loop::{try_get, Handled, Is, Message, MessageHandler};
+use reqwest::StatusCode;
+use steamid_ng::SteamID;
+use thiserror::Error;
+
+use super::tf2bd::Tf2bdPlayerlist;
+use crate::{events::Refresh, MonitorState};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait before trying to fetch a list again after it failed
+const FAILED_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+/// Players with any of these attributes in a TF2BD playerlist are taken as
+/// bots, others (e.g. `suspicious` or `racist`) are left out
+const BOT_ATTRIBUTES: [&str; 2] = ["cheater", "bot"];
+
+#[derive(Debug, Error)]
+pub enum BotListError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("Server responded with {0}")]
+    Status(StatusCode),
+    #[error("No steamids were found in the list")]
+    Empty,
+}
+
+/// The accounts on each subscribed bot list, keyed by the list's URL
+#[derive(Debug, Default)]
+pub struct BotLists {
+    lists: HashMap<String, HashSet<SteamID>>,
+}
+
+impl BotLists {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any subscribed list contains the player
+    #[must_use]
+    pub fn contains(&self, steamid: SteamID) -> bool {
+        self.lists.values().any(|l| l.contains(&steamid))
+    }
+
+    /// The URLs of the lists that contain the player
+    pub fn lists_containing(&self, steamid: SteamID) -> impl Iterator<Item = &str> {
+        self.lists
+            .iter()
+            .filter(move |(_, l)| l.contains(&steamid))
+            .map(|(url, _)| url.as_str())
+    }
+
+    /// Number of accounts on a list, if it has been fetched
+    #[must_use]
+    pub fn len_of(&self, url: &str) -> Option<usize> {
+        self.lists.get(url).map(HashSet::len)
+    }
+
+    pub fn set_list(&mut self, url: String, accounts: HashSet<SteamID>) {
+        self.lists.insert(url, accounts);
+    }
+
+    /// Forget any lists that are no longer subscribed to
+    pub fn retain_subscribed(&mut self, subscriptions: &[String]) {
+        self.lists.retain(|url, _| subscriptions.contains(url));
+    }
+}
+
+/// Parses a bot list, which is either a TF2BD playerlist or plain text with
+/// one steamid per line. Only players with one of the [`BOT_ATTRIBUTES`] are
+/// taken from playerlists. Blank lines and lines starting with `#` or `//` are
+/// ignored.
+///
+/// # Errors
+/// If no steamids could be found
+pub fn parse_bot_list(contents: &str) -> Result<HashSet<SteamID>, BotListError> {
+    let accounts: HashSet<SteamID> =
+        if let Ok(playerlist) = serde_json::from_str::<Tf2bdPlayerlist>(contents) {
+            playerlist
+                .players
+                .into_iter()
+                .filter(|p| {
+                    p.attributes
+                        .iter()
+                        .any(|a| BOT_ATTRIBUTES.contains(&a.as_str()))
+                })
+                .map(|p| p.steamid)
+                .collect()
+        } else {
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with("//"))
+                .filter_map(|l| match l.parse::<u64>() {
+                    Ok(id) => Some(SteamID::from(id)),
+                    Err(_) => SteamID::try_from(l).ok(),
+                })
+                .collect()
+        };
+
+    if accounts.is_empty() {
+        return Err(BotListError::Empty);
+    }
+
+    Ok(accounts)
+}
+
+async fn fetch_bot_list(url: &str) -> Result<HashSet<SteamID>, BotListError> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(BotListError::Status(response.status()));
+    }
+
+    parse_bot_list(&response.text().await?)
+}
+
+// Messages *********************
+
+#[derive(Debug)]
+pub struct BotListFetched {
+    pub url: String,
+    pub result: Result<HashSet<SteamID>, BotListError>,
+}
+impl Message<MonitorState> for BotListFetched {
+    fn update_state(self, state: &mut MonitorState) {
+        // Ignore lists that were unsubscribed from while being fetched
+        if !state.settings.bot_list_subscriptions.contains(&self.url) {
+            return;
+        }
+
+        match self.result {
+            Ok(accounts) => {
+                tracing::info!("Fetched {} accounts from {}", accounts.len(), self.url);
+                state.players.bot_lists.set_list(self.url, accounts);
+            }
+            Err(e) => tracing::error!("Failed to fetch bot list {}: {e}", self.url),
+        }
+    }
+}
+
+// Handlers *********************
+
+/// Fetches subscribed bot lists when they are first subscribed to and again
+/// every [`Settings::bot_list_refresh_hours`]. Lists that couldn't be fetched
+/// are tried again sooner.
+///
+/// [`Settings::bot_list_refresh_hours`]: crate::settings::Settings::bot_list_refresh_hours
+pub struct FetchBotLists {
+    /// When each list was last fetched successfully
+    last_fetched: HashMap<String, Instant>,
+    /// When each list last failed to be fetched
+    last_failed: HashMap<String, Instant>,
+    in_progress: HashSet<String>,
+    retry_delay: Duration,
+}
+
+impl Default for FetchBotLists {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FetchBotLists {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_retry_delay(FAILED_RETRY_DELAY)
+    }
+
+    /// Wait `retry_delay` before fetching a list again after it failed
+    #[must_use]
+    pub fn with_retry_delay(retry_delay: Duration) -> Self {
+        Self {
+            last_fetched: HashMap::new(),
+            last_failed: HashMap::new(),
+            in_progress: HashSet::new(),
+            retry_delay,
+        }
+    }
+}
+
+impl<IM, OM> MessageHandler<MonitorState, IM, OM> for FetchBotLists
+where
+    IM: Is<Refresh> + Is<BotListFetched>,
+    OM: Is<BotListFetched>,
+{
+    fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
+        if let Some(BotListFetched { url, result }) = try_get(message) {
+            self.in_progress.remove(url);
+            if result.is_ok() {
+                self.last_fetched.insert(url.clone(), Instant::now());
+                self.last_failed.remove(url);
+            } else {
+                self.last_failed.insert(url.clone(), Instant::now());
+            }
+            return Handled::none();
+        }
+
+        try_get::<Refresh>(message)?;
+
+        let refresh_interval =
+            Duration::from_secs(u64::from(state.settings.bot_list_refresh_hours) * 60 * 60);
+        let subscriptions = &state.settings.bot_list_subscriptions;
+        self.last_fetched
+            .retain(|url, _| subscriptions.contains(url));
+        self.last_failed
+            .retain(|url, _| subscriptions.contains(url));
+
+        let mut fetches = Vec::new();
+        for url in subscriptions {
+            if self.in_progress.contains(url)
+                || self
+                    .last_fetched
+                    .get(url)
+                    .is_some_and(|t| t.elapsed() < refresh_interval)
+                || self
+                    .last_failed
+                    .get(url)
+                    .is_some_and(|t| t.elapsed() < self.retry_delay)
+            {
+                continue;
+            }
+
+            tracing::debug!("Fetching bot list {url}");
+            self.in_progress.insert(url.clone());
+
+            let url = url.clone();
+            fetches.push(Handled::future(async move {
+                let result = fetch_bot_list(&url).await;
+                Some(BotListFetched { url, result }.into())
+            }));
+        }
+
+        Handled::multiple(fetches)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use event_loop::{define_events, Action, EventLoop};
+    use steamid_ng::SteamID;
+
+    use super::{parse_bot_list, BotListError, BotListFetched, FetchBotLists};
+    use crate::{
+        events::Refresh,
+        masterbase::{queue::UploadQueue, UploadStatus},
+        players::{records::Records, Players},
+        server::Server,
+        settings::Settings,
+        MonitorState,
+    };
+
+    const URL: &str = "https://example.com/bots.json";
+
+    define_events!(
+        MonitorState,
+        TestMessage {
+            Refresh,
+            BotListFetched,
+        },
+        TestHandler { FetchBotLists },
+    );
+
+    fn test_state() -> MonitorState {
+        let mut settings = Settings::default();
+        settings.bot_list_subscriptions = vec![URL.into()];
+
+        MonitorState {
+            server: Server::new(),
+            settings,
+            players: Players::new(Records::default(), None, None),
+            upload_status: UploadStatus::default(),
+            upload_queue: UploadQueue::default(),
+        }
+    }
+
+    fn num_fetches(actions: &[Action<TestMessage>]) -> usize {
+        actions
+            .iter()
+            .filter(|a| matches!(a, Action::Future(_)))
+            .count()
+    }
+
+    #[test]
+    pub fn only_bots_are_taken_from_playerlists() {
+        let list = r#"{
+            "$schema": "https://raw.githubusercontent.com/PazerOP/tf2_bot_detector/master/schemas/v3/playerlist.schema.json",
+            "players": [
+                { "steamid": 76561198000000001, "attributes": ["cheater"] },
+                { "steamid": 76561198000000002, "attributes": ["suspicious"] },
+                { "steamid": 76561198000000003, "attributes": ["racist"] },
+                { "steamid": 76561198000000004, "attributes": ["bot", "racist"] }
+            ]
+        }"#;
+
+        let accounts = parse_bot_list(list).expect("List has bots");
+        assert_eq!(accounts.len(), 2);
+        assert!(accounts.contains(&SteamID::from(76_561_198_000_000_001)));
+        assert!(accounts.contains(&SteamID::from(76_561_198_000_000_004)));
+    }
+
+    #[test]
+    pub fn failed_fetches_are_retried() {
+        let mut state = test_state();
+        let mut event_loop: EventLoop<MonitorState, TestMessage, TestHandler> =
+            EventLoop::new().add_handler(FetchBotLists::with_retry_delay(Duration::ZERO));
+
+        let actions = event_loop.handle_message(Refresh.into(), &mut state);
+        assert_eq!(num_fetches(&actions), 1);
+        let actions = event_loop.handle_message(Refresh.into(), &mut state);
+        assert_eq!(num_fetches(&actions), 0);
+
+        let failed = BotListFetched {
+            url: URL.into(),
+            result: Err(BotListError::Empty),
+        };
+        event_loop.handle_message(failed.into(), &mut state);
+        let actions = event_loop.handle_message(Refresh.into(), &mut state);
+        assert_eq!(num_fetches(&actions), 1);
+
+        let fetched = BotListFetched {
+            url: URL.into(),
+            result: Ok([SteamID::from(76_561_198_000_000_001)].into()),
+        };
+        event_loop.handle_message(fetched.into(), &mut state);
+        let actions = event_loop.handle_message(Refresh.into(), &mut state);
+        assert_eq!(num_fetches(&actions), 0);
+    }
+}
@@ -0,0 +1,145 @@
+//! Tracks which accounts have been on a server with players marked as
+//! cheaters or bots. Bot hosters tend to rotate through fresh accounts while
+//! playing alongside the same marked ones, so this helps spot the new
+//! accounts.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::Path,
+};
+
+use atomic_write_file::AtomicWriteFile;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use steamid_ng::SteamID;
+
+use crate::settings::ConfigFilesError;
+
+pub const CO_OCCURRENCE_FILE_NAME: &str = "co_occurrence.bin";
+/// How many accounts are remembered before the least recently seen are
+/// forgotten
+pub const MAX_TRACKED_ACCOUNTS: usize = 10_000;
+
+/// The marked players an account has been on a server with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeenWith {
+    /// How many separate times the account was on a server with any marked
+    /// player
+    pub times: u32,
+    /// How many times the account was on a server with each marked player
+    pub marked: HashMap<SteamID, u32>,
+    pub last: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct CoOccurrence {
+    accounts: HashMap<SteamID, SeenWith>,
+    /// Pairs of (marked, other) accounts currently on the server together, so
+    /// the same match isn't counted more than once
+    present: HashSet<(SteamID, SteamID)>,
+}
+
+impl CoOccurrence {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn get(&self, steamid: SteamID) -> Option<&SeenWith> {
+        self.accounts.get(&steamid)
+    }
+
+    /// How many separate times the account has been on a server with marked
+    /// players
+    #[must_use]
+    pub fn times_with_marked(&self, steamid: SteamID) -> u32 {
+        self.accounts.get(&steamid).map_or(0, |s| s.times)
+    }
+
+    /// Accounts that have been on a server with marked players at least
+    /// `threshold` times
+    pub fn accounts_over(&self, threshold: u32) -> impl Iterator<Item = SteamID> + '_ {
+        self.accounts
+            .iter()
+            .filter(move |(_, s)| s.times >= threshold)
+            .map(|(&steamid, _)| steamid)
+    }
+
+    /// Records which accounts are currently on the server with the `marked`
+    /// ones. A pair is only counted again once one of them has left and come
+    /// back, so this can be called as often as needed. `ignore` is never
+    /// recorded (i.e. the user).
+    pub fn update(&mut self, connected: &[SteamID], marked: &[SteamID], ignore: Option<SteamID>) {
+        let present: HashSet<(SteamID, SteamID)> = marked
+            .iter()
+            .flat_map(|&m| {
+                connected
+                    .iter()
+                    .filter(move |&&s| s != m && Some(s) != ignore)
+                    .map(move |&s| (m, s))
+            })
+            .collect();
+
+        let now = Utc::now();
+        let mut newly_with_marked = HashSet::new();
+        for &(m, s) in present.difference(&self.present) {
+            let seen_with = self.accounts.entry(s).or_insert_with(|| SeenWith {
+                times: 0,
+                marked: HashMap::new(),
+                last: now,
+            });
+            *seen_with.marked.entry(m).or_default() += 1;
+            seen_with.last = now;
+
+            if !self.present.iter().any(|&(_, o)| o == s) {
+                newly_with_marked.insert(s);
+            }
+        }
+
+        for s in newly_with_marked {
+            if let Some(seen_with) = self.accounts.get_mut(&s) {
+                seen_with.times += 1;
+            }
+        }
+
+        self.present = present;
+        self.prune();
+    }
+
+    /// Forget the least recently seen accounts if too many are being tracked
+    fn prune(&mut self) {
+        if self.accounts.len() <= MAX_TRACKED_ACCOUNTS {
+            return;
+        }
+
+        let mut by_age: Vec<(DateTime<Utc>, SteamID)> =
+            self.accounts.iter().map(|(&s, sw)| (sw.last, s)).collect();
+        by_age.sort_unstable_by_key(|(last, _)| *last);
+
+        let excess = self.accounts.len() - MAX_TRACKED_ACCOUNTS;
+        for (_, s) in by_age.into_iter().take(excess) {
+            self.accounts.remove(&s);
+        }
+    }
+
+    /// # Errors
+    /// If the file could not be read from disk or the data could not be deserialized
+    pub fn load_from(&mut self, path: &Path) -> Result<(), ConfigFilesError> {
+        let contents = std::fs::read(path)?;
+        self.accounts = pot::from_slice(&contents)?;
+        Ok(())
+    }
+
+    /// # Errors
+    /// If the data could not be serialized or the file could not be written back to disk
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigFilesError> {
+        let contents = pot::to_vec(&self.accounts)?;
+
+        let mut file = AtomicWriteFile::open(path)?;
+        file.write_all(&contents)?;
+        file.commit()?;
+        Ok(())
+    }
+}
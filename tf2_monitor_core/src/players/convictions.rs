@@ -0,0 +1,191 @@
+//! Community verdicts from the masterbase, i.e. whether an account has been
+//! convicted of cheating based on the demos uploaded by everyone.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use event_loop::{try_get, Handled, Is, Message, MessageHandler};
+use reqwest::StatusCode;
+use steamid_ng::SteamID;
+
+use super::new_players::NewPlayers;
+use crate::{
+    events::{InternalPreferences, Preferences},
+    masterbase::{self, fetch_convictions},
+    steam::api::ProfileLookupBatchTick,
+    MonitorState,
+};
+
+const BATCH_SIZE: usize = 100;
+/// Verdicts fetched more recently than this aren't fetched again
+const CACHE_MINUTES: i64 = 60;
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MasterbaseVerdict {
+    pub convicted: bool,
+    pub fetched: DateTime<Utc>,
+}
+
+impl MasterbaseVerdict {
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        Utc::now().signed_duration_since(self.fetched).num_minutes() >= CACHE_MINUTES
+    }
+}
+
+// Messages *********************
+
+#[derive(Debug)]
+pub struct ConvictionsFetched {
+    pub requested: Vec<SteamID>,
+    pub result: Result<HashMap<SteamID, bool>, masterbase::Error>,
+}
+impl Message<MonitorState> for ConvictionsFetched {
+    fn update_state(self, state: &mut MonitorState) {
+        let Ok(convicted) = self.result else {
+            return;
+        };
+
+        let fetched = Utc::now();
+        for s in self.requested {
+            let verdict = MasterbaseVerdict {
+                convicted: convicted.get(&s).copied().unwrap_or(false),
+                fetched,
+            };
+
+            if verdict.convicted
+                && !state
+                    .players
+                    .masterbase_verdicts
+                    .get(&s)
+                    .is_some_and(|v| v.convicted)
+            {
+                tracing::warn!("{} has been convicted by the masterbase", u64::from(s));
+            }
+
+            state.players.masterbase_verdicts.insert(s, verdict);
+        }
+    }
+}
+
+// Handlers *********************
+
+/// Fetches the masterbase verdicts of new players in batches while MAC
+/// integration is enabled.
+#[derive(Default)]
+pub struct LookupConvictions {
+    batch_buffer: Vec<SteamID>,
+    in_progress: bool,
+    retry_at: Option<Instant>,
+    /// The masterbase has rejected the current key, so don't make any more
+    /// requests until it is changed.
+    key_rejected: bool,
+}
+
+impl LookupConvictions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle_result(
+        &mut self,
+        requested: &[SteamID],
+        result: &Result<HashMap<SteamID, bool>, masterbase::Error>,
+    ) {
+        self.in_progress = false;
+
+        match result {
+            Ok(_) => self.retry_at = None,
+            Err(masterbase::Error::Status(StatusCode::UNAUTHORIZED, _)) => {
+                tracing::error!("Masterbase verdict lookups disabled until the key is changed.");
+                self.key_rejected = true;
+                self.batch_buffer.clear();
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to fetch masterbase verdicts of {} accounts ({e}), retrying in {}s",
+                    requested.len(),
+                    RETRY_DELAY.as_secs()
+                );
+                self.batch_buffer.extend(requested);
+                self.retry_at = Some(Instant::now() + RETRY_DELAY);
+            }
+        }
+    }
+}
+
+impl<IM, OM> MessageHandler<MonitorState, IM, OM> for LookupConvictions
+where
+    IM: Is<NewPlayers> + Is<ProfileLookupBatchTick> + Is<Preferences> + Is<ConvictionsFetched>,
+    OM: Is<ConvictionsFetched>,
+{
+    fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
+        if let Some(ConvictionsFetched { requested, result }) = try_get(message) {
+            self.handle_result(requested, result);
+            return Handled::none();
+        }
+
+        // Look up everyone again with a new key
+        if let Some(Preferences {
+            internal:
+                Some(InternalPreferences {
+                    masterbase_key: Some(_),
+                    ..
+                }),
+            external: _,
+        }) = try_get(message)
+        {
+            self.key_rejected = false;
+            self.retry_at = None;
+            self.batch_buffer.extend(&state.players.connected);
+        }
+
+        if !state.settings.upload_demos
+            || state.settings.offline
+            || state.settings.masterbase_key.is_empty()
+            || self.key_rejected
+        {
+            return None;
+        }
+
+        if let Some(NewPlayers(new_players)) = try_get(message) {
+            self.batch_buffer.extend(new_players.iter().filter(|s| {
+                state
+                    .players
+                    .masterbase_verdicts
+                    .get(s)
+                    .map_or(true, MasterbaseVerdict::is_stale)
+            }));
+        }
+
+        // Send off a batch, one at a time
+        try_get::<ProfileLookupBatchTick>(message)?;
+        if self.in_progress
+            || self.batch_buffer.is_empty()
+            || self.retry_at.is_some_and(|t| t > Instant::now())
+        {
+            return None;
+        }
+
+        self.batch_buffer.sort_unstable();
+        self.batch_buffer.dedup();
+        let requested: Vec<SteamID> = self
+            .batch_buffer
+            .drain(..self.batch_buffer.len().min(BATCH_SIZE))
+            .collect();
+        self.in_progress = true;
+
+        let host = state.settings.masterbase_host.clone();
+        let key = state.settings.masterbase_key.clone();
+        let http = state.settings.masterbase_http;
+        Handled::future(async move {
+            let result = fetch_convictions(&host, &key, http, &requested).await;
+            Some(ConvictionsFetched { requested, result }.into())
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use tf_demo_parser::demo::parser::analyser::Class;
 
 use crate::console::commands::{g15::G15Player, regexes::StatusLine};
 
@@ -13,7 +14,13 @@ pub struct GameInfo {
     pub state: PlayerState,
     pub kills: u32,
     pub deaths: u32,
-    pub alive: bool,
+    /// Only known once the player has shown up in `g15_dumpplayer`
+    pub alive: Option<bool>,
+    /// Only known once the player has shown up in `g15_dumpplayer`
+    pub class: Option<Class>,
+    /// The team the matchmaking lobby has put them on, which is known before
+    /// they've finished loading in. Only on Valve servers.
+    pub lobby_team: Option<Team>,
     #[serde(skip)]
     /// How many cycles has passed since the player has been seen
     last_seen: u32,
@@ -32,7 +39,9 @@ impl Default for GameInfo {
             kills: 0,
             deaths: 0,
             last_seen: 0,
-            alive: false,
+            alive: None,
+            class: None,
+            lobby_team: None,
         }
     }
 }
@@ -76,7 +85,10 @@ impl GameInfo {
             self.deaths = deaths;
         }
         if let Some(alive) = g15.alive {
-            self.alive = alive;
+            self.alive = Some(alive);
+        }
+        if let Some(class) = g15.class {
+            self.class = Some(class);
         }
 
         self.acknowledge();
@@ -97,7 +109,7 @@ impl GameInfo {
         // Makes it easy to spot bots taking up a player slot that can't be kicked.
         else if self.state != PlayerState::Spawning
             || status.state != PlayerState::Active
-            || self.alive
+            || self.alive == Some(true)
             || self.team == Team::Spectators
         {
             self.state = status.state;
@@ -106,6 +118,16 @@ impl GameInfo {
         self.acknowledge();
     }
 
+    /// Their team, or the one the lobby has them on if they haven't joined
+    /// one yet
+    #[must_use]
+    pub fn team_or_lobby(&self) -> Team {
+        match (self.team, self.lobby_team) {
+            (Team::Unassigned, Some(team)) => team,
+            (team, _) => team,
+        }
+    }
+
     pub(crate) fn next_cycle(&mut self) {
         const DISCONNECTED_THRESHOLD: u32 = 2;
 
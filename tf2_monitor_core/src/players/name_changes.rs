@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use event_loop::{try_get, Handled, Is, Message, MessageHandler};
+use steamid_ng::SteamID;
+
+use crate::{console::ConsoleOutput, MonitorState};
+
+#[derive(Debug, Clone)]
+pub struct NameChange {
+    pub steamid: SteamID,
+    pub from: String,
+    pub to: String,
+    pub changed: DateTime<Utc>,
+}
+
+// Messages *********************
+
+/// Players in the server who have just changed their name
+#[derive(Debug, Clone)]
+pub struct NamesChanged(pub Vec<NameChange>);
+impl Message<MonitorState> for NamesChanged {
+    fn update_state(self, state: &mut MonitorState) {
+        for change in self.0 {
+            state
+                .players
+                .name_changes
+                .entry(change.steamid)
+                .or_default()
+                .push(change);
+        }
+    }
+}
+
+// Handlers *********************
+
+/// Compares the names in status and `g15_dumpplayer` output against the
+/// names players already had. This has to see the output before it updates
+/// the state.
+pub struct DetectNameChanges;
+impl<IM, OM> MessageHandler<MonitorState, IM, OM> for DetectNameChanges
+where
+    IM: Is<ConsoleOutput>,
+    OM: Is<NamesChanged>,
+{
+    fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
+        let console_out: &ConsoleOutput = try_get(message)?;
+
+        let names: Vec<(SteamID, &str)> = match console_out {
+            ConsoleOutput::Status(s) => vec![(s.steamid, s.name.as_str())],
+            ConsoleOutput::G15(ps) => ps
+                .iter()
+                .filter_map(|p| Some((p.steamid?, p.name.as_deref()?)))
+                .collect(),
+            _ => return Handled::none(),
+        };
+
+        let changed = Utc::now();
+        let changes: Vec<_> = names
+            .into_iter()
+            .filter_map(|(steamid, name)| {
+                let old = &state.players.game_info.get(&steamid)?.name;
+                if old.is_empty() || name.is_empty() || old == name {
+                    return None;
+                }
+
+                Some(NameChange {
+                    steamid,
+                    from: old.clone(),
+                    to: name.to_owned(),
+                    changed,
+                })
+            })
+            .collect();
+
+        if changes.is_empty() {
+            return Handled::none();
+        }
+
+        Handled::single(NamesChanged(changes))
+    }
+}
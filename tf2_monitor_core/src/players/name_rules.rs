@@ -0,0 +1,98 @@
+//! Rules that match player names against regexes, for bot waves that join
+//! with templated names. Records marked by a rule are tagged with it so the
+//! marks can be found and undone later.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::records::Verdict;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NameRuleAction {
+    MarkBot,
+    MarkSuspicious,
+    /// Only show a badge next to the player
+    Badge,
+}
+
+impl NameRuleAction {
+    pub const ALL: &'static [Self] = &[Self::MarkBot, Self::MarkSuspicious, Self::Badge];
+
+    /// The verdict the rule gives players, if it gives one
+    #[must_use]
+    pub const fn verdict(self) -> Option<Verdict> {
+        match self {
+            Self::MarkBot => Some(Verdict::Bot),
+            Self::MarkSuspicious => Some(Verdict::Suspicious),
+            Self::Badge => None,
+        }
+    }
+}
+
+impl std::fmt::Display for NameRuleAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::MarkBot => "Mark as Bot",
+            Self::MarkSuspicious => "Mark as Suspicious",
+            Self::Badge => "Badge only",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameRule {
+    /// A regex matched against the player's name
+    pub pattern: String,
+    pub action: NameRuleAction,
+}
+
+/// The rules from the settings with their regexes compiled, so checking names
+/// is cheap
+#[derive(Debug, Default)]
+pub struct NameRules {
+    rules: Vec<(NameRule, Regex)>,
+    /// Patterns that couldn't be compiled and their errors
+    invalid: Vec<(String, String)>,
+}
+
+impl NameRules {
+    /// Rules whose patterns aren't valid regexes are left out
+    #[must_use]
+    pub fn new(rules: &[NameRule]) -> Self {
+        let mut compiled = Vec::new();
+        let mut invalid = Vec::new();
+        for rule in rules {
+            match Regex::new(&rule.pattern) {
+                Ok(regex) => compiled.push((rule.clone(), regex)),
+                Err(e) => {
+                    tracing::error!("Invalid name rule \"{}\": {e}", rule.pattern);
+                    invalid.push((rule.pattern.clone(), e.to_string()));
+                }
+            }
+        }
+
+        Self {
+            rules: compiled,
+            invalid,
+        }
+    }
+
+    /// The first rule that matches the name
+    #[must_use]
+    pub fn matching(&self, name: &str) -> Option<&NameRule> {
+        self.rules
+            .iter()
+            .find(|(_, regex)| regex.is_match(name))
+            .map(|(rule, _)| rule)
+    }
+
+    /// Why the rule's pattern couldn't be used, if it's invalid
+    #[must_use]
+    pub fn pattern_error(&self, pattern: &str) -> Option<&str> {
+        self.invalid
+            .iter()
+            .find(|(p, _)| p == pattern)
+            .map(|(_, e)| e.as_str())
+    }
+}
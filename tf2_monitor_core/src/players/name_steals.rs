@@ -0,0 +1,134 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use steamid_ng::SteamID;
+
+use super::{game_info::GameInfo, records::Records, steam_info::SteamInfo};
+use crate::console::commands::regexes::INVIS_CHARS;
+
+/// Name-stealing bots copy the name of a player in the server, often with an
+/// invisible character added so the game doesn't rename them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameSteal {
+    /// Whichever of the two looks more like a bot
+    pub stealer: SteamID,
+    /// The player whose name was copied
+    pub original: SteamID,
+}
+
+pub struct NameSteals {
+    steals: Vec<NameSteal>,
+}
+
+impl NameSteals {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { steals: Vec::new() }
+    }
+
+    #[must_use]
+    pub fn steals(&self) -> &[NameSteal] {
+        &self.steals
+    }
+
+    /// The name steal the player is part of, either as the stealer or the
+    /// original
+    #[must_use]
+    pub fn involving(&self, steamid: SteamID) -> Option<&NameSteal> {
+        self.steals
+            .iter()
+            .find(|s| s.stealer == steamid || s.original == steamid)
+    }
+
+    /// Finds connected players with the same name once invisible characters
+    /// are ignored. Of each group with the same name, the player with a
+    /// record, then the older account, then more playtime, is taken to be
+    /// the original.
+    pub fn find_name_steals(
+        &mut self,
+        connected: &[SteamID],
+        game_info: &HashMap<SteamID, GameInfo>,
+        steam_info: &HashMap<SteamID, SteamInfo>,
+        records: &Records,
+    ) {
+        let mut names: HashMap<String, Vec<SteamID>> = HashMap::new();
+        for &s in connected {
+            let Some(gi) = game_info.get(&s) else {
+                continue;
+            };
+            let name = normalise_name(&gi.name);
+            if name.is_empty() {
+                continue;
+            }
+            names.entry(name).or_default().push(s);
+        }
+
+        // Most likely to be the original first
+        let compare = |a: &SteamID, b: &SteamID| {
+            let recorded = |s: &SteamID| records.contains_key(s);
+            let created = |s: &SteamID| steam_info.get(s).and_then(|si| si.time_created);
+            let playtime = |s: &SteamID| steam_info.get(s).and_then(|si| si.playtime);
+            let time = |s: &SteamID| game_info.get(s).map(|gi| gi.time);
+
+            recorded(b)
+                .cmp(&recorded(a))
+                .then_with(|| match (created(a), created(b)) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    _ => Ordering::Equal,
+                })
+                .then_with(|| match (playtime(a), playtime(b)) {
+                    (Some(a), Some(b)) => b.cmp(&a),
+                    _ => Ordering::Equal,
+                })
+                .then_with(|| time(b).cmp(&time(a)))
+        };
+
+        self.steals.clear();
+        for mut group in names.into_values().filter(|g| g.len() > 1) {
+            group.sort_by(compare);
+            let original = group[0];
+            self.steals.extend(
+                group[1..]
+                    .iter()
+                    .map(|&stealer| NameSteal { stealer, original }),
+            );
+        }
+    }
+}
+
+impl Default for NameSteals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The name without invisible characters or whitespace, so copied names with
+/// some added still compare the same
+#[must_use]
+pub fn normalise_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control() && !INVIS_CHARS.contains(c))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::normalise_name;
+
+    #[test]
+    fn invisible_characters_are_ignored() {
+        let name = "Pyro Main";
+        for copied in [
+            "Pyro Main\u{200f}",
+            "\u{2800}Pyro Main",
+            "Pyro\u{3164}Main",
+            "Pyro Main\u{200b}\u{200b}",
+            "Pyro\u{00a0}Main\u{feff}",
+            "Pyro Main\n",
+        ] {
+            assert_eq!(normalise_name(copied), normalise_name(name), "{copied:?}");
+        }
+
+        assert_ne!(normalise_name("Pyro Main2"), normalise_name(name));
+        assert_eq!(normalise_name("\u{2800}\u{200f} "), "");
+    }
+}
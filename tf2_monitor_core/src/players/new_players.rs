@@ -37,7 +37,9 @@ where
             | ConsoleOutput::ServerIP(_)
             | ConsoleOutput::Map(_)
             | ConsoleOutput::PlayerCount(_)
-            | ConsoleOutput::DemoStop(_) => {}
+            | ConsoleOutput::DemoStop(_)
+            | ConsoleOutput::VoteResult(_)
+            | ConsoleOutput::LobbyDebug(_) => {}
             ConsoleOutput::G15(ps) => {
                 steamids.extend(ps.iter().filter_map(|p| p.steamid));
             }
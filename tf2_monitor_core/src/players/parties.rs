@@ -25,6 +25,8 @@ impl Parties {
 
     /// Given a set of players and all of their friends, as well as a list to limit which accounts will be analysed,
     /// create a set of groups where all the members in a group are friends with each other.
+    /// Players who have disconnected shouldn't be in `connected`, see
+    /// [`Players::present`](super::Players::present).
     pub fn find_parties(&mut self, friends: &HashMap<SteamID, FriendInfo>, connected: &[SteamID]) {
         let are_friends = |a: SteamID, b: SteamID| {
             friends
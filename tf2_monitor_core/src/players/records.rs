@@ -4,6 +4,7 @@ use std::{
     io::{ErrorKind, Write},
     ops::{Deref, DerefMut},
     path::PathBuf,
+    time::SystemTime,
 };
 
 use atomic_write_file::AtomicWriteFile;
@@ -12,9 +13,27 @@ use serde::{Deserialize, Serialize};
 use serde_json::Map;
 use steamid_ng::SteamID;
 
-use crate::settings::{merge_json_objects, AppDetails, ConfigFilesError, Settings};
+use crate::settings::{
+    file_modified, instance, merge_json_objects, AppDetails, ConfigFilesError, Settings,
+};
 
 pub const RECORDS_FILE_NAME: &str = "playerlist.json";
+/// Key in a record's custom data where detected bans are listed
+pub const BANS_DETECTED_KEY: &str = "bansDetected";
+/// Key in a record's custom data where suspected alt accounts are listed
+pub const LINKED_ACCOUNTS_KEY: &str = "linkedAccounts";
+/// Key in a record's custom data where counts of how the player voted are kept
+pub const VOTE_STATS_KEY: &str = "voteStats";
+/// The name rule that gave a record its verdict, see [`NameRuleMark`]
+pub const NAME_RULE_KEY: &str = "nameRule";
+/// How many verdict changes are remembered per record
+pub const MAX_VERDICT_HISTORY: usize = 20;
+/// How many encounter dates are remembered per record
+pub const MAX_RECENT_ENCOUNTERS: usize = 10;
+/// How out of date the time a player's current name was last used can get
+/// before it is updated, so seeing them every refresh doesn't keep changing
+/// their record
+const NAME_LAST_USED_INTERVAL_SECONDS: i64 = 60 * 60;
 
 // PlayerList
 
@@ -23,6 +42,26 @@ pub struct Records {
     #[serde(skip)]
     pub path: Option<PathBuf>,
     pub records: HashMap<SteamID, PlayerRecord>,
+    /// Records have (possibly) changed since they were last saved
+    #[serde(skip)]
+    dirty: bool,
+    /// When the records were last loaded or saved, records modified after this
+    /// were changed in this session
+    #[serde(skip)]
+    synced_at: Option<DateTime<Utc>>,
+    /// Modified time of the file when it was last loaded or saved, to tell
+    /// when something else has changed it
+    #[serde(skip)]
+    saved_mtime: Option<SystemTime>,
+}
+
+/// The outcome of merging records that were changed outside of the app
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReloadSummary {
+    pub added: usize,
+    pub updated: usize,
+    /// Records changed both here and externally
+    pub conflicts: usize,
 }
 
 impl Records {
@@ -60,9 +99,12 @@ impl Records {
     /// # Errors
     /// If the file could not be located, read, or parsed.
     pub fn load_from(path: PathBuf) -> Result<Self, ConfigFilesError> {
+        let saved_mtime = file_modified(&path);
         let contents = std::fs::read_to_string(&path)?;
         let mut playerlist: Self = serde_json::from_str(&contents)?;
         playerlist.path = Some(path);
+        playerlist.synced_at = Some(Utc::now());
+        playerlist.saved_mtime = saved_mtime;
 
         // Map all of the steamids to the records. They were not included when
         // serializing/deserializing the records to prevent duplication in the
@@ -85,11 +127,29 @@ impl Records {
         self.retain(|_, r| !r.is_empty());
     }
 
-    /// Attempt to save the `PlayerRecords` to the file it was loaded from
+    /// Attempt to save the `PlayerRecords` to the file it was loaded from. If
+    /// something else has changed the file since it was loaded or saved, its
+    /// records are merged in first, see [`Self::reload`].
     ///
     /// # Errors
     /// If it failed to serialize or write back to the file.
     pub fn save(&mut self) -> Result<(), ConfigFilesError> {
+        if instance::is_read_only() {
+            return Ok(());
+        }
+
+        // Don't overwrite changes made by something else since the last save
+        if self.changed_on_disk() {
+            match self.reload() {
+                Ok(summary) => {
+                    tracing::info!("Merged records changed on disk before saving: {summary:?}");
+                }
+                Err(e) => tracing::warn!(
+                    "Records were changed on disk but couldn't be merged before saving: {e}"
+                ),
+            }
+        }
+
         self.prune();
 
         let path = self.path.as_ref().ok_or(ConfigFilesError::NoConfigSet)?;
@@ -100,9 +160,34 @@ impl Records {
         write!(file, "{contents}")?;
         file.commit()?;
 
+        self.dirty = false;
+        self.synced_at = Some(Utc::now());
+        self.saved_mtime = file_modified(path);
         Ok(())
     }
 
+    /// Save the records only if they have changed since they were last saved.
+    /// Returns whether they were written.
+    ///
+    /// # Errors
+    /// If it failed to serialize or write back to the file.
+    pub fn save_if_dirty(&mut self) -> Result<bool, ConfigFilesError> {
+        if !self.dirty {
+            return Ok(false);
+        }
+
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Whether the records may have changed since they were last saved. Any
+    /// mutable access to the records (e.g. to change a verdict or notes) counts
+    /// as a change.
+    #[must_use]
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
     pub fn save_ok(&mut self) {
         match self.save() {
             Ok(()) => tracing::debug!("Successfully saved player records to {:?}", self.path),
@@ -110,10 +195,125 @@ impl Records {
         }
     }
 
+    /// Whether the file has been changed by something else since it was last
+    /// loaded or saved
+    #[must_use]
+    pub fn changed_on_disk(&self) -> bool {
+        self.path
+            .as_ref()
+            .and_then(|p| file_modified(p))
+            .is_some_and(|mtime| Some(mtime) != self.saved_mtime)
+    }
+
+    /// Load the file again and merge it with these records, see
+    /// [`Self::merge_external`]
+    ///
+    /// # Errors
+    /// If the file could not be read or parsed
+    pub fn reload(&mut self) -> Result<ReloadSummary, ConfigFilesError> {
+        let path = self.path.clone().ok_or(ConfigFilesError::NoConfigSet)?;
+        let external = Self::load_from(path)?;
+
+        let summary = self.merge_external(external.records);
+        self.synced_at = external.synced_at;
+        self.saved_mtime = external.saved_mtime;
+        Ok(summary)
+    }
+
+    /// Merges records that were changed outside of the app. The most recently
+    /// modified version of each record is kept, unless it was changed both
+    /// here and externally since the records were last loaded or saved, in
+    /// which case the one with the more severe verdict is kept.
+    ///
+    /// Records that are missing from `external` are kept, since they may be
+    /// new ones that haven't been saved yet.
+    pub fn merge_external(&mut self, external: HashMap<SteamID, PlayerRecord>) -> ReloadSummary {
+        let mut summary = ReloadSummary::default();
+        let changed_since_sync = |r: &PlayerRecord| self.synced_at.map_or(true, |t| r.modified > t);
+
+        let mut merged = Vec::new();
+        for (steamid, theirs) in external {
+            let Some(ours) = self.records.get(&steamid) else {
+                summary.added += 1;
+                merged.push((steamid, theirs));
+                continue;
+            };
+
+            if ours.modified == theirs.modified {
+                continue;
+            }
+
+            let take_theirs = if changed_since_sync(ours) && changed_since_sync(&theirs) {
+                summary.conflicts += 1;
+                let take_theirs = match theirs.verdict.severity().cmp(&ours.verdict.severity()) {
+                    std::cmp::Ordering::Greater => true,
+                    std::cmp::Ordering::Less => false,
+                    std::cmp::Ordering::Equal => theirs.modified > ours.modified,
+                };
+                tracing::warn!(
+                    "Record for {} was changed both here ({}) and externally ({}), keeping the {} one.",
+                    u64::from(steamid),
+                    ours.verdict,
+                    theirs.verdict,
+                    if take_theirs { "external" } else { "local" }
+                );
+                take_theirs
+            } else {
+                theirs.modified > ours.modified
+            };
+
+            if take_theirs {
+                summary.updated += 1;
+                merged.push((steamid, theirs));
+            }
+        }
+
+        self.records.extend(merged);
+        summary
+    }
+
     pub fn update_name(&mut self, steamid: SteamID, name: &str) {
         if let Some(record) = self.records.get_mut(&steamid) {
-            record.add_previous_name(name);
+            if record.add_previous_name(name) {
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Link two accounts as suspected alts of each other. Links are kept on
+    /// both records.
+    pub fn link_accounts(&mut self, a: SteamID, b: SteamID) {
+        if a == b {
+            return;
+        }
+
+        self.records.entry(a).or_default().add_linked_account(b);
+        self.records.entry(b).or_default().add_linked_account(a);
+        self.dirty = true;
+    }
+
+    /// Remove the link between two accounts from both records
+    pub fn unlink_accounts(&mut self, a: SteamID, b: SteamID) {
+        if let Some(record) = self.records.get_mut(&a) {
+            record.remove_linked_account(b);
+        }
+        if let Some(record) = self.records.get_mut(&b) {
+            record.remove_linked_account(a);
         }
+        self.dirty = true;
+    }
+
+    /// Delete a record, also removing any links other records have to it
+    pub fn remove_record(&mut self, steamid: SteamID) -> Option<PlayerRecord> {
+        let record = self.records.remove(&steamid)?;
+        self.dirty = true;
+        for linked in record.linked_accounts() {
+            if let Some(other) = self.records.get_mut(&linked) {
+                other.remove_linked_account(steamid);
+            }
+        }
+
+        Some(record)
     }
 }
 
@@ -127,6 +327,7 @@ impl Deref for Records {
 
 impl DerefMut for Records {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        self.dirty = true;
         &mut self.records
     }
 }
@@ -139,11 +340,19 @@ impl DerefMut for Records {
 pub struct PlayerRecord {
     custom_data: serde_json::Value,
     verdict: Verdict,
-    previous_names: Vec<String>,
+    /// Most recently used first
+    previous_names: Vec<PreviousName>,
     last_seen: Option<DateTime<Utc>>,
     /// Time of last manual change made by the user.
     modified: DateTime<Utc>,
     created: DateTime<Utc>,
+    /// When the verdict was changed and what it was changed to, oldest first
+    verdict_history: Vec<(DateTime<Utc>, Verdict)>,
+    /// How many times the player has joined a server with the user
+    encounters: u32,
+    first_encountered: Option<DateTime<Utc>>,
+    /// When the most recent encounters were, oldest first
+    recent_encounters: Vec<DateTime<Utc>>,
 }
 
 impl PlayerRecord {
@@ -158,7 +367,50 @@ impl PlayerRecord {
                     .is_some_and(|m| m.values().all(value_is_empty))
         }
 
-        self.verdict == Verdict::Player && value_is_empty(&self.custom_data)
+        self.verdict == Verdict::Player
+            && value_is_empty(&self.custom_data)
+            && self
+                .verdict_history
+                .iter()
+                .all(|(_, v)| *v == Verdict::Player)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "PreviousNameRepr")]
+pub struct PreviousName {
+    pub name: String,
+    /// When the player was last seen using the name, if it was recorded
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// Older playerlists only have the name
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PreviousNameRepr {
+    Name(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        last_used: Option<DateTime<Utc>>,
+    },
+}
+
+impl From<PreviousNameRepr> for PreviousName {
+    fn from(repr: PreviousNameRepr) -> Self {
+        match repr {
+            PreviousNameRepr::Name(name) => Self {
+                name,
+                last_used: None,
+            },
+            PreviousNameRepr::Full { name, last_used } => Self { name, last_used },
+        }
+    }
+}
+
+impl Display for PreviousName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
     }
 }
 
@@ -171,6 +423,10 @@ impl Default for PlayerRecord {
             last_seen: None,
             modified: default_date(),
             created: default_date(),
+            verdict_history: Vec::new(),
+            encounters: 0,
+            first_encountered: None,
+            recent_encounters: Vec::new(),
         }
     }
 }
@@ -195,22 +451,71 @@ impl PlayerRecord {
         self.verdict
     }
     pub fn set_verdict(&mut self, verdict: Verdict) -> &mut Self {
+        if verdict != self.verdict {
+            // Records from before history was kept still know their old verdict
+            if self.verdict_history.is_empty() && self.verdict != Verdict::Player {
+                self.verdict_history.push((self.modified, self.verdict));
+            }
+
+            self.verdict_history.push((Utc::now(), verdict));
+            if self.verdict_history.len() > MAX_VERDICT_HISTORY {
+                let excess = self.verdict_history.len() - MAX_VERDICT_HISTORY;
+                self.verdict_history.drain(..excess);
+            }
+        }
+
         self.verdict = verdict;
         self.modified = Utc::now();
         self
     }
+    /// Past verdict changes, oldest first
     #[must_use]
-    pub fn previous_names(&self) -> &[String] {
+    pub fn verdict_history(&self) -> &[(DateTime<Utc>, Verdict)] {
+        &self.verdict_history
+    }
+    /// Most recently used first
+    #[must_use]
+    pub fn previous_names(&self) -> &[PreviousName] {
         &self.previous_names
     }
-    pub fn add_previous_name(&mut self, name: &str) -> &mut Self {
-        if self.previous_names.first().is_some_and(|n| n == name) {
-            return self;
+    /// Names the player has used, most recently used first
+    pub fn previous_name_strs(&self) -> impl Iterator<Item = &str> {
+        self.previous_names.iter().map(|n| n.name.as_str())
+    }
+    /// Remember that the player is using `name` now, returning whether
+    /// anything changed
+    pub fn add_previous_name(&mut self, name: &str) -> bool {
+        self.add_previous_name_at(name, Some(Utc::now()))
+    }
+    /// Remember `name` as the latest one the player has used, e.g. from an
+    /// imported playerlist where it was last used at some other time. Returns
+    /// whether anything changed.
+    pub(crate) fn add_previous_name_at(
+        &mut self,
+        name: &str,
+        last_used: Option<DateTime<Utc>>,
+    ) -> bool {
+        if let Some(latest) = self.previous_names.first_mut().filter(|n| n.name == name) {
+            let outdated = last_used.is_some_and(|new| {
+                latest.last_used.map_or(true, |old| {
+                    (new - old).num_seconds() >= NAME_LAST_USED_INTERVAL_SECONDS
+                })
+            });
+            if outdated {
+                latest.last_used = last_used;
+            }
+            return outdated;
         }
 
-        self.previous_names.retain(|n| n != name);
-        self.previous_names.insert(0, name.to_owned());
-        self
+        self.previous_names.retain(|n| n.name != name);
+        self.previous_names.insert(
+            0,
+            PreviousName {
+                name: name.to_owned(),
+                last_used,
+            },
+        );
+        true
     }
     #[must_use]
     pub const fn modified(&self) -> DateTime<Utc> {
@@ -229,6 +534,200 @@ impl PlayerRecord {
     pub fn mark_seen(&mut self) {
         self.last_seen = Some(Utc::now());
     }
+
+    pub fn set_last_seen(&mut self, last_seen: DateTime<Utc>) {
+        self.last_seen = Some(last_seen);
+    }
+
+    /// Count a new encounter with the player. This should only be called once
+    /// each time they join a server with the user.
+    pub fn add_encounter(&mut self) {
+        let now = Utc::now();
+        self.last_seen = Some(now);
+        self.encounters += 1;
+        self.first_encountered.get_or_insert(now);
+
+        self.recent_encounters.push(now);
+        if self.recent_encounters.len() > MAX_RECENT_ENCOUNTERS {
+            let excess = self.recent_encounters.len() - MAX_RECENT_ENCOUNTERS;
+            self.recent_encounters.drain(..excess);
+        }
+    }
+
+    #[must_use]
+    pub const fn encounters(&self) -> u32 {
+        self.encounters
+    }
+
+    #[must_use]
+    pub const fn first_encountered(&self) -> Option<DateTime<Utc>> {
+        self.first_encountered
+    }
+
+    /// When the most recent encounters were, oldest first
+    #[must_use]
+    pub fn recent_encounters(&self) -> &[DateTime<Utc>] {
+        &self.recent_encounters
+    }
+
+    /// Any new bans that have been noticed on the account
+    #[must_use]
+    pub fn bans_detected(&self) -> Vec<BanDetected> {
+        self.custom_data
+            .get(BANS_DETECTED_KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Doesn't count as the record being modified, since the user didn't
+    /// change it
+    ///
+    /// # Panics
+    /// If the list of bans could not be serialized
+    pub fn add_ban_detected(&mut self, ban: BanDetected) -> &mut Self {
+        let mut bans = self.bans_detected();
+        bans.push(ban);
+
+        let mut val = Map::new();
+        val.insert(
+            BANS_DETECTED_KEY.to_string(),
+            serde_json::to_value(bans).expect("Serializing detected bans"),
+        );
+        merge_json_objects(&mut self.custom_data, serde_json::Value::Object(val));
+        self
+    }
+}
+
+impl PlayerRecord {
+    #[must_use]
+    pub fn vote_stats(&self) -> VoteStats {
+        self.custom_data
+            .get(VOTE_STATS_KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Doesn't count as the record being modified, since the user didn't
+    /// change it
+    ///
+    /// # Panics
+    /// If the stats could not be serialized
+    pub fn set_vote_stats(&mut self, stats: VoteStats) -> &mut Self {
+        let mut val = Map::new();
+        val.insert(
+            VOTE_STATS_KEY.to_string(),
+            serde_json::to_value(stats).expect("Serializing vote stats"),
+        );
+        merge_json_objects(&mut self.custom_data, serde_json::Value::Object(val));
+        self
+    }
+}
+
+impl PlayerRecord {
+    /// The name rule that gave the record its verdict, if one did
+    #[must_use]
+    pub fn name_rule_mark(&self) -> Option<NameRuleMark> {
+        self.custom_data
+            .get(NAME_RULE_KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    /// `None` removes the mark
+    ///
+    /// # Panics
+    /// If the mark could not be serialized
+    pub fn set_name_rule_mark(&mut self, mark: Option<&NameRuleMark>) -> &mut Self {
+        let mut val = Map::new();
+        val.insert(
+            NAME_RULE_KEY.to_string(),
+            serde_json::to_value(mark).expect("Serializing name rule mark"),
+        );
+        self.set_custom_data(serde_json::Value::Object(val))
+    }
+}
+
+impl PlayerRecord {
+    /// Accounts that have been linked to this one as suspected alts
+    #[must_use]
+    pub fn linked_accounts(&self) -> Vec<SteamID> {
+        self.custom_data
+            .get(LINKED_ACCOUNTS_KEY)
+            .and_then(serde_json::Value::as_array)
+            .map(|a| {
+                a.iter()
+                    .filter_map(serde_json::Value::as_str)
+                    .filter_map(|s| s.parse::<u64>().ok())
+                    .map(SteamID::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Prefer [`Records::link_accounts`] so the link is added to both records
+    pub fn add_linked_account(&mut self, steamid: SteamID) -> &mut Self {
+        let mut linked = self.linked_accounts();
+        if linked.contains(&steamid) {
+            return self;
+        }
+
+        linked.push(steamid);
+        self.set_linked_accounts(&linked)
+    }
+
+    /// Prefer [`Records::unlink_accounts`] so the link is removed from both
+    /// records
+    pub fn remove_linked_account(&mut self, steamid: SteamID) -> &mut Self {
+        let mut linked = self.linked_accounts();
+        let num_linked = linked.len();
+        linked.retain(|&s| s != steamid);
+        if linked.len() == num_linked {
+            return self;
+        }
+
+        self.set_linked_accounts(&linked)
+    }
+
+    fn set_linked_accounts(&mut self, linked: &[SteamID]) -> &mut Self {
+        // Stored as strings like other steamids so they don't lose precision in JS
+        let linked: Vec<serde_json::Value> = linked
+            .iter()
+            .map(|&s| serde_json::Value::String(u64::from(s).to_string()))
+            .collect();
+
+        let mut val = Map::new();
+        val.insert(
+            LINKED_ACCOUNTS_KEY.to_string(),
+            serde_json::Value::Array(linked),
+        );
+        self.set_custom_data(serde_json::Value::Object(val))
+    }
+}
+
+/// How a player has voted in votekicks that have ended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VoteStats {
+    pub voted_yes: u32,
+    /// Voted No on kicking a player marked as a cheater, which they're only
+    /// likely to do if they're with them
+    pub voted_no_on_cheater_kicks: u32,
+}
+
+/// Which name rule marked a player, and the verdict it gave them. The mark
+/// is only undone if the record still has that verdict.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameRuleMark {
+    pub pattern: String,
+    pub verdict: Verdict,
+}
+
+/// A record of the ban counts on an account increasing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BanDetected {
+    pub detected: DateTime<Utc>,
+    pub vac_bans: u32,
+    pub game_bans: u32,
 }
 
 #[must_use]
@@ -257,6 +756,19 @@ impl Display for Verdict {
     }
 }
 
+impl Verdict {
+    /// How bad the verdict is, e.g. to decide which to keep when merging
+    #[must_use]
+    pub const fn severity(self) -> u8 {
+        match self {
+            Self::Trusted => 0,
+            Self::Player => 1,
+            Self::Suspicious => 2,
+            Self::Cheater | Self::Bot => 3,
+        }
+    }
+}
+
 impl Default for Verdict {
     fn default() -> Self {
         Self::Player
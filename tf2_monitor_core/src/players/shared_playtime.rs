@@ -0,0 +1,128 @@
+//! How long the user has been in the same matches as each other player,
+//! worked out from their analysed demos. Players who keep turning up in the
+//! user's lobbies are often worth a closer look.
+
+use std::{collections::HashMap, io::Write, path::Path};
+
+use atomic_write_file::AtomicWriteFile;
+use md5::Digest;
+use steamid_ng::SteamID;
+
+use crate::{demos::analyser::AnalysedDemo, settings::ConfigFilesError};
+
+pub const SHARED_PLAYTIME_FILE_NAME: &str = "shared_playtime.bin";
+/// Used for demos that don't say how long their ticks are
+const DEFAULT_INTERVAL_PER_TICK: f32 = 0.015;
+
+#[derive(Debug, Default)]
+pub struct SharedPlaytime {
+    /// Seconds shared with each player in each demo, by the demo's hash, so a
+    /// demo analysed again replaces its old numbers instead of adding to them
+    demos: HashMap<String, HashMap<SteamID, u32>>,
+    totals: HashMap<SteamID, u32>,
+}
+
+impl SharedPlaytime {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the demo has already been counted
+    #[must_use]
+    pub fn contains(&self, demo: Digest) -> bool {
+        self.demos.contains_key(&format!("{demo:x}"))
+    }
+
+    /// How many demos have been counted
+    #[must_use]
+    pub fn num_demos(&self) -> usize {
+        self.demos.len()
+    }
+
+    /// Seconds the user has been in the same matches as the player, across
+    /// all the demos counted
+    #[must_use]
+    pub fn seconds_with(&self, steamid: SteamID) -> u32 {
+        self.totals.get(&steamid).copied().unwrap_or(0)
+    }
+
+    /// Counts the demo, replacing what was counted for it before
+    pub fn add_demo(&mut self, demo: Digest, analysed: &AnalysedDemo) {
+        let key = format!("{demo:x}");
+        if let Some(old) = self.demos.remove(&key) {
+            for (s, seconds) in old {
+                if let Some(total) = self.totals.get_mut(&s) {
+                    *total = total.saturating_sub(seconds);
+                }
+            }
+        }
+
+        let shared = shared_seconds(analysed);
+        for (&s, &seconds) in &shared {
+            *self.totals.entry(s).or_default() += seconds;
+        }
+        self.totals.retain(|_, t| *t > 0);
+        self.demos.insert(key, shared);
+    }
+
+    fn recalculate_totals(&mut self) {
+        self.totals.clear();
+        for (&s, &seconds) in self.demos.values().flatten() {
+            *self.totals.entry(s).or_default() += seconds;
+        }
+    }
+
+    /// # Errors
+    /// If the file could not be read from disk or the data could not be deserialized
+    pub fn load_from(&mut self, path: &Path) -> Result<(), ConfigFilesError> {
+        let contents = std::fs::read(path)?;
+        self.demos = pot::from_slice(&contents)?;
+        self.recalculate_totals();
+        Ok(())
+    }
+
+    /// # Errors
+    /// If the data could not be serialized or the file could not be written back to disk
+    pub fn save_to(&self, path: &Path) -> Result<(), ConfigFilesError> {
+        let contents = pot::to_vec(&self.demos)?;
+
+        let mut file = AtomicWriteFile::open(path)?;
+        file.write_all(&contents)?;
+        file.commit()?;
+        Ok(())
+    }
+}
+
+/// Seconds each other player in the demo was in the match at the same time
+/// as the user who recorded it. Nothing if the user couldn't be found in the
+/// demo.
+#[must_use]
+#[allow(
+    clippy::cast_sign_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss
+)]
+pub fn shared_seconds(analysed: &AnalysedDemo) -> HashMap<SteamID, u32> {
+    let Some(user) = analysed.players.get(&analysed.user) else {
+        return HashMap::new();
+    };
+
+    let interval_per_tick = if analysed.interval_per_tick > 0.0 {
+        analysed.interval_per_tick
+    } else {
+        DEFAULT_INTERVAL_PER_TICK
+    };
+
+    analysed
+        .players
+        .iter()
+        .filter(|(&s, _)| s != analysed.user)
+        .filter_map(|(&s, p)| {
+            let start = p.first_tick.max(user.first_tick);
+            let end = p.last_tick.min(user.last_tick);
+            let seconds = (end.saturating_sub(start) as f32 * interval_per_tick) as u32;
+            (seconds > 0).then_some((s, seconds))
+        })
+        .collect()
+}
@@ -17,13 +17,30 @@ pub struct SteamInfo {
     pub game_bans: u32,
     pub days_since_last_ban: Option<u32>,
     pub playtime: Option<u64>,
+    #[serde(default)]
+    pub steam_level: Option<u32>,
+    /// IDs of the steam groups the account is a member of, if they were
+    /// requested and the profile is public
+    #[serde(default)]
+    pub groups: Option<Vec<u64>>,
     pub fetched: DateTime<Utc>,
 }
 
 impl SteamInfo {
+    /// Whether this info is older than `cache_hours` and should be fetched
+    /// again
     #[must_use]
-    pub fn expired(&self) -> bool {
-        Utc::now().signed_duration_since(self.fetched).num_hours() > 3
+    pub fn expired(&self, cache_hours: u32) -> bool {
+        Utc::now().signed_duration_since(self.fetched).num_hours() > i64::from(cache_hours)
+    }
+
+    /// The groups in the provided watchlist that this account is a member of
+    pub fn watched_groups<'a>(&'a self, watchlist: &'a [u64]) -> impl Iterator<Item = u64> + 'a {
+        self.groups
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|g| watchlist.contains(g))
     }
 }
 
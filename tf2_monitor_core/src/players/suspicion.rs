@@ -0,0 +1,307 @@
+//! A rough score of how suspicious an account looks from what is already known
+//! about it. It is only ever shown to the user, it never changes a verdict.
+
+use chrono::Utc;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use steamid_ng::SteamID;
+
+use super::{
+    friends::FriendInfo,
+    game_info::GameInfo,
+    steam_info::{ProfileVisibility, SteamInfo},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Signal {
+    YoungAccount,
+    HiddenProfile,
+    Bans,
+    NoPlaytime,
+    LowLevel,
+    NamePattern,
+    MarkedFriends,
+}
+
+impl Signal {
+    pub const ALL: &'static [Self] = &[
+        Self::YoungAccount,
+        Self::HiddenProfile,
+        Self::Bans,
+        Self::NoPlaytime,
+        Self::LowLevel,
+        Self::NamePattern,
+        Self::MarkedFriends,
+    ];
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::YoungAccount => "Young account",
+            Self::HiddenProfile => "Private or friends only profile",
+            Self::Bans => "VAC or game bans",
+            Self::NoPlaytime => "No TF2 playtime",
+            Self::LowLevel => "Low Steam level",
+            Self::NamePattern => "Name matches a pattern",
+            Self::MarkedFriends => "Friends with a marked player",
+        })
+    }
+}
+
+/// How much each [`Signal`] adds to the score. A weight of 0 turns the signal
+/// off.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SuspicionWeights {
+    pub young_account: u32,
+    /// Accounts created fewer than this many days ago are young
+    pub young_account_days: u32,
+    pub hidden_profile: u32,
+    pub bans: u32,
+    pub no_playtime: u32,
+    pub low_level: u32,
+    /// Steam levels below this are low
+    pub low_level_below: u32,
+    pub name_pattern: u32,
+    /// Regexes to match names against, e.g. the names of bots advertising
+    /// twitch channels
+    pub name_patterns: Vec<String>,
+    pub marked_friends: u32,
+}
+
+impl Default for SuspicionWeights {
+    fn default() -> Self {
+        Self {
+            young_account: 2,
+            young_account_days: 30,
+            hidden_profile: 1,
+            bans: 3,
+            no_playtime: 2,
+            low_level: 1,
+            low_level_below: 2,
+            name_pattern: 3,
+            name_patterns: Vec::new(),
+            marked_friends: 3,
+        }
+    }
+}
+
+impl SuspicionWeights {
+    #[must_use]
+    pub const fn weight(&self, signal: Signal) -> u32 {
+        match signal {
+            Signal::YoungAccount => self.young_account,
+            Signal::HiddenProfile => self.hidden_profile,
+            Signal::Bans => self.bans,
+            Signal::NoPlaytime => self.no_playtime,
+            Signal::LowLevel => self.low_level,
+            Signal::NamePattern => self.name_pattern,
+            Signal::MarkedFriends => self.marked_friends,
+        }
+    }
+
+    pub fn set_weight(&mut self, signal: Signal, weight: u32) {
+        *match signal {
+            Signal::YoungAccount => &mut self.young_account,
+            Signal::HiddenProfile => &mut self.hidden_profile,
+            Signal::Bans => &mut self.bans,
+            Signal::NoPlaytime => &mut self.no_playtime,
+            Signal::LowLevel => &mut self.low_level,
+            Signal::NamePattern => &mut self.name_pattern,
+            Signal::MarkedFriends => &mut self.marked_friends,
+        } = weight;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SignalScore {
+    pub signal: Signal,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SuspicionScore {
+    pub score: u32,
+    /// The score if every signal that is turned on was seen
+    pub max: u32,
+    /// The signals that were seen
+    pub signals: Vec<SignalScore>,
+}
+
+impl SuspicionScore {
+    /// The score out of the max, between 0 and 1
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> f32 {
+        if self.max == 0 {
+            return 0.0;
+        }
+        self.score as f32 / self.max as f32
+    }
+}
+
+/// Works out [`SuspicionScore`]s, with the name patterns compiled ahead of
+/// time
+#[derive(Debug)]
+pub struct Scorer {
+    weights: SuspicionWeights,
+    name_patterns: Vec<Regex>,
+    /// Patterns that couldn't be compiled and their errors
+    invalid_patterns: Vec<(String, String)>,
+}
+
+impl Default for Scorer {
+    fn default() -> Self {
+        Self::new(&SuspicionWeights::default())
+    }
+}
+
+impl Scorer {
+    /// Name patterns that aren't valid regexes are left out
+    #[must_use]
+    pub fn new(weights: &SuspicionWeights) -> Self {
+        let mut name_patterns = Vec::new();
+        let mut invalid_patterns = Vec::new();
+        for pattern in &weights.name_patterns {
+            match Regex::new(pattern) {
+                Ok(regex) => name_patterns.push(regex),
+                Err(e) => {
+                    tracing::error!("Invalid name pattern \"{pattern}\": {e}");
+                    invalid_patterns.push((pattern.clone(), e.to_string()));
+                }
+            }
+        }
+
+        Self {
+            weights: weights.clone(),
+            name_patterns,
+            invalid_patterns,
+        }
+    }
+
+    #[must_use]
+    pub const fn weights(&self) -> &SuspicionWeights {
+        &self.weights
+    }
+
+    /// Why the name pattern couldn't be used, if it's invalid
+    #[must_use]
+    pub fn pattern_error(&self, pattern: &str) -> Option<&str> {
+        self.invalid_patterns
+            .iter()
+            .find(|(p, _)| p == pattern)
+            .map(|(_, e)| e.as_str())
+    }
+
+    /// `is_marked` tells whether a friend has been marked as a cheater or bot
+    #[must_use]
+    pub fn score(
+        &self,
+        steam_info: Option<&SteamInfo>,
+        friend_info: Option<&FriendInfo>,
+        game_info: Option<&GameInfo>,
+        is_marked: impl Fn(SteamID) -> bool,
+    ) -> SuspicionScore {
+        let weights = &self.weights;
+        let mut score = SuspicionScore {
+            max: Signal::ALL.iter().map(|&s| weights.weight(s)).sum(),
+            ..Default::default()
+        };
+
+        for &signal in Signal::ALL {
+            let weight = weights.weight(signal);
+            if weight == 0 {
+                continue;
+            }
+
+            let seen = match signal {
+                Signal::YoungAccount => steam_info
+                    .and_then(|si| si.time_created)
+                    .and_then(|t| i64::try_from(t).ok())
+                    .is_some_and(|t| {
+                        (Utc::now().timestamp() - t) / (60 * 60 * 24)
+                            < i64::from(weights.young_account_days)
+                    }),
+                Signal::HiddenProfile => steam_info.is_some_and(|si| {
+                    matches!(
+                        si.profile_visibility,
+                        ProfileVisibility::Private | ProfileVisibility::FriendsOnly
+                    )
+                }),
+                Signal::Bans => steam_info.is_some_and(|si| si.vac_bans + si.game_bans > 0),
+                Signal::NoPlaytime => steam_info
+                    .and_then(|si| si.playtime)
+                    .is_some_and(|minutes| minutes < 60),
+                Signal::LowLevel => steam_info
+                    .and_then(|si| si.steam_level)
+                    .is_some_and(|level| level < weights.low_level_below),
+                Signal::NamePattern => game_info
+                    .map(|gi| gi.name.as_str())
+                    .or_else(|| steam_info.map(|si| si.account_name.as_str()))
+                    .is_some_and(|name| self.name_patterns.iter().any(|r| r.is_match(name))),
+                Signal::MarkedFriends => {
+                    friend_info.is_some_and(|fi| fi.friends().iter().any(|f| is_marked(f.steamid)))
+                }
+            };
+
+            if seen {
+                score.score += weight;
+                score.signals.push(SignalScore { signal, weight });
+            }
+        }
+
+        score
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use steamid_ng::SteamID;
+
+    use super::{Scorer, Signal, SuspicionWeights};
+    use crate::players::{
+        friends::{Friend, FriendInfo},
+        game_info::GameInfo,
+    };
+
+    #[test]
+    fn zero_weights_turn_signals_off() {
+        let marked = SteamID::from(76_561_198_000_000_002);
+        let friends = FriendInfo {
+            public: Some(true),
+            friends: vec![Friend {
+                steamid: marked,
+                friend_since: 0,
+            }],
+        };
+        let game_info = GameInfo {
+            name: String::from("twitch.tv/bot123"),
+            ..Default::default()
+        };
+
+        let mut weights = SuspicionWeights {
+            name_patterns: vec![String::from(r"twitch\.tv"), String::from("(")],
+            ..Default::default()
+        };
+        let scorer = Scorer::new(&weights);
+        assert!(scorer.pattern_error("(").is_some());
+        let score = scorer.score(None, Some(&friends), Some(&game_info), |s| s == marked);
+        assert_eq!(score.score, weights.name_pattern + weights.marked_friends);
+        assert_eq!(score.signals.len(), 2);
+
+        let old_max = score.max;
+        weights.set_weight(Signal::MarkedFriends, 0);
+        let score = Scorer::new(&weights).score(None, Some(&friends), Some(&game_info), |_| true);
+        assert_eq!(score.score, weights.name_pattern);
+        assert!(score
+            .signals
+            .iter()
+            .all(|s| s.signal != Signal::MarkedFriends));
+        assert_eq!(
+            score.max,
+            old_max - SuspicionWeights::default().marked_friends
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Importing and exporting records as [TF2 Bot Detector](https://github.com/PazerOP/tf2_bot_detector)
+//! playerlists.
+
+use std::{fmt::Display, io::Write, path::Path};
+
+use atomic_write_file::AtomicWriteFile;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use steamid_ng::SteamID;
+
+use super::records::{PlayerRecord, Records, Verdict};
+use crate::settings::ConfigFilesError;
+
+pub const TF2BD_SCHEMA: &str =
+    "https://raw.githubusercontent.com/PazerOP/tf2_bot_detector/master/schemas/v3/playerlist.schema.json";
+/// Key in a record's custom data for TF2BD attributes that don't correspond to
+/// a [`Verdict`] (e.g. `racist`), so they aren't lost when exporting again.
+pub const TF2BD_ATTRIBUTES_KEY: &str = "tf2bdAttributes";
+
+const ATTRIBUTE_CHEATER: &str = "cheater";
+const ATTRIBUTE_SUSPICIOUS: &str = "suspicious";
+const ATTRIBUTE_EXPLOITER: &str = "exploiter";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "RawPlayerlist")]
+pub struct Tf2bdPlayerlist {
+    #[serde(rename = "$schema")]
+    pub schema: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_info: Option<Tf2bdFileInfo>,
+    pub players: Vec<Tf2bdPlayer>,
+    /// Players in the file that couldn't be read (e.g. with a malformed
+    /// steamid), which are left out instead of failing the whole list
+    #[serde(skip)]
+    pub invalid_players: usize,
+}
+
+/// A playerlist as it is in the file, before each player is read
+#[derive(Deserialize)]
+struct RawPlayerlist {
+    #[serde(rename = "$schema", default = "default_schema")]
+    schema: String,
+    #[serde(default)]
+    file_info: Option<Tf2bdFileInfo>,
+    #[serde(default)]
+    players: Vec<Value>,
+}
+
+impl From<RawPlayerlist> for Tf2bdPlayerlist {
+    fn from(raw: RawPlayerlist) -> Self {
+        let num_players = raw.players.len();
+        let players: Vec<Tf2bdPlayer> = raw
+            .players
+            .into_iter()
+            .filter_map(|p| {
+                serde_json::from_value(p)
+                    .map_err(|e| tracing::warn!("Skipping invalid player in playerlist: {e}"))
+                    .ok()
+            })
+            .collect();
+
+        Self {
+            schema: raw.schema,
+            file_info: raw.file_info,
+            invalid_players: num_players - players.len(),
+            players,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Tf2bdFileInfo {
+    pub authors: Vec<String>,
+    pub description: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tf2bdPlayer {
+    #[serde(
+        serialize_with = "serialize_steamid",
+        deserialize_with = "deserialize_steamid"
+    )]
+    pub steamid: SteamID,
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<Tf2bdLastSeen>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub proof: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tf2bdLastSeen {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_name: Option<String>,
+    /// Unix timestamp
+    pub time: i64,
+}
+
+impl Tf2bdPlayer {
+    /// The verdict implied by this player's attributes, if any
+    #[must_use]
+    pub fn verdict(&self) -> Option<Verdict> {
+        if self
+            .attributes
+            .iter()
+            .any(|a| a == ATTRIBUTE_CHEATER || a == ATTRIBUTE_EXPLOITER)
+        {
+            Some(Verdict::Cheater)
+        } else if self.attributes.iter().any(|a| a == ATTRIBUTE_SUSPICIOUS) {
+            Some(Verdict::Suspicious)
+        } else {
+            None
+        }
+    }
+
+    /// Attributes which aren't fully represented by a [`Verdict`]
+    fn extra_attributes(&self) -> impl Iterator<Item = &str> {
+        self.attributes
+            .iter()
+            .map(String::as_str)
+            .filter(|&a| a != ATTRIBUTE_CHEATER && a != ATTRIBUTE_SUSPICIOUS)
+    }
+
+    fn last_seen_time(&self) -> Option<DateTime<Utc>> {
+        self.last_seen
+            .as_ref()
+            .and_then(|ls| DateTime::from_timestamp(ls.time, 0))
+    }
+}
+
+/// What to do when an imported player already has a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergePolicy {
+    /// Leave existing records untouched
+    SkipExisting,
+    /// Replace the verdict of existing records
+    Overwrite,
+    /// Keep whichever of the two verdicts is worse
+    KeepWorse,
+}
+
+impl Display for MergePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::SkipExisting => "Skip existing",
+            Self::Overwrite => "Overwrite",
+            Self::KeepWorse => "Keep worse verdict",
+        };
+        write!(f, "{str}")
+    }
+}
+
+/// How an import did (or would) change the records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// Players who didn't have a record yet
+    pub added: usize,
+    /// Existing records that were changed
+    pub updated: usize,
+    /// Existing records with a different verdict to the imported one
+    pub conflicts: usize,
+    /// Players in the playerlist that couldn't be read
+    pub invalid: usize,
+}
+
+impl Records {
+    /// Read a TF2BD playerlist file
+    ///
+    /// # Errors
+    /// If the file could not be read or is not a valid playerlist
+    pub fn read_tf2bd(path: &Path) -> Result<Tf2bdPlayerlist, ConfigFilesError> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Works out how merging the playerlist would change the records, without
+    /// actually changing anything.
+    #[must_use]
+    pub fn preview_tf2bd(&self, list: &Tf2bdPlayerlist, policy: MergePolicy) -> ImportSummary {
+        let mut summary = ImportSummary {
+            invalid: list.invalid_players,
+            ..Default::default()
+        };
+        for player in &list.players {
+            merge_player(&mut summary, self.get(&player.steamid), player, policy);
+        }
+        summary
+    }
+
+    /// Merge a playerlist into the records
+    pub fn merge_tf2bd(&mut self, list: &Tf2bdPlayerlist, policy: MergePolicy) -> ImportSummary {
+        let mut summary = ImportSummary {
+            invalid: list.invalid_players,
+            ..Default::default()
+        };
+        for player in &list.players {
+            if let Some(record) =
+                merge_player(&mut summary, self.get(&player.steamid), player, policy)
+            {
+                self.insert(player.steamid, record);
+            }
+        }
+        summary
+    }
+
+    /// Read a TF2BD playerlist file and merge it into the records
+    ///
+    /// # Errors
+    /// If the file could not be read or is not a valid playerlist
+    pub fn import_tf2bd(
+        &mut self,
+        path: &Path,
+        policy: MergePolicy,
+    ) -> Result<ImportSummary, ConfigFilesError> {
+        let list = Self::read_tf2bd(path)?;
+        Ok(self.merge_tf2bd(&list, policy))
+    }
+
+    /// Convert the records into a TF2BD playerlist. Only records with a verdict
+    /// or attributes TF2BD understands are included.
+    #[must_use]
+    pub fn to_tf2bd(&self) -> Tf2bdPlayerlist {
+        let mut players: Vec<Tf2bdPlayer> = self
+            .iter()
+            .filter_map(|(&steamid, record)| {
+                let mut attributes = Vec::new();
+                match record.verdict() {
+                    Verdict::Cheater | Verdict::Bot => {
+                        attributes.push(ATTRIBUTE_CHEATER.to_string())
+                    }
+                    Verdict::Suspicious => attributes.push(ATTRIBUTE_SUSPICIOUS.to_string()),
+                    Verdict::Player | Verdict::Trusted => {}
+                }
+                for a in extra_attributes(record) {
+                    if !attributes.contains(&a) {
+                        attributes.push(a);
+                    }
+                }
+
+                if attributes.is_empty() {
+                    return None;
+                }
+
+                Some(Tf2bdPlayer {
+                    steamid,
+                    attributes,
+                    last_seen: record.last_seen().map(|t| Tf2bdLastSeen {
+                        player_name: record.previous_name_strs().next().map(String::from),
+                        time: t.timestamp(),
+                    }),
+                    proof: Vec::new(),
+                })
+            })
+            .collect();
+        players.sort_by_key(|p| u64::from(p.steamid));
+
+        Tf2bdPlayerlist {
+            schema: default_schema(),
+            file_info: Some(Tf2bdFileInfo {
+                authors: Vec::new(),
+                description: "Exported from TF2 Monitor".to_string(),
+                title: "TF2 Monitor playerlist".to_string(),
+                update_url: None,
+            }),
+            players,
+            invalid_players: 0,
+        }
+    }
+
+    /// Write the records to a file as a TF2BD playerlist, returning how many
+    /// players were exported.
+    ///
+    /// # Errors
+    /// If the playerlist could not be serialized or written to disk
+    pub fn export_tf2bd(&self, path: &Path) -> Result<usize, ConfigFilesError> {
+        let list = self.to_tf2bd();
+
+        let mut file = AtomicWriteFile::open(path)?;
+        write!(file, "{}", serde_json::to_string_pretty(&list)?)?;
+        file.commit()?;
+
+        Ok(list.players.len())
+    }
+}
+
+/// Merges an imported player with their existing record (if any), recording the
+/// outcome in the summary and returning the new record if it changed.
+fn merge_player(
+    summary: &mut ImportSummary,
+    existing: Option<&PlayerRecord>,
+    player: &Tf2bdPlayer,
+    policy: MergePolicy,
+) -> Option<PlayerRecord> {
+    let imported_verdict = player.verdict();
+
+    let Some(existing) = existing else {
+        let mut record = PlayerRecord::default();
+        apply_player(&mut record, player, imported_verdict);
+        if record.is_empty() {
+            return None;
+        }
+
+        summary.added += 1;
+        return Some(record);
+    };
+
+    if imported_verdict.is_some_and(|v| v != existing.verdict())
+        && existing.verdict() != Verdict::Player
+    {
+        summary.conflicts += 1;
+    }
+
+    let verdict = match policy {
+        MergePolicy::SkipExisting => return None,
+        MergePolicy::Overwrite => imported_verdict,
+        // Equally bad verdicts (e.g. Bot and Cheater) keep the existing one
+        MergePolicy::KeepWorse => {
+            imported_verdict.filter(|v| v.severity() > existing.verdict().severity())
+        }
+    };
+
+    let mut record = existing.clone();
+    if apply_player(&mut record, player, verdict) {
+        summary.updated += 1;
+        Some(record)
+    } else {
+        None
+    }
+}
+
+/// Applies the imported information to a record, returning whether anything
+/// changed.
+fn apply_player(record: &mut PlayerRecord, player: &Tf2bdPlayer, verdict: Option<Verdict>) -> bool {
+    let mut changed = false;
+
+    if let Some(verdict) = verdict {
+        if verdict != record.verdict() {
+            record.set_verdict(verdict);
+            changed = true;
+        }
+    }
+
+    // Attributes that don't map to verdicts
+    let mut extras = extra_attributes(record);
+    let num_extras = extras.len();
+    for a in player.extra_attributes() {
+        if !extras.iter().any(|e| e == a) {
+            extras.push(a.to_string());
+        }
+    }
+    if extras.len() != num_extras {
+        let mut val = Map::new();
+        val.insert(TF2BD_ATTRIBUTES_KEY.to_string(), Value::from(extras));
+        record.set_custom_data(Value::Object(val));
+        changed = true;
+    }
+
+    // Last seen
+    if let Some(last_seen) = player.last_seen_time() {
+        if record.last_seen().map_or(true, |t| t < last_seen) {
+            record.set_last_seen(last_seen);
+            changed = true;
+        }
+    }
+
+    // Name
+    if let Some(name) = player
+        .last_seen
+        .as_ref()
+        .and_then(|ls| ls.player_name.as_deref())
+    {
+        if !record.previous_name_strs().any(|n| n == name) {
+            record.add_previous_name_at(name, player.last_seen_time());
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn extra_attributes(record: &PlayerRecord) -> Vec<String> {
+    record
+        .custom_data()
+        .get(TF2BD_ATTRIBUTES_KEY)
+        .and_then(Value::as_array)
+        .map(|a| {
+            a.iter()
+                .filter_map(Value::as_str)
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn default_schema() -> String {
+    TF2BD_SCHEMA.to_string()
+}
+
+fn serialize_steamid<S: Serializer>(steamid: &SteamID, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&steamid.steam3())
+}
+
+/// TF2BD playerlists can store steamids as either a steamid64 number or a
+/// string in any of the steamid formats
+fn deserialize_steamid<'de, D: Deserializer<'de>>(d: D) -> Result<SteamID, D::Error> {
+    match Value::deserialize(d)? {
+        Value::Number(n) => n
+            .as_u64()
+            .map(SteamID::from)
+            .ok_or_else(|| serde::de::Error::custom(format!("Invalid steamid {n}"))),
+        Value::String(s) => SteamID::try_from(s.as_str())
+            .map_err(|e| serde::de::Error::custom(format!("Invalid steamid {s}: {e:?}"))),
+        v => Err(serde::de::Error::custom(format!("Invalid steamid {v}"))),
+    }
+}
@@ -0,0 +1,159 @@
+//! Keeping the user's own friends list up to date while the app is running.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use event_loop::{try_get, Handled, Is, Message, MessageHandler};
+use steam_rs::Steam;
+use steamid_ng::SteamID;
+
+use super::friends::Friend;
+use crate::{
+    events::Refresh,
+    steam::{
+        self,
+        api::{request_account_friends, SteamAPIError},
+    },
+    MonitorState,
+};
+
+/// How often the user's friends are read again
+pub const USER_FRIENDS_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Messages *********************
+
+/// Read the user's friends list again now instead of waiting for the next
+/// periodic refresh
+#[derive(Debug, Clone)]
+pub struct RefreshUserFriends;
+impl<S> Message<S> for RefreshUserFriends {}
+
+#[derive(Debug)]
+pub struct UserFriendsFetched {
+    pub user: SteamID,
+    /// From Steam's files on this computer, which don't say when each friend
+    /// was added and can be out of date
+    pub local: Result<Vec<Friend>, steam::Error>,
+    /// From the Steam API, if there is a key
+    pub api: Option<Result<Vec<Friend>, SteamAPIError>>,
+}
+
+impl Message<MonitorState> for UserFriendsFetched {
+    fn update_state(self, state: &mut MonitorState) {
+        if let Err(e) = &self.local {
+            tracing::error!("Failed to read the local friends list: {e}");
+        }
+        if let Some(Err(e)) = &self.api {
+            tracing::error!("Failed to fetch your friends list from the Steam API: {e}");
+        }
+
+        let known: HashMap<SteamID, u64> = state
+            .players
+            .friend_info
+            .get(&self.user)
+            .map(|fi| {
+                fi.friends
+                    .iter()
+                    .map(|f| (f.steamid, f.friend_since))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // The API is authoritative, but local files at least keep up with
+        // friends added while the game is running
+        let friends: Vec<Friend> = match (self.api, self.local) {
+            (Some(Ok(friends)), _) => friends,
+            (_, Ok(friends)) => friends
+                .into_iter()
+                .map(|f| Friend {
+                    friend_since: known.get(&f.steamid).copied().unwrap_or(f.friend_since),
+                    ..f
+                })
+                .collect(),
+            _ => return,
+        };
+
+        let new_friends = friends
+            .iter()
+            .filter(|f| !known.contains_key(&f.steamid))
+            .count();
+        if !known.is_empty() && new_friends > 0 {
+            tracing::info!(
+                "{new_friends} new {} detected",
+                if new_friends == 1 {
+                    "friend"
+                } else {
+                    "friends"
+                }
+            );
+        }
+
+        state.players.update_friends_list(self.user, friends);
+    }
+}
+
+// Handlers *********************
+
+/// Reads the user's friends list on the first refresh, then every
+/// [`USER_FRIENDS_REFRESH_INTERVAL`] or when asked to with
+/// [`RefreshUserFriends`].
+#[derive(Default)]
+pub struct FetchUserFriends {
+    last_fetched: Option<Instant>,
+    in_progress: bool,
+}
+
+impl FetchUserFriends {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<IM, OM> MessageHandler<MonitorState, IM, OM> for FetchUserFriends
+where
+    IM: Is<Refresh> + Is<RefreshUserFriends> + Is<UserFriendsFetched>,
+    OM: Is<UserFriendsFetched>,
+{
+    fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
+        if try_get::<UserFriendsFetched>(message).is_some() {
+            self.in_progress = false;
+            return Handled::none();
+        }
+
+        if try_get::<RefreshUserFriends>(message).is_none() {
+            try_get::<Refresh>(message)?;
+            if self
+                .last_fetched
+                .is_some_and(|t| t.elapsed() < USER_FRIENDS_REFRESH_INTERVAL)
+            {
+                return Handled::none();
+            }
+        }
+
+        let user = state.settings.steam_user?;
+        if self.in_progress {
+            return Handled::none();
+        }
+        self.in_progress = true;
+        self.last_fetched = Some(Instant::now());
+
+        let key = state.settings.steam_api_key.clone();
+        let offline = state.settings.offline;
+        Handled::future(async move {
+            let local = tokio::task::spawn_blocking(move || steam::find_steam_user_friends(user))
+                .await
+                .unwrap_or_else(|e| Err(steam::Error::Io(std::io::Error::other(e))));
+
+            let api = if key.is_empty() || offline {
+                None
+            } else {
+                Some(request_account_friends(&Steam::new(&key), user).await)
+            };
+
+            Some(UserFriendsFetched { user, local, api }.into())
+        })
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use steamid_ng::SteamID;
 use tf_demo_parser::demo::gameevent_gen::{VoteCastEvent, VoteOptionsEvent};
@@ -11,6 +12,15 @@ use crate::{
     players::Players,
 };
 
+/// How long votes stay open for before they fail
+pub const VOTE_DURATION_SECONDS: i64 = 30;
+/// A kill seen in both the console and the demo within this many seconds of
+/// each other is only counted once
+const DUPLICATE_KILL_SECONDS: i64 = 5;
+/// Valve's matchmaking servers are all named e.g. "Valve Matchmaking Server
+/// (Virginia srcds1004-iad1 #54)"
+const VALVE_HOSTNAME_PREFIX: &str = "Valve Matchmaking Server";
+
 // Server
 
 pub struct Server {
@@ -40,6 +50,70 @@ pub struct VoteEvent {
     pub idx: u32,
     pub options: Vec<String>,
     pub votes: Vec<CastVote>,
+    /// `None` until the vote has ended
+    pub outcome: Option<VoteOutcome>,
+    /// When the options were seen, which is the first sign of the vote in
+    /// the demo
+    pub started: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoteOutcome {
+    Passed,
+    Failed,
+}
+
+impl VoteEvent {
+    /// The option that was picked in a cast vote, e.g. "Yes"
+    #[must_use]
+    pub fn option(&self, vote: &CastVote) -> Option<&str> {
+        self.options.get(vote.option as usize).map(String::as_str)
+    }
+
+    /// How many players have voted for `option`
+    #[must_use]
+    pub fn count(&self, option: &str) -> usize {
+        self.votes
+            .iter()
+            .filter(|v| self.option(v) == Some(option))
+            .count()
+    }
+
+    /// The option the player voted for, if they have
+    #[must_use]
+    pub fn voted(&self, steamid: SteamID) -> Option<&str> {
+        self.votes
+            .iter()
+            .find(|v| v.steamid == Some(steamid))
+            .and_then(|v| self.option(v))
+    }
+
+    /// Seconds until the vote closes, or 0 if it already has
+    #[must_use]
+    pub fn seconds_left(&self) -> i64 {
+        let elapsed = Utc::now().signed_duration_since(self.started).num_seconds();
+        (VOTE_DURATION_SECONDS - elapsed).max(0)
+    }
+
+    /// Votekicks are the only votes with just Yes and No as the options
+    #[must_use]
+    pub fn is_kick(&self) -> bool {
+        self.options == ["Yes", "No"]
+    }
+
+    /// The player a votekick is against. They automatically vote No as soon as
+    /// the vote starts, so are the first to have voted No.
+    #[must_use]
+    pub fn kick_target(&self) -> Option<SteamID> {
+        if !self.is_kick() {
+            return None;
+        }
+
+        self.votes
+            .iter()
+            .find(|v| self.option(v) == Some("No"))
+            .and_then(|v| v.steamid)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -85,6 +159,15 @@ impl Server {
         self.hostname.as_deref()
     }
 
+    /// Whether this is a Valve matchmaking server, which are the only ones
+    /// with a lobby for `tf_lobby_debug` to show
+    #[must_use]
+    pub fn is_valve_server(&self) -> bool {
+        self.hostname
+            .as_deref()
+            .is_some_and(|h| h.starts_with(VALVE_HOSTNAME_PREFIX))
+    }
+
     #[must_use]
     pub const fn max_players(&self) -> Option<u32> {
         self.max_players
@@ -114,6 +197,15 @@ impl Server {
     pub fn vote_history(&self) -> &[VoteEvent] {
         &self.vote_history
     }
+
+    /// The vote that is still open, if there is one. Votes that end without
+    /// a result being seen are closed once their time runs out.
+    #[must_use]
+    pub fn active_vote(&self) -> Option<&VoteEvent> {
+        self.vote_history
+            .last()
+            .filter(|v| v.outcome.is_none() && v.seconds_left() > 0)
+    }
 }
 
 impl Default for Server {
@@ -126,12 +218,11 @@ impl Server {
     // **** Message handling ****
 
     /// Handles any io output from running commands / reading the console log
-    /// file. Returns:
-    /// * Some<`SteamID`> of a player if they have been newly added to the
-    ///   server.
-    pub fn handle_console_output(&mut self, response: ConsoleOutput) {
+    /// file. Returns the vote that has just ended, if one has.
+    pub fn handle_console_output(&mut self, response: ConsoleOutput) -> Option<VoteEvent> {
         use ConsoleOutput::{
-            Chat, DemoStop, Hostname, Kill, Map, PlayerCount, ServerIP, Status, G15,
+            Chat, DemoStop, Hostname, Kill, LobbyDebug, Map, PlayerCount, ServerIP, Status,
+            VoteResult, G15,
         };
         match response {
             Chat(chat) => self.handle_chat(chat),
@@ -149,8 +240,11 @@ impl Server {
                 self.max_players = Some(playercount.max);
                 self.num_players = Some(playercount.players);
             }
-            G15(_) | Status(_) | DemoStop(_) => {}
+            VoteResult(regexes::VoteResult(outcome)) => return self.end_vote(outcome),
+            G15(_) | Status(_) | DemoStop(_) | LobbyDebug(_) => {}
         }
+
+        None
     }
 
     fn handle_chat(&mut self, chat: ChatMessage) {
@@ -160,16 +254,78 @@ impl Server {
 
     fn handle_kill(&mut self, kill: PlayerKill) {
         tracing::debug!("Kill: {:?}", kill);
+        if self
+            .recent_kills()
+            .any(|k| k.tick.is_some() && k.same_players(&kill))
+        {
+            return;
+        }
+
         self.kill_history.push(kill);
     }
 
-    pub fn handle_demo_message(&mut self, demo_message: DemoMessage, players: &Players) {
-        match demo_message.event {
-            DemoEvent::VoteOptions(options) => self.handle_vote_options(&options),
-            DemoEvent::VoteCast(cast_vote, steamid) => self.handle_vote_cast(&cast_vote, steamid),
-            DemoEvent::VoteStarted(_) | DemoEvent::LatestTick => {}
+    /// Kills from the demo have the right SteamIDs, so they replace the same
+    /// kill if it has already been seen in the console
+    fn handle_demo_kill(&mut self, kill: PlayerKill) {
+        if let Some(existing) = self
+            .recent_kills()
+            .find(|k| (k.tick.is_none() || k.tick == kill.tick) && k.same_players(&kill))
+        {
+            *existing = PlayerKill {
+                timestamp: existing.timestamp,
+                ..kill
+            };
+            return;
         }
+
+        self.kill_history.push(kill);
+    }
+
+    /// Newest first
+    fn recent_kills(&mut self) -> impl Iterator<Item = &mut PlayerKill> {
+        let now = Utc::now();
+        self.kill_history.iter_mut().rev().take_while(move |k| {
+            now.signed_duration_since(k.timestamp).num_seconds() < DUPLICATE_KILL_SECONDS
+        })
+    }
+
+    /// Returns the vote that has just ended, if one has
+    pub fn handle_demo_message(
+        &mut self,
+        demo_message: DemoMessage,
+        players: &Players,
+    ) -> Option<VoteEvent> {
+        let outcome = match demo_message.event {
+            DemoEvent::VoteOptions(options) => {
+                self.handle_vote_options(&options);
+                None
+            }
+            DemoEvent::VoteCast(cast_vote, steamid) => {
+                self.handle_vote_cast(&cast_vote, steamid);
+                None
+            }
+            DemoEvent::VoteEnded(outcome) => Some(outcome),
+            DemoEvent::Kill(kill) => {
+                self.handle_demo_kill(kill);
+                None
+            }
+            DemoEvent::VoteStarted(_) | DemoEvent::LatestTick => None,
+        };
         self.check_shunted_votes(players);
+
+        outcome.and_then(|outcome| self.end_vote(outcome))
+    }
+
+    /// Sets the outcome of the latest vote. The outcome can be seen in both
+    /// the console and the demo, so this returns the vote only the first time.
+    fn end_vote(&mut self, outcome: VoteOutcome) -> Option<VoteEvent> {
+        let vote = self
+            .vote_history
+            .last_mut()
+            .filter(|v| v.outcome.is_none())?;
+        tracing::info!("Vote {outcome:?}");
+        vote.outcome = Some(outcome);
+        Some(vote.clone())
     }
 
     fn handle_vote_options(&mut self, options: &VoteOptionsEvent) {
@@ -193,6 +349,8 @@ impl Server {
             idx: options.voteidx,
             options: values,
             votes: Vec::new(),
+            outcome: None,
+            started: Utc::now(),
         };
 
         self.vote_history.push(vote);
@@ -242,3 +400,44 @@ impl Server {
         std::mem::swap(&mut temp, &mut self.shunted_vote_cast_events);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use steamid_ng::SteamID;
+
+    use super::Server;
+    use crate::console::commands::regexes::PlayerKill;
+
+    fn kill(tick: Option<u32>, steamids: bool) -> PlayerKill {
+        PlayerKill {
+            killer_name: String::from("Killer"),
+            killer_steamid: steamids.then_some(SteamID::from(76_561_198_000_000_001)),
+            victim_name: String::from("Victim"),
+            victim_steamid: steamids.then_some(SteamID::from(76_561_198_000_000_002)),
+            weapon: String::from("scattergun"),
+            crit: false,
+            timestamp: Utc::now(),
+            tick,
+        }
+    }
+
+    #[test]
+    fn kills_in_console_and_demo_are_counted_once() {
+        // Console first, then the demo fills in the SteamIDs
+        let mut server = Server::new();
+        server.handle_kill(kill(None, false));
+        server.handle_demo_kill(kill(Some(100), true));
+        assert_eq!(server.kill_history().len(), 1);
+        assert!(server.kill_history()[0].killer_steamid.is_some());
+
+        // Demo first
+        server.handle_demo_kill(kill(Some(200), true));
+        server.handle_kill(kill(None, false));
+        assert_eq!(server.kill_history().len(), 2);
+
+        // The same kill read from the demo twice
+        server.handle_demo_kill(kill(Some(200), true));
+        assert_eq!(server.kill_history().len(), 2);
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     fmt::Display,
     io::{self, ErrorKind, Write},
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 use atomic_write_file::AtomicWriteFile;
@@ -11,15 +12,42 @@ use serde_json::{Map, Value};
 use steamid_ng::SteamID;
 use thiserror::Error;
 
-use crate::{players::records::Verdict, steam};
+use crate::{
+    console::commands::KickReason,
+    players::{name_rules::NameRule, records::Verdict, suspicion::SuspicionWeights},
+    steam,
+};
+
+pub mod instance;
+pub mod profiles;
+pub mod watcher;
 
 pub const CONFIG_FILE_NAME: &str = "config.yaml";
+/// A file with this name next to the executable turns on portable mode
+pub const PORTABLE_MARKER_FILE_NAME: &str = "portable.txt";
+/// In portable mode, config files are kept in this directory next to the
+/// executable
+pub const PORTABLE_CONFIG_DIR_NAME: &str = "config";
 
 #[derive(Debug, Clone, Copy)]
 pub struct AppDetails<'a> {
     pub qualifier: &'a str,
     pub organization: &'a str,
     pub application: &'a str,
+    /// The settings profile in use. Each named profile keeps its config
+    /// files in its own subdirectory of the config directory, `None` is the
+    /// default profile which uses the config directory itself.
+    pub profile: Option<&'a str>,
+    /// Used instead of the usual config directory for the platform, e.g. in
+    /// portable mode
+    pub config_dir: Option<&'a Path>,
+}
+
+impl<'a> AppDetails<'a> {
+    #[must_use]
+    pub const fn with_profile(self, profile: Option<&'a str>) -> Self {
+        Self { profile, ..self }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -38,6 +66,8 @@ pub enum ConfigFilesError {
     Steam(#[from] steam::Error),
     #[error("No config file path is set")]
     NoConfigSet,
+    #[error("Invalid profile name \"{0}\"")]
+    InvalidProfileName(String),
 }
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
 pub enum FriendsAPIUsage {
@@ -77,18 +107,56 @@ pub struct Settings {
     pub steam_api_key: String,
     pub friends_api_usage: FriendsAPIUsage,
     pub request_playtime: bool,
+    pub request_steam_level: bool,
+    pub request_groups: bool,
+    /// Steam group IDs whose members should be flagged
+    pub group_watchlist: Vec<u64>,
+    /// Automatically mark members of watched groups as [`Verdict::Suspicious`]
+    pub mark_group_members_suspicious: bool,
+    /// How long fetched steam profile info is considered fresh for
+    pub steam_info_cache_hours: u32,
+    /// Cached steam profiles fetched longer ago than this are removed when
+    /// the cache is saved
+    pub steam_cache_max_age_days: u32,
+    /// Most steam profiles to keep in the cache, the least recently fetched
+    /// are removed first
+    pub steam_cache_max_entries: usize,
+    /// URLs of remote bot lists to mark players from
+    pub bot_list_subscriptions: Vec<String>,
+    /// How often subscribed bot lists are fetched again
+    pub bot_list_refresh_hours: u32,
+    /// How many players who have left the server are remembered
+    pub max_history_len: usize,
     pub rcon_port: u16,
     pub external: serde_json::Value,
     pub autokick_bots: bool,
+    /// Give this reason for every votekick, instead of one based on the
+    /// player's verdict
+    pub kick_reason_override: Option<KickReason>,
+    /// How much each signal adds to a player's suspicion score
+    pub suspicion: SuspicionWeights,
+    /// Checked against players' names when they join or change name
+    pub name_rules: Vec<NameRule>,
 
     pub minimal_demo_parsing: bool,
 
     pub masterbase_key: String,
     pub masterbase_host: String,
+    /// The settings as they were last written to disk, to tell if they need
+    /// saving again
+    #[serde(skip)]
+    saved_contents: Option<String>,
+    /// Modified time of the file when it was last loaded or saved, to tell
+    /// when something else has changed it
+    #[serde(skip)]
+    saved_mtime: Option<SystemTime>,
     #[serde(skip)]
     pub upload_demos: bool,
     #[serde(skip)]
     pub masterbase_http: bool,
+    /// Don't make any requests to the Steam API or the masterbase
+    #[serde(skip)]
+    pub offline: bool,
 
     pub webui_port: u16,
     pub autolaunch_ui: bool,
@@ -96,6 +164,13 @@ pub struct Settings {
 
 #[allow(dead_code)]
 impl Settings {
+    /// The reason to give for votekicking a player with this verdict
+    #[must_use]
+    pub fn kick_reason(&self, verdict: Verdict) -> KickReason {
+        self.kick_reason_override
+            .unwrap_or_else(|| KickReason::for_verdict(verdict))
+    }
+
     /// Attempts to set the TF2 directory by locating and reading steam config files
     ///
     /// # Errors
@@ -165,30 +240,123 @@ impl Settings {
     /// directory could be found)
     pub fn load_from(config_file_path: PathBuf) -> Result<Self, ConfigFilesError> {
         // Read config.yaml file if it exists, otherwise try to create a default file.
+        let saved_mtime = file_modified(&config_file_path);
         let contents = std::fs::read_to_string(&config_file_path)?;
         let mut settings = serde_yaml::from_str::<Self>(&contents)?;
         tracing::debug!("Successfully loaded {config_file_path:?}");
         settings.config_path = Some(config_file_path);
+        settings.saved_mtime = saved_mtime;
+        settings.saved_contents = serde_yaml::to_string(&settings).ok();
         Ok(settings)
     }
 
-    /// Attempt to save the settings back to the loaded configuration file
+    /// Attempt to save the settings back to the loaded configuration file. If
+    /// something else has changed the file since it was loaded or saved, its
+    /// changes are merged in first, see [`Self::reload`].
     ///
     /// # Errors
     /// If the settings could not be serialized or written back to disk
-    pub fn save(&self) -> Result<(), ConfigFilesError> {
+    pub fn save(&mut self) -> Result<(), ConfigFilesError> {
+        if instance::is_read_only() {
+            return Ok(());
+        }
+
+        // Don't overwrite changes made by something else since the last save
+        if self.changed_on_disk() {
+            if let Err(e) = self.reload() {
+                tracing::warn!(
+                    "Settings were changed on disk but couldn't be merged before saving: {e}"
+                );
+            }
+        }
+
         let config_path = self
             .config_path
             .as_ref()
             .ok_or(ConfigFilesError::NoConfigSet)?;
 
+        let contents = serde_yaml::to_string(self)?;
         let mut file = AtomicWriteFile::open(config_path)?;
-        write!(&mut file, "{}", serde_yaml::to_string(self)?)?;
+        write!(&mut file, "{contents}")?;
         file.commit()?;
 
+        self.saved_contents = Some(contents);
+        self.saved_mtime = file_modified(config_path);
+        Ok(())
+    }
+
+    /// Whether the file has been changed by something else since it was last
+    /// loaded or saved
+    #[must_use]
+    pub fn changed_on_disk(&self) -> bool {
+        self.config_path
+            .as_ref()
+            .and_then(|p| file_modified(p))
+            .is_some_and(|mtime| Some(mtime) != self.saved_mtime)
+    }
+
+    /// Load the settings from the file again. Settings that were changed
+    /// here since they were last saved are kept, the rest are replaced by the
+    /// ones in the file. Things that were found at runtime (e.g. the TF2
+    /// directory) are kept.
+    ///
+    /// # Errors
+    /// If the file could not be read or parsed
+    pub fn reload(&mut self) -> Result<(), ConfigFilesError> {
+        let config_path = self
+            .config_path
+            .clone()
+            .ok_or(ConfigFilesError::NoConfigSet)?;
+
+        let external = Self::load_from(config_path.clone())?;
+        let saved_mtime = external.saved_mtime;
+        let saved_contents = external.saved_contents.clone();
+
+        let mut settings = match self.saved_contents.as_deref() {
+            Some(base) => {
+                let mut merged = serde_yaml::to_value(&external)?;
+                keep_changes(
+                    &mut merged,
+                    &serde_yaml::from_str(base)?,
+                    serde_yaml::to_value(&*self)?,
+                );
+                serde_yaml::from_value(merged)?
+            }
+            None => external,
+        };
+        settings.config_path = Some(config_path);
+        settings.saved_mtime = saved_mtime;
+        settings.saved_contents = saved_contents;
+        settings.steam_user = self.steam_user;
+        settings.tf2_directory = self.tf2_directory.take();
+        settings.upload_demos = self.upload_demos;
+        settings.masterbase_http = self.masterbase_http;
+        settings.offline = self.offline;
+
+        *self = settings;
         Ok(())
     }
 
+    /// Save the settings only if they have changed since they were last
+    /// saved. Returns whether they were written.
+    ///
+    /// # Errors
+    /// If the settings could not be serialized or written back to disk
+    pub fn save_if_dirty(&mut self) -> Result<bool, ConfigFilesError> {
+        if !self.is_dirty() {
+            return Ok(false);
+        }
+
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Whether the settings have changed since they were last saved
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        serde_yaml::to_string(self).ok() != self.saved_contents
+    }
+
     pub fn save_ok(&mut self) {
         match self.save() {
             Ok(()) => tracing::debug!("Successfully saved settings to {:?}", self.config_path),
@@ -201,21 +369,29 @@ impl Settings {
     }
 
     /// Attempts to find (and create) a directory to be used for configuration
-    /// files
+    /// files of the profile in `app_details`
     ///
     /// # Errors
     /// If a valid config file directory could not be found (usually because a
-    /// valid home directory was not found)
+    /// valid home directory was not found), or the profile name is invalid
     pub fn locate_config_directory(app_details: AppDetails) -> Result<PathBuf, ConfigFilesError> {
-        let dirs = ProjectDirs::from(
-            app_details.qualifier,
-            app_details.organization,
-            app_details.application,
-        )
-        .ok_or(ConfigFilesError::NoValidHome)?;
-        let dir = dirs.config_dir();
-        std::fs::create_dir_all(dir)?;
-        Ok(PathBuf::from(dir))
+        let mut dir = if let Some(dir) = app_details.config_dir {
+            dir.to_path_buf()
+        } else {
+            let dirs = ProjectDirs::from(
+                app_details.qualifier,
+                app_details.organization,
+                app_details.application,
+            )
+            .ok_or(ConfigFilesError::NoValidHome)?;
+            PathBuf::from(dirs.config_dir())
+        };
+        if let Some(profile) = app_details.profile {
+            profiles::validate_profile_name(profile)?;
+            dir = dir.join(profiles::PROFILES_DIR_NAME).join(profile);
+        }
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
     }
 
     /// # Errors
@@ -226,6 +402,42 @@ impl Settings {
     }
 }
 
+/// When the file was last modified, if it exists
+pub(crate) fn file_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The directory to keep all config files in when in portable mode, i.e.
+/// [`PORTABLE_CONFIG_DIR_NAME`] next to the executable. Portable mode is used
+/// if `forced` or if there is a [`PORTABLE_MARKER_FILE_NAME`] file next to the
+/// executable, otherwise this is `None`.
+///
+/// # Errors
+/// If portable mode is being used but the directory couldn't be found or
+/// created
+pub fn locate_portable_config_directory(forced: bool) -> Result<Option<PathBuf>, ConfigFilesError> {
+    let exe_dir = match std::env::current_exe() {
+        Ok(exe) => exe.parent().map(Path::to_path_buf),
+        Err(e) if forced => return Err(e.into()),
+        Err(_) => None,
+    };
+    let Some(exe_dir) = exe_dir else {
+        return if forced {
+            Err(io::Error::new(ErrorKind::NotFound, "Executable has no parent directory").into())
+        } else {
+            Ok(None)
+        };
+    };
+
+    if !forced && !exe_dir.join(PORTABLE_MARKER_FILE_NAME).is_file() {
+        return Ok(None);
+    }
+
+    let dir = exe_dir.join(PORTABLE_CONFIG_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+    Ok(Some(dir))
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -236,22 +448,69 @@ impl Default for Settings {
             steam_api_key: String::new(),
             masterbase_key: String::new(),
             masterbase_host: "megaanticheat.com".into(),
+            saved_contents: None,
+            saved_mtime: None,
             friends_api_usage: FriendsAPIUsage::CheatersOnly,
             request_playtime: true,
+            request_steam_level: false,
+            request_groups: false,
+            group_watchlist: Vec::new(),
+            mark_group_members_suspicious: false,
+            steam_info_cache_hours: 3,
+            steam_cache_max_age_days: 30,
+            steam_cache_max_entries: 20_000,
+            bot_list_subscriptions: Vec::new(),
+            bot_list_refresh_hours: 6,
+            max_history_len: 100,
             webui_port: 3621,
             autolaunch_ui: false,
             rcon_port: 27015,
             external: serde_json::Value::Object(Map::new()),
             upload_demos: false,
+            offline: false,
             minimal_demo_parsing: false,
             masterbase_http: false,
             autokick_bots: false,
+            kick_reason_override: None,
+            suspicion: SuspicionWeights::default(),
+            name_rules: Vec::new(),
         }
     }
 }
 
 // Useful
 
+/// Applies the changes made from `base` to `ours` on top of `theirs`, going
+/// into mappings so only the values that were actually changed replace the
+/// ones in `theirs`.
+fn keep_changes(theirs: &mut serde_yaml::Value, base: &serde_yaml::Value, ours: serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match (theirs, base, ours) {
+        (Value::Mapping(theirs), Value::Mapping(base), Value::Mapping(ours)) => {
+            for (k, base_value) in base {
+                if !ours.contains_key(k) && theirs.get(k) == Some(base_value) {
+                    theirs.remove(k);
+                }
+            }
+
+            for (k, v) in ours {
+                let base_value = base.get(&k).unwrap_or(&Value::Null);
+                if let Some(their_value) = theirs.get_mut(&k) {
+                    keep_changes(their_value, base_value, v);
+                } else if v != *base_value {
+                    theirs.insert(k, v);
+                }
+            }
+        }
+        (theirs, base, ours) => {
+            if ours != *base {
+                *theirs = ours;
+            }
+        }
+    }
+}
+
 /// Combines the second provided Json Object into the first. If the given
 /// [Value]s are not `Value::Object`s, this will do nothing.
 pub fn merge_json_objects(a: &mut Value, b: Value) {
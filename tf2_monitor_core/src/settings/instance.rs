@@ -0,0 +1,279 @@
+//! Stops two copies of the monitor from using the same config files at the
+//! same time, which would have them both saving over each other's changes and
+//! sending rcon commands twice.
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, System};
+use thiserror::Error;
+use uuid::Uuid;
+
+pub const LOCK_FILE_NAME: &str = "instance.lock";
+/// How long a lock file that can't be read yet is assumed to still be being
+/// written by another instance that is starting up
+const UNREADABLE_LOCK_GRACE: Duration = Duration::from_secs(2);
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Stop config files from being saved and rcon commands from being sent, for
+/// when another instance is already running. This can't be undone.
+pub fn set_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+#[must_use]
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("Another instance is already running (process {})", .0.pid)]
+    HeldBy(LockOwner),
+    #[error("IO({0})")]
+    Io(#[from] io::Error),
+}
+
+/// The process holding the lock. The start time is kept so a lock left behind
+/// by a crash isn't mistaken for a running instance if the process ID has
+/// been reused since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub started: u64,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        let pid = std::process::id();
+        let mut system = System::new();
+        let started = if system.refresh_process(Pid::from_u32(pid)) {
+            system
+                .process(Pid::from_u32(pid))
+                .map_or(0, sysinfo::Process::start_time)
+        } else {
+            0
+        };
+
+        Self { pid, started }
+    }
+
+    /// Whether the process that took the lock is still running
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        let mut system = System::new();
+        let pid = Pid::from_u32(self.pid);
+        system.refresh_process(pid)
+            && system
+                .process(pid)
+                .is_some_and(|p| self.started == 0 || p.start_time() == self.started)
+    }
+
+    /// Ends the process holding the lock. Returns whether it could be.
+    #[must_use]
+    pub fn kill(&self) -> bool {
+        let mut system = System::new();
+        let pid = Pid::from_u32(self.pid);
+        system.refresh_process(pid) && system.process(pid).is_some_and(sysinfo::Process::kill)
+    }
+}
+
+/// Held for as long as the app is running, the lock file is removed when this
+/// is dropped.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+    owner: LockOwner,
+}
+
+impl InstanceLock {
+    /// Takes the lock in `dir`, replacing it if the instance that held it is
+    /// no longer running.
+    ///
+    /// # Errors
+    /// - `HeldBy` if another instance that is still running holds the lock
+    /// - `Io` if the lock file couldn't be read or written
+    pub fn acquire(dir: &Path) -> Result<Self, LockError> {
+        let path = dir.join(LOCK_FILE_NAME);
+        let owner = LockOwner::current();
+        let started = Instant::now();
+        let mut removed_stale = false;
+
+        loop {
+            match create_lock(&path, owner) {
+                Ok(()) => return Ok(Self { path, owner }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            match read_owner(&path) {
+                Some(held_by) if held_by.is_running() => return Err(LockError::HeldBy(held_by)),
+                Some(_) => {}
+                // Another instance may have only just created it
+                None if started.elapsed() < UNREADABLE_LOCK_GRACE
+                    && modified_within(&path, UNREADABLE_LOCK_GRACE) =>
+                {
+                    std::thread::sleep(RETRY_INTERVAL);
+                    continue;
+                }
+                None => {}
+            }
+
+            // Someone else replaced the same stale lock first
+            if removed_stale {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "Couldn't replace the stale lock file",
+                )
+                .into());
+            }
+
+            tracing::warn!("Removing instance lock left behind by a previous crash");
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => removed_stale = true,
+            }
+        }
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Don't remove a lock that has since been taken over
+        if read_owner(&self.path) != Some(self.owner) {
+            return;
+        }
+
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::error!("Failed to remove instance lock {:?}: {e}", self.path);
+        }
+    }
+}
+
+/// Creates the lock file with the owner already written to it, so no other
+/// instance can see it empty. The owner is written to a temporary file that is
+/// then hard linked into place, which fails if the lock already exists.
+///
+/// # Errors
+/// `AlreadyExists` if the lock is already there
+fn create_lock(path: &Path, owner: LockOwner) -> io::Result<()> {
+    let contents = serde_json::to_string(&owner).map_err(io::Error::other)?;
+    let temp = path.with_file_name(format!("{LOCK_FILE_NAME}.{}.tmp", Uuid::new_v4().simple()));
+    std::fs::write(&temp, &contents)?;
+
+    let linked = std::fs::hard_link(&temp, path);
+    if let Err(e) = std::fs::remove_file(&temp) {
+        tracing::warn!("Failed to remove temporary lock file {temp:?}: {e}");
+    }
+
+    match linked {
+        // Not every filesystem has hard links, so fall back to writing it in
+        // place. Instances starting up then wait a moment for it to be written.
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => {
+            tracing::debug!("Couldn't link instance lock into place ({e}), writing it directly");
+            let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+            file.write_all(contents.as_bytes())
+        }
+        linked => linked,
+    }
+}
+
+fn modified_within(path: &Path, duration: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < duration)
+}
+
+/// `None` if the lock file is missing or unreadable, e.g. if the app crashed
+/// while writing it
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InstanceLock, LockError, LockOwner, LOCK_FILE_NAME};
+
+    #[test]
+    fn stale_lock_is_replaced() {
+        let dir = std::env::temp_dir().join(format!("tf2monitor-lock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let lock = InstanceLock::acquire(&dir).unwrap();
+        assert!(matches!(
+            InstanceLock::acquire(&dir),
+            Err(LockError::HeldBy(_))
+        ));
+        drop(lock);
+        assert!(!dir.join(LOCK_FILE_NAME).exists());
+
+        // A process that has since exited
+        let stale = LockOwner {
+            pid: std::process::id(),
+            started: 1,
+        };
+        std::fs::write(
+            dir.join(LOCK_FILE_NAME),
+            serde_json::to_string(&stale).unwrap(),
+        )
+        .unwrap();
+        assert!(!stale.is_running());
+        assert!(InstanceLock::acquire(&dir).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lock_is_never_seen_empty() {
+        let dir = std::env::temp_dir().join(format!("tf2monitor-lock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let lock = InstanceLock::acquire(&dir).unwrap();
+        let contents = std::fs::read_to_string(dir.join(LOCK_FILE_NAME)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LockOwner>(&contents).ok(),
+            Some(lock.owner)
+        );
+        // Only the lock itself is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        drop(lock);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lock_being_written_is_held() {
+        let dir = std::env::temp_dir().join(format!("tf2monitor-lock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Another instance has created the lock but not written to it yet
+        std::fs::write(dir.join(LOCK_FILE_NAME), "").unwrap();
+        let writer = std::thread::spawn({
+            let path = dir.join(LOCK_FILE_NAME);
+            move || {
+                std::thread::sleep(std::time::Duration::from_millis(300));
+                let owner = LockOwner::current();
+                std::fs::write(path, serde_json::to_string(&owner).unwrap()).unwrap();
+            }
+        });
+
+        assert!(matches!(
+            InstanceLock::acquire(&dir),
+            Err(LockError::HeldBy(owner)) if owner.pid == std::process::id()
+        ));
+        writer.join().unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+//! Named settings profiles, e.g. for using the monitor with several accounts
+//! that each have their own API keys, TF2 directory and playerlist.
+
+use std::io::ErrorKind;
+
+use super::{AppDetails, ConfigFilesError, Settings};
+
+/// Subdirectory of the config directory that named profiles are kept in
+pub const PROFILES_DIR_NAME: &str = "profiles";
+/// Remembers which profile was last used, in the default profile's directory
+pub const ACTIVE_PROFILE_FILE_NAME: &str = "active_profile.txt";
+/// Shown in place of the default profile, so can't be used as a name
+pub const DEFAULT_PROFILE_NAME: &str = "Default";
+
+/// Profile names are used as directory names, so they can't contain path
+/// separators or anything else that would escape the profiles directory.
+///
+/// # Errors
+/// If the name is empty or can't safely be used as a directory name
+pub fn validate_profile_name(name: &str) -> Result<(), ConfigFilesError> {
+    let valid = !name.trim().is_empty()
+        && name.trim() == name
+        && name != "."
+        && name != ".."
+        && !name.eq_ignore_ascii_case(DEFAULT_PROFILE_NAME)
+        && !name.chars().any(|c| {
+            c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ConfigFilesError::InvalidProfileName(name.to_owned()))
+    }
+}
+
+/// The names of all the named profiles, sorted. Doesn't include the default
+/// profile.
+///
+/// # Errors
+/// If the config directory could not be found or read
+pub fn list_profiles(app_details: AppDetails) -> Result<Vec<String>, ConfigFilesError> {
+    let dir =
+        Settings::locate_config_directory(app_details.with_profile(None))?.join(PROFILES_DIR_NAME);
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut profiles: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| validate_profile_name(name).is_ok())
+        .collect();
+    profiles.sort_by_key(|name| name.to_lowercase());
+    Ok(profiles)
+}
+
+/// The profile that was in use last time, `None` for the default profile
+///
+/// # Errors
+/// If the config directory could not be found or the file could not be read
+pub fn load_active_profile(app_details: AppDetails) -> Result<Option<String>, ConfigFilesError> {
+    let path = Settings::locate_config_directory(app_details.with_profile(None))?
+        .join(ACTIVE_PROFILE_FILE_NAME);
+
+    let name = match std::fs::read_to_string(path) {
+        Ok(name) => name.trim().to_owned(),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    validate_profile_name(&name)?;
+    Ok(Some(name))
+}
+
+/// Remember the profile in use for next time, `None` for the default profile
+///
+/// # Errors
+/// If the config directory could not be found or the file could not be
+/// written
+pub fn save_active_profile(
+    app_details: AppDetails,
+    profile: Option<&str>,
+) -> Result<(), ConfigFilesError> {
+    let path = Settings::locate_config_directory(app_details.with_profile(None))?
+        .join(ACTIVE_PROFILE_FILE_NAME);
+    std::fs::write(path, profile.unwrap_or_default())?;
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! Notices when the settings or playerlist files are changed by something
+//! else, e.g. when they are synced between computers.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFile {
+    Settings,
+    Records,
+}
+
+#[allow(clippy::module_name_repetitions)]
+pub struct ConfigWatcher {
+    recv: Receiver<Event>,
+    settings_path: Option<PathBuf>,
+    records_path: Option<PathBuf>,
+
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Files are saved by replacing them, so the directories they are in are
+    /// watched rather than the files themselves.
+    ///
+    /// # Errors
+    /// If the [`notify::Watcher`] could not be started.
+    pub fn new(
+        settings_path: Option<&Path>,
+        records_path: Option<&Path>,
+    ) -> Result<Self, notify::Error> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(move |res: Result<Event, notify::Error>| match res {
+                Ok(event) => {
+                    let _ = tx.send(event);
+                }
+                Err(err) => {
+                    tracing::error!("Error while watching config files: {}", err);
+                }
+            })?;
+
+        let mut watched: Vec<&Path> = Vec::new();
+        for dir in [settings_path, records_path]
+            .into_iter()
+            .flatten()
+            .filter_map(Path::parent)
+            .filter(|d| !d.as_os_str().is_empty())
+        {
+            if !watched.contains(&dir) {
+                watcher.watch(dir, RecursiveMode::NonRecursive)?;
+                watched.push(dir);
+            }
+        }
+
+        Ok(Self {
+            recv: rx,
+            settings_path: settings_path.map(Path::to_path_buf),
+            records_path: records_path.map(Path::to_path_buf),
+            _watcher: watcher,
+        })
+    }
+
+    /// Which files have had changes reported since this was last called. This
+    /// includes the app's own saves, so check whether the file actually
+    /// changed on disk before reloading it.
+    pub fn changed_files(&mut self) -> Vec<ConfigFile> {
+        let mut changed = Vec::new();
+        while let Ok(event) = self.recv.try_recv() {
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+
+            for path in &event.paths {
+                let file = if same_file_name(path, self.settings_path.as_deref()) {
+                    ConfigFile::Settings
+                } else if same_file_name(path, self.records_path.as_deref()) {
+                    ConfigFile::Records
+                } else {
+                    continue;
+                };
+
+                if !changed.contains(&file) {
+                    changed.push(file);
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+fn same_file_name(path: &Path, other: Option<&Path>) -> bool {
+    other.is_some_and(|o| o.file_name() == path.file_name())
+}
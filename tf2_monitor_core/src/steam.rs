@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use keyvalues_parser::Vdf;
 use steamid_ng::SteamID;
 use steamlocate::SteamDir;
+use sysinfo::System;
 
 use crate::players::friends::Friend;
 
@@ -121,6 +122,17 @@ pub fn locate_steam_launch_configs(steam_user: SteamID) -> Result<PathBuf, Error
     Ok(steam.path().join(local_config_path))
 }
 
+/// Whether a Steam client process is currently running
+#[must_use]
+pub fn is_steam_running() -> bool {
+    let mut system = System::new();
+    system.refresh_processes();
+    system
+        .processes()
+        .values()
+        .any(|p| matches!(p.name(), "steam" | "steam.exe" | "steam_osx"))
+}
+
 /// Attempts to open the TF2 directory or locate it if it's not in the expected
 /// place
 ///
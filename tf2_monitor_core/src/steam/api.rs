@@ -1,10 +1,14 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use chrono::Utc;
 use event_loop::{try_get, Handled, Is, Message, MessageHandler};
+use reqwest::StatusCode;
+use serde::Deserialize;
 use steam_rs::{
     steam_user::{get_friend_list, get_player_bans, get_player_summaries},
     Steam,
@@ -21,13 +25,25 @@ use crate::{
         records::{PlayerRecord, Verdict},
         steam_info::SteamInfo,
     },
-    settings::FriendsAPIUsage,
+    settings::{FriendsAPIUsage, Settings},
     MonitorState,
 };
 
 use super::TF2_GAME_ID;
 
 const BATCH_SIZE: usize = 20; // adjust as needed
+/// Ticks closer together than this are ignored, so ones that pile up while the
+/// app is busy don't send a burst of batches
+const MIN_BATCH_SPACING: Duration = Duration::from_millis(250);
+
+/// How long to wait before retrying a failed batch, indexed by how many times
+/// it has failed already. Batches that fail again after the last retry are
+/// dropped until they're requested again.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+    Duration::from_secs(120),
+];
 
 #[derive(Debug, Error)]
 pub enum SteamAPIError {
@@ -43,6 +59,56 @@ pub enum SteamAPIError {
     SteamAPIUser(#[from] steam_rs::errors::SteamUserError),
     #[error("Player does not own TF2")]
     GameNotOwned,
+    #[error("The Steam API key was rejected")]
+    InvalidKey,
+    #[error("Rate limited by the Steam API")]
+    RateLimited,
+    #[error("Steam API server error (status {0})")]
+    ServerError(StatusCode),
+    #[error("Couldn't reach the Steam API: {0}")]
+    Network(reqwest::Error),
+    #[error("No profile was found with the custom URL {0:?}")]
+    VanityNotFound(String),
+    #[error("The Steam API couldn't resolve the custom URL (result {0})")]
+    VanityLookupFailed(u32),
+}
+
+impl From<reqwest::Error> for SteamAPIError {
+    /// The URLs of requests include the API key, so they are left out of the
+    /// error to keep the key out of the logs.
+    fn from(error: reqwest::Error) -> Self {
+        Self::Network(error.without_url())
+    }
+}
+
+impl SteamAPIError {
+    /// Whether retrying the same request is pointless (e.g. the API key is
+    /// invalid)
+    #[must_use]
+    pub const fn is_permanent(&self) -> bool {
+        matches!(self, Self::InvalidKey)
+    }
+}
+
+/// Which of the optional lookups to make for each account. These have to be
+/// requested individually for each account, so can use up a lot more API
+/// requests.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LookupOptions {
+    pub playtime: bool,
+    pub steam_level: bool,
+    pub groups: bool,
+}
+
+impl From<&Settings> for LookupOptions {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            playtime: settings.request_playtime,
+            steam_level: settings.request_steam_level,
+            groups: settings.request_groups,
+        }
+    }
 }
 
 // Messages *************************
@@ -54,10 +120,14 @@ impl<S> event_loop::Message<S> for ProfileLookupBatchTick {}
 type ProfileResult = Result<Vec<(SteamID, Result<SteamInfo, SteamAPIError>)>, SteamAPIError>;
 
 #[derive(Debug)]
-pub struct ProfileLookupResult(pub ProfileResult);
+pub struct ProfileLookupResult {
+    /// The accounts that were included in the lookup
+    pub requested: Vec<SteamID>,
+    pub result: ProfileResult,
+}
 impl Message<MonitorState> for ProfileLookupResult {
     fn update_state(self, state: &mut MonitorState) {
-        let results = match &self.0 {
+        let results = match &self.result {
             Err(e) => {
                 tracing::error!("Profile lookup failed: {e}");
                 return;
@@ -65,13 +135,33 @@ impl Message<MonitorState> for ProfileLookupResult {
             Ok(results) => results,
         };
 
+        let mut records_changed = false;
         for (steamid, result) in results {
             match result {
                 Ok(steaminfo) => {
                     if let Some(r) = state.players.records.get_mut(steamid) {
                         r.add_previous_name(&steaminfo.account_name);
                     }
-                    state.players.steam_info.insert(*steamid, steaminfo.clone());
+
+                    // Members of watched groups
+                    if state.settings.mark_group_members_suspicious
+                        && state.players.verdict(*steamid) == Verdict::Player
+                        && steaminfo
+                            .watched_groups(&state.settings.group_watchlist)
+                            .next()
+                            .is_some()
+                    {
+                        tracing::info!(
+                            "Marking {} as suspicious for being in a watched steam group",
+                            u64::from(*steamid)
+                        );
+                        let record = state.players.records.entry(*steamid).or_default();
+                        record.set_verdict(Verdict::Suspicious);
+                        record.add_previous_name(&steaminfo.account_name);
+                        records_changed = true;
+                    }
+
+                    state.players.insert_steam_info(*steamid, steaminfo.clone());
                 }
                 Err(e) => {
                     tracing::error!(
@@ -82,6 +172,10 @@ impl Message<MonitorState> for ProfileLookupResult {
                 }
             }
         }
+
+        if records_changed {
+            state.players.records.save_ok();
+        }
     }
 }
 
@@ -103,6 +197,8 @@ impl Message<MonitorState> for FriendLookupResult {
     }
 }
 
+/// Explicitly requested profile lookups. These are always fetched again, even
+/// if the current steam info hasn't expired yet.
 #[derive(Debug)]
 pub enum ProfileLookupRequest {
     Single(SteamID),
@@ -113,17 +209,111 @@ impl<S> Message<S> for ProfileLookupRequest {}
 
 // Handlers *************************
 
+struct FailedBatch {
+    players: Vec<SteamID>,
+    attempts: usize,
+    retry_at: Instant,
+}
+
 pub struct LookupProfiles {
     batch_buffer: VecDeque<SteamID>,
     in_progress: Vec<SteamID>,
+    /// Accounts which should be looked up regardless of how fresh their info is
+    forced: HashSet<SteamID>,
+    /// Batches waiting to be retried
+    failed: Vec<FailedBatch>,
+    /// How many times in a row the lookup for a particular account has failed
+    attempts: HashMap<SteamID, usize>,
+    /// The Steam API has rejected the current key, so don't make any more
+    /// requests until it is changed.
+    key_rejected: bool,
+    last_batch_tick: Option<Instant>,
 }
 
 impl LookupProfiles {
     #[must_use]
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             batch_buffer: VecDeque::new(),
             in_progress: Vec::new(),
+            forced: HashSet::new(),
+            failed: Vec::new(),
+            attempts: HashMap::new(),
+            key_rejected: false,
+            last_batch_tick: None,
+        }
+    }
+
+    fn handle_result(&mut self, requested: &[SteamID], result: &ProfileResult) {
+        self.in_progress.retain(|s| !requested.contains(s));
+
+        match result {
+            Ok(_) => {
+                for s in requested {
+                    self.attempts.remove(s);
+                }
+            }
+            Err(e) if e.is_permanent() => {
+                tracing::error!("Steam API lookups disabled until the API key is changed: {e}");
+                self.key_rejected = true;
+                self.batch_buffer.clear();
+                self.failed.clear();
+                self.attempts.clear();
+            }
+            Err(e) => {
+                let attempts = requested
+                    .iter()
+                    .map(|s| {
+                        let attempts = self.attempts.entry(*s).or_default();
+                        *attempts += 1;
+                        *attempts
+                    })
+                    .max()
+                    .unwrap_or(1);
+                let Some(&delay) = RETRY_DELAYS.get(attempts - 1) else {
+                    tracing::error!(
+                        "Profile lookup of {} accounts failed {attempts} times ({e}), giving up on them",
+                        requested.len()
+                    );
+                    for s in requested {
+                        self.attempts.remove(s);
+                    }
+                    return;
+                };
+
+                tracing::warn!(
+                    "Profile lookup of {} accounts failed ({e}), retrying in {}s (attempt {attempts})",
+                    requested.len(),
+                    delay.as_secs()
+                );
+
+                self.failed.push(FailedBatch {
+                    players: requested.to_vec(),
+                    attempts,
+                    retry_at: Instant::now() + delay,
+                });
+            }
+        }
+    }
+
+    /// Moves any failed batches which are due to be retried back into the
+    /// batch buffer.
+    fn queue_retries(&mut self) {
+        let now = Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) =
+            self.failed.drain(..).partition(|f| f.retry_at <= now);
+        self.failed = waiting;
+
+        for batch in due {
+            tracing::debug!(
+                "Retrying profile lookup of {} accounts (attempt {})",
+                batch.players.len(),
+                batch.attempts + 1
+            );
+            for s in batch.players.into_iter().rev() {
+                self.forced.insert(s);
+                self.batch_buffer.push_front(s);
+            }
         }
     }
 }
@@ -136,10 +326,20 @@ impl Default for LookupProfiles {
 
 impl<IM, OM> MessageHandler<MonitorState, IM, OM> for LookupProfiles
 where
-    IM: Is<NewPlayers> + Is<ProfileLookupBatchTick> + Is<Preferences> + Is<ProfileLookupRequest>,
+    IM: Is<NewPlayers>
+        + Is<ProfileLookupBatchTick>
+        + Is<Preferences>
+        + Is<ProfileLookupRequest>
+        + Is<ProfileLookupResult>,
     OM: Is<ProfileLookupResult>,
 {
     fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
+        // Finished lookups can be requested again, failed ones are retried later
+        if let Some(ProfileLookupResult { requested, result }) = try_get(message) {
+            self.handle_result(requested, result);
+            return Handled::none();
+        }
+
         // Re-request connected players if the API key has changed
         if let Some(Preferences {
             internal:
@@ -153,10 +353,21 @@ where
                     rcon_port: _,
                     dumb_autokick: _,
                     request_playtime: _,
+                    steam_info_cache_hours: _,
+                    request_steam_level: _,
+                    request_groups: _,
+                    group_watchlist: _,
+                    mark_group_members_suspicious: _,
+                    bot_list_subscriptions: _,
+                    max_history_len: _,
                 }),
             external: _,
         }) = try_get(message)
         {
+            self.key_rejected = false;
+            self.failed.clear();
+            self.attempts.clear();
+
             if new_key.is_empty() {
                 self.batch_buffer.clear();
                 return Handled::none();
@@ -165,8 +376,22 @@ where
             self.batch_buffer.extend(&state.players.connected);
         }
 
-        // Don't request anything if there's no API key
-        if state.settings.steam_api_key.is_empty() {
+        // Lookup groups of connected players once it's enabled
+        if let Some(Preferences {
+            internal:
+                Some(InternalPreferences {
+                    request_groups: Some(true),
+                    ..
+                }),
+            external: _,
+        }) = try_get(message)
+        {
+            self.batch_buffer.extend(&state.players.connected);
+            self.forced.extend(&state.players.connected);
+        }
+
+        // Don't request anything if there's no API key or it has been rejected
+        if state.settings.steam_api_key.is_empty() || self.key_rejected || state.settings.offline {
             return None;
         }
 
@@ -178,25 +403,53 @@ where
         // Request specifically-requested accounts
         if let Some(lookup) = try_get::<ProfileLookupRequest>(message) {
             match lookup {
-                ProfileLookupRequest::Single(p) => self.batch_buffer.push_back(*p),
-                ProfileLookupRequest::Multiple(ps) => self.batch_buffer.extend(ps),
+                ProfileLookupRequest::Single(p) => {
+                    self.batch_buffer.push_back(*p);
+                    self.forced.insert(*p);
+                }
+                ProfileLookupRequest::Multiple(ps) => {
+                    self.batch_buffer.extend(ps);
+                    self.forced.extend(ps);
+                }
             }
         }
 
         // Send of lookup batch
         if try_get::<ProfileLookupBatchTick>(message).is_some() {
+            if self
+                .last_batch_tick
+                .is_some_and(|t| t.elapsed() < MIN_BATCH_SPACING)
+            {
+                return Handled::none();
+            }
+            self.last_batch_tick = Some(Instant::now());
+
+            self.queue_retries();
+
+            let cache_hours = state.settings.steam_info_cache_hours;
+            let mut queued = HashSet::new();
             self.batch_buffer.retain(|s| {
+                // Already queued
+                if !queued.insert(*s) {
+                    return false;
+                }
+
                 // Already retrieving
                 if self.in_progress.contains(s) {
                     return false;
                 }
 
+                // Explicitly requested
+                if self.forced.contains(s) {
+                    return true;
+                }
+
                 // Already present and reasonably recent
                 !state
                     .players
                     .steam_info
                     .get(s)
-                    .is_some_and(|si| !si.expired())
+                    .is_some_and(|si| !si.expired(cache_hours))
             });
             if self.batch_buffer.is_empty() {
                 return Handled::none();
@@ -207,14 +460,30 @@ where
                 .drain(0..BATCH_SIZE.min(self.batch_buffer.len()))
                 .collect();
 
+            for s in &batch {
+                self.forced.remove(s);
+            }
             self.in_progress.extend_from_slice(&batch);
 
-            let client = Arc::new(Steam::new(&state.settings.steam_api_key));
-            let request_playtime = state.settings.request_playtime;
+            // Retries are already known to fail, so there's no need to find out
+            // why again
+            let diagnose = !batch.iter().any(|s| self.attempts.contains_key(s));
+
+            let key = state.settings.steam_api_key.clone();
+            let client = Arc::new(Steam::new(&key));
+            let options = LookupOptions::from(&state.settings);
             return Handled::future(async move {
+                let result = match request_steam_info(client, &batch, options).await {
+                    Err(e) if diagnose => Err(diagnose_failure(&key, e).await),
+                    result => result,
+                };
+
                 Some(
-                    ProfileLookupResult(request_steam_info(client, &batch, request_playtime).await)
-                        .into(),
+                    ProfileLookupResult {
+                        requested: batch,
+                        result,
+                    }
+                    .into(),
                 )
             });
         }
@@ -223,36 +492,105 @@ where
     }
 }
 
+/// How many friend lookups can be waiting on the Steam API at once
+const MAX_FRIEND_LOOKUPS_IN_FLIGHT: usize = 3;
+/// Minimum time between starting consecutive friend lookups
+const FRIEND_LOOKUP_SPACING: Duration = Duration::from_millis(500);
+
+struct QueuedFriendLookup {
+    steamid: SteamID,
+    /// Whether the player was in the server when the lookup was queued, so it
+    /// can be dropped if they leave before it is sent.
+    was_connected: bool,
+}
+
 pub struct LookupFriends {
     in_progess: Vec<SteamID>,
+    queue: VecDeque<QueuedFriendLookup>,
+    max_in_flight: usize,
+    spacing: Duration,
+    next_request_at: Instant,
 }
 
 impl LookupFriends {
     #[must_use]
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
+        Self::with_limits(MAX_FRIEND_LOOKUPS_IN_FLIGHT, FRIEND_LOOKUP_SPACING)
+    }
+
+    /// Limit how many friend lookups can be in flight at once, and the minimum
+    /// time between starting each of them.
+    #[must_use]
+    pub fn with_limits(max_in_flight: usize, spacing: Duration) -> Self {
         Self {
             in_progess: Vec::new(),
+            queue: VecDeque::new(),
+            max_in_flight: max_in_flight.max(1),
+            spacing,
+            next_request_at: Instant::now(),
         }
     }
 
     fn lookup_players<'a, M: Is<FriendLookupResult>>(
         &mut self,
+        state: &MonitorState,
         key: &str,
         players: impl IntoIterator<Item = &'a SteamID>,
     ) -> Option<Handled<M>> {
-        Handled::multiple(players.into_iter().map(|&p| {
-            self.in_progess.push(p);
+        for &p in players {
+            if self.in_progess.contains(&p) || self.queue.iter().any(|q| q.steamid == p) {
+                continue;
+            }
+
+            self.queue.push_back(QueuedFriendLookup {
+                steamid: p,
+                was_connected: state.players.connected.contains(&p),
+            });
+        }
+
+        self.drain_queue(state, key)
+    }
+
+    /// Start as many of the queued lookups as the in-flight limit allows
+    fn drain_queue<M: Is<FriendLookupResult>>(
+        &mut self,
+        state: &MonitorState,
+        key: &str,
+    ) -> Option<Handled<M>> {
+        let mut lookups = Vec::new();
+
+        while self.in_progess.len() < self.max_in_flight {
+            let Some(QueuedFriendLookup {
+                steamid,
+                was_connected,
+            }) = self.queue.pop_front()
+            else {
+                break;
+            };
+
+            // Left the server before we got to them
+            if was_connected && !state.players.connected.contains(&steamid) {
+                continue;
+            }
+
+            let start_at = self.next_request_at.max(Instant::now());
+            self.next_request_at = start_at + self.spacing;
+            self.in_progess.push(steamid);
+
             let client = Steam::new(key);
-            Handled::future(async move {
+            lookups.push(Handled::future(async move {
+                tokio::time::sleep_until(start_at.into()).await;
                 Some(
                     FriendLookupResult {
-                        steamid: p,
-                        result: request_account_friends(&client, p).await,
+                        steamid,
+                        result: request_account_friends(&client, steamid).await,
                     }
                     .into(),
                 )
-            })
-        }))
+            }));
+        }
+
+        Handled::multiple(lookups)
     }
 
     /// Takes a list of steamids and does friend lookups on the ones which fit
@@ -323,7 +661,7 @@ impl LookupFriends {
             return Handled::none();
         }
 
-        self.lookup_players(key, &queued_friendlist_req)
+        self.lookup_players(state, key, &queued_friendlist_req)
     }
 }
 
@@ -339,7 +677,7 @@ where
     OM: Is<FriendLookupResult>,
 {
     fn handle_message(&mut self, state: &MonitorState, message: &IM) -> Option<Handled<OM>> {
-        if state.settings.steam_api_key.is_empty() {
+        if state.settings.steam_api_key.is_empty() || state.settings.offline {
             return Handled::none();
         }
 
@@ -374,7 +712,8 @@ where
             };
 
             self.in_progess.retain(|s| s != steamid);
-            return out;
+            let next = self.drain_queue(state, &state.settings.steam_api_key);
+            return Handled::multiple([out, next]);
         }
 
         // Lookup any players that might need to be after a change to their verdicts
@@ -450,6 +789,9 @@ where
 /// Make a request to the Steam web API for the chosen player and return the
 /// important steam info.
 ///
+/// Playtime, steam level and groups are only included if requested in the
+/// [`LookupOptions`].
+///
 /// # Errors
 /// Returns `Err` if the overall api request failed.
 /// Individual elements in the Vec may be `Err` if specific accounts were not
@@ -457,36 +799,39 @@ where
 pub async fn request_steam_info(
     client: Arc<Steam>,
     playerids: &[SteamID],
-    include_playtime: bool,
+    options: LookupOptions,
 ) -> Result<Vec<(SteamID, Result<SteamInfo, SteamAPIError>)>, SteamAPIError> {
     tracing::debug!("Requesting steam accounts: {:?}", playerids);
 
     let summaries = request_player_summary(&client, playerids).await?;
     let bans = request_account_bans(&client, playerids).await?;
 
-    let playtimes = if include_playtime && !playerids.is_empty() {
-        let mut join_handles: JoinSet<(SteamID, Result<u64, SteamAPIError>)> = JoinSet::new();
-
-        for p in playerids {
-            let client = client.clone();
-            let p = *p;
-            join_handles.spawn(async move {
-                let playtime = request_game_playtime(&client, p).await;
-                (p, playtime)
-            });
-        }
+    let id_to_playtime = if options.playtime {
+        request_for_each(&client, playerids, |client, p| async move {
+            request_game_playtime(&client, p).await
+        })
+        .await
+    } else {
+        HashMap::new()
+    };
 
-        let mut playtimes = Vec::new();
-        while let Some(playtime) = join_handles.join_next().await {
-            let Ok(playtime) = playtime else {
-                continue;
-            };
-            playtimes.push(playtime);
-        }
+    let id_to_steam_level = if options.steam_level {
+        request_for_each(&client, playerids, |client, p| async move {
+            request_steam_level(&client, p).await
+        })
+        .await
+    } else {
+        HashMap::new()
+    };
 
-        playtimes
+    // Private profiles will fail this request, they just won't have groups
+    let id_to_groups = if options.groups {
+        request_for_each(&client, playerids, |client, p| async move {
+            request_user_groups(&client, p).await
+        })
+        .await
     } else {
-        Vec::new()
+        HashMap::new()
     };
 
     let id_to_summary: HashMap<_, _> = summaries
@@ -497,10 +842,6 @@ pub async fn request_steam_info(
         .into_iter()
         .map(|ban| (ban.steam_id.clone(), ban))
         .collect();
-    let id_to_playtime: HashMap<_, _> = playtimes
-        .into_iter()
-        .filter_map(|(s, r)| r.ok().map(|r| (s, r)))
-        .collect();
 
     Ok(playerids
         .iter()
@@ -532,6 +873,8 @@ pub async fn request_steam_info(
                         None
                     },
                     playtime: id_to_playtime.get(&player).copied(),
+                    steam_level: id_to_steam_level.get(&player).copied(),
+                    groups: id_to_groups.get(&player).cloned(),
                     fetched: Utc::now(),
                 };
                 Ok(steam_info)
@@ -542,6 +885,108 @@ pub async fn request_steam_info(
         .collect())
 }
 
+/// Find the account behind a custom profile URL, i.e.
+/// `steamcommunity.com/id/<vanity>`
+///
+/// # Errors
+/// If the request failed or no account has that custom URL. Only
+/// [`SteamAPIError::VanityNotFound`] means the URL definitely isn't in use,
+/// anything else is worth trying again later.
+pub async fn resolve_vanity_url(key: &str, vanity: &str) -> Result<SteamID, SteamAPIError> {
+    let response = reqwest::Client::new()
+        .get("https://api.steampowered.com/ISteamUser/ResolveVanityURL/v1/")
+        .query(&[("key", key), ("vanityurl", vanity)])
+        .send()
+        .await?;
+
+    match response.status() {
+        StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED => return Err(SteamAPIError::InvalidKey),
+        StatusCode::TOO_MANY_REQUESTS => return Err(SteamAPIError::RateLimited),
+        s if s.is_server_error() => return Err(SteamAPIError::ServerError(s)),
+        _ => {}
+    }
+
+    parse_vanity_response(&response.text().await?, vanity)
+}
+
+/// The API reports whether there was a match with a result code, 1 for a
+/// match and 42 for no match. Anything else means it couldn't tell.
+fn parse_vanity_response(body: &str, vanity: &str) -> Result<SteamID, SteamAPIError> {
+    #[derive(Deserialize)]
+    struct Response {
+        response: VanityResponse,
+    }
+
+    #[derive(Deserialize)]
+    struct VanityResponse {
+        success: u32,
+        steamid: Option<String>,
+    }
+
+    const NO_MATCH: u32 = 42;
+
+    let body: Response = serde_json::from_str(body)?;
+    match body.response.success {
+        1 => body
+            .response
+            .steamid
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(SteamID::from)
+            .ok_or(SteamAPIError::VanityLookupFailed(1)),
+        NO_MATCH => Err(SteamAPIError::VanityNotFound(vanity.to_string())),
+        result => Err(SteamAPIError::VanityLookupFailed(result)),
+    }
+}
+
+/// The steam API library doesn't expose the status code of failed requests, so
+/// make a cheap request to find out whether the failure was caused by an
+/// invalid key, rate limiting or the API being down.
+async fn diagnose_failure(key: &str, error: SteamAPIError) -> SteamAPIError {
+    let url = format!(
+        "https://api.steampowered.com/ISteamUser/GetPlayerBans/v1/?key={key}&steamids=76561197960287930"
+    );
+
+    match reqwest::get(&url).await {
+        Ok(r) if r.status() == StatusCode::FORBIDDEN || r.status() == StatusCode::UNAUTHORIZED => {
+            SteamAPIError::InvalidKey
+        }
+        Ok(r) if r.status() == StatusCode::TOO_MANY_REQUESTS => SteamAPIError::RateLimited,
+        Ok(r) if r.status().is_server_error() => SteamAPIError::ServerError(r.status()),
+        Ok(_) => error,
+        Err(e) => e.into(),
+    }
+}
+
+/// Concurrently makes a request for each of the provided players, only
+/// returning the ones which succeeded.
+async fn request_for_each<T, F, Fut>(
+    client: &Arc<Steam>,
+    players: &[SteamID],
+    request: F,
+) -> HashMap<SteamID, T>
+where
+    T: Send + 'static,
+    F: Fn(Arc<Steam>, SteamID) -> Fut,
+    Fut: Future<Output = Result<T, SteamAPIError>> + Send + 'static,
+{
+    let mut join_handles: JoinSet<(SteamID, Result<T, SteamAPIError>)> = JoinSet::new();
+
+    for &p in players {
+        let fut = request(client.clone(), p);
+        join_handles.spawn(async move { (p, fut.await) });
+    }
+
+    let mut results = HashMap::new();
+    while let Some(result) = join_handles.join_next().await {
+        let Ok((p, Ok(result))) = result else {
+            continue;
+        };
+        results.insert(p, result);
+    }
+
+    results
+}
+
 async fn request_player_summary(
     client: &Steam,
     players: &[SteamID],
@@ -605,3 +1050,162 @@ async fn request_game_playtime(client: &Steam, player: SteamID) -> Result<u64, S
     game.map(|g| g.playtime_forever)
         .ok_or(SteamAPIError::GameNotOwned)
 }
+
+async fn request_steam_level(client: &Steam, player: SteamID) -> Result<u32, SteamAPIError> {
+    let steamid = steam_rs::steam_id::SteamId::new(u64::from(player));
+    Ok(u32::from(client.get_steam_level(steamid).await?))
+}
+
+async fn request_user_groups(client: &Steam, player: SteamID) -> Result<Vec<u64>, SteamAPIError> {
+    let steamid = steam_rs::steam_id::SteamId::new(u64::from(player));
+    let groups = client.get_user_group_list(steamid).await?;
+
+    Ok(groups
+        .groups
+        .into_iter()
+        .filter_map(|g| g.gid.parse().ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use event_loop::{define_events, Action, EventLoop};
+    use steamid_ng::SteamID;
+
+    use super::{
+        parse_vanity_response, FriendLookupResult, LookupFriends, LookupProfiles, SteamAPIError,
+        RETRY_DELAYS,
+    };
+    use crate::{
+        events::{Preferences, UserUpdates},
+        masterbase::{queue::UploadQueue, UploadStatus},
+        players::{new_players::NewPlayers, records::Records, Players},
+        server::Server,
+        settings::{FriendsAPIUsage, Settings},
+        MonitorState,
+    };
+
+    define_events!(
+        MonitorState,
+        TestMessage {
+            NewPlayers,
+            FriendLookupResult,
+            UserUpdates,
+            Preferences,
+        },
+        TestHandler { LookupFriends },
+    );
+
+    fn test_state(connected: &[SteamID]) -> MonitorState {
+        let mut settings = Settings::default();
+        settings.steam_api_key = "key".into();
+        settings.friends_api_usage = FriendsAPIUsage::All;
+
+        let mut players = Players::new(Records::default(), None, None);
+        players.connected = connected.to_vec();
+
+        MonitorState {
+            server: Server::new(),
+            settings,
+            players,
+            upload_status: UploadStatus::default(),
+            upload_queue: UploadQueue::default(),
+        }
+    }
+
+    fn num_lookups(actions: &[Action<TestMessage>]) -> usize {
+        actions
+            .iter()
+            .filter(|a| matches!(a, Action::Future(_)))
+            .count()
+    }
+
+    fn lookup_result(steamid: SteamID) -> TestMessage {
+        FriendLookupResult {
+            steamid,
+            result: Ok(Vec::new()),
+        }
+        .into()
+    }
+
+    #[test]
+    pub fn bounded_friend_lookups() {
+        let players: Vec<SteamID> = (1..=10_u64).map(SteamID::from).collect();
+        let mut state = test_state(&players);
+        let mut event_loop: EventLoop<MonitorState, TestMessage, TestHandler> =
+            EventLoop::new().add_handler(LookupFriends::with_limits(3, Duration::ZERO));
+
+        // Only the first few lookups are started
+        let actions = event_loop.handle_message(NewPlayers(players.clone()).into(), &mut state);
+        assert_eq!(num_lookups(&actions), 3);
+
+        // Each result lets another one through
+        let actions = event_loop.handle_message(lookup_result(players[0]), &mut state);
+        assert_eq!(num_lookups(&actions), 1);
+
+        // Queued players who leave the server are dropped
+        state.players.connected.retain(|&s| s != players[4]);
+        let started: usize = players[1..]
+            .iter()
+            .filter(|&&s| s != players[4])
+            .map(|&s| num_lookups(&event_loop.handle_message(lookup_result(s), &mut state)))
+            .sum();
+        assert_eq!(started, 5);
+    }
+
+    #[tokio::test]
+    pub async fn network_errors_leave_out_the_key() {
+        let error = reqwest::get("http://127.0.0.1:1/?key=SECRETKEY&steamids=1")
+            .await
+            .expect_err("Nothing should be listening on port 1");
+        let error = SteamAPIError::from(error);
+
+        assert!(matches!(error, SteamAPIError::Network(_)));
+        assert!(!error.to_string().contains("SECRETKEY"));
+        assert!(!format!("{error:?}").contains("SECRETKEY"));
+    }
+
+    #[test]
+    pub fn failed_batches_are_dropped_after_the_last_retry() {
+        let players = [SteamID::from(76_561_197_960_287_930)];
+        let mut lookups = LookupProfiles::new();
+
+        for attempt in 1..=RETRY_DELAYS.len() {
+            lookups.handle_result(&players, &Err(SteamAPIError::RateLimited));
+            assert_eq!(lookups.failed.len(), 1);
+            assert_eq!(lookups.failed[0].attempts, attempt);
+            lookups.failed.clear();
+        }
+
+        lookups.handle_result(&players, &Err(SteamAPIError::RateLimited));
+        assert!(lookups.failed.is_empty());
+        assert!(lookups.attempts.is_empty());
+    }
+
+    #[test]
+    pub fn only_no_match_means_a_custom_url_is_unused() {
+        let found = r#"{"response":{"steamid":"76561198000000001","success":1}}"#;
+        assert_eq!(
+            parse_vanity_response(found, "name").expect("Found the account"),
+            SteamID::from(76_561_198_000_000_001)
+        );
+
+        let no_match = r#"{"response":{"success":42,"message":"No match"}}"#;
+        assert!(matches!(
+            parse_vanity_response(no_match, "name"),
+            Err(SteamAPIError::VanityNotFound(v)) if v == "name"
+        ));
+
+        let failed = r#"{"response":{"success":2}}"#;
+        assert!(matches!(
+            parse_vanity_response(failed, "name"),
+            Err(SteamAPIError::VanityLookupFailed(2))
+        ));
+        assert!(matches!(
+            parse_vanity_response("<html>Too many requests</html>", "name"),
+            Err(SteamAPIError::Serde(_))
+        ));
+    }
+}
@@ -1,12 +1,17 @@
-use std::{fs::File, io::Read, path::PathBuf};
-
-use anyhow::{Context, Result};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use atomic_write_file::AtomicWriteFile;
 use regex::Regex;
 use steamid_ng::SteamID;
 use substring::Substring;
 use tracing::Level;
 
-use crate::steam::{locate_steam_launch_configs, TF2_GAME_ID};
+use crate::steam::{is_steam_running, locate_steam_launch_configs, TF2_GAME_ID};
 
 /// `-condebug` enables the console.log file to be written to by the game.
 /// `-conclearlog` clears the console.log file on launch of TF2
@@ -14,6 +19,7 @@ use crate::steam::{locate_steam_launch_configs, TF2_GAME_ID};
 /// `-g15` enables Logitech G15 keyboard support (used for the console command
 /// `g15_dumpplayer`)
 pub const TF2_REQUIRED_OPTS: [&str; 4] = ["-condebug", "-conclearlog", "-usercon", "-g15"];
+pub const LAUNCH_CONFIG_BACKUP_SUFFIX: &str = ".bak";
 
 /// Read the local steam library folders for data (stored in VDF/KeyValues
 /// format) on the configured launch options for the given app ID.
@@ -21,6 +27,9 @@ pub const TF2_REQUIRED_OPTS: [&str; 4] = ["-condebug", "-conclearlog", "-usercon
 /// provides an interface to read and write launch options based on a set of
 /// required options.
 pub struct LaunchOptions {
+    config_path: PathBuf,
+    /// The whole file, if it was valid UTF-8 and so is safe to write back
+    contents: Option<String>,
     launch_args_regex: Regex,
     app_data: Option<String>,
     new_app_data: Option<String>,
@@ -55,6 +64,7 @@ impl LaunchOptions {
                 .context(format!("Failed to read {config_path:?}"))?;
         }
         let binding = &String::from_utf8_lossy(&data);
+        let contents = String::from_utf8(data.clone()).ok();
 
         let apps_regex =
             Regex::new(r#"\t{4}"[aA]pps"([\s\S]+)\t{5}}"#).expect("Apps regex construction");
@@ -87,6 +97,8 @@ impl LaunchOptions {
             .expect("Constructing launch options regex");
 
         Ok(Self {
+            config_path,
+            contents,
             launch_args_regex: launch_options_regex,
             app_data: matched_app_block,
             new_app_data: None,
@@ -127,4 +139,96 @@ impl LaunchOptions {
 
         Ok(missing_args)
     }
+
+    /// Adds any missing required options to the end of the target app's launch
+    /// options. Nothing is written to disk until [`Self::write`] is called.
+    ///
+    /// # Errors
+    /// - No app data is stored in this object (`self.app_data` is None).
+    pub fn add_missing_args(&mut self) -> Result<(), anyhow::Error> {
+        let missing = self.check_missing_args()?.join(" ");
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let app_data = self
+            .new_app_data
+            .as_ref()
+            .or(self.app_data.as_ref())
+            .context("No data currently stored.")?;
+
+        let new_app_data = if let Some(caps) = self.launch_args_regex.captures(app_data) {
+            let current = caps.get(1).expect("Launch options capture group");
+            let options = if current.as_str().trim().is_empty() {
+                missing
+            } else {
+                format!("{} {missing}", current.as_str().trim_end())
+            };
+            format!(
+                "{}{options}{}",
+                &app_data[..current.start()],
+                &app_data[current.end()..]
+            )
+        } else {
+            format!("{app_data}\n\t\t\t\t\t\t\"LaunchOptions\"\t\t\"{missing}\"")
+        };
+
+        self.new_app_data = Some(new_app_data);
+        Ok(())
+    }
+
+    /// Writes the changed launch options back to `localconfig.vdf`, after
+    /// copying the original file next to it with the
+    /// [`LAUNCH_CONFIG_BACKUP_SUFFIX`]. Steam overwrites the file when it
+    /// exits, so this refuses to do anything while Steam is running.
+    ///
+    /// # Errors
+    /// - Steam is running
+    /// - The file wasn't valid UTF-8, or has changed since it was read
+    /// - The backup or new file couldn't be written
+    pub fn write(&self) -> Result<(), anyhow::Error> {
+        let (Some(app_data), Some(new_app_data)) = (&self.app_data, &self.new_app_data) else {
+            return Ok(());
+        };
+
+        if is_steam_running() {
+            bail!("Steam is running. Close Steam before changing launch options, otherwise it will overwrite them when it exits.");
+        }
+
+        let contents = self
+            .contents
+            .as_ref()
+            .context("localconfig.vdf isn't valid UTF-8, so it can't be safely edited.")?;
+        let current = std::fs::read_to_string(&self.config_path)
+            .context(format!("Failed to read {:?}", self.config_path))?;
+        if &current != contents {
+            bail!("localconfig.vdf has changed since it was read.");
+        }
+
+        let app_start = contents
+            .find(app_data.as_str())
+            .context("Could not find specified app in localconfig.vdf for the current user.")?;
+        let new_contents = format!(
+            "{}{new_app_data}{}",
+            &contents[..app_start],
+            &contents[app_start + app_data.len()..]
+        );
+
+        let mut backup_path = self.config_path.clone().into_os_string();
+        backup_path.push(LAUNCH_CONFIG_BACKUP_SUFFIX);
+        std::fs::copy(&self.config_path, &backup_path)
+            .context(format!("Failed to back up {:?}", self.config_path))?;
+
+        let mut file = AtomicWriteFile::open(&self.config_path)
+            .context(format!("Failed to open {:?}", self.config_path))?;
+        file.write_all(new_contents.as_bytes())?;
+        file.commit()?;
+
+        tracing::info!(
+            "Updated launch options in {:?} (backup at {:?})",
+            self.config_path,
+            backup_path
+        );
+        Ok(())
+    }
 }
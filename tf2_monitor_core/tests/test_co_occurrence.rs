@@ -0,0 +1,42 @@
+use tf2_monitor_core::{players::co_occurrence::CoOccurrence, steamid_ng::SteamID};
+
+#[test]
+fn same_match_is_counted_once() {
+    let user = SteamID::from(76_561_198_000_000_001);
+    let cheater = SteamID::from(76_561_198_000_000_002);
+    let other = SteamID::from(76_561_198_000_000_003);
+
+    let mut co_occurrence = CoOccurrence::new();
+    let connected = [user, cheater, other];
+    for _ in 0..5 {
+        co_occurrence.update(&connected, &[cheater], Some(user));
+    }
+
+    assert_eq!(co_occurrence.times_with_marked(other), 1);
+    assert_eq!(co_occurrence.times_with_marked(user), 0);
+    assert_eq!(co_occurrence.times_with_marked(cheater), 0);
+
+    // Leaving and joining a new match counts again
+    co_occurrence.update(&[user, other], &[], Some(user));
+    co_occurrence.update(&connected, &[cheater], Some(user));
+    assert_eq!(co_occurrence.times_with_marked(other), 2);
+    assert_eq!(co_occurrence.get(other).unwrap().marked[&cheater], 2);
+}
+
+#[test]
+fn multiple_marked_players_count_as_one_time() {
+    let cheater_a = SteamID::from(76_561_198_000_000_002);
+    let cheater_b = SteamID::from(76_561_198_000_000_003);
+    let other = SteamID::from(76_561_198_000_000_004);
+
+    let mut co_occurrence = CoOccurrence::new();
+    co_occurrence.update(
+        &[cheater_a, cheater_b, other],
+        &[cheater_a, cheater_b],
+        None,
+    );
+
+    assert_eq!(co_occurrence.times_with_marked(other), 1);
+    assert_eq!(co_occurrence.get(other).unwrap().marked.len(), 2);
+    assert_eq!(co_occurrence.accounts_over(1).count(), 3);
+}
@@ -0,0 +1,23 @@
+use tf2_monitor_core::demos::analyser::AnalysedDemo;
+
+/// A demo with a valid header, followed by a packet that is cut off partway
+/// through, like a demo left behind by TF2 crashing while recording
+const TRUNCATED_DEMO: &[u8] = include_bytes!("data/truncated.dem");
+
+#[test]
+fn truncated_demo_gives_partial_results() {
+    let demo = AnalysedDemo::new(TRUNCATED_DEMO, None).expect("Should still be analysed");
+
+    assert!(demo.truncated);
+    assert!(!demo.is_outdated());
+    assert_eq!(demo.parse_warnings.len(), 1);
+    assert_eq!(demo.header.map, "cp_badlands");
+    assert_eq!(demo.header.nick, "Bash09");
+    assert!(demo.players.is_empty());
+    assert!(demo.kills.is_empty());
+}
+
+#[test]
+fn truncated_header_is_an_error() {
+    assert!(AnalysedDemo::new(&TRUNCATED_DEMO[..500], None).is_err());
+}
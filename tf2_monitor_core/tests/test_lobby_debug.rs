@@ -0,0 +1,48 @@
+use regex::Regex;
+use tf2_monitor_core::{
+    console::commands::regexes::{LobbyMember, REGEX_LOBBY_MEMBER},
+    players::game_info::Team,
+    steamid_ng::SteamID,
+};
+
+fn parse(path: &str) -> Vec<LobbyMember> {
+    let output = std::fs::read_to_string(format!("tests/data/{path}.txt"))
+        .expect("No lobby debug log file found?");
+    let regex = Regex::new(REGEX_LOBBY_MEMBER).unwrap();
+    output
+        .lines()
+        .filter_map(|line| regex.captures(line))
+        .map(|caps| LobbyMember::parse(&caps).unwrap())
+        .collect()
+}
+
+#[test]
+fn lobby_members_are_parsed() {
+    let members = parse("lobby_debug");
+    assert_eq!(members.len(), 7);
+
+    let first = &members[0];
+    assert_eq!(
+        first.steamid,
+        SteamID::from_steam3("[U:1:1076379306]").unwrap()
+    );
+    assert_eq!(first.team, Team::Red);
+    assert_eq!(first.member_type, "MATCH_PLAYER");
+    assert!(!first.pending);
+
+    assert_eq!(members[1].team, Team::Blu);
+    assert_eq!(members[4].member_type, "INVALID_PLAYER");
+
+    let pending = &members[6];
+    assert_eq!(
+        pending.steamid,
+        SteamID::from_steam3("[U:1:903281377]").unwrap()
+    );
+    assert_eq!(pending.team, Team::Blu);
+    assert!(pending.pending);
+}
+
+#[test]
+fn community_servers_have_no_lobby() {
+    assert!(parse("lobby_debug_none").is_empty());
+}
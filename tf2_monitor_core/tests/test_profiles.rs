@@ -0,0 +1,17 @@
+use tf2_monitor_core::settings::profiles::validate_profile_name;
+
+#[test]
+fn profile_names_stay_inside_profiles_dir() {
+    assert!(validate_profile_name("Alt account").is_ok());
+    assert!(validate_profile_name("second-pc_2").is_ok());
+
+    assert!(validate_profile_name("").is_err());
+    assert!(validate_profile_name("   ").is_err());
+    assert!(validate_profile_name(" padded").is_err());
+    assert!(validate_profile_name(".").is_err());
+    assert!(validate_profile_name("..").is_err());
+    assert!(validate_profile_name("../config").is_err());
+    assert!(validate_profile_name("a\\b").is_err());
+    assert!(validate_profile_name("C:").is_err());
+    assert!(validate_profile_name("default").is_err());
+}
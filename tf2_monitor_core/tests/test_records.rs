@@ -0,0 +1,229 @@
+use std::time::{Duration, SystemTime};
+
+use tf2_monitor_core::{
+    players::records::{
+        PlayerRecord, Records, Verdict, MAX_RECENT_ENCOUNTERS, MAX_VERDICT_HISTORY,
+    },
+    steamid_ng::SteamID,
+};
+
+#[test]
+fn verdict_history_only_records_changes() {
+    let mut record = PlayerRecord::default();
+    record.set_verdict(Verdict::Player);
+    assert!(record.verdict_history().is_empty());
+    assert!(record.is_empty());
+
+    record.set_verdict(Verdict::Cheater);
+    record.set_verdict(Verdict::Cheater);
+    record.set_verdict(Verdict::Suspicious);
+
+    let history: Vec<Verdict> = record.verdict_history().iter().map(|(_, v)| *v).collect();
+    assert_eq!(history, [Verdict::Cheater, Verdict::Suspicious]);
+
+    // Having been marked as something else is worth keeping
+    record.set_verdict(Verdict::Player);
+    assert!(!record.is_empty());
+}
+
+#[test]
+fn verdict_history_is_bounded() {
+    let mut record = PlayerRecord::default();
+    for _ in 0..MAX_VERDICT_HISTORY {
+        record.set_verdict(Verdict::Bot);
+        record.set_verdict(Verdict::Cheater);
+    }
+
+    assert_eq!(record.verdict_history().len(), MAX_VERDICT_HISTORY);
+    assert_eq!(
+        record.verdict_history().last().map(|(_, v)| *v),
+        Some(Verdict::Cheater)
+    );
+}
+
+#[test]
+fn old_records_load_without_history() {
+    let mut record: PlayerRecord =
+        serde_json::from_str(r#"{"custom_data": {}, "verdict": "Cheater", "previous_names": []}"#)
+            .expect("Deserializing record");
+    assert!(record.verdict_history().is_empty());
+    assert_eq!(record.verdict(), Verdict::Cheater);
+
+    // The verdict from before history was kept isn't lost
+    record.set_verdict(Verdict::Suspicious);
+    let history: Vec<Verdict> = record.verdict_history().iter().map(|(_, v)| *v).collect();
+    assert_eq!(history, [Verdict::Cheater, Verdict::Suspicious]);
+}
+
+#[test]
+fn old_previous_names_load() {
+    let mut record: PlayerRecord = serde_json::from_str(
+        r#"{"custom_data": {}, "verdict": "Cheater", "previous_names": ["newest", "older"]}"#,
+    )
+    .expect("Deserializing record");
+    let names: Vec<&str> = record.previous_name_strs().collect();
+    assert_eq!(names, ["newest", "older"]);
+    assert!(record
+        .previous_names()
+        .iter()
+        .all(|n| n.last_used.is_none()));
+
+    // Moved to the front with a time instead of being duplicated
+    record.add_previous_name("older");
+    let names: Vec<&str> = record.previous_name_strs().collect();
+    assert_eq!(names, ["older", "newest"]);
+    assert!(record.previous_names()[0].last_used.is_some());
+
+    // Seeing the same name again soon after doesn't change the record
+    let last_used = record.previous_names()[0].last_used;
+    assert!(!record.add_previous_name("older"));
+    assert_eq!(record.previous_names()[0].last_used, last_used);
+
+    // Saved in the new form, which loads the same
+    let saved = serde_json::to_string(&record).expect("Serializing record");
+    assert!(saved.contains("last_used"));
+    let reloaded: PlayerRecord = serde_json::from_str(&saved).expect("Deserializing record");
+    assert_eq!(reloaded.previous_names(), record.previous_names());
+}
+
+#[test]
+fn linked_accounts_are_symmetric() {
+    let a = SteamID::from(76_561_197_960_266_728);
+    let b = SteamID::from(76_561_197_960_267_728);
+    let c = SteamID::from(76_561_197_960_268_728);
+
+    let mut records = Records::default();
+    records.link_accounts(a, b);
+    records.link_accounts(a, c);
+    records.link_accounts(a, b);
+
+    assert_eq!(records[&a].linked_accounts(), [b, c]);
+    assert_eq!(records[&b].linked_accounts(), [a]);
+    assert_eq!(records[&c].linked_accounts(), [a]);
+
+    records.unlink_accounts(b, a);
+    assert_eq!(records[&a].linked_accounts(), [c]);
+    assert!(records[&b].linked_accounts().is_empty());
+    assert!(records[&b].is_empty());
+
+    // Deleting a record shouldn't leave links to it behind
+    records.remove_record(c);
+    assert!(records[&a].linked_accounts().is_empty());
+}
+
+#[test]
+fn encounters_are_counted_and_bounded() {
+    let mut record = PlayerRecord::default();
+    for _ in 0..MAX_RECENT_ENCOUNTERS + 5 {
+        record.add_encounter();
+    }
+
+    assert_eq!(record.encounters() as usize, MAX_RECENT_ENCOUNTERS + 5);
+    assert_eq!(record.recent_encounters().len(), MAX_RECENT_ENCOUNTERS);
+    assert!(record.first_encountered() <= record.recent_encounters().first().copied());
+    assert!(record.last_seen().is_some());
+
+    // Encounters alone aren't worth keeping a record for
+    assert!(record.is_empty());
+}
+
+#[test]
+fn changes_mark_records_dirty() {
+    let mut records = Records::default();
+    assert!(!records.is_dirty());
+
+    records
+        .entry(SteamID::from(76_561_198_000_000_001))
+        .or_default()
+        .set_verdict(Verdict::Cheater);
+    assert!(records.is_dirty());
+}
+
+#[test]
+fn reloading_keeps_newer_and_more_severe_records() {
+    let path = std::env::temp_dir().join(format!(
+        "tf2monitor-test-{}-playerlist.json",
+        std::process::id()
+    ));
+    let changed_here = SteamID::from(76_561_198_000_000_001);
+    let changed_externally = SteamID::from(76_561_198_000_000_002);
+    let changed_both = SteamID::from(76_561_198_000_000_003);
+
+    let mut records = Records::load_or_create(path.clone()).unwrap();
+    for s in [changed_here, changed_externally, changed_both] {
+        records
+            .entry(s)
+            .or_default()
+            .set_verdict(Verdict::Suspicious);
+    }
+    records.save().unwrap();
+
+    let mut external = Records::load_from(path.clone()).unwrap();
+    std::fs::remove_file(&path).ok();
+    external
+        .get_mut(&changed_externally)
+        .unwrap()
+        .set_verdict(Verdict::Cheater);
+    external
+        .get_mut(&changed_both)
+        .unwrap()
+        .set_verdict(Verdict::Trusted);
+
+    records
+        .get_mut(&changed_here)
+        .unwrap()
+        .set_verdict(Verdict::Bot);
+    records
+        .get_mut(&changed_both)
+        .unwrap()
+        .set_verdict(Verdict::Bot);
+
+    let summary = records.merge_external(external.records);
+    assert_eq!(summary.updated, 1);
+    assert_eq!(summary.conflicts, 1);
+    assert_eq!(records[&changed_here].verdict(), Verdict::Bot);
+    assert_eq!(records[&changed_externally].verdict(), Verdict::Cheater);
+    assert_eq!(records[&changed_both].verdict(), Verdict::Bot);
+}
+
+#[test]
+fn saving_merges_records_changed_on_disk() {
+    let path = std::env::temp_dir().join(format!(
+        "tf2monitor-test-{}-external-playerlist.json",
+        std::process::id()
+    ));
+    let changed_here = SteamID::from(76_561_198_000_000_001);
+    let added_externally = SteamID::from(76_561_198_000_000_002);
+
+    let mut records = Records::load_or_create(path.clone()).unwrap();
+    records
+        .entry(changed_here)
+        .or_default()
+        .set_verdict(Verdict::Suspicious);
+    records.save().unwrap();
+
+    // Something else adds a record between loading and saving
+    let mut external = Records::load_from(path.clone()).unwrap();
+    external
+        .entry(added_externally)
+        .or_default()
+        .set_verdict(Verdict::Cheater);
+    external.save().unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(10))
+        .unwrap();
+
+    records
+        .get_mut(&changed_here)
+        .unwrap()
+        .set_verdict(Verdict::Bot);
+    records.save().unwrap();
+
+    let saved = Records::load_from(path.clone()).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(saved[&changed_here].verdict(), Verdict::Bot);
+    assert_eq!(saved[&added_externally].verdict(), Verdict::Cheater);
+}
@@ -0,0 +1,38 @@
+use std::time::{Duration, SystemTime};
+
+use tf2_monitor_core::settings::Settings;
+
+#[test]
+fn saving_merges_settings_changed_on_disk() {
+    let path = std::env::temp_dir().join(format!(
+        "tf2monitor-test-{}-config.yaml",
+        std::process::id()
+    ));
+
+    let mut settings = Settings::load_or_create(path.clone()).unwrap();
+    settings.save().unwrap();
+    let mut settings = Settings::load_from(path.clone()).unwrap();
+
+    // Something else changes other settings between loading and saving
+    let mut external = Settings::load_from(path.clone()).unwrap();
+    external.rcon_password = String::from("external");
+    external.update_external_preferences(serde_json::json!({ "theme": "external" }));
+    external.save().unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(10))
+        .unwrap();
+
+    settings.steam_api_key = String::from("local");
+    settings.update_external_preferences(serde_json::json!({ "font": "local" }));
+    settings.save().unwrap();
+
+    let saved = Settings::load_from(path.clone()).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(saved.steam_api_key, "local");
+    assert_eq!(saved.rcon_password, "external");
+    assert_eq!(saved.external["theme"], "external");
+    assert_eq!(saved.external["font"], "local");
+}
@@ -0,0 +1,60 @@
+use tf2_monitor_core::{
+    demos::analyser::{AnalysedDemo, DemoPlayer},
+    md5,
+    players::shared_playtime::SharedPlaytime,
+    steamid_ng::SteamID,
+};
+
+const USER: u64 = 76_561_198_000_000_001;
+const LOBBY_MATE: u64 = 76_561_198_000_000_002;
+const OTHER: u64 = 76_561_198_000_000_003;
+
+/// A demo with the truncated demo's header, at 2 ticks per second so the
+/// times come out exactly, with each player in it for the given ticks
+fn demo(players: &[(u64, u32, u32)]) -> AnalysedDemo {
+    let mut demo = AnalysedDemo::new(include_bytes!("data/truncated.dem"), None).unwrap();
+    demo.user = SteamID::from(USER);
+    demo.interval_per_tick = 0.5;
+    demo.players = players
+        .iter()
+        .map(|&(s, first_tick, last_tick)| {
+            let player = DemoPlayer {
+                first_tick,
+                last_tick,
+                ..Default::default()
+            };
+            (SteamID::from(s), player)
+        })
+        .collect();
+    demo
+}
+
+#[test]
+fn overlapping_time_is_added_up() {
+    let lobby_mate = SteamID::from(LOBBY_MATE);
+    let other = SteamID::from(OTHER);
+
+    // The lobby mate joins a minute after the user, the other player leaves
+    // before the user joins
+    let first = demo(&[(USER, 120, 1200), (LOBBY_MATE, 240, 1800), (OTHER, 0, 120)]);
+    // Both are in the whole match with the user
+    let second = demo(&[(USER, 0, 600), (LOBBY_MATE, 0, 600), (OTHER, 0, 600)]);
+    let first_id = md5::compute(b"first");
+    let second_id = md5::compute(b"second");
+
+    let mut shared = SharedPlaytime::new();
+    shared.add_demo(first_id, &first);
+    assert_eq!(shared.seconds_with(lobby_mate), 480);
+    assert_eq!(shared.seconds_with(other), 0);
+
+    shared.add_demo(second_id, &second);
+    assert_eq!(shared.seconds_with(lobby_mate), 780);
+    assert_eq!(shared.seconds_with(other), 300);
+    assert_eq!(shared.seconds_with(SteamID::from(USER)), 0);
+    assert!(shared.contains(first_id));
+
+    // Analysing a demo again replaces what it counted
+    shared.add_demo(second_id, &second);
+    assert_eq!(shared.seconds_with(lobby_mate), 780);
+    assert_eq!(shared.num_demos(), 2);
+}
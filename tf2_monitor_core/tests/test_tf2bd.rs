@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use tf2_monitor_core::{
+    players::{
+        records::{PlayerRecord, Records, Verdict},
+        tf2bd::{MergePolicy, Tf2bdPlayerlist, TF2BD_ATTRIBUTES_KEY},
+    },
+    steamid_ng::SteamID,
+};
+
+const PLAYERLIST: &str = "tests/data/tf2bd_playerlist.json";
+
+fn steamid(account_id: u64) -> SteamID {
+    SteamID::from(76_561_197_960_265_728 + account_id)
+}
+
+#[test]
+fn import_tf2bd() {
+    let mut records = Records::default();
+    let summary = records
+        .import_tf2bd(Path::new(PLAYERLIST), MergePolicy::KeepWorse)
+        .expect("Importing playerlist");
+
+    assert_eq!(summary.added, 4);
+    assert_eq!(summary.updated, 0);
+    assert_eq!(summary.conflicts, 0);
+
+    let cheater = records.get(&steamid(1000)).expect("Cheater record");
+    assert_eq!(cheater.verdict(), Verdict::Cheater);
+    assert_eq!(cheater.previous_name_strs().collect::<Vec<_>>(), ["MYG)T"]);
+    assert_eq!(
+        cheater.last_seen().map(|t| t.timestamp()),
+        Some(1_700_000_000)
+    );
+
+    let suspicious = records.get(&steamid(2000)).expect("Suspicious record");
+    assert_eq!(suspicious.verdict(), Verdict::Suspicious);
+
+    let exploiter = records.get(&steamid(3000)).expect("Exploiter record");
+    assert_eq!(exploiter.verdict(), Verdict::Cheater);
+
+    let racist = records.get(&steamid(4000)).expect("Racist record");
+    assert_eq!(racist.verdict(), Verdict::Player);
+    assert_eq!(
+        racist.custom_data()[TF2BD_ATTRIBUTES_KEY],
+        serde_json::json!(["racist"])
+    );
+}
+
+#[test]
+fn merge_policies() {
+    let list = Records::read_tf2bd(Path::new(PLAYERLIST)).expect("Reading playerlist");
+
+    let mut records = Records::default();
+    let mut trusted = PlayerRecord::default();
+    trusted.set_verdict(Verdict::Trusted);
+    records.insert(steamid(1000), trusted);
+    let mut bot = PlayerRecord::default();
+    bot.set_verdict(Verdict::Bot);
+    records.insert(steamid(2000), bot);
+
+    let skip = records.preview_tf2bd(&list, MergePolicy::SkipExisting);
+    assert_eq!((skip.added, skip.updated, skip.conflicts), (2, 0, 2));
+
+    let keep_worse = records.preview_tf2bd(&list, MergePolicy::KeepWorse);
+    assert_eq!(
+        (keep_worse.added, keep_worse.updated, keep_worse.conflicts),
+        (2, 1, 2)
+    );
+
+    // Previewing doesn't change anything
+    assert_eq!(records.len(), 2);
+
+    let overwrite = records.merge_tf2bd(&list, MergePolicy::Overwrite);
+    assert_eq!(
+        (overwrite.added, overwrite.updated, overwrite.conflicts),
+        (2, 2, 2)
+    );
+    assert_eq!(records[&steamid(1000)].verdict(), Verdict::Cheater);
+    assert_eq!(records[&steamid(2000)].verdict(), Verdict::Suspicious);
+}
+
+#[test]
+fn keep_worse_keeps_existing_on_tie() {
+    let list = Records::read_tf2bd(Path::new(PLAYERLIST)).expect("Reading playerlist");
+
+    let mut records = Records::default();
+    let mut bot = PlayerRecord::default();
+    bot.set_verdict(Verdict::Bot);
+    records.insert(steamid(1000), bot);
+
+    records.merge_tf2bd(&list, MergePolicy::KeepWorse);
+    // Imported as a cheater, which is no worse than a bot
+    assert_eq!(records[&steamid(1000)].verdict(), Verdict::Bot);
+    assert_eq!(records[&steamid(3000)].verdict(), Verdict::Cheater);
+}
+
+#[test]
+fn invalid_steamids_are_skipped() {
+    let list: Tf2bdPlayerlist = serde_json::from_value(serde_json::json!({
+        "players": [
+            { "attributes": ["cheater"], "steamid": "[U:1:1000]" },
+            { "attributes": ["cheater"], "steamid": "not a steamid" },
+            { "attributes": ["cheater"], "steamid": true },
+            { "attributes": ["suspicious"], "steamid": 76_561_197_960_267_728_u64 },
+        ]
+    }))
+    .expect("Reading playerlist");
+    assert_eq!(list.players.len(), 2);
+    assert_eq!(list.invalid_players, 2);
+
+    let mut records = Records::default();
+    let summary = records.merge_tf2bd(&list, MergePolicy::KeepWorse);
+    assert_eq!((summary.added, summary.invalid), (2, 2));
+}
+
+#[test]
+fn round_trip_tf2bd() {
+    let mut records = Records::default();
+    records
+        .import_tf2bd(Path::new(PLAYERLIST), MergePolicy::Overwrite)
+        .expect("Importing playerlist");
+
+    let mut bot = PlayerRecord::default();
+    bot.set_verdict(Verdict::Bot);
+    records.insert(steamid(5000), bot);
+    let mut trusted = PlayerRecord::default();
+    trusted.set_verdict(Verdict::Trusted);
+    records.insert(steamid(6000), trusted);
+
+    let path = std::env::temp_dir().join("tf2monitor_test_tf2bd_export.json");
+    let exported = records.export_tf2bd(&path).expect("Exporting playerlist");
+    // Trusted players have no TF2BD equivalent
+    assert_eq!(exported, 5);
+
+    let mut reimported = Records::default();
+    let summary = reimported
+        .import_tf2bd(&path, MergePolicy::Overwrite)
+        .expect("Importing exported playerlist");
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(summary.added, 5);
+    for (steamid, record) in reimported.iter() {
+        let original = &records[steamid];
+        let expected = match original.verdict() {
+            Verdict::Bot => Verdict::Cheater,
+            v => v,
+        };
+        assert_eq!(record.verdict(), expected);
+        assert_eq!(record.custom_data(), original.custom_data());
+        assert_eq!(record.previous_names(), original.previous_names());
+        assert_eq!(
+            record.last_seen().map(|t| t.timestamp()),
+            original.last_seen().map(|t| t.timestamp())
+        );
+    }
+}
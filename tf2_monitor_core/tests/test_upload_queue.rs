@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use tf2_monitor_core::masterbase::queue::{QueuedUpload, UploadQueue};
+
+#[test]
+fn failed_retries_back_off_until_retried_manually() {
+    let path = PathBuf::from("missing/demo.dem");
+    let mut queue = UploadQueue::default();
+    queue.push(QueuedUpload::new(
+        path.clone(),
+        1024,
+        Some(7),
+        String::from("Connection reset"),
+    ));
+    assert!(queue.uploads[0].retry_in().is_some());
+
+    // The demo doesn't exist, so it isn't still recording
+    queue.retry_now(&path);
+    let upload = queue.start_next().expect("Upload should be due");
+    assert_eq!(upload.offset, 1024);
+    assert!(queue.start_next().is_none());
+
+    queue.finish(&path, Err(String::from("Still offline")));
+    let upload = &queue.uploads[0];
+    assert_eq!(upload.attempts, 1);
+    assert!(!upload.in_progress);
+    assert!(upload.session_id.is_none());
+    assert!(upload.retry_in().is_some());
+    assert!(queue.start_next().is_none());
+
+    queue.retry_now(&path);
+    assert!(queue.start_next().is_some());
+    queue.finish(&path, Ok(()));
+    assert!(queue.uploads.is_empty());
+}